@@ -0,0 +1,556 @@
+//! `/albums` – creation, status and the hand-off to the Import stage.
+
+use axum::{extract::State, http::StatusCode};
+use std::collections::BTreeMap;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use shared::pipeline::Stage;
+use sqlx::{Executor, PgPool, Postgres, QueryBuilder};
+use time::OffsetDateTime;
+use tracing::{info, instrument};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    error::{ApiError, ApiResult, Json, Path, Problem, Query},
+    jobs, manifest, removal,
+    roots::Roots,
+    tracks::{load_album_tracks, MediaFile, Track},
+    AppState,
+};
+
+/*──────── model ──────────────────────────────────────────────────────────*/
+
+/// Mirrors the `album_kind` Postgres enum.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "album_kind", rename_all = "lowercase")]
+pub enum AlbumKind {
+    Studio,
+    Concert,
+    Hybrid,
+    #[default]
+    Unknown,
+}
+
+/// Where the media for an album comes from (`albums.source`).
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AlbumSource {
+    /// Files arrive through the API into `<inbox>/<album_id>/`.
+    Upload {
+        #[serde(default, skip_deserializing)]
+        path: Option<String>,
+    },
+    /// Existing folder under the media / inbox roots.
+    LibraryScan {
+        path:     String,
+        /// Set when created by `POST /imports/batch`.
+        #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+        batch_id: Option<Uuid>,
+    },
+    /// Archive to be downloaded by the Fetch worker.
+    Remote { url: String },
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct Album {
+    pub id:          Uuid,
+    pub title:       Option<String>,
+    pub artist:      Option<String>,
+    pub year:        Option<i32>,
+    pub venue:       Option<String>,
+    #[sqlx(rename = "kind")]
+    pub album_kind:  AlbumKind,
+    #[schema(value_type = AlbumSource)]
+    pub source:      serde_json::Value,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub imported_at: Option<OffsetDateTime>,
+}
+
+/// Mirrors the `file_status` Postgres enum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[sqlx(type_name = "file_status", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FileStatus {
+    New,
+    FpDone,
+    TagDone,
+    Ready,
+    Error,
+}
+
+impl FileStatus {
+    /// Pipeline steps behind this file (0‥=3); `ERROR` is terminal too.
+    fn steps_done(self) -> u32 {
+        match self {
+            FileStatus::New     => 0,
+            FileStatus::FpDone  => 1,
+            FileStatus::TagDone => 2,
+            FileStatus::Ready | FileStatus::Error => 3,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AlbumStatus {
+    #[serde(flatten)]
+    pub album:    Album,
+    pub tracks:   Vec<Track>,
+    /// stage → job counts, every stage present
+    #[schema(value_type = BTreeMap<String, JobCounts>)]
+    pub jobs:     BTreeMap<&'static str, JobCounts>,
+    /// 0‥100, share of per-file pipeline steps finished
+    pub progress: f32,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct JobCounts {
+    pub queued:    i64,
+    pub running:   i64,
+    pub done:      i64,
+    pub error:     i64,
+    /// Superseded by a reprocess, or the album was deleted.
+    pub cancelled: i64,
+}
+
+impl JobCounts {
+    fn add(&mut self, status: &str, n: i64) {
+        match status {
+            "queued"    => self.queued    += n,
+            "running"   => self.running   += n,
+            "done"      => self.done      += n,
+            "error"     => self.error     += n,
+            "cancelled" => self.cancelled += n,
+            _ => {}
+        }
+    }
+}
+
+/// Roll-up of an album's files for listings.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum PipelineState {
+    Ready,
+    InProgress,
+    Error,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct AlbumSummary {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub album: Album,
+    pub state: PipelineState,
+    #[serde(skip)]
+    sort_key:  String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AlbumPage {
+    pub items:       Vec<AlbumSummary>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    #[default]
+    ImportedAt,
+    Title,
+    Artist,
+    Year,
+}
+
+impl SortKey {
+    /// (expression, cast) – expressions match the `albums_sort_*` indexes.
+    fn sql(self) -> (&'static str, &'static str) {
+        match self {
+            SortKey::ImportedAt => ("COALESCE(imported_at, 'epoch'::timestamptz)", "timestamptz"),
+            SortKey::Title      => ("COALESCE(lower(title), '')",  "text"),
+            SortKey::Artist     => ("COALESCE(lower(artist), '')", "text"),
+            SortKey::Year       => ("COALESCE(year, 0)",           "int"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(deny_unknown_fields)]
+pub struct AlbumQuery {
+    pub artist:     Option<String>,
+    pub year_from:  Option<i32>,
+    pub year_to:    Option<i32>,
+    pub album_kind: Option<AlbumKind>,
+    pub source:     Option<String>,
+    pub state:      Option<PipelineState>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub imported_from: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub imported_to:   Option<OffsetDateTime>,
+    #[serde(default)]
+    pub sort:       SortKey,
+    #[serde(default)]
+    pub order:      SortOrder,
+    pub limit:      Option<i64>,
+    pub cursor:     Option<String>,
+}
+
+/// Opaque to clients: base64url(JSON).  Pinned to the sort it came from.
+#[derive(Debug, Serialize, Deserialize)]
+struct Cursor {
+    sort:  SortKey,
+    order: SortOrder,
+    key:   String,
+    id:    Uuid,
+}
+
+pub(crate) const ALBUM_COLUMNS: &str =
+    "id, title, artist, year, venue, kind, source, imported_at";
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NewAlbum {
+    pub title:      Option<String>,
+    pub artist:     Option<String>,
+    pub year:       Option<i32>,
+    /// Concerts: where it was recorded.
+    pub venue:      Option<String>,
+    #[serde(default)]
+    pub album_kind: AlbumKind,
+    pub source:     AlbumSource,
+}
+
+/// Descriptive fields of a new album row.
+#[derive(Debug, Default)]
+pub(crate) struct AlbumMeta {
+    pub title:  Option<String>,
+    pub artist: Option<String>,
+    pub year:   Option<i32>,
+    pub venue:  Option<String>,
+}
+
+/*──────── handlers ───────────────────────────────────────────────────────*/
+
+#[utoipa::path(
+    post, path = "/albums", tag = "albums", request_body = NewAlbum,
+    responses(
+        (status = 201, description = "Album created", body = Album),
+        (status = 400, response = Problem),
+        (status = 409, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all)]
+pub async fn create_album(
+    State(app): State<AppState>,
+    Json(req): Json<NewAlbum>,
+) -> ApiResult<(StatusCode, Json<Album>)> {
+    let id = Uuid::new_v4();
+    let source = validate_source(&app, id, req.source)?;
+    let title  = clean_text("title", req.title)?;
+    let artist = clean_text("artist", req.artist)?;
+    let venue  = clean_text("venue", req.venue)?;
+    if let Some(y) = req.year {
+        check_year(y)?;
+    }
+
+    if let AlbumSource::LibraryScan { path, .. } = &source {
+        refuse_tombstoned(&app.db, path).await?;
+    }
+
+    let meta = AlbumMeta { title, artist, year: req.year, venue };
+    let album = insert_album(&app.db, id, meta, req.album_kind, &source).await?;
+    info!(%id, "album created");
+    Ok((StatusCode::CREATED, Json(album)))
+}
+
+/// `GET /albums` – filtered, sorted, keyset-paginated listing.  Cursors
+/// encode the last (sort key, id) seen, so pages stay stable while imports
+/// add albums.
+#[utoipa::path(
+    get, path = "/albums", tag = "albums", params(AlbumQuery),
+    responses(
+        (status = 200, description = "One page of albums", body = AlbumPage),
+        (status = 400, response = Problem),
+    ),
+)]
+pub async fn list_albums(
+    State(app): State<AppState>,
+    Query(q): Query<AlbumQuery>,
+) -> ApiResult<Json<AlbumPage>> {
+    let limit = q.limit.unwrap_or(50).clamp(1, 200);
+    let (key_expr, key_cast) = q.sort.sql();
+    let cursor = q.cursor.as_deref().map(decode_cursor).transpose()?;
+    if cursor.as_ref().is_some_and(|c| c.sort != q.sort || c.order != q.order) {
+        return Err(ApiError::BadRequest("cursor belongs to a different sort".into()));
+    }
+
+    let mut qb = QueryBuilder::<Postgres>::new(format!(
+        "WITH a AS (
+           SELECT al.*, {key_expr}::text AS sort_key, {key_expr} AS sort_val,
+                  COALESCE(st.state, 'in_progress') AS state
+             FROM albums al
+             LEFT JOIN LATERAL (
+               SELECT CASE WHEN bool_or(f.status = 'ERROR') THEN 'error'
+                           WHEN bool_and(f.status = 'READY') THEN 'ready'
+                      END AS state
+                 FROM tracks t JOIN files f ON f.track_id = t.id
+                WHERE t.album_id = al.id
+             ) st ON true
+         )
+         SELECT {ALBUM_COLUMNS}, state, sort_key FROM a WHERE true"
+    ));
+    if let Some(artist) = &q.artist {
+        qb.push(" AND lower(artist) = lower(").push_bind(artist.trim().to_owned()).push(")");
+    }
+    if let Some(y) = q.year_from     { qb.push(" AND year >= ").push_bind(y); }
+    if let Some(y) = q.year_to       { qb.push(" AND year <= ").push_bind(y); }
+    if let Some(k) = q.album_kind    { qb.push(" AND kind = ").push_bind(k); }
+    if let Some(s) = &q.source       { qb.push(" AND source->>'type' = ").push_bind(s.clone()); }
+    if let Some(s) = q.state         { qb.push(" AND state = ").push_bind(s); }
+    if let Some(t) = q.imported_from { qb.push(" AND imported_at >= ").push_bind(t); }
+    if let Some(t) = q.imported_to   { qb.push(" AND imported_at < ").push_bind(t); }
+
+    let (cmp, dir) = match q.order {
+        SortOrder::Asc  => (">", "ASC"),
+        SortOrder::Desc => ("<", "DESC"),
+    };
+    if let Some(c) = &cursor {
+        qb.push(format!(" AND (sort_val, id) {cmp} (("))
+          .push_bind(c.key.clone())
+          .push(format!(")::{key_cast}, "))
+          .push_bind(c.id)
+          .push(")");
+    }
+    qb.push(format!(" ORDER BY sort_val {dir}, id {dir} LIMIT ")).push_bind(limit + 1);
+
+    let mut items: Vec<AlbumSummary> = qb
+        .build_query_as()
+        .fetch_all(&app.db)
+        .await
+        .map_err(|e| match e.as_database_error().and_then(|d| d.code()) {
+            // cursor key that doesn't cast (hand-edited cursor)
+            Some(c) if c == "22P02" || c == "22007" => ApiError::BadRequest("invalid cursor".into()),
+            _ => e.into(),
+        })?;
+
+    let next_cursor = if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        items.last().map(|last| encode_cursor(&Cursor {
+            sort: q.sort, order: q.order, key: last.sort_key.clone(), id: last.album.id,
+        }))
+    } else {
+        None
+    };
+    Ok(Json(AlbumPage { items, next_cursor }))
+}
+
+/// `GET /albums/:id` – album metadata, tracks with their files, per-stage
+/// job counts and an overall progress figure.
+#[utoipa::path(
+    get, path = "/albums/{id}", tag = "albums",
+    params(("id" = Uuid, Path, description = "Album id")),
+    responses(
+        (status = 200, description = "Album with tracks, job counts and progress", body = AlbumStatus),
+        (status = 404, response = Problem),
+    ),
+)]
+pub async fn get_album(Path(id): Path<Uuid>, State(app): State<AppState>)
+    -> ApiResult<Json<AlbumStatus>>
+{
+    Ok(Json(album_status(&app.db, id).await?))
+}
+
+/// What `GET /albums/:id` answers.
+pub(crate) async fn album_status(db: &PgPool, id: Uuid) -> ApiResult<AlbumStatus> {
+    let album: Album = sqlx::query_as(&format!("SELECT {ALBUM_COLUMNS} FROM albums WHERE id=$1"))
+        .bind(id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| ApiError::not_found("album", id))?;
+
+    let tracks = load_album_tracks(db, id).await?;
+    let files: Vec<&MediaFile> = tracks.iter().flat_map(|t| &t.files).collect();
+
+    let counts: Vec<(String, String, i64)> = sqlx::query_as(
+        "SELECT stage, status, COUNT(*) FROM jobs
+          WHERE payload->>'album_id' = $1
+             OR payload->>'file_id' = ANY($2)
+       GROUP BY stage, status",
+    )
+    .bind(id.to_string())
+    .bind(files.iter().map(|f| f.id.to_string()).collect::<Vec<_>>())
+    .fetch_all(db)
+    .await?;
+
+    let mut jobs: BTreeMap<&'static str, JobCounts> =
+        Stage::ALL.iter().map(|s| (s.as_str(), JobCounts::default())).collect();
+    for (stage, status, n) in counts {
+        if let Some(c) = jobs.get_mut(stage.as_str()) {
+            c.add(&status, n);
+        }
+    }
+
+    let progress = progress(&files);
+    Ok(AlbumStatus { album, tracks, jobs, progress })
+}
+
+/// Queue the Import stage.  Refused with `409` + diff while a declared
+/// manifest doesn't match what's on disk.
+#[utoipa::path(
+    put, path = "/albums/{id}/complete", tag = "albums",
+    params(("id" = Uuid, Path, description = "Album id")),
+    responses(
+        (status = 200, description = "Import queued"),
+        (status = 404, response = Problem),
+        (status = 409, response = Problem),
+    ),
+)]
+#[instrument(skip_all)]
+pub async fn complete_album(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+) -> ApiResult<()> {
+    let row: Option<(Option<String>,)> = sqlx::query_as("SELECT source->>'path' FROM albums WHERE id=$1")
+        .bind(id)
+        .fetch_optional(&app.db)
+        .await?;
+    let (path,) = row.ok_or_else(|| ApiError::not_found("album", id))?;
+
+    if let Some(root) = path {
+        let diff = manifest::verify(&app.db, id, root.into()).await?;
+        if let Some(diff) = diff.filter(|d| d.is_blocking()) {
+            info!(%id, "manifest mismatch – import refused");
+            return Err(ApiError::ManifestMismatch(diff));
+        }
+    }
+
+    jobs::enqueue(&app.db, Stage::Import, Some(id), None).await?;
+    info!("queued import job");
+    Ok(())
+}
+
+pub(crate) async fn insert_album<'e, E>(
+    db:     E,
+    id:     Uuid,
+    meta:   AlbumMeta,
+    kind:   AlbumKind,
+    source: &AlbumSource,
+) -> ApiResult<Album>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let album = sqlx::query_as(&format!(
+        "INSERT INTO albums(id, title, artist, year, venue, kind, source)
+              VALUES ($1,$2,$3,$4,$5,$6,$7)
+           RETURNING {ALBUM_COLUMNS}"
+    ))
+    .bind(id)
+    .bind(meta.title)
+    .bind(meta.artist)
+    .bind(meta.year)
+    .bind(meta.venue)
+    .bind(kind)
+    .bind(serde_json::to_value(source)?)
+    .fetch_one(db)
+    .await?;
+    Ok(album)
+}
+
+fn progress(files: &[&MediaFile]) -> f32 {
+    if files.is_empty() {
+        return 0.0;
+    }
+    let done: u32 = files.iter().map(|f| f.status.steps_done()).sum();
+    let pct = done as f32 * 100.0 / (files.len() as f32 * 3.0);
+    (pct * 10.0).round() / 10.0
+}
+
+fn encode_cursor(c: &Cursor) -> String {
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(c).expect("cursor serialises"))
+}
+
+fn decode_cursor(raw: &str) -> ApiResult<Cursor> {
+    URL_SAFE_NO_PAD
+        .decode(raw)
+        .ok()
+        .and_then(|b| serde_json::from_slice(&b).ok())
+        .ok_or_else(|| ApiError::BadRequest("invalid cursor".into()))
+}
+
+/*──────── validation ─────────────────────────────────────────────────────*/
+
+/// Normalise the client-supplied source; paths are canonicalised and must
+/// stay inside the media / inbox roots.
+fn validate_source(app: &AppState, id: Uuid, src: AlbumSource)
+    -> ApiResult<AlbumSource>
+{
+    match src {
+        AlbumSource::Upload { .. } => Ok(AlbumSource::Upload {
+            path: Some(app.roots.album_inbox(id).to_string_lossy().into_owned()),
+        }),
+        AlbumSource::LibraryScan { path, .. } => Ok(AlbumSource::LibraryScan {
+            path:     folder(&app.roots, &path)?,
+            batch_id: None,
+        }),
+        AlbumSource::Remote { url } => {
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                return Err(unprocessable(format!("url {url:?} must be http(s)")));
+            }
+            Ok(AlbumSource::Remote { url })
+        }
+    }
+}
+
+/// Canonical form of a client-supplied album folder.
+pub(crate) fn folder(roots: &Roots, path: &str) -> ApiResult<String> {
+    let real = roots.resolve(std::path::Path::new(path))
+        .filter(|p| p.is_dir())
+        .ok_or_else(|| unprocessable(format!(
+            "path {path:?} is not a directory under the media or inbox root"
+        )))?;
+    Ok(real.to_string_lossy().into_owned())
+}
+
+/// `409` if `path` was deleted with a tombstone.
+pub(crate) async fn refuse_tombstoned(db: &PgPool, path: &str) -> ApiResult<()> {
+    match removal::tombstoned(db, path).await? {
+        Some(gone) => Err(ApiError::Conflict(format!(
+            "{path:?} belongs to deleted album {gone} – lift its tombstone to import it again"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Trim; blank → NULL; cap length so a bad client can't stuff megabytes in.
+pub(crate) fn clean_text(field: &str, v: Option<String>) -> ApiResult<Option<String>> {
+    let Some(s) = v.map(|s| s.trim().to_owned()).filter(|s| !s.is_empty()) else {
+        return Ok(None);
+    };
+    if s.chars().count() > 512 {
+        return Err(unprocessable(format!("{field} longer than 512 characters")));
+    }
+    Ok(Some(s))
+}
+
+pub(crate) fn check_year(y: i32) -> ApiResult<()> {
+    if !(1000..=9999).contains(&y) {
+        return Err(unprocessable(format!("year {y} out of range")));
+    }
+    Ok(())
+}
+
+fn unprocessable(msg: String) -> ApiError {
+    ApiError::Unprocessable(msg)
+}
@@ -0,0 +1,1066 @@
+//! Library export and restore.
+//!
+//! `GET /export` dumps what a lost database can't get back from `/media`:
+//! albums and tracks as corrected, file paths with checksums, chosen
+//! matches and match decisions, edit history, merges, tombstones and
+//! playlists – as one JSON document or as NDJSON (a `header` line, then one
+//! record per line).  The `setlist-archive` tool does the same from a shell.
+//!
+//! `POST /restore` reads either back.  Each archived file is re-linked to
+//! the `files` row with the same path, else the same SHA-256.  An album is
+//! matched by id, else by the album most of its files belong to now, and
+//! gets its metadata, chosen release and history back.  An album nothing
+//! matches is recreated from those of its files still on disk under the
+//! media root.  Whatever can't be placed is listed in the report;
+//! `?dry_run=true` rolls the whole restore back.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path as FsPath,
+};
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use shared::pipeline::Stage;
+use sqlx::{PgPool, Postgres, Transaction};
+use time::OffsetDateTime;
+use tracing::{info, instrument};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    albums::{AlbumKind, FileStatus},
+    audit,
+    auth::Principal,
+    error::{ApiError, ApiResult, Json, Problem, Query},
+    history::{self, Entity, HistoryEntry},
+    jobs, AppState,
+};
+
+pub const FORMAT: &str = "setlist-export";
+/// Bumped when a restore can no longer read older archives as they are.
+pub const VERSION: u32 = 1;
+pub const RESTORE_MAX_BYTES: usize = 512 << 20;
+
+/*──────── archive ────────────────────────────────────────────────────────*/
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveFormat {
+    /// One document.
+    #[default]
+    Json,
+    /// A `header` record, then one album, playlist or tombstone per line.
+    Ndjson,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(deny_unknown_fields)]
+pub struct ExportQuery {
+    /// `json` (default) or `ndjson`.
+    pub format: Option<ArchiveFormat>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(deny_unknown_fields)]
+pub struct RestoreQuery {
+    /// Report what would happen, change nothing.
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ArchiveHeader {
+    /// Always `setlist-export`.
+    pub format:      String,
+    pub version:     u32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub exported_at: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Archive {
+    #[serde(flatten)]
+    pub header:     ArchiveHeader,
+    pub albums:     Vec<ArchivedAlbum>,
+    #[serde(default)]
+    pub playlists:  Vec<ArchivedPlaylist>,
+    #[serde(default)]
+    pub tombstones: Vec<ArchivedTombstone>,
+}
+
+/// One NDJSON line.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record {
+    Header(ArchiveHeader),
+    Album(ArchivedAlbum),
+    Playlist(ArchivedPlaylist),
+    Tombstone(ArchivedTombstone),
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct ArchivedAlbum {
+    pub id:           Uuid,
+    pub title:        Option<String>,
+    pub artist:       Option<String>,
+    pub year:         Option<i32>,
+    pub venue:        Option<String>,
+    pub kind:         AlbumKind,
+    pub source:       serde_json::Value,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub imported_at:  Option<OffsetDateTime>,
+    pub needs_review: bool,
+    #[sqlx(skip)]
+    pub tracks:       Vec<ArchivedTrack>,
+    /// The chosen MusicBrainz release.
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub release:      Option<ChosenMatch>,
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub decisions:    Vec<ArchivedDecision>,
+    /// Edits to the album and its tracks, oldest first.
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub history:      Vec<HistoryEntry>,
+    /// Albums folded into this one.
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub merged_from:  Vec<ArchivedMerge>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct ArchivedTrack {
+    pub id:           Uuid,
+    pub disc:         Option<i32>,
+    pub index:        Option<i32>,
+    pub title:        Option<String>,
+    pub duration_sec: Option<i32>,
+    #[sqlx(skip)]
+    pub files:        Vec<ArchivedFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct ArchivedFile {
+    pub id:         Uuid,
+    pub path:       String,
+    pub codec:      String,
+    pub size_bytes: Option<i64>,
+    /// Hex; missing for files imported before checksums were kept.
+    pub sha256:     Option<String>,
+    pub status:     FileStatus,
+    /// The chosen MusicBrainz recording.
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub recording:  Option<ChosenMatch>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct ChosenMatch {
+    pub mbid:     Uuid,
+    pub score:    Option<f32>,
+    pub raw_json: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct ArchivedDecision {
+    /// Set for track decisions; album decisions have none.
+    pub file_id:    Option<Uuid>,
+    pub action:     String,
+    pub mbid:       Option<Uuid>,
+    pub metadata:   Option<serde_json::Value>,
+    pub decided_by: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub decided_at: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct ArchivedMerge {
+    pub from_id:   Uuid,
+    pub path:      Option<String>,
+    pub title:     Option<String>,
+    pub artist:    Option<String>,
+    pub merged_by: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub merged_at: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct ArchivedPlaylist {
+    pub id:         Uuid,
+    /// User name; restored to the caller if there's no such user.
+    pub owner:      String,
+    pub name:       String,
+    pub comment:    Option<String>,
+    pub public:     bool,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    /// Track ids in order.
+    #[sqlx(skip)]
+    pub tracks:     Vec<Uuid>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct ArchivedTombstone {
+    pub album_id:   Uuid,
+    pub path:       Option<String>,
+    pub file_paths: Vec<String>,
+    pub title:      Option<String>,
+    pub artist:     Option<String>,
+    pub deleted_by: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub deleted_at: OffsetDateTime,
+}
+
+/*──────── restore report ─────────────────────────────────────────────────*/
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct RestoreReport {
+    pub dry_run:             bool,
+    pub albums_created:      u32,
+    pub albums_updated:      u32,
+    /// Re-linked to an existing `files` row with the same path…
+    pub files_by_path:       u32,
+    /// …or, failing that, the same SHA-256.
+    pub files_by_checksum:   u32,
+    /// Found on disk and inserted again (albums that were recreated).
+    pub files_from_disk:     u32,
+    pub playlists_restored:  u32,
+    pub playlists_existing:  u32,
+    pub tombstones_restored: u32,
+    pub unmatched:           Vec<Unmatched>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UnmatchedKind {
+    Album,
+    Track,
+    File,
+    PlaylistEntry,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Unmatched {
+    pub kind:   UnmatchedKind,
+    /// Archived id.
+    pub id:     Uuid,
+    pub detail: String,
+}
+
+/*──────── handlers ───────────────────────────────────────────────────────*/
+
+/// `GET /export?format=` – the whole library's metadata, as a download.
+#[utoipa::path(
+    get, path = "/export", tag = "archive", params(ExportQuery),
+    responses(
+        (status = 200, description = "JSON document or NDJSON stream", body = Archive,
+         content_type = ["application/json", "application/x-ndjson"]),
+        (status = 400, response = Problem),
+        (status = 403, response = Problem),
+    ),
+)]
+#[instrument(skip_all)]
+pub async fn export(State(app): State<AppState>, Query(q): Query<ExportQuery>) -> ApiResult<Response> {
+    let archive = load(&app.db).await?;
+    let (albums, playlists) = (archive.albums.len(), archive.playlists.len());
+    let day = archive.header.exported_at.date();
+    let (body, mime, ext) = match q.format.unwrap_or_default() {
+        ArchiveFormat::Json => (serde_json::to_vec(&archive)?, "application/json", "json"),
+        ArchiveFormat::Ndjson => {
+            let Archive { header, albums, playlists, tombstones } = archive;
+            let records = std::iter::once(Record::Header(header))
+                .chain(albums.into_iter().map(Record::Album))
+                .chain(playlists.into_iter().map(Record::Playlist))
+                .chain(tombstones.into_iter().map(Record::Tombstone));
+            let mut out = Vec::new();
+            for r in records {
+                serde_json::to_writer(&mut out, &r)?;
+                out.push(b'\n');
+            }
+            (out, "application/x-ndjson", "ndjson")
+        }
+    };
+    info!(albums, playlists, bytes = body.len(), "library exported");
+
+    let mut res = body.into_response();
+    let h = res.headers_mut();
+    h.insert(header::CONTENT_TYPE, HeaderValue::from_static(mime));
+    if let Ok(v) = HeaderValue::from_str(&format!("attachment; filename=\"setlist-{day}.{ext}\"")) {
+        h.insert(header::CONTENT_DISPOSITION, v);
+    }
+    Ok(res)
+}
+
+/// `POST /restore?dry_run=` – body is an export, JSON or NDJSON.
+#[utoipa::path(
+    post, path = "/restore", tag = "archive", params(RestoreQuery),
+    request_body(content = Archive, content_type = "application/json",
+                 description = "An export; NDJSON is accepted too"),
+    responses(
+        (status = 200, description = "What was restored, and what couldn't be placed", body = RestoreReport),
+        (status = 403, response = Problem),
+        (status = 413, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(user = %who.username))]
+pub async fn restore(
+    State(app): State<AppState>,
+    who: Principal,
+    Query(q): Query<RestoreQuery>,
+    body: Bytes,
+) -> ApiResult<Json<RestoreReport>> {
+    let archive = parse(&body)?;
+    drop(body);
+
+    // archived path → canonical path, for files still on disk under a root
+    let roots = app.roots.clone();
+    let paths: Vec<String> = archive
+        .albums
+        .iter()
+        .flat_map(|a| &a.tracks)
+        .flat_map(|t| &t.files)
+        .map(|f| f.path.clone())
+        .collect();
+    let on_disk: HashMap<String, String> = tokio::task::spawn_blocking(move || {
+        paths
+            .into_iter()
+            .filter_map(|p| {
+                let real = roots.resolve(FsPath::new(&p)).filter(|r| r.is_file())?;
+                Some((p, real.to_string_lossy().into_owned()))
+            })
+            .collect()
+    })
+    .await?;
+
+    let mut tx = app.db.begin().await?;
+    history::attribute(&mut tx, &who.username, "restore").await?;
+    let mut r = Restorer {
+        tx,
+        on_disk,
+        tracks: HashMap::new(),
+        report: RestoreReport { dry_run: q.dry_run.unwrap_or(false), ..Default::default() },
+    };
+    for a in archive.albums {
+        r.album(a).await?;
+    }
+    for p in archive.playlists {
+        r.playlist(p, &who).await?;
+    }
+    for t in archive.tombstones {
+        r.tombstone(t).await?;
+    }
+
+    let Restorer { mut tx, report, .. } = r;
+    audit::record(
+        &mut *tx, &who.username, "library.restore", ("library", None),
+        serde_json::json!({
+            "exported_at": archive.header.exported_at.unix_timestamp(),
+            "dry_run": report.dry_run,
+            "albums_created": report.albums_created,
+            "albums_updated": report.albums_updated,
+            "unmatched": report.unmatched.len(),
+        }),
+    )
+    .await?;
+    if report.dry_run {
+        tx.rollback().await?;
+    } else {
+        tx.commit().await?;
+    }
+    info!(
+        created = report.albums_created, updated = report.albums_updated,
+        unmatched = report.unmatched.len(), dry_run = report.dry_run, "library restored",
+    );
+    Ok(Json(report))
+}
+
+/*──────── export ─────────────────────────────────────────────────────────*/
+
+#[derive(sqlx::FromRow)]
+struct Owned<T> {
+    owner: Uuid,
+    #[sqlx(flatten)]
+    item:  T,
+}
+
+/// Group `(owner, item)` rows, keeping their order.
+fn by_owner<T>(rows: Vec<Owned<T>>) -> HashMap<Uuid, Vec<T>> {
+    let mut out: HashMap<Uuid, Vec<T>> = HashMap::new();
+    for r in rows {
+        out.entry(r.owner).or_default().push(r.item);
+    }
+    out
+}
+
+/// Everything, from one snapshot.
+async fn load(db: &PgPool) -> sqlx::Result<Archive> {
+    let mut tx = db.begin().await?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY").execute(&mut *tx).await?;
+
+    let mut albums: Vec<ArchivedAlbum> = sqlx::query_as(
+        "SELECT id, title, artist, year, venue, kind, source, imported_at, needs_review
+           FROM albums ORDER BY id",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let mut tracks = by_owner::<ArchivedTrack>(
+        sqlx::query_as(
+            r#"SELECT album_id AS owner, id, disc, "index", title, duration_sec FROM tracks
+                WHERE album_id IS NOT NULL ORDER BY album_id, disc, "index""#,
+        )
+        .fetch_all(&mut *tx)
+        .await?,
+    );
+    let mut files = by_owner::<ArchivedFile>(
+        sqlx::query_as(
+            "SELECT track_id AS owner, id, path, codec, size_bytes, sha256, status FROM files
+              WHERE track_id IS NOT NULL ORDER BY path",
+        )
+        .fetch_all(&mut *tx)
+        .await?,
+    );
+    let mut recordings = by_owner::<ChosenMatch>(
+        sqlx::query_as(
+            "SELECT file_id AS owner, mb_recording AS mbid, score, raw_json
+               FROM matches_track WHERE chosen",
+        )
+        .fetch_all(&mut *tx)
+        .await?,
+    );
+    let mut releases = by_owner::<ChosenMatch>(
+        sqlx::query_as(
+            "SELECT album_id AS owner, mb_release AS mbid, confidence AS score, raw_json
+               FROM matches_album WHERE chosen",
+        )
+        .fetch_all(&mut *tx)
+        .await?,
+    );
+    let mut decisions = by_owner::<ArchivedDecision>(
+        sqlx::query_as(
+            "SELECT COALESCE(d.album_id, t.album_id) AS owner, d.file_id, d.action, d.mbid, d.metadata,
+                    d.decided_by, d.decided_at
+               FROM match_decisions d
+               LEFT JOIN files f  ON f.id = d.file_id
+               LEFT JOIN tracks t ON t.id = f.track_id
+              WHERE COALESCE(d.album_id, t.album_id) IS NOT NULL
+           ORDER BY d.id",
+        )
+        .fetch_all(&mut *tx)
+        .await?,
+    );
+    let mut edits = by_owner::<HistoryEntry>(
+        sqlx::query_as(
+            "SELECT COALESCE(t.album_id, a.id) AS owner, h.id, h.entity, h.entity_id, h.field, h.old_value,
+                    h.new_value, h.changed_by, h.via, h.changed_at
+               FROM metadata_history h
+               LEFT JOIN albums a ON h.entity = 'album' AND a.id = h.entity_id
+               LEFT JOIN tracks t ON h.entity = 'track' AND t.id = h.entity_id
+              WHERE COALESCE(t.album_id, a.id) IS NOT NULL
+           ORDER BY h.id",
+        )
+        .fetch_all(&mut *tx)
+        .await?,
+    );
+    let mut merges = by_owner::<ArchivedMerge>(
+        sqlx::query_as(
+            "SELECT into_id AS owner, from_id, path, title, artist, merged_by, merged_at
+               FROM album_merges ORDER BY merged_at",
+        )
+        .fetch_all(&mut *tx)
+        .await?,
+    );
+    for a in &mut albums {
+        a.tracks = tracks.remove(&a.id).unwrap_or_default();
+        for t in &mut a.tracks {
+            t.files = files.remove(&t.id).unwrap_or_default();
+            for f in &mut t.files {
+                f.recording = recordings.remove(&f.id).and_then(|mut m| m.pop());
+            }
+        }
+        a.release = releases.remove(&a.id).and_then(|mut m| m.pop());
+        a.decisions = decisions.remove(&a.id).unwrap_or_default();
+        a.history = edits.remove(&a.id).unwrap_or_default();
+        a.merged_from = merges.remove(&a.id).unwrap_or_default();
+    }
+
+    let mut playlists: Vec<ArchivedPlaylist> = sqlx::query_as(
+        "SELECT p.id, u.username AS owner, p.name, p.comment, p.public, p.created_at
+           FROM playlists p JOIN users u ON u.id = p.owner_id
+       ORDER BY p.created_at, p.id",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let entries: Vec<(Uuid, Uuid)> =
+        sqlx::query_as("SELECT playlist_id, track_id FROM playlist_entries ORDER BY playlist_id, position")
+            .fetch_all(&mut *tx)
+            .await?;
+    let mut entries = by_owner(entries.into_iter().map(|(owner, item)| Owned { owner, item }).collect());
+    for p in &mut playlists {
+        p.tracks = entries.remove(&p.id).unwrap_or_default();
+    }
+
+    let tombstones: Vec<ArchivedTombstone> = sqlx::query_as(
+        "SELECT album_id, path, file_paths, title, artist, deleted_by, deleted_at
+           FROM tombstones ORDER BY deleted_at",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    let header =
+        ArchiveHeader { format: FORMAT.into(), version: VERSION, exported_at: OffsetDateTime::now_utc() };
+    Ok(Archive { header, albums, playlists, tombstones })
+}
+
+/*──────── restore ────────────────────────────────────────────────────────*/
+
+/// A whole JSON document, or NDJSON starting with a `header` record.
+fn parse(body: &[u8]) -> ApiResult<Archive> {
+    let archive = match serde_json::from_slice::<Archive>(body) {
+        Ok(a) => a,
+        Err(whole) => {
+            let mut lines = body
+                .split(|&b| b == b'\n')
+                .enumerate()
+                .filter(|(_, l)| !l.iter().all(u8::is_ascii_whitespace));
+            let header = match lines.next().map(|(_, l)| serde_json::from_slice::<Record>(l)) {
+                Some(Ok(Record::Header(h))) => h,
+                _ => return Err(ApiError::Unprocessable(format!("not a {FORMAT} archive: {whole}"))),
+            };
+            let mut a = Archive { header, albums: vec![], playlists: vec![], tombstones: vec![] };
+            for (n, line) in lines {
+                match serde_json::from_slice::<Record>(line) {
+                    Ok(Record::Album(x)) => a.albums.push(x),
+                    Ok(Record::Playlist(x)) => a.playlists.push(x),
+                    Ok(Record::Tombstone(x)) => a.tombstones.push(x),
+                    Ok(Record::Header(_)) => {
+                        return Err(ApiError::Unprocessable(format!("line {}: a second header", n + 1)))
+                    }
+                    Err(e) => return Err(ApiError::Unprocessable(format!("line {}: {e}", n + 1))),
+                }
+            }
+            a
+        }
+    };
+    if archive.header.format != FORMAT {
+        let format = &archive.header.format;
+        return Err(ApiError::Unprocessable(format!("not a {FORMAT} archive: format {format:?}")));
+    }
+    if archive.header.version > VERSION {
+        return Err(ApiError::Unprocessable(format!(
+            "archive version {} is newer than this server reads ({VERSION})",
+            archive.header.version
+        )));
+    }
+    Ok(archive)
+}
+
+/// Where an archived file is now.
+#[derive(Debug, Clone, Copy, sqlx::FromRow)]
+struct Current {
+    file_id:  Uuid,
+    track_id: Uuid,
+    album_id: Option<Uuid>,
+}
+
+struct Restorer<'t> {
+    tx:      Transaction<'t, Postgres>,
+    /// Archived path → canonical path, for files on disk under a root.
+    on_disk: HashMap<String, String>,
+    /// Archived track id → track id now, for playlists.
+    tracks:  HashMap<Uuid, Uuid>,
+    report:  RestoreReport,
+}
+
+impl Restorer<'_> {
+    fn unmatched(&mut self, kind: UnmatchedKind, id: Uuid, detail: impl Into<String>) {
+        self.report.unmatched.push(Unmatched { kind, id, detail: detail.into() });
+    }
+
+    async fn album(&mut self, a: ArchivedAlbum) -> ApiResult<()> {
+        let links = self.link_files(&a).await?;
+        let (exists,): (bool,) = sqlx::query_as("SELECT EXISTS (SELECT 1 FROM albums WHERE id=$1)")
+            .bind(a.id)
+            .fetch_one(&mut *self.tx)
+            .await?;
+        // the album most of its files belong to now
+        let mut votes: HashMap<Uuid, usize> = HashMap::new();
+        for album in links.values().filter_map(|c| c.album_id) {
+            *votes.entry(album).or_default() += 1;
+        }
+        let existing = if exists { Some(a.id) } else { votes.into_iter().max_by_key(|v| v.1).map(|v| v.0) };
+
+        let target = match existing {
+            Some(target) => {
+                self.update_album(target, &a, &links).await?;
+                target
+            }
+            None if a.tracks.iter().flat_map(|t| &t.files).any(|f| self.on_disk.contains_key(&f.path)) => {
+                self.create_album(&a).await?;
+                a.id
+            }
+            None => {
+                self.unmatched(UnmatchedKind::Album, a.id, format!(
+                    "{} – none of its files found by path, checksum or on disk",
+                    a.title.as_deref().unwrap_or("untitled"),
+                ));
+                return Ok(());
+            }
+        };
+        self.restore_matches(target, &a).await
+    }
+
+    /// Archived file id → where that file is now, by path, else checksum.
+    async fn link_files(&mut self, a: &ArchivedAlbum) -> ApiResult<HashMap<Uuid, Current>> {
+        let files: Vec<&ArchivedFile> = a.tracks.iter().flat_map(|t| &t.files).collect();
+        let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        let sums: Vec<&str> = files.iter().filter_map(|f| f.sha256.as_deref()).collect();
+        type Row = (String, Option<String>, Uuid, Uuid, Option<Uuid>);
+        let rows: Vec<(String, Option<String>, Current)> = sqlx::query_as::<_, Row>(
+            "SELECT f.path, f.sha256, f.id, f.track_id, t.album_id
+               FROM files f JOIN tracks t ON t.id = f.track_id
+              WHERE f.path = ANY($1) OR f.sha256 = ANY($2)",
+        )
+        .bind(&paths)
+        .bind(&sums)
+        .fetch_all(&mut *self.tx)
+        .await?
+        .into_iter()
+        .map(|(p, s, file_id, track_id, album_id)| (p, s, Current { file_id, track_id, album_id }))
+        .collect();
+
+        let mut out = HashMap::new();
+        let mut taken = HashSet::new();
+        for f in &files {
+            if let Some((_, _, c)) = rows.iter().find(|r| r.0 == f.path) {
+                taken.insert(c.file_id);
+                out.insert(f.id, *c);
+                self.report.files_by_path += 1;
+            }
+        }
+        for f in &files {
+            let Some(sum) = f.sha256.as_deref().filter(|_| !out.contains_key(&f.id)) else { continue };
+            let found = rows.iter().find(|r| r.1.as_deref() == Some(sum) && !taken.contains(&r.2.file_id));
+            if let Some((_, _, c)) = found {
+                taken.insert(c.file_id);
+                out.insert(f.id, *c);
+                self.report.files_by_checksum += 1;
+            }
+        }
+        Ok(out)
+    }
+
+    /// Put the archived metadata back on an album that's still (or again)
+    /// in the library.
+    async fn update_album(&mut self, target: Uuid, a: &ArchivedAlbum, links: &HashMap<Uuid, Current>)
+        -> ApiResult<()>
+    {
+        for t in &a.tracks {
+            if let Some(now) = t.files.iter().find_map(|f| links.get(&f.id)) {
+                self.tracks.insert(t.id, now.track_id);
+            }
+        }
+        self.restore_history(target, a).await?;
+        sqlx::query("UPDATE albums SET title=$2, artist=$3, year=$4, venue=$5, kind=$6 WHERE id=$1")
+            .bind(target)
+            .bind(&a.title)
+            .bind(&a.artist)
+            .bind(a.year)
+            .bind(&a.venue)
+            .bind(a.kind)
+            .execute(&mut *self.tx)
+            .await?;
+        for t in &a.tracks {
+            if let Some(now) = t.files.iter().find_map(|f| links.get(&f.id)) {
+                sqlx::query("UPDATE tracks SET title=$2 WHERE id=$1")
+                    .bind(now.track_id)
+                    .bind(&t.title)
+                    .execute(&mut *self.tx)
+                    .await?;
+            }
+        }
+        self.restore_positions(a, links).await?;
+        for t in &a.tracks {
+            for f in &t.files {
+                match links.get(&f.id) {
+                    Some(now) => {
+                        sqlx::query("UPDATE files SET sha256 = COALESCE(sha256, $2) WHERE id=$1")
+                            .bind(now.file_id)
+                            .bind(&f.sha256)
+                            .execute(&mut *self.tx)
+                            .await?;
+                        self.restore_recording(now.file_id, f).await?;
+                    }
+                    None if self.on_disk.contains_key(&f.path) => {
+                        let detail = format!("{}: on disk but not imported", f.path);
+                        self.unmatched(UnmatchedKind::File, f.id, detail)
+                    }
+                    None => self.unmatched(UnmatchedKind::File, f.id, format!("{}: not found", f.path)),
+                }
+            }
+        }
+        self.report.albums_updated += 1;
+        Ok(())
+    }
+
+    /// Archived disc / index back onto the mapped tracks, in two passes
+    /// (through negative indices) like a merge.  A track whose slot is held
+    /// by one staying put is left where it is and reported.
+    async fn restore_positions(&mut self, a: &ArchivedAlbum, links: &HashMap<Uuid, Current>) -> ApiResult<()> {
+        let mut moves: HashMap<Uuid, (Uuid, i32, i32)> = HashMap::new();
+        for t in &a.tracks {
+            let (Some(disc), Some(index)) = (t.disc, t.index) else { continue };
+            if let Some(now) = t.files.iter().find_map(|f| links.get(&f.id)) {
+                moves.entry(now.track_id).or_insert((t.id, disc, index));
+            }
+        }
+        let ids: Vec<Uuid> = moves.keys().copied().collect();
+        type Slot = (Option<Uuid>, Option<i32>, Option<i32>);
+        let now: HashMap<Uuid, Slot> = sqlx::query_as::<_, (Uuid, Option<Uuid>, Option<i32>, Option<i32>)>(
+            r#"SELECT id, album_id, disc, "index" FROM tracks
+                WHERE album_id IN (SELECT album_id FROM tracks WHERE id = ANY($1))"#,
+        )
+        .bind(&ids)
+        .fetch_all(&mut *self.tx)
+        .await?
+        .into_iter()
+        .map(|(id, album, disc, index)| (id, (album, disc, index)))
+        .collect();
+
+        // drop moves onto a slot someone else keeps, until none are left
+        loop {
+            let mut held: HashMap<Slot, Uuid> = now
+                .iter()
+                .filter(|(id, _)| !moves.contains_key(id))
+                .map(|(&id, &slot)| (slot, id))
+                .collect();
+            let mut blocked = None;
+            for (&id, &(_, disc, index)) in &moves {
+                let slot = (now.get(&id).and_then(|s| s.0), Some(disc), Some(index));
+                if let Some(other) = held.insert(slot, id) {
+                    blocked = Some((id, other, disc, index));
+                    break;
+                }
+            }
+            let Some((id, other, disc, index)) = blocked else { break };
+            let (archived, ..) = moves.remove(&id).expect("blocked move");
+            self.unmatched(UnmatchedKind::Track, archived, format!(
+                "disc {disc} index {index} is taken by track {other}",
+            ));
+        }
+
+        let ids: Vec<Uuid> = moves.keys().copied().collect();
+        let discs: Vec<i32> = moves.values().map(|m| m.1).collect();
+        let indices: Vec<i32> = moves.values().map(|m| m.2).collect();
+        sqlx::query(
+            r#"UPDATE tracks t
+                  SET disc = l.disc, "index" = -l.idx
+                 FROM unnest($1::uuid[], $2::int[], $3::int[]) AS l(id, disc, idx)
+                WHERE t.id = l.id"#,
+        )
+        .bind(&ids)
+        .bind(&discs)
+        .bind(&indices)
+        .execute(&mut *self.tx)
+        .await?;
+        sqlx::query(r#"UPDATE tracks SET "index" = -"index" WHERE id = ANY($1)"#)
+            .bind(&ids)
+            .execute(&mut *self.tx)
+            .await?;
+        Ok(())
+    }
+
+    /// Recreate an album none of whose files are in the library, from
+    /// those still on disk.
+    async fn create_album(&mut self, a: &ArchivedAlbum) -> ApiResult<()> {
+        sqlx::query(
+            "INSERT INTO albums(id, title, artist, year, venue, kind, source, imported_at, needs_review)
+             VALUES ($1,$2,$3,$4,$5,$6,$7,COALESCE($8, now()),$9)",
+        )
+        .bind(a.id)
+        .bind(&a.title)
+        .bind(&a.artist)
+        .bind(a.year)
+        .bind(&a.venue)
+        .bind(a.kind)
+        .bind(&a.source)
+        .bind(a.imported_at)
+        .bind(a.needs_review)
+        .execute(&mut *self.tx)
+        .await?;
+        for t in &a.tracks {
+            let (track_id,): (Uuid,) = sqlx::query_as(
+                r#"INSERT INTO tracks(id, album_id, disc, "index", title, duration_sec)
+                   VALUES (CASE WHEN EXISTS (SELECT 1 FROM tracks WHERE id=$1)
+                                THEN gen_random_uuid() ELSE $1 END, $2,$3,$4,$5,$6)
+                RETURNING id"#,
+            )
+            .bind(t.id)
+            .bind(a.id)
+            .bind(t.disc)
+            .bind(t.index)
+            .bind(&t.title)
+            .bind(t.duration_sec)
+            .fetch_one(&mut *self.tx)
+            .await?;
+            self.tracks.insert(t.id, track_id);
+
+            for f in &t.files {
+                let Some(real) = self.on_disk.get(&f.path).cloned() else {
+                    self.unmatched(UnmatchedKind::File, f.id, format!("{}: not found", f.path));
+                    continue;
+                };
+                // mid-pipeline files start over; finished ones keep their state
+                let status = match f.status {
+                    FileStatus::Ready | FileStatus::Error => f.status,
+                    _ => FileStatus::New,
+                };
+                let inserted: Option<(Uuid,)> = sqlx::query_as(
+                    "INSERT INTO files(id, track_id, path, codec, size_bytes, sha256, status)
+                     VALUES (CASE WHEN EXISTS (SELECT 1 FROM files WHERE id=$1)
+                                  THEN gen_random_uuid() ELSE $1 END, $2,$3,$4,$5,$6,$7)
+                     ON CONFLICT (path) DO NOTHING
+                     RETURNING id",
+                )
+                .bind(f.id)
+                .bind(track_id)
+                .bind(&real)
+                .bind(&f.codec)
+                .bind(f.size_bytes)
+                .bind(&f.sha256)
+                .bind(status)
+                .fetch_optional(&mut *self.tx)
+                .await?;
+                let Some((file_id,)) = inserted else {
+                    let detail = format!("{real}: already belongs to another track");
+                    self.unmatched(UnmatchedKind::File, f.id, detail);
+                    continue;
+                };
+                self.report.files_from_disk += 1;
+                if status == FileStatus::New {
+                    jobs::enqueue(&mut *self.tx, Stage::Fingerprint, Some(a.id), Some(file_id)).await?;
+                } else {
+                    self.restore_recording(file_id, f).await?;
+                }
+            }
+        }
+        self.restore_history(a.id, a).await?;
+        self.report.albums_created += 1;
+        Ok(())
+    }
+
+    async fn restore_recording(&mut self, file_id: Uuid, f: &ArchivedFile) -> sqlx::Result<()> {
+        let Some(m) = &f.recording else { return Ok(()) };
+        sqlx::query("UPDATE matches_track SET chosen = FALSE WHERE file_id=$1 AND mb_recording <> $2")
+            .bind(file_id)
+            .bind(m.mbid)
+            .execute(&mut *self.tx)
+            .await?;
+        sqlx::query(
+            "INSERT INTO matches_track(file_id, mb_recording, score, raw_json, chosen)
+             VALUES ($1,$2,$3,$4,TRUE)
+             ON CONFLICT (file_id, mb_recording) DO UPDATE SET chosen = TRUE",
+        )
+        .bind(file_id)
+        .bind(m.mbid)
+        .bind(m.score)
+        .bind(&m.raw_json)
+        .execute(&mut *self.tx)
+        .await?;
+        Ok(())
+    }
+
+    /// The archived history of `a`, onto `target` and the tracks mapped so
+    /// far.  Runs before any metadata is written back, so the restore's own
+    /// changes get later ids and stay the newest to list and revert; tx 0
+    /// keeps the trigger from folding those changes into restored rows.
+    async fn restore_history(&mut self, target: Uuid, a: &ArchivedAlbum) -> sqlx::Result<()> {
+        for h in &a.history {
+            let entity_id = match h.entity {
+                Entity::Album => target,
+                Entity::Track => match self.tracks.get(&h.entity_id) {
+                    Some(&t) => t,
+                    None => continue,
+                },
+            };
+            sqlx::query(
+                "INSERT INTO metadata_history(entity, entity_id, field, old_value, new_value, changed_by, via,
+                                              changed_at, tx_id)
+                 SELECT $1,$2,$3,$4,$5,$6,$7,$8,0
+                  WHERE NOT EXISTS (SELECT 1 FROM metadata_history
+                                     WHERE entity = $1 AND entity_id = $2 AND field = $3
+                                       AND changed_at = $8)",
+            )
+            .bind(h.entity)
+            .bind(entity_id)
+            .bind(&h.field)
+            .bind(&h.old_value)
+            .bind(&h.new_value)
+            .bind(&h.changed_by)
+            .bind(&h.via)
+            .bind(h.changed_at)
+            .execute(&mut *self.tx)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Chosen release, decisions and merges, onto `target`.
+    async fn restore_matches(&mut self, target: Uuid, a: &ArchivedAlbum) -> ApiResult<()> {
+        if let Some(m) = &a.release {
+            sqlx::query("UPDATE matches_album SET chosen = FALSE WHERE album_id=$1 AND mb_release <> $2")
+                .bind(target)
+                .bind(m.mbid)
+                .execute(&mut *self.tx)
+                .await?;
+            sqlx::query(
+                "INSERT INTO matches_album(album_id, mb_release, confidence, raw_json, chosen)
+                 VALUES ($1,$2,$3,$4,TRUE)
+                 ON CONFLICT (album_id, mb_release) DO UPDATE SET chosen = TRUE",
+            )
+            .bind(target)
+            .bind(m.mbid)
+            .bind(m.score)
+            .bind(&m.raw_json)
+            .execute(&mut *self.tx)
+            .await?;
+        }
+
+        // archived file id → file id now
+        let files: Vec<Uuid> = a.tracks.iter().flat_map(|t| &t.files).map(|f| f.id).collect();
+        let paths: Vec<&str> = a.tracks.iter().flat_map(|t| &t.files).map(|f| f.path.as_str()).collect();
+        let now: Vec<(Uuid, Uuid)> = sqlx::query_as(
+            "SELECT a.id, f.id FROM unnest($1::uuid[], $2::text[]) AS a(id, path)
+               JOIN files f ON f.id = a.id OR f.path = a.path
+               JOIN tracks t ON t.id = f.track_id AND t.album_id = $3",
+        )
+        .bind(&files)
+        .bind(&paths)
+        .bind(target)
+        .fetch_all(&mut *self.tx)
+        .await?;
+        let file_now: HashMap<Uuid, Uuid> = now.into_iter().collect();
+
+        for d in &a.decisions {
+            let (album, file) = match d.file_id {
+                None => (Some(target), None),
+                Some(f) => match file_now.get(&f) {
+                    Some(&f) => (None, Some(f)),
+                    None => continue,
+                },
+            };
+            sqlx::query(
+                "INSERT INTO match_decisions(file_id, album_id, action, mbid, metadata, decided_by,
+                                             decided_at)
+                 SELECT $1,$2,$3,$4,$5,$6,$7
+                  WHERE NOT EXISTS (SELECT 1 FROM match_decisions
+                                     WHERE file_id IS NOT DISTINCT FROM $1
+                                       AND album_id IS NOT DISTINCT FROM $2
+                                       AND action = $3 AND decided_at = $7)",
+            )
+            .bind(file)
+            .bind(album)
+            .bind(&d.action)
+            .bind(d.mbid)
+            .bind(&d.metadata)
+            .bind(&d.decided_by)
+            .bind(d.decided_at)
+            .execute(&mut *self.tx)
+            .await?;
+        }
+
+        for m in &a.merged_from {
+            sqlx::query(
+                "INSERT INTO album_merges(from_id, into_id, path, title, artist, merged_by, merged_at)
+                 VALUES ($1,$2,$3,$4,$5,$6,$7) ON CONFLICT (from_id) DO NOTHING",
+            )
+            .bind(m.from_id)
+            .bind(target)
+            .bind(&m.path)
+            .bind(&m.title)
+            .bind(&m.artist)
+            .bind(&m.merged_by)
+            .bind(m.merged_at)
+            .execute(&mut *self.tx)
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn playlist(&mut self, p: ArchivedPlaylist, who: &Principal) -> ApiResult<()> {
+        let (exists,): (bool,) = sqlx::query_as("SELECT EXISTS (SELECT 1 FROM playlists WHERE id=$1)")
+            .bind(p.id)
+            .fetch_one(&mut *self.tx)
+            .await?;
+        if exists {
+            self.report.playlists_existing += 1;
+            return Ok(());
+        }
+        sqlx::query(
+            "INSERT INTO playlists(id, owner_id, name, comment, public, created_at)
+             VALUES ($1, COALESCE((SELECT id FROM users WHERE username=$2), $3), $4, $5, $6, $7)",
+        )
+        .bind(p.id)
+        .bind(&p.owner)
+        .bind(who.user_id)
+        .bind(&p.name)
+        .bind(&p.comment)
+        .bind(p.public)
+        .bind(p.created_at)
+        .execute(&mut *self.tx)
+        .await?;
+
+        let known: Vec<(Uuid,)> = sqlx::query_as("SELECT id FROM tracks WHERE id = ANY($1)")
+            .bind(&p.tracks)
+            .fetch_all(&mut *self.tx)
+            .await?;
+        let known: HashSet<Uuid> = known.into_iter().map(|(t,)| t).collect();
+        let mut tracks = Vec::with_capacity(p.tracks.len());
+        for t in &p.tracks {
+            match self.tracks.get(t).copied().or_else(|| known.get(t).copied()) {
+                Some(now) => tracks.push(now),
+                None => {
+                    let detail = format!("track in playlist {:?}", p.name);
+                    self.unmatched(UnmatchedKind::PlaylistEntry, *t, detail)
+                }
+            }
+        }
+        sqlx::query(
+            "INSERT INTO playlist_entries(playlist_id, position, track_id)
+             SELECT $1, n, t FROM unnest($2::uuid[]) WITH ORDINALITY AS e(t, n)",
+        )
+        .bind(p.id)
+        .bind(&tracks)
+        .execute(&mut *self.tx)
+        .await?;
+        self.report.playlists_restored += 1;
+        Ok(())
+    }
+
+    async fn tombstone(&mut self, t: ArchivedTombstone) -> ApiResult<()> {
+        let done = sqlx::query(
+            "INSERT INTO tombstones(album_id, path, file_paths, title, artist, deleted_by, deleted_at)
+             VALUES ($1,$2,$3,$4,$5,$6,$7) ON CONFLICT (album_id) DO NOTHING",
+        )
+        .bind(t.album_id)
+        .bind(&t.path)
+        .bind(&t.file_paths)
+        .bind(&t.title)
+        .bind(&t.artist)
+        .bind(&t.deleted_by)
+        .bind(t.deleted_at)
+        .execute(&mut *self.tx)
+        .await?;
+        self.report.tombstones_restored += done.rows_affected() as u32;
+        Ok(())
+    }
+}
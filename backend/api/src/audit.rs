@@ -0,0 +1,87 @@
+//! Append-only audit trail (`audit_log`) for destructive and administrative
+//! actions.  Rows are written in the same transaction as the change.
+
+use axum::extract::State;
+use serde::{Deserialize, Serialize};
+use sqlx::{Executor, Postgres, QueryBuilder};
+use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    error::{ApiResult, Json, Problem, Query},
+    AppState,
+};
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct AuditEntry {
+    pub id:          i64,
+    pub actor:       String,
+    pub action:      String,
+    pub target_kind: String,
+    pub target_id:   Option<Uuid>,
+    pub detail:      serde_json::Value,
+    #[serde(with = "time::serde::rfc3339")]
+    pub at:          OffsetDateTime,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(deny_unknown_fields)]
+pub struct AuditQuery {
+    pub target_id: Option<Uuid>,
+    /// Exact action, e.g. `album.delete`.
+    pub action:    Option<String>,
+    /// Only entries with a smaller id (next page).
+    pub before:    Option<i64>,
+    pub limit:     Option<i64>,
+}
+
+/// Record `action` by `actor`; returns the entry id.
+pub async fn record<'e, E>(
+    db:     E,
+    actor:  &str,
+    action: &str,
+    target: (&str, Option<Uuid>),
+    detail: serde_json::Value,
+) -> sqlx::Result<i64>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let (id,) = sqlx::query_as(
+        "INSERT INTO audit_log(actor, action, target_kind, target_id, detail)
+              VALUES ($1,$2,$3,$4,$5)
+           RETURNING id",
+    )
+    .bind(actor)
+    .bind(action)
+    .bind(target.0)
+    .bind(target.1)
+    .bind(detail)
+    .fetch_one(db)
+    .await?;
+    Ok(id)
+}
+
+/// `GET /audit` – newest first.
+#[utoipa::path(
+    get, path = "/audit", tag = "auth", params(AuditQuery),
+    responses(
+        (status = 200, body = Vec<AuditEntry>),
+        (status = 400, response = Problem),
+        (status = 403, response = Problem),
+    ),
+)]
+pub async fn list(State(app): State<AppState>, Query(q): Query<AuditQuery>)
+    -> ApiResult<Json<Vec<AuditEntry>>>
+{
+    let mut qb = QueryBuilder::<Postgres>::new(
+        "SELECT id, actor, action, target_kind, target_id, detail, at FROM audit_log WHERE true",
+    );
+    if let Some(t) = q.target_id { qb.push(" AND target_id = ").push_bind(t); }
+    if let Some(a) = q.action    { qb.push(" AND action = ").push_bind(a); }
+    if let Some(b) = q.before    { qb.push(" AND id < ").push_bind(b); }
+    qb.push(" ORDER BY id DESC LIMIT ").push_bind(q.limit.unwrap_or(100).clamp(1, 500));
+
+    Ok(Json(qb.build_query_as().fetch_all(&app.db).await?))
+}
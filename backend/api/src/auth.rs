@@ -0,0 +1,698 @@
+//! Users, sessions, API tokens and scope checks.
+//!
+//! `POST /auth/login` trades a password for a session token, returned in the
+//! body and as an HttpOnly cookie so `<audio>` and `EventSource` work from
+//! the web UI.  Scripts use long-lived API tokens from `/auth/tokens`.  Both
+//! go in `Authorization: Bearer …`; only their SHA-256 is stored.
+//!
+//! Subsonic clients (`/rest/*`) can't send a Bearer token; they get app
+//! passwords – API tokens of kind `subsonic` whose secret is kept in clear,
+//! because Subsonic token auth sends md5(secret + salt).  Those are only
+//! accepted by [`subsonic_principal`].
+//!
+//! Every route group in `main.rs` is wrapped by [`scoped`] with the scope it
+//! needs.  `admin` implies all others.
+
+use std::time::Duration;
+
+use argon2::{
+    password_hash::{rand_core::{OsRng, RngCore}, SaltString},
+    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+};
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use subtle::ConstantTimeEq;
+use time::OffsetDateTime;
+use tracing::{info, instrument, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    error::{ApiError, ApiResult, Json, Path, Problem},
+    AppState,
+};
+
+const COOKIE: &str = "setlist_session";
+
+/*──────── config ─────────────────────────────────────────────────────────*/
+
+#[derive(Clone, Copy, Debug)]
+pub struct AuthConfig {
+    pub session_ttl:   Duration,   // SESSION_TTL_HOURS   (default 168)
+    pub secure_cookie: bool,       // AUTH_COOKIE_SECURE  (default off – tailnet is plain http)
+}
+
+impl AuthConfig {
+    pub fn from_env() -> Self {
+        let hours = std::env::var("SESSION_TTL_HOURS").ok().and_then(|v| v.parse().ok()).unwrap_or(168);
+        Self {
+            session_ttl:   Duration::from_secs(hours * 3600),
+            secure_cookie: std::env::var("AUTH_COOKIE_SECURE").is_ok_and(|v| v == "1" || v == "true"),
+        }
+    }
+}
+
+/*──────── model ──────────────────────────────────────────────────────────*/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum Scope {
+    /// Library metadata, listings, events.
+    Read,
+    /// Audio bytes (`/…/stream`).
+    Stream,
+    /// Create albums, upload, manifests, start imports.
+    Upload,
+    /// Metadata corrections and review decisions.
+    Edit,
+    /// Users, metrics – and every other scope.
+    Admin,
+}
+
+impl sqlx::postgres::PgHasArrayType for Scope {
+    fn array_type_info() -> sqlx::postgres::PgTypeInfo {
+        <String as sqlx::postgres::PgHasArrayType>::array_type_info()
+    }
+}
+
+/// Who is calling; put in the request extensions by [`require`].
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Principal {
+    pub user_id:  Uuid,
+    pub username: String,
+    pub token_id: Uuid,
+    /// Token scopes ∩ user scopes.
+    pub scopes:   Vec<Scope>,
+}
+
+impl Principal {
+    pub fn has(&self, scope: Scope) -> bool {
+        self.scopes.contains(&Scope::Admin) || self.scopes.contains(&scope)
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Principal {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> ApiResult<Self> {
+        parts
+            .extensions
+            .get::<Principal>()
+            .cloned()
+            .ok_or_else(|| ApiError::Unauthorized("authentication required".into()))
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct User {
+    pub id:          Uuid,
+    pub username:    String,
+    pub scopes:      Vec<Scope>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at:  OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub disabled_at: Option<OffsetDateTime>,
+}
+
+const USER_COLUMNS: &str = "id, username, scopes, created_at, disabled_at";
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct Token {
+    pub id:           Uuid,
+    /// `session`, `api` or `subsonic`
+    pub kind:         String,
+    pub name:         Option<String>,
+    pub scopes:       Vec<Scope>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at:   OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub expires_at:   Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_used_at: Option<OffsetDateTime>,
+}
+
+const TOKEN_COLUMNS: &str = "id, kind, name, scopes, created_at, expires_at, last_used_at";
+
+/// A freshly minted token – the only time the secret is shown.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IssuedToken {
+    pub token: String,
+    #[serde(flatten)]
+    pub info:  Token,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Login {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NewToken {
+    pub name:            String,
+    pub scopes:          Vec<Scope>,
+    /// Omit for a token that lives until revoked.
+    pub expires_in_days: Option<u32>,
+    /// App password for Subsonic clients; only valid on `/rest/*`.
+    #[serde(default)]
+    pub subsonic:        bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NewUser {
+    pub username: String,
+    pub password: String,
+    /// Defaults to `read` + `stream`.
+    pub scopes:   Option<Vec<Scope>>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct UserPatch {
+    /// Also ends the user's sessions.
+    pub password: Option<String>,
+    pub scopes:   Option<Vec<Scope>>,
+    pub disabled: Option<bool>,
+}
+
+/*──────── middleware ─────────────────────────────────────────────────────*/
+
+/// Put `router` behind authentication; `Some(scope)` also demands it.
+pub fn scoped(router: Router<AppState>, app: &AppState, scope: Option<Scope>) -> Router<AppState> {
+    router.route_layer(middleware::from_fn_with_state(
+        app.clone(),
+        move |st: State<AppState>, req: Request, next: Next| require(st, scope, req, next),
+    ))
+}
+
+async fn require(State(app): State<AppState>, scope: Option<Scope>, mut req: Request, next: Next)
+    -> ApiResult<Response>
+{
+    let secret = presented_token(req.headers())
+        .ok_or_else(|| ApiError::Unauthorized("authentication required".into()))?;
+    let who = resolve(&app.db, secret)
+        .await?
+        .ok_or_else(|| ApiError::Unauthorized("invalid or expired token".into()))?;
+    if let Some(scope) = scope.filter(|s| !who.has(*s)) {
+        return Err(ApiError::Forbidden(format!("token lacks the {scope:?} scope").to_lowercase()));
+    }
+    req.extensions_mut().insert(who);
+    Ok(next.run(req).await)
+}
+
+/// `Authorization: Bearer …`, else the session cookie.
+pub(crate) fn presented_token(headers: &HeaderMap) -> Option<&str> {
+    if let Some(v) = headers.get(header::AUTHORIZATION) {
+        return v.to_str().ok()?.strip_prefix("Bearer ").map(str::trim);
+    }
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|c| c.split(';'))
+        .find_map(|kv| kv.trim().strip_prefix(COOKIE)?.strip_prefix('='))
+}
+
+#[derive(sqlx::FromRow)]
+struct Presented {
+    token_id:     Uuid,
+    token_scopes: Vec<Scope>,
+    user_id:      Uuid,
+    username:     String,
+    user_scopes:  Vec<Scope>,
+}
+
+pub(crate) async fn resolve(db: &PgPool, secret: &str) -> sqlx::Result<Option<Principal>> {
+    let Some(p) = sqlx::query_as::<_, Presented>(
+        "SELECT t.id AS token_id, t.scopes AS token_scopes,
+                u.id AS user_id, u.username, u.scopes AS user_scopes
+           FROM auth_tokens t JOIN users u ON u.id = t.user_id
+          WHERE t.token_hash = $1
+            AND t.kind <> 'subsonic'
+            AND t.revoked_at IS NULL
+            AND (t.expires_at IS NULL OR t.expires_at > now())
+            AND u.disabled_at IS NULL",
+    )
+    .bind(digest(secret))
+    .fetch_optional(db)
+    .await?
+    else {
+        return Ok(None);
+    };
+    touch(db, p.token_id).await?;
+    Ok(Some(p.into_principal()))
+}
+
+/// What a Subsonic client proves it knows.
+pub enum SubsonicCredential<'a> {
+    /// `t` = md5(secret + `s`), hex.
+    Token { token: &'a str, salt: &'a str },
+    /// `p`, already decoded from `enc:<hex>`.
+    Password(&'a str),
+}
+
+#[derive(sqlx::FromRow)]
+struct SubsonicSecret {
+    #[sqlx(flatten)]
+    presented: Presented,
+    secret:    String,
+}
+
+/// Principal for `username` if `cred` matches one of their live Subsonic
+/// app passwords.
+pub async fn subsonic_principal(db: &PgPool, username: &str, cred: SubsonicCredential<'_>)
+    -> sqlx::Result<Option<Principal>>
+{
+    let candidates: Vec<SubsonicSecret> = sqlx::query_as(
+        "SELECT t.id AS token_id, t.scopes AS token_scopes, t.subsonic_secret AS secret,
+                u.id AS user_id, u.username, u.scopes AS user_scopes
+           FROM auth_tokens t JOIN users u ON u.id = t.user_id
+          WHERE lower(u.username) = lower($1)
+            AND t.kind = 'subsonic'
+            AND t.revoked_at IS NULL
+            AND (t.expires_at IS NULL OR t.expires_at > now())
+            AND u.disabled_at IS NULL",
+    )
+    .bind(username)
+    .fetch_all(db)
+    .await?;
+
+    let matches = |secret: &str| match cred {
+        SubsonicCredential::Token { token, salt } => {
+            let want = hex::encode(Md5::digest(format!("{secret}{salt}").as_bytes()));
+            bool::from(want.as_bytes().ct_eq(token.to_ascii_lowercase().as_bytes()))
+        }
+        SubsonicCredential::Password(p) => bool::from(secret.as_bytes().ct_eq(p.as_bytes())),
+    };
+    let Some(hit) = candidates.into_iter().find(|c| matches(&c.secret)) else {
+        return Ok(None);
+    };
+    touch(db, hit.presented.token_id).await?;
+    Ok(Some(hit.presented.into_principal()))
+}
+
+impl Presented {
+    /// Token scopes narrowed to what the user still holds.
+    fn into_principal(self) -> Principal {
+        let user = Principal {
+            user_id:  self.user_id,
+            username: self.username,
+            token_id: self.token_id,
+            scopes:   self.user_scopes,
+        };
+        let scopes = self.token_scopes.into_iter().filter(|s| user.has(*s)).collect();
+        Principal { scopes, ..user }
+    }
+}
+
+/// At most one `last_used_at` write a minute per token.
+async fn touch(db: &PgPool, token_id: Uuid) -> sqlx::Result<()> {
+    sqlx::query(
+        "UPDATE auth_tokens SET last_used_at = now()
+          WHERE id=$1 AND (last_used_at IS NULL OR last_used_at < now() - interval '1 minute')",
+    )
+    .bind(token_id)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/*──────── sessions ───────────────────────────────────────────────────────*/
+
+/// `POST /auth/login`
+#[utoipa::path(
+    post, path = "/auth/login", tag = "auth", request_body = Login, security(()),
+    responses(
+        (status = 200, description = "Session token (also set as a cookie)", body = IssuedToken),
+        (status = 401, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(username = %l.username))]
+pub async fn login(State(app): State<AppState>, Json(l): Json<Login>) -> ApiResult<Response> {
+    let row: Option<(Uuid, String, Vec<Scope>)> = sqlx::query_as(
+        "SELECT id, password_hash, scopes FROM users
+          WHERE lower(username) = lower($1) AND disabled_at IS NULL",
+    )
+    .bind(l.username.trim())
+    .fetch_optional(&app.db)
+    .await?;
+
+    // unknown users still pay for a hash, so timing doesn't reveal them
+    let (user_id, hash, scopes) = row.unwrap_or_default();
+    let ok = tokio::task::spawn_blocking(move || {
+        let hash = if user_id.is_nil() { dummy_hash() } else { hash };
+        verify_password(&l.password, &hash) && !user_id.is_nil()
+    })
+    .await?;
+    if !ok {
+        warn!("failed login");
+        return Err(ApiError::Unauthorized("invalid username or password".into()));
+    }
+
+    let expires = OffsetDateTime::now_utc() + app.auth.session_ttl;
+    let issued = issue(&app.db, user_id, "session", None, &scopes, Some(expires)).await?;
+    info!("logged in");
+
+    let mut cookie = format!(
+        "{COOKIE}={}; Path=/; HttpOnly; SameSite=Strict; Max-Age={}",
+        issued.token,
+        app.auth.session_ttl.as_secs(),
+    );
+    if app.auth.secure_cookie {
+        cookie.push_str("; Secure");
+    }
+    let mut res = Json(issued).into_response();
+    res.headers_mut().insert(header::SET_COOKIE, HeaderValue::from_str(&cookie).expect("ascii"));
+    Ok(res)
+}
+
+/// `POST /auth/logout` – revoke the token used for this request.
+#[utoipa::path(
+    post, path = "/auth/logout", tag = "auth",
+    responses((status = 204, description = "Token revoked, cookie cleared"), (status = 401, response = Problem)),
+)]
+pub async fn logout(State(app): State<AppState>, who: Principal) -> ApiResult<Response> {
+    sqlx::query("UPDATE auth_tokens SET revoked_at = now() WHERE id=$1")
+        .bind(who.token_id)
+        .execute(&app.db)
+        .await?;
+    let mut res = StatusCode::NO_CONTENT.into_response();
+    res.headers_mut().insert(
+        header::SET_COOKIE,
+        HeaderValue::from_static("setlist_session=; Path=/; HttpOnly; SameSite=Strict; Max-Age=0"),
+    );
+    Ok(res)
+}
+
+/// `GET /auth/me`
+#[utoipa::path(
+    get, path = "/auth/me", tag = "auth",
+    responses((status = 200, body = Principal), (status = 401, response = Problem)),
+)]
+pub async fn me(who: Principal) -> Json<Principal> {
+    Json(who)
+}
+
+/*──────── API tokens ─────────────────────────────────────────────────────*/
+
+/// `GET /auth/tokens` – the caller's live sessions and API tokens.
+#[utoipa::path(
+    get, path = "/auth/tokens", tag = "auth",
+    responses((status = 200, body = Vec<Token>), (status = 401, response = Problem)),
+)]
+pub async fn list_tokens(State(app): State<AppState>, who: Principal) -> ApiResult<Json<Vec<Token>>> {
+    let tokens = sqlx::query_as(&format!(
+        "SELECT {TOKEN_COLUMNS} FROM auth_tokens
+          WHERE user_id=$1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > now())
+       ORDER BY created_at DESC"
+    ))
+    .bind(who.user_id)
+    .fetch_all(&app.db)
+    .await?;
+    Ok(Json(tokens))
+}
+
+/// `POST /auth/tokens` – scopes can't exceed the caller's own.
+#[utoipa::path(
+    post, path = "/auth/tokens", tag = "auth", request_body = NewToken,
+    responses(
+        (status = 201, description = "Secret is shown only in this response", body = IssuedToken),
+        (status = 401, response = Problem),
+        (status = 403, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(user = %who.username))]
+pub async fn create_token(
+    State(app): State<AppState>,
+    who: Principal,
+    Json(t): Json<NewToken>,
+) -> ApiResult<(StatusCode, Json<IssuedToken>)> {
+    let name = crate::albums::clean_text("name", Some(t.name))?
+        .ok_or_else(|| ApiError::Unprocessable("name required".into()))?;
+    if t.scopes.is_empty() {
+        return Err(ApiError::Unprocessable("at least one scope required".into()));
+    }
+    if let Some(s) = t.scopes.iter().find(|s| !who.has(**s)) {
+        return Err(ApiError::Forbidden(format!("cannot grant {s:?} – you don't hold it").to_lowercase()));
+    }
+    let expires = t.expires_in_days.map(|d| OffsetDateTime::now_utc() + time::Duration::days(d.into()));
+    let kind = if t.subsonic { "subsonic" } else { "api" };
+    let issued = issue(&app.db, who.user_id, kind, Some(name), &t.scopes, expires).await?;
+    info!(token_id = %issued.info.id, kind, "API token created");
+    Ok((StatusCode::CREATED, Json(issued)))
+}
+
+/// `DELETE /auth/tokens/:id` – own tokens; admins may revoke anyone's.
+#[utoipa::path(
+    delete, path = "/auth/tokens/{id}", tag = "auth",
+    params(("id" = Uuid, Path, description = "Token id")),
+    responses((status = 204, description = "Revoked"), (status = 401, response = Problem), (status = 404, response = Problem)),
+)]
+pub async fn revoke_token(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+) -> ApiResult<StatusCode> {
+    let res = sqlx::query(
+        "UPDATE auth_tokens SET revoked_at = now()
+          WHERE id=$1 AND revoked_at IS NULL AND (user_id=$2 OR $3)",
+    )
+    .bind(id)
+    .bind(who.user_id)
+    .bind(who.has(Scope::Admin))
+    .execute(&app.db)
+    .await?;
+    if res.rows_affected() == 0 {
+        return Err(ApiError::not_found("token", id));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/*──────── users (admin) ──────────────────────────────────────────────────*/
+
+/// `GET /users`
+#[utoipa::path(
+    get, path = "/users", tag = "auth",
+    responses((status = 200, body = Vec<User>), (status = 401, response = Problem), (status = 403, response = Problem)),
+)]
+pub async fn list_users(State(app): State<AppState>) -> ApiResult<Json<Vec<User>>> {
+    let users = sqlx::query_as(&format!("SELECT {USER_COLUMNS} FROM users ORDER BY lower(username)"))
+        .fetch_all(&app.db)
+        .await?;
+    Ok(Json(users))
+}
+
+/// `POST /users`
+#[utoipa::path(
+    post, path = "/users", tag = "auth", request_body = NewUser,
+    responses(
+        (status = 201, body = User),
+        (status = 401, response = Problem),
+        (status = 403, response = Problem),
+        (status = 409, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(username = %u.username))]
+pub async fn create_user(State(app): State<AppState>, Json(u): Json<NewUser>)
+    -> ApiResult<(StatusCode, Json<User>)>
+{
+    let scopes = u.scopes.unwrap_or_else(|| vec![Scope::Read, Scope::Stream]);
+    let user = insert_user(&app.db, &u.username, u.password, &scopes).await?;
+    info!(user_id = %user.id, "user created");
+    Ok((StatusCode::CREATED, Json(user)))
+}
+
+/// `PATCH /users/:id` – password, scopes, disable/enable.  Admins can't
+/// lock themselves out.
+#[utoipa::path(
+    patch, path = "/users/{id}", tag = "auth", request_body = UserPatch,
+    params(("id" = Uuid, Path, description = "User id")),
+    responses(
+        (status = 200, body = User),
+        (status = 401, response = Problem),
+        (status = 403, response = Problem),
+        (status = 404, response = Problem),
+        (status = 409, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id))]
+pub async fn patch_user(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+    Json(p): Json<UserPatch>,
+) -> ApiResult<Json<User>> {
+    if id == who.user_id
+        && (p.disabled == Some(true) || p.scopes.as_ref().is_some_and(|s| !s.contains(&Scope::Admin)))
+    {
+        return Err(ApiError::Conflict("refusing to disable or demote your own account".into()));
+    }
+    let hash = match p.password {
+        Some(pw) => Some(hash_password(pw).await?),
+        None => None,
+    };
+
+    let mut tx = app.db.begin().await?;
+    let user: User = sqlx::query_as(&format!(
+        "UPDATE users
+            SET password_hash = COALESCE($2, password_hash),
+                scopes        = COALESCE($3, scopes),
+                disabled_at   = CASE WHEN $4 IS NULL THEN disabled_at
+                                     WHEN $4 THEN COALESCE(disabled_at, now()) END
+          WHERE id=$1
+      RETURNING {USER_COLUMNS}"
+    ))
+    .bind(id)
+    .bind(&hash)
+    .bind(&p.scopes)
+    .bind(p.disabled)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::not_found("user", id))?;
+    if hash.is_some() || p.disabled == Some(true) {
+        sqlx::query("UPDATE auth_tokens SET revoked_at = now() WHERE user_id=$1 AND kind='session' AND revoked_at IS NULL")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    info!("user updated");
+    Ok(Json(user))
+}
+
+/*──────── bootstrap ──────────────────────────────────────────────────────*/
+
+/// First start: create an admin from `SETLIST_ADMIN_USER` (default `admin`)
+/// / `SETLIST_ADMIN_PASSWORD`.  No-op once any user exists.
+pub async fn bootstrap(db: &PgPool) -> anyhow::Result<()> {
+    let (users,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users").fetch_one(db).await?;
+    if users > 0 {
+        return Ok(());
+    }
+    let Ok(password) = std::env::var("SETLIST_ADMIN_PASSWORD") else {
+        warn!("no users yet – set SETLIST_ADMIN_PASSWORD to create the first admin");
+        return Ok(());
+    };
+    let name = std::env::var("SETLIST_ADMIN_USER").unwrap_or_else(|_| "admin".into());
+    insert_user(db, &name, password, &[Scope::Admin])
+        .await
+        .map_err(|e| anyhow::anyhow!("creating admin {name:?}: {e:?}"))?;
+    info!(username = %name, "bootstrap admin created");
+    Ok(())
+}
+
+/*──────── helpers ────────────────────────────────────────────────────────*/
+
+async fn insert_user(db: &PgPool, username: &str, password: String, scopes: &[Scope]) -> ApiResult<User> {
+    let username = username.trim();
+    if username.is_empty()
+        || username.len() > 64
+        || !username.bytes().all(|b| b.is_ascii_alphanumeric() || b"._-".contains(&b))
+    {
+        return Err(ApiError::Unprocessable("username must be 1–64 of [A-Za-z0-9._-]".into()));
+    }
+    let hash = hash_password(password).await?;
+    sqlx::query_as(&format!(
+        "INSERT INTO users(id, username, password_hash, scopes) VALUES ($1,$2,$3,$4)
+      RETURNING {USER_COLUMNS}"
+    ))
+    .bind(Uuid::new_v4())
+    .bind(username)
+    .bind(hash)
+    .bind(scopes)
+    .fetch_one(db)
+    .await
+    .map_err(|e| match e.as_database_error().and_then(|d| d.code()) {
+        Some(c) if c == "23505" => ApiError::Conflict(format!("user {username:?} already exists")),
+        _ => e.into(),
+    })
+}
+
+async fn issue(
+    db:      &PgPool,
+    user_id: Uuid,
+    kind:    &str,
+    name:    Option<String>,
+    scopes:  &[Scope],
+    expires: Option<OffsetDateTime>,
+) -> ApiResult<IssuedToken> {
+    let mut raw = [0u8; 32];
+    OsRng.fill_bytes(&mut raw);
+    let token = match kind {
+        "session"  => format!("sls_{}", URL_SAFE_NO_PAD.encode(raw)),
+        // typed into phone apps – 24 characters, still 144 bits
+        "subsonic" => URL_SAFE_NO_PAD.encode(&raw[..18]),
+        _          => format!("slt_{}", URL_SAFE_NO_PAD.encode(raw)),
+    };
+    let subsonic_secret = (kind == "subsonic").then(|| token.clone());
+
+    let info: Token = sqlx::query_as(&format!(
+        "INSERT INTO auth_tokens(id, user_id, kind, name, token_hash, scopes, expires_at, subsonic_secret)
+              VALUES ($1,$2,$3,$4,$5,$6,$7,$8)
+           RETURNING {TOKEN_COLUMNS}"
+    ))
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(kind)
+    .bind(name)
+    .bind(digest(&token))
+    .bind(scopes)
+    .bind(expires)
+    .bind(subsonic_secret)
+    .fetch_one(db)
+    .await?;
+    Ok(IssuedToken { token, info })
+}
+
+fn digest(secret: &str) -> Vec<u8> {
+    Sha256::digest(secret.as_bytes()).to_vec()
+}
+
+async fn hash_password(password: String) -> ApiResult<String> {
+    if !(8..=1024).contains(&password.chars().count()) {
+        return Err(ApiError::Unprocessable("password must be 8–1024 characters".into()));
+    }
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|h| h.to_string())
+            .map_err(|e| ApiError::Internal(anyhow::anyhow!("argon2: {e}")))
+    })
+    .await?
+}
+
+fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .is_ok_and(|h| Argon2::default().verify_password(password.as_bytes(), &h).is_ok())
+}
+
+/// Hash nobody's password matches; verifying against it costs the same as a
+/// real check.
+fn dummy_hash() -> String {
+    static HASH: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+    HASH.get_or_init(|| {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default().hash_password(salt.as_str().as_bytes(), &salt).expect("argon2").to_string()
+    })
+    .clone()
+}
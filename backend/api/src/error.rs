@@ -0,0 +1,259 @@
+//! One error type for every handler, rendered as RFC 7807
+//! `application/problem+json`.
+//!
+//! `code` is the stable, machine-readable part – clients switch on it, never
+//! on `detail`.  Internal errors are logged in full and reach the client as a
+//! bare `internal` problem, so SQL text and paths never leak.
+
+use axum::{
+    extract::{
+        multipart::MultipartError,
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+        FromRequest, FromRequestParts,
+    },
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use tracing::error;
+use utoipa::{ToResponse, ToSchema};
+
+use crate::manifest::ManifestDiff;
+
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    /// No or invalid credentials – answered with `WWW-Authenticate: Bearer`.
+    Unauthorized(String),
+    /// Authenticated, but lacking the scope.
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    /// `PUT /albums/:id/complete` while the folder doesn't match the manifest.
+    ManifestMismatch(ManifestDiff),
+    PreconditionFailed(String),
+    /// Destructive request missing its explicit confirmation.
+    ConfirmationRequired(String),
+    PayloadTooLarge(String),
+    UnsupportedMediaType(String),
+    Unprocessable(String),
+    Locked(String),
+    /// Rate limit hit; seconds until the next request is allowed
+    /// (sent as `Retry-After`).
+    RateLimited(u64),
+    Unavailable(String),
+    Internal(anyhow::Error),
+}
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_)           => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_)         => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_)            => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_)             => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_)
+            | ApiError::ManifestMismatch(_)   => StatusCode::CONFLICT,
+            ApiError::PreconditionFailed(_)   => StatusCode::PRECONDITION_FAILED,
+            ApiError::ConfirmationRequired(_) => StatusCode::PRECONDITION_REQUIRED,
+            ApiError::PayloadTooLarge(_)      => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::Unprocessable(_)        => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Locked(_)               => StatusCode::LOCKED,
+            ApiError::RateLimited(_)          => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Unavailable(_)          => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_)             => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Stable identifier; part of the API contract.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_)           => "bad_request",
+            ApiError::Unauthorized(_)         => "unauthorized",
+            ApiError::Forbidden(_)            => "forbidden",
+            ApiError::NotFound(_)             => "not_found",
+            ApiError::Conflict(_)             => "conflict",
+            ApiError::ManifestMismatch(_)     => "manifest_mismatch",
+            ApiError::PreconditionFailed(_)   => "precondition_failed",
+            ApiError::ConfirmationRequired(_) => "confirmation_required",
+            ApiError::PayloadTooLarge(_)      => "payload_too_large",
+            ApiError::UnsupportedMediaType(_) => "unsupported_media_type",
+            ApiError::Unprocessable(_)        => "validation_failed",
+            ApiError::Locked(_)               => "locked",
+            ApiError::RateLimited(_)          => "rate_limited",
+            ApiError::Unavailable(_)          => "unavailable",
+            ApiError::Internal(_)             => "internal",
+        }
+    }
+
+    pub fn not_found(what: &str, id: impl std::fmt::Display) -> Self {
+        ApiError::NotFound(format!("{what} {id} not found"))
+    }
+}
+
+/// RFC 7807 body.  `type` is `urn:setlist:problem:<code>`.
+#[derive(Debug, Serialize, ToSchema, ToResponse)]
+#[response(description = "Problem details (RFC 7807)", content_type = "application/problem+json")]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub kind:   String,
+    pub title:  String,
+    pub status: u16,
+    pub code:   String,
+    pub detail: Option<String>,
+    /// Only on `manifest_mismatch`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff:   Option<ManifestDiff>,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let code   = self.code();
+        let retry_after = match self {
+            ApiError::RateLimited(secs) => Some(secs),
+            _ => None,
+        };
+        let (detail, diff) = match self {
+            ApiError::Internal(e) => {
+                error!("internal error: {e:#}");
+                (None, None)
+            }
+            ApiError::ManifestMismatch(d) => (Some("files on disk don't match the manifest".to_owned()), Some(d)),
+            ApiError::RateLimited(secs) => (Some(format!("too many requests – retry in {secs} s")), None),
+            ApiError::BadRequest(m)
+            | ApiError::Unauthorized(m)
+            | ApiError::Forbidden(m)
+            | ApiError::NotFound(m)
+            | ApiError::Conflict(m)
+            | ApiError::PreconditionFailed(m)
+            | ApiError::ConfirmationRequired(m)
+            | ApiError::PayloadTooLarge(m)
+            | ApiError::UnsupportedMediaType(m)
+            | ApiError::Unprocessable(m)
+            | ApiError::Locked(m)
+            | ApiError::Unavailable(m) => (Some(m), None),
+        };
+        let body = Problem {
+            kind:   format!("urn:setlist:problem:{code}"),
+            title:  status.canonical_reason().unwrap_or("Error").to_owned(),
+            status: status.as_u16(),
+            code:   code.to_owned(),
+            detail,
+            diff,
+        };
+        let mut res = (status, axum::Json(body)).into_response();
+        let h = res.headers_mut();
+        h.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/problem+json"));
+        if status == StatusCode::UNAUTHORIZED {
+            h.insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        if let Some(secs) = retry_after {
+            h.insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        res
+    }
+}
+
+/*──────── conversions ────────────────────────────────────────────────────*/
+
+/// Constraint violations the schema enforces for us become client errors;
+/// everything else is a 500.
+impl From<sqlx::Error> for ApiError {
+    fn from(e: sqlx::Error) -> Self {
+        if matches!(e, sqlx::Error::RowNotFound) {
+            return ApiError::NotFound("not found".into());
+        }
+        match e.as_database_error().and_then(|d| d.code()).as_deref() {
+            Some("23505") => ApiError::Conflict("already exists".into()),
+            Some("23503") => ApiError::Conflict("referenced record missing or still in use".into()),
+            Some("55P03") => ApiError::Locked("resource is busy".into()),
+            _ => ApiError::Internal(e.into()),
+        }
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<sqlx::Error>() {
+            Ok(db) => db.into(),
+            Err(e) => ApiError::Internal(e),
+        }
+    }
+}
+
+impl From<std::io::Error> for ApiError {
+    fn from(e: std::io::Error) -> Self {
+        ApiError::Internal(e.into())
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(e: serde_json::Error) -> Self {
+        ApiError::Internal(e.into())
+    }
+}
+
+impl From<tokio::task::JoinError> for ApiError {
+    fn from(e: tokio::task::JoinError) -> Self {
+        ApiError::Internal(e.into())
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(r: JsonRejection) -> Self {
+        match r {
+            JsonRejection::JsonDataError(_)          => ApiError::Unprocessable(r.body_text()),
+            JsonRejection::MissingJsonContentType(_) => ApiError::UnsupportedMediaType(r.body_text()),
+            _ if r.status() == StatusCode::PAYLOAD_TOO_LARGE => ApiError::PayloadTooLarge(r.body_text()),
+            _ => ApiError::BadRequest(r.body_text()),
+        }
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(r: QueryRejection) -> Self {
+        ApiError::BadRequest(r.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(r: PathRejection) -> Self {
+        ApiError::BadRequest(r.body_text())
+    }
+}
+
+impl From<MultipartError> for ApiError {
+    fn from(e: MultipartError) -> Self {
+        ApiError::BadRequest(e.body_text())
+    }
+}
+
+/*──────── extractors ─────────────────────────────────────────────────────*/
+// Drop-in replacements for axum's, with rejections rendered as problems.
+
+#[derive(Debug, FromRequest)]
+#[from_request(via(axum::Json), rejection(ApiError))]
+pub struct Json<T>(pub T);
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+#[derive(Debug, FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(ApiError))]
+pub struct Path<T>(pub T);
+
+#[derive(Debug, FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(ApiError))]
+pub struct Query<T>(pub T);
+
+/// Router fallback: unknown routes are problems too.
+pub async fn no_route() -> ApiError {
+    ApiError::NotFound("no such endpoint".into())
+}
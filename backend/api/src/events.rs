@@ -0,0 +1,226 @@
+//! Live pipeline progress over Server-Sent Events.
+//!
+//! Triggers on `files` / `jobs` append to `pipeline_events` and
+//! `pg_notify('pipeline_events', …)`.  One background task LISTENs and fans
+//! out through a broadcast channel; each SSE client first replays rows after
+//! its `Last-Event-ID`, then follows the live feed.
+
+use std::{convert::Infallible, sync::Arc, time::Duration};
+
+use axum::{
+    extract::State,
+    http::HeaderMap,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures_util::{stream, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgListener, PgPool};
+use time::OffsetDateTime;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{error::{ApiError, ApiResult, Path, Problem}, AppState};
+
+const CHANNEL: &str = "pipeline_events";
+/// Page size for replays; a client further behind gets one page per connection.
+const MAX_REPLAY: i64 = 5_000;
+/// Events older than this are pruned by the listener task.
+const RETENTION: &str = "7 days";
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct PipelineEvent {
+    pub id:       i64,
+    pub album_id: Option<Uuid>,
+    pub file_id:  Option<Uuid>,
+    pub kind:     String,
+    pub stage:    Option<String>,
+    pub status:   String,
+    pub detail:   Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+pub type EventBus = broadcast::Sender<Arc<PipelineEvent>>;
+
+/// Start the LISTEN task; the returned sender lives in `AppState`.
+pub fn spawn_listener(db: PgPool) -> EventBus {
+    let (tx, _) = broadcast::channel(1024);
+    tokio::spawn(listen_loop(db, tx.clone()));
+    tx
+}
+
+async fn listen_loop(db: PgPool, tx: EventBus) {
+    let mut last_id: Option<i64> = None;
+    loop {
+        if let Err(e) = listen_once(&db, &tx, &mut last_id).await {
+            warn!("event listener dropped: {e:#} – reconnecting");
+        }
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+}
+
+async fn listen_once(db: &PgPool, tx: &EventBus, last_id: &mut Option<i64>) -> anyhow::Result<()> {
+    let mut listener = PgListener::connect_with(db).await?;
+    listener.listen(CHANNEL).await?;
+    info!("listening for pipeline events");
+
+    // catch up on whatever was inserted while we were disconnected; the
+    // first connect starts after the newest event
+    let mut since = match *last_id {
+        Some(id) => id,
+        None => {
+            let (max,): (Option<i64>,) = sqlx::query_as("SELECT max(id) FROM pipeline_events")
+                .fetch_one(db)
+                .await?;
+            max.unwrap_or(0)
+        }
+    };
+    loop {
+        let page = fetch_since(db, None, since).await?;
+        let full = page.len() as i64 == MAX_REPLAY;
+        for ev in page {
+            since = ev.id;
+            let _ = tx.send(Arc::new(ev));
+        }
+        *last_id = Some(since);
+        if !full {
+            break;
+        }
+    }
+
+    let mut prune = tokio::time::interval(Duration::from_secs(3600));
+    loop {
+        tokio::select! {
+            n = listener.recv() => {
+                let n = n?;
+                match serde_json::from_str::<PipelineEvent>(n.payload()) {
+                    Ok(ev) => {
+                        *last_id = Some(ev.id);
+                        let _ = tx.send(Arc::new(ev));   // Err = nobody listening
+                    }
+                    Err(e) => warn!("bad event payload: {e}"),
+                }
+            }
+            _ = prune.tick() => {
+                sqlx::query(&format!(
+                    "DELETE FROM pipeline_events WHERE created_at < now() - interval '{RETENTION}'"
+                ))
+                .execute(db)
+                .await?;
+            }
+        }
+    }
+}
+
+async fn fetch_since(db: &PgPool, album: Option<Uuid>, since: i64) -> sqlx::Result<Vec<PipelineEvent>> {
+    sqlx::query_as(
+        "SELECT id, album_id, file_id, kind, stage, status, detail, created_at
+           FROM pipeline_events
+          WHERE id > $1 AND ($2::uuid IS NULL OR album_id = $2)
+       ORDER BY id
+          LIMIT $3",
+    )
+    .bind(since)
+    .bind(album)
+    .bind(MAX_REPLAY)
+    .fetch_all(db)
+    .await
+}
+
+/*──────── handlers ───────────────────────────────────────────────────────*/
+
+type EventStream = Sse<std::pin::Pin<Box<dyn Stream<Item = Result<Event, Infallible>> + Send>>>;
+
+/// `GET /events` – every album.
+#[utoipa::path(
+    get, path = "/events", tag = "events",
+    params(("Last-Event-ID" = Option<i64>, Header, description = "Replay events after this id")),
+    responses(
+        (
+            status = 200,
+            description = "Server-sent events; `data` is a PipelineEvent",
+            content_type = "text/event-stream",
+            body = PipelineEvent,
+        ),
+    ),
+)]
+pub async fn all_events(State(app): State<AppState>, headers: HeaderMap)
+    -> ApiResult<EventStream>
+{
+    stream_events(app, None, &headers).await
+}
+
+/// `GET /albums/:id/events`
+#[utoipa::path(
+    get, path = "/albums/{id}/events", tag = "events",
+    params(
+        ("id" = Uuid, Path, description = "Album id"),
+        ("Last-Event-ID" = Option<i64>, Header, description = "Replay events after this id"),
+    ),
+    responses(
+        (
+            status = 200,
+            description = "Server-sent events; `data` is a PipelineEvent",
+            content_type = "text/event-stream",
+            body = PipelineEvent,
+        ),
+        (status = 404, response = Problem),
+    ),
+)]
+pub async fn album_events(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    headers: HeaderMap,
+) -> ApiResult<EventStream> {
+    let exists: Option<(Uuid,)> = sqlx::query_as("SELECT id FROM albums WHERE id=$1")
+        .bind(id)
+        .fetch_optional(&app.db)
+        .await?;
+    if exists.is_none() {
+        return Err(ApiError::not_found("album", id));
+    }
+    stream_events(app, Some(id), &headers).await
+}
+
+async fn stream_events(app: AppState, album: Option<Uuid>, headers: &HeaderMap)
+    -> ApiResult<EventStream>
+{
+    // subscribe *before* replaying so nothing slips between the two
+    let rx = app.events.subscribe();
+    let since = headers
+        .get("Last-Event-ID")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<i64>().ok());
+
+    let backlog = match since {
+        Some(since) => fetch_since(&app.db, album, since).await?,
+        None => Vec::new(),
+    };
+    let replayed = backlog.last().map(|e| e.id).or(since).unwrap_or(0);
+    let behind = backlog.len() as i64 == MAX_REPLAY;
+
+    // A lagging client ends its stream; the browser reconnects with
+    // Last-Event-ID and replays from the table.  So does one whose replay
+    // filled a page: there may be more after it.
+    let live = BroadcastStream::new(rx)
+        .take_while(|r| std::future::ready(r.is_ok()))
+        .filter_map(move |r| std::future::ready(
+            r.ok().filter(|e| e.id > replayed && (album.is_none() || e.album_id == album))
+        ));
+
+    let backlog = stream::iter(backlog.into_iter().map(Arc::new));
+    let events = if behind { backlog.boxed() } else { backlog.chain(live).boxed() };
+    let events = events.map(|e| Ok(to_sse(&e)));
+    Ok(Sse::new(events.boxed()).keep_alive(KeepAlive::default()))
+}
+
+fn to_sse(e: &PipelineEvent) -> Event {
+    Event::default()
+        .id(e.id.to_string())
+        .event(&e.kind)
+        .json_data(e)
+        .unwrap_or_else(|_| Event::default().comment("unserialisable event"))
+}
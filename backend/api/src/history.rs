@@ -0,0 +1,314 @@
+//! Metadata edit history and revert.
+//!
+//! A trigger on `albums` and `tracks` writes one `metadata_history` row per
+//! changed field (album title, artist, year, venue, kind; track title, disc,
+//! index) – whether the change comes from an API handler, a worker or plain
+//! SQL.
+//! Handlers name the user with [`attribute`] inside their transaction;
+//! anything else is recorded under its Postgres `application_name`.
+//!
+//! Reverting to entry *n* puts every field changed by *n* or later back to
+//! its value just before *n*.  The revert is a change like any other, so it
+//! can itself be reverted.
+
+use axum::extract::State;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, QueryBuilder, Transaction};
+use time::OffsetDateTime;
+use tracing::{info, instrument};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    audit,
+    auth::Principal,
+    error::{ApiError, ApiResult, Json, Path, Problem, Query},
+    tracks, AppState,
+};
+
+const HISTORY_COLUMNS: &str =
+    "id, entity, entity_id, field, old_value, new_value, changed_by, via, changed_at";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum Entity {
+    Album,
+    Track,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct HistoryEntry {
+    pub id:         i64,
+    pub entity:     Entity,
+    pub entity_id:  Uuid,
+    /// Column name, e.g. `title`.
+    pub field:      String,
+    pub old_value:  serde_json::Value,
+    pub new_value:  serde_json::Value,
+    /// User name, or the writer's `application_name` (workers, SQL).
+    pub changed_by: String,
+    /// `api`, `review`, `revert` – or `sql` outside the API.
+    pub via:        String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub changed_at: OffsetDateTime,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(deny_unknown_fields)]
+pub struct HistoryQuery {
+    pub field:  Option<String>,
+    /// Only entries with a smaller id (next page).
+    pub before: Option<i64>,
+    pub limit:  Option<i64>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RevertRequest {
+    /// History entry id: undo it and everything after it.
+    pub to: i64,
+}
+
+/// What a history view or revert covers: an album with its current tracks,
+/// or a single track.
+struct Scope {
+    album:  Option<Uuid>,
+    albums: Vec<Uuid>,
+    tracks: Vec<Uuid>,
+}
+
+/// Name the user (and route) behind this transaction's metadata writes.
+pub(crate) async fn attribute(tx: &mut Transaction<'_, Postgres>, actor: &str, via: &str)
+    -> sqlx::Result<()>
+{
+    sqlx::query("SELECT set_config('setlist.actor', $1, true), set_config('setlist.via', $2, true)")
+        .bind(actor)
+        .bind(via)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/*──────── handlers ───────────────────────────────────────────────────────*/
+
+/// `GET /albums/:id/history` – changes to the album and its tracks, newest
+/// first.
+#[utoipa::path(
+    get, path = "/albums/{id}/history", tag = "albums",
+    params(("id" = Uuid, Path, description = "Album id"), HistoryQuery),
+    responses(
+        (status = 200, body = Vec<HistoryEntry>),
+        (status = 400, response = Problem),
+        (status = 404, response = Problem),
+    ),
+)]
+pub async fn album_history(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    Query(q): Query<HistoryQuery>,
+) -> ApiResult<Json<Vec<HistoryEntry>>> {
+    let mut tx = app.db.begin().await?;
+    let scope = album_scope(&mut tx, id, false).await?;
+    tx.commit().await?;
+    Ok(Json(list(&app.db, &scope, q).await?))
+}
+
+/// `GET /tracks/:id/history` – newest first.
+#[utoipa::path(
+    get, path = "/tracks/{id}/history", tag = "tracks",
+    params(("id" = Uuid, Path, description = "Track id"), HistoryQuery),
+    responses(
+        (status = 200, body = Vec<HistoryEntry>),
+        (status = 400, response = Problem),
+        (status = 404, response = Problem),
+    ),
+)]
+pub async fn track_history(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    Query(q): Query<HistoryQuery>,
+) -> ApiResult<Json<Vec<HistoryEntry>>> {
+    let mut tx = app.db.begin().await?;
+    let scope = track_scope(&mut tx, id, false).await?;
+    tx.commit().await?;
+    Ok(Json(list(&app.db, &scope, q).await?))
+}
+
+/// `POST /albums/:id/history/revert` – restore the album and its tracks to
+/// how they were before entry `to`.
+#[utoipa::path(
+    post, path = "/albums/{id}/history/revert", tag = "albums", request_body = RevertRequest,
+    params(("id" = Uuid, Path, description = "Album id")),
+    responses(
+        (status = 200, description = "The changes the revert made", body = Vec<HistoryEntry>),
+        (status = 404, response = Problem),
+        (status = 409, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id, to = r.to))]
+pub async fn revert_album(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+    Json(r): Json<RevertRequest>,
+) -> ApiResult<Json<Vec<HistoryEntry>>> {
+    let mut tx = app.db.begin().await?;
+    let scope = album_scope(&mut tx, id, true).await?;
+    let changes = revert(&mut tx, &scope, r.to, &who).await?;
+    audit::record(
+        &mut *tx, &who.username, "album.revert", ("album", Some(id)),
+        serde_json::json!({ "to": r.to, "changes": changes.len() }),
+    )
+    .await?;
+    tx.commit().await?;
+    info!(changes = changes.len(), "album metadata reverted");
+    Ok(Json(changes))
+}
+
+/// `POST /tracks/:id/history/revert` – restore the track to how it was
+/// before entry `to`.
+#[utoipa::path(
+    post, path = "/tracks/{id}/history/revert", tag = "tracks", request_body = RevertRequest,
+    params(("id" = Uuid, Path, description = "Track id")),
+    responses(
+        (status = 200, description = "The changes the revert made", body = Vec<HistoryEntry>),
+        (status = 404, response = Problem),
+        (status = 409, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id, to = r.to))]
+pub async fn revert_track(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+    Json(r): Json<RevertRequest>,
+) -> ApiResult<Json<Vec<HistoryEntry>>> {
+    let mut tx = app.db.begin().await?;
+    let scope = track_scope(&mut tx, id, true).await?;
+    let changes = revert(&mut tx, &scope, r.to, &who).await?;
+    audit::record(
+        &mut *tx, &who.username, "track.revert", ("track", Some(id)),
+        serde_json::json!({ "to": r.to, "changes": changes.len() }),
+    )
+    .await?;
+    tx.commit().await?;
+    info!(changes = changes.len(), "track metadata reverted");
+    Ok(Json(changes))
+}
+
+/*──────── helpers ────────────────────────────────────────────────────────*/
+
+async fn album_scope(tx: &mut Transaction<'_, Postgres>, id: Uuid, lock: bool) -> ApiResult<Scope> {
+    let lock = if lock { " FOR UPDATE" } else { "" };
+    let found: Option<(Uuid,)> = sqlx::query_as(&format!("SELECT id FROM albums WHERE id=$1{lock}"))
+        .bind(id)
+        .fetch_optional(&mut **tx)
+        .await?;
+    found.ok_or_else(|| ApiError::not_found("album", id))?;
+    let tracks: Vec<(Uuid,)> = sqlx::query_as("SELECT id FROM tracks WHERE album_id=$1")
+        .bind(id)
+        .fetch_all(&mut **tx)
+        .await?;
+    Ok(Scope { album: Some(id), albums: vec![id], tracks: tracks.into_iter().map(|(t,)| t).collect() })
+}
+
+async fn track_scope(tx: &mut Transaction<'_, Postgres>, id: Uuid, lock: bool) -> ApiResult<Scope> {
+    let lock = if lock { " FOR UPDATE" } else { "" };
+    let found: Option<(Option<Uuid>,)> = sqlx::query_as(&format!("SELECT album_id FROM tracks WHERE id=$1{lock}"))
+        .bind(id)
+        .fetch_optional(&mut **tx)
+        .await?;
+    let (album,) = found.ok_or_else(|| ApiError::not_found("track", id))?;
+    Ok(Scope { album, albums: vec![], tracks: vec![id] })
+}
+
+async fn list(db: &PgPool, scope: &Scope, q: HistoryQuery) -> sqlx::Result<Vec<HistoryEntry>> {
+    let mut qb = QueryBuilder::<Postgres>::new(format!("SELECT {HISTORY_COLUMNS} FROM metadata_history WHERE "));
+    push_scope(&mut qb, scope);
+    if let Some(f) = q.field  { qb.push(" AND field = ").push_bind(f); }
+    if let Some(b) = q.before { qb.push(" AND id < ").push_bind(b); }
+    qb.push(" ORDER BY id DESC LIMIT ").push_bind(q.limit.unwrap_or(100).clamp(1, 500));
+    qb.build_query_as().fetch_all(db).await
+}
+
+fn push_scope(qb: &mut QueryBuilder<'_, Postgres>, scope: &Scope) {
+    qb.push("((entity = 'album' AND entity_id = ANY(").push_bind(scope.albums.clone());
+    qb.push(")) OR (entity = 'track' AND entity_id = ANY(").push_bind(scope.tracks.clone());
+    qb.push(")))");
+}
+
+/// Put every field changed at or after `to` back to its value before the
+/// first such change; returns the history rows this wrote.
+async fn revert(tx: &mut Transaction<'_, Postgres>, scope: &Scope, to: i64, who: &Principal)
+    -> ApiResult<Vec<HistoryEntry>>
+{
+    let mut qb = QueryBuilder::<Postgres>::new("SELECT 1 FROM metadata_history WHERE id = ");
+    qb.push_bind(to).push(" AND ");
+    push_scope(&mut qb, scope);
+    if qb.build().fetch_optional(&mut **tx).await?.is_none() {
+        return Err(ApiError::not_found("history entry", to));
+    }
+
+    attribute(tx, &who.username, "revert").await?;
+    // earliest change per field at or after `to` – its old value is the target
+    let undo = "SELECT entity_id, jsonb_object_agg(field, old_value) AS o
+                  FROM (SELECT DISTINCT ON (entity_id, field) entity_id, field, old_value
+                          FROM metadata_history
+                         WHERE entity = $1 AND entity_id = ANY($2) AND id >= $3
+                      ORDER BY entity_id, field, id) first
+              GROUP BY entity_id";
+    sqlx::query(&format!(
+        "UPDATE albums a
+            SET title  = CASE WHEN u.o ? 'title'  THEN r.title  ELSE a.title  END,
+                artist = CASE WHEN u.o ? 'artist' THEN r.artist ELSE a.artist END,
+                year   = CASE WHEN u.o ? 'year'   THEN r.year   ELSE a.year   END,
+                venue  = CASE WHEN u.o ? 'venue'  THEN r.venue  ELSE a.venue  END,
+                kind   = CASE WHEN u.o ? 'kind'   THEN r.kind   ELSE a.kind   END
+           FROM ({undo}) u, jsonb_populate_record(NULL::albums, u.o) r
+          WHERE a.id = u.entity_id"
+    ))
+    .bind(Entity::Album)
+    .bind(&scope.albums)
+    .bind(to)
+    .execute(&mut **tx)
+    .await?;
+    // moved tracks go negative first, like a disc reorder, so
+    // UNIQUE(album_id, disc, "index") never sees a transient duplicate
+    for sign in [-1, 1] {
+        sqlx::query(&format!(
+            r#"UPDATE tracks t
+                  SET title   = CASE WHEN u.o ? 'title' THEN r.title ELSE t.title END,
+                      disc    = CASE WHEN u.o ? 'disc'  THEN r.disc  ELSE t.disc  END,
+                      "index" = CASE WHEN u.o ?| array['disc', 'index']
+                                     THEN $4 * abs(CASE WHEN u.o ? 'index' THEN r."index" ELSE t."index" END)
+                                     ELSE t."index" END
+                 FROM ({undo}) u, jsonb_populate_record(NULL::tracks, u.o) r
+                WHERE t.id = u.entity_id"#
+        ))
+        .bind(Entity::Track)
+        .bind(&scope.tracks)
+        .bind(to)
+        .bind(sign)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| match e.as_database_error().and_then(|d| d.code()) {
+            Some(c) if c == "23505" => ApiError::Conflict("a reverted track's disc/index is taken".into()),
+            _ => e.into(),
+        })?;
+    }
+
+    let changes: Vec<HistoryEntry> = sqlx::query_as(&format!(
+        "SELECT {HISTORY_COLUMNS} FROM metadata_history WHERE tx_id = txid_current() ORDER BY id"
+    ))
+    .fetch_all(&mut **tx)
+    .await?;
+    if changes.iter().any(|c| c.entity == Entity::Album) {
+        tracks::retag(tx, scope.album, None).await?;
+    } else if !changes.is_empty() {
+        let touched: Vec<Uuid> = changes.iter().map(|c| c.entity_id).collect();
+        tracks::retag(tx, scope.album, Some(&touched)).await?;
+    }
+    Ok(changes)
+}
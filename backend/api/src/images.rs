@@ -0,0 +1,583 @@
+//! Album artwork: typed images (front, back, ticket, poster, other) and the
+//! `/albums/:id/cover` shortcut.
+//!
+//! Uploaded images live under `IMAGE_ROOT/<album_id>/<image_id>.<ext>`;
+//! resized JPEG thumbnails are cached under `IMAGE_ROOT/thumbs/<album_id>/`
+//! and dropped whenever the album's images change.
+//!
+//! The cover is the primary image, else the newest `front`, else a
+//! `cover` / `folder` / `front` picture in the album folder, else art
+//! embedded in the first audio files (extracted once into the cache).
+
+use std::{
+    io::Cursor,
+    path::{Path as FsPath, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use axum::{
+    extract::{Multipart, State},
+    http::{HeaderMap, StatusCode},
+    response::Response,
+};
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, ImageFormat, ImageReader};
+use lofty::{PictureType, TaggedFileExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use tracing::{info, instrument, warn};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    albums::clean_text,
+    error::{ApiError, ApiResult, Json, Path, Problem, Query},
+    stream, AppState,
+};
+
+/// Thumbnail edges we render; requests snap up to the next one.
+const SIZES: &[u32] = &[64, 128, 256, 512, 1024, 2048];
+/// Folder files taken as the cover, in order of preference.
+const FOLDER_COVERS: &[&str] = &["cover", "folder", "front"];
+
+/*──────── config ─────────────────────────────────────────────────────────*/
+
+#[derive(Clone, Debug)]
+pub struct ImageStore {
+    pub root:      PathBuf,   // IMAGE_ROOT       (default /var/lib/setlist/images)
+    pub max_bytes: u64,       // IMAGE_MAX_BYTES  (default 25 MiB)
+}
+
+impl ImageStore {
+    pub fn from_env() -> Self {
+        let var = |k: &str| std::env::var(k).ok();
+        Self {
+            root:      var("IMAGE_ROOT").unwrap_or_else(|| "/var/lib/setlist/images".into()).into(),
+            max_bytes: var("IMAGE_MAX_BYTES").and_then(|v| v.parse().ok()).unwrap_or(25 << 20),
+        }
+    }
+
+    fn original(&self, album_id: Uuid, id: Uuid, mime: &str) -> PathBuf {
+        self.root.join(album_id.to_string()).join(format!("{id}.{}", ext(mime)))
+    }
+
+    fn thumbs(&self, album_id: Uuid) -> PathBuf {
+        self.root.join("thumbs").join(album_id.to_string())
+    }
+
+    /// Blocking: forget every thumbnail of the album.
+    fn invalidate(&self, album_id: Uuid) {
+        let _ = std::fs::remove_dir_all(self.thumbs(album_id));
+    }
+
+    /// Blocking: all stored images and thumbnails of a deleted album.
+    pub fn remove_album(&self, album_id: Uuid) {
+        self.invalidate(album_id);
+        let _ = std::fs::remove_dir_all(self.root.join(album_id.to_string()));
+    }
+
+    /// Blocking: move `from`'s originals under `into` after a merge.
+    pub fn move_album(&self, from: Uuid, into: Uuid) -> std::io::Result<()> {
+        let src = self.root.join(from.to_string());
+        if src.is_dir() {
+            let dst = self.root.join(into.to_string());
+            std::fs::create_dir_all(&dst)?;
+            for e in std::fs::read_dir(&src)? {
+                let e = e?;
+                std::fs::rename(e.path(), dst.join(e.file_name()))?;
+            }
+        }
+        self.remove_album(from);
+        self.invalidate(into);
+        Ok(())
+    }
+}
+
+/*──────── model ──────────────────────────────────────────────────────────*/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum ImageKind {
+    Front,
+    Back,
+    Ticket,
+    Poster,
+    Other,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct AlbumImage {
+    pub id:         Uuid,
+    pub album_id:   Uuid,
+    pub kind:       ImageKind,
+    pub mime:       String,
+    pub width:      i32,
+    pub height:     i32,
+    pub bytes:      i64,
+    pub caption:    Option<String>,
+    pub is_primary: bool,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+const IMAGE_COLUMNS: &str = "id, album_id, kind, mime, width, height, bytes, caption, is_primary, created_at";
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ImagePatch {
+    pub kind:    Option<ImageKind>,
+    pub caption: Option<String>,
+    /// Only `true` is accepted – pick another image to move the cover.
+    pub primary: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(deny_unknown_fields)]
+pub struct SizeQuery {
+    /// Longest edge in pixels; snapped up to 64, 128, 256, 512, 1024 or 2048.
+    /// Omit for the original.
+    pub size: Option<u32>,
+}
+
+/*──────── handlers ───────────────────────────────────────────────────────*/
+
+/// `GET /albums/:id/images`
+#[utoipa::path(
+    get, path = "/albums/{id}/images", tag = "albums",
+    params(("id" = Uuid, Path, description = "Album id")),
+    responses((status = 200, body = Vec<AlbumImage>), (status = 404, response = Problem)),
+)]
+pub async fn list_images(Path(id): Path<Uuid>, State(app): State<AppState>)
+    -> ApiResult<Json<Vec<AlbumImage>>>
+{
+    album_exists(&app.db, id).await?;
+    let images = sqlx::query_as(&format!(
+        "SELECT {IMAGE_COLUMNS} FROM album_images WHERE album_id=$1 ORDER BY is_primary DESC, created_at"
+    ))
+    .bind(id)
+    .fetch_all(&app.db)
+    .await?;
+    Ok(Json(images))
+}
+
+/// `POST /albums/:id/images` – the first `front` becomes the cover unless
+/// one is already set.
+#[utoipa::path(
+    post, path = "/albums/{id}/images", tag = "albums",
+    params(("id" = Uuid, Path, description = "Album id")),
+    request_body(
+        content = Vec<u8>,
+        content_type = "multipart/form-data",
+        description = "Parts: `file` (JPEG, PNG or WebP), `kind`, optional `caption`, `primary=true`",
+    ),
+    responses(
+        (status = 201, body = AlbumImage),
+        (status = 400, response = Problem),
+        (status = 404, response = Problem),
+        (status = 413, response = Problem),
+        (status = 415, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id))]
+pub async fn upload_image(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    mut mp: Multipart,
+) -> ApiResult<(StatusCode, Json<AlbumImage>)> {
+    album_exists(&app.db, id).await?;
+    let (mut data, mut kind, mut caption, mut primary) = (None, None, None, false);
+    while let Some(mut field) = mp.next_field().await? {
+        match field.name().unwrap_or_default() {
+            "file" => {
+                let mut buf = Vec::new();
+                while let Some(chunk) = field.chunk().await? {
+                    if (buf.len() + chunk.len()) as u64 > app.images.max_bytes {
+                        return Err(ApiError::PayloadTooLarge(format!(
+                            "images are limited to {} bytes", app.images.max_bytes
+                        )));
+                    }
+                    buf.extend_from_slice(&chunk);
+                }
+                data = Some(buf);
+            }
+            "kind" => {
+                let v = field.text().await?;
+                kind = Some(serde_json::from_value::<ImageKind>(serde_json::Value::String(v.clone()))
+                    .map_err(|_| ApiError::Unprocessable(format!("unknown image kind {v:?}")))?);
+            }
+            "caption" => caption = clean_text("caption", Some(field.text().await?))?,
+            "primary" => primary = field.text().await?.trim() == "true",
+            _ => {}
+        }
+    }
+    let data = data.ok_or_else(|| ApiError::Unprocessable("`file` field missing".into()))?;
+    let kind = kind.ok_or_else(|| ApiError::Unprocessable("`kind` field missing".into()))?;
+
+    let (mime, width, height) = tokio::task::spawn_blocking({
+        let data = data.clone();
+        move || probe(&data)
+    })
+    .await??;
+    let image_id = Uuid::new_v4();
+    let dest = app.images.original(id, image_id, mime);
+    write_atomic(&dest, &data).await?;
+
+    let mut tx = app.db.begin().await?;
+    let has_cover: Option<(Uuid,)> = sqlx::query_as("SELECT id FROM album_images WHERE album_id=$1 AND is_primary")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    let primary = primary || (kind == ImageKind::Front && has_cover.is_none());
+    if primary {
+        sqlx::query("UPDATE album_images SET is_primary = FALSE WHERE album_id=$1 AND is_primary")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    let image: AlbumImage = sqlx::query_as(&format!(
+        "INSERT INTO album_images(id, album_id, kind, mime, width, height, bytes, caption, is_primary)
+              VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9)
+           RETURNING {IMAGE_COLUMNS}"
+    ))
+    .bind(image_id)
+    .bind(id)
+    .bind(kind)
+    .bind(mime)
+    .bind(width as i32)
+    .bind(height as i32)
+    .bind(data.len() as i64)
+    .bind(caption)
+    .bind(primary)
+    .fetch_one(&mut *tx)
+    .await
+    .inspect_err(|_| { let _ = std::fs::remove_file(&dest); })?;
+    tx.commit().await?;
+
+    let store = app.images.clone();
+    tokio::task::spawn_blocking(move || store.invalidate(id)).await?;
+    info!(%image_id, ?kind, width, height, "image stored");
+    Ok((StatusCode::CREATED, Json(image)))
+}
+
+/// `GET /albums/:id/images/:image_id` – the image itself, optionally resized.
+#[utoipa::path(
+    get, path = "/albums/{id}/images/{image_id}", tag = "albums",
+    params(
+        ("id" = Uuid, Path, description = "Album id"),
+        ("image_id" = Uuid, Path, description = "Image id"),
+        SizeQuery,
+    ),
+    responses(
+        (status = 200, description = "Image bytes", content_type = "image/*"),
+        (status = 304, description = "Not modified"),
+        (status = 404, response = Problem),
+    ),
+)]
+pub async fn get_image(
+    Path((id, image_id)): Path<(Uuid, Uuid)>,
+    State(app): State<AppState>,
+    Query(q): Query<SizeQuery>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let row: Option<(String,)> = sqlx::query_as("SELECT mime FROM album_images WHERE id=$1 AND album_id=$2")
+        .bind(image_id)
+        .bind(id)
+        .fetch_optional(&app.db)
+        .await?;
+    let (mime,) = row.ok_or_else(|| ApiError::not_found("image", image_id))?;
+    let src = app.images.original(id, image_id, &mime);
+    render(&app, id, src, static_mime(&mime), q.size, &headers).await
+}
+
+/// `PATCH /albums/:id/images/:image_id` – kind, caption, or make it the cover.
+#[utoipa::path(
+    patch, path = "/albums/{id}/images/{image_id}", tag = "albums", request_body = ImagePatch,
+    params(("id" = Uuid, Path, description = "Album id"), ("image_id" = Uuid, Path, description = "Image id")),
+    responses((status = 200, body = AlbumImage), (status = 404, response = Problem), (status = 422, response = Problem)),
+)]
+#[instrument(skip_all, fields(%id, %image_id))]
+pub async fn patch_image(
+    Path((id, image_id)): Path<(Uuid, Uuid)>,
+    State(app): State<AppState>,
+    Json(p): Json<ImagePatch>,
+) -> ApiResult<Json<AlbumImage>> {
+    if p.primary == Some(false) {
+        return Err(ApiError::Unprocessable("set `primary: true` on another image instead".into()));
+    }
+    let caption = clean_text("caption", p.caption.clone())?;
+
+    let mut tx = app.db.begin().await?;
+    if p.primary == Some(true) {
+        sqlx::query("UPDATE album_images SET is_primary = FALSE WHERE album_id=$1 AND is_primary AND id <> $2")
+            .bind(id)
+            .bind(image_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    let image: AlbumImage = sqlx::query_as(&format!(
+        "UPDATE album_images
+            SET kind       = COALESCE($3, kind),
+                caption    = CASE WHEN $4 THEN $5 ELSE caption END,
+                is_primary = is_primary OR $6
+          WHERE id=$1 AND album_id=$2
+      RETURNING {IMAGE_COLUMNS}"
+    ))
+    .bind(image_id)
+    .bind(id)
+    .bind(p.kind)
+    .bind(p.caption.is_some())
+    .bind(caption)
+    .bind(p.primary == Some(true))
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::not_found("image", image_id))?;
+    tx.commit().await?;
+
+    let store = app.images.clone();
+    tokio::task::spawn_blocking(move || store.invalidate(id)).await?;
+    info!("image updated");
+    Ok(Json(image))
+}
+
+/// `DELETE /albums/:id/images/:image_id`
+#[utoipa::path(
+    delete, path = "/albums/{id}/images/{image_id}", tag = "albums",
+    params(("id" = Uuid, Path, description = "Album id"), ("image_id" = Uuid, Path, description = "Image id")),
+    responses((status = 204, description = "Deleted"), (status = 404, response = Problem)),
+)]
+#[instrument(skip_all, fields(%id, %image_id))]
+pub async fn delete_image(
+    Path((id, image_id)): Path<(Uuid, Uuid)>,
+    State(app): State<AppState>,
+) -> ApiResult<StatusCode> {
+    let row: Option<(String,)> =
+        sqlx::query_as("DELETE FROM album_images WHERE id=$1 AND album_id=$2 RETURNING mime")
+            .bind(image_id)
+            .bind(id)
+            .fetch_optional(&app.db)
+            .await?;
+    let (mime,) = row.ok_or_else(|| ApiError::not_found("image", image_id))?;
+
+    let store = app.images.clone();
+    tokio::task::spawn_blocking(move || {
+        let _ = std::fs::remove_file(store.original(id, image_id, &mime));
+        store.invalidate(id);
+    })
+    .await?;
+    info!("image deleted");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// `GET /albums/:id/cover?size=`
+#[utoipa::path(
+    get, path = "/albums/{id}/cover", tag = "albums",
+    params(("id" = Uuid, Path, description = "Album id"), SizeQuery),
+    responses(
+        (status = 200, description = "Cover image", content_type = "image/*"),
+        (status = 304, description = "Not modified"),
+        (status = 404, response = Problem),
+    ),
+)]
+pub async fn cover(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    Query(q): Query<SizeQuery>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    album_cover(&app, id, q.size, &headers).await
+}
+
+/*──────── rendering ──────────────────────────────────────────────────────*/
+
+/// The album's cover by the fallback order in the module docs; also backs
+/// Subsonic `getCoverArt`.
+pub(crate) async fn album_cover(app: &AppState, id: Uuid, size: Option<u32>, headers: &HeaderMap)
+    -> ApiResult<Response>
+{
+    let stored: Option<(Uuid, String)> = sqlx::query_as(
+        "SELECT id, mime FROM album_images
+          WHERE album_id=$1 AND (is_primary OR kind = 'front')
+       ORDER BY is_primary DESC, created_at DESC LIMIT 1",
+    )
+    .bind(id)
+    .fetch_optional(&app.db)
+    .await?;
+    if let Some((image_id, mime)) = stored {
+        let src = app.images.original(id, image_id, &mime);
+        return render(app, id, src, static_mime(&mime), size, headers).await;
+    }
+
+    let folder: Option<(Option<String>,)> = sqlx::query_as("SELECT source->>'path' FROM albums WHERE id=$1")
+        .bind(id)
+        .fetch_optional(&app.db)
+        .await?;
+    let (folder,) = folder.ok_or_else(|| ApiError::not_found("album", id))?;
+    let audio: Vec<(Uuid, String)> = sqlx::query_as(
+        "SELECT f.id, f.path FROM files f JOIN tracks t ON t.id = f.track_id
+          WHERE t.album_id=$1 ORDER BY t.disc, t.\"index\" LIMIT 5",
+    )
+    .bind(id)
+    .fetch_all(&app.db)
+    .await?;
+
+    let store = app.images.clone();
+    let found = tokio::task::spawn_blocking(move || {
+        folder.as_deref().and_then(|d| folder_cover(FsPath::new(d)))
+            .or_else(|| audio.iter().find_map(|(fid, p)| embedded_cover(&store, id, *fid, FsPath::new(p))))
+    })
+    .await?;
+    let (src, mime) = found.ok_or_else(|| ApiError::NotFound(format!("album {id} has no cover")))?;
+    render(app, id, src, mime, size, headers).await
+}
+
+/// Serve `src` as is, or a cached JPEG thumbnail of it.
+async fn render(
+    app:     &AppState,
+    album:   Uuid,
+    src:     PathBuf,
+    mime:    &'static str,
+    size:    Option<u32>,
+    headers: &HeaderMap,
+) -> ApiResult<Response> {
+    let Some(size) = size else {
+        return stream::serve(&src, mime, headers).await;
+    };
+    let edge = SIZES.iter().copied().find(|s| *s >= size).unwrap_or(SIZES[SIZES.len() - 1]);
+    let store = app.images.clone();
+    let thumb = tokio::task::spawn_blocking(move || thumbnail(&store, album, &src, edge)).await??;
+    stream::serve(&thumb, "image/jpeg", headers).await
+}
+
+/// Blocking: cached thumbnail path, rendering it first if needed.  Keyed by
+/// source path + mtime + length, so a replaced file gets a fresh one.
+fn thumbnail(store: &ImageStore, album: Uuid, src: &FsPath, edge: u32) -> ApiResult<PathBuf> {
+    let meta = std::fs::metadata(src).map_err(|_| ApiError::NotFound("image missing on disk".into()))?;
+    let mtime = meta.modified().ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let key = Sha256::digest(format!("{}|{mtime}|{}", src.display(), meta.len()));
+    let dest = store.thumbs(album).join(format!("{}-{edge}.jpg", &hex::encode(key)[..16]));
+    if dest.exists() {
+        return Ok(dest);
+    }
+
+    let img = ImageReader::open(src)?
+        .with_guessed_format()?
+        .decode()
+        .map_err(|e| ApiError::Internal(anyhow::anyhow!("decode {}: {e}", src.display())))?;
+    let img = if img.width().max(img.height()) > edge {
+        img.resize(edge, edge, FilterType::Lanczos3)
+    } else {
+        img
+    };
+    let mut out = Vec::new();
+    JpegEncoder::new_with_quality(&mut out, 85)
+        .encode_image(&img.to_rgb8())
+        .map_err(|e| ApiError::Internal(anyhow::anyhow!("encode thumbnail: {e}")))?;
+    write_atomic_blocking(&dest, &out)?;
+    Ok(dest)
+}
+
+/// Blocking: `cover.jpg`, `Folder.png`, … in the album folder.
+fn folder_cover(dir: &FsPath) -> Option<(PathBuf, &'static str)> {
+    let entries: Vec<PathBuf> = std::fs::read_dir(dir).ok()?.filter_map(|e| Some(e.ok()?.path())).collect();
+    FOLDER_COVERS.iter().find_map(|stem| {
+        entries.iter().find_map(|p| {
+            let s = p.file_stem()?.to_str()?.to_ascii_lowercase();
+            let mime = match p.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+                "jpg" | "jpeg" => "image/jpeg",
+                "png"          => "image/png",
+                "webp"         => "image/webp",
+                _ => return None,
+            };
+            (s == *stem).then(|| (p.clone(), mime))
+        })
+    })
+}
+
+/// Blocking: front cover (or first picture) embedded in `audio`, extracted
+/// once into the thumbnail cache.
+fn embedded_cover(store: &ImageStore, album: Uuid, file_id: Uuid, audio: &FsPath)
+    -> Option<(PathBuf, &'static str)>
+{
+    let mtime = std::fs::metadata(audio).ok()?.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    for (fmt, mime) in [(ImageFormat::Jpeg, "image/jpeg"), (ImageFormat::Png, "image/png")] {
+        let p = store.thumbs(album).join(format!("embedded-{file_id}-{mtime:x}.{}", fmt.extensions_str()[0]));
+        if p.exists() {
+            return Some((p, mime));
+        }
+    }
+
+    let tagged = lofty::read_from_path(audio)
+        .inspect_err(|e| warn!(path = %audio.display(), "reading tags for cover: {e}"))
+        .ok()?;
+    let pictures: Vec<_> = tagged.tags().iter().flat_map(|t| t.pictures()).collect();
+    let pic = pictures.iter().find(|p| p.pic_type() == PictureType::CoverFront).or(pictures.first())?;
+    let (ext, mime) = match image::guess_format(pic.data()).ok()? {
+        ImageFormat::Jpeg => ("jpg", "image/jpeg"),
+        ImageFormat::Png  => ("png", "image/png"),
+        _ => return None,
+    };
+    let dest = store.thumbs(album).join(format!("embedded-{file_id}-{mtime:x}.{ext}"));
+    write_atomic_blocking(&dest, pic.data()).ok()?;
+    Some((dest, mime))
+}
+
+/*──────── helpers ────────────────────────────────────────────────────────*/
+
+async fn album_exists(db: &PgPool, id: Uuid) -> ApiResult<()> {
+    let row: Option<(Uuid,)> = sqlx::query_as("SELECT id FROM albums WHERE id=$1")
+        .bind(id)
+        .fetch_optional(db)
+        .await?;
+    row.map(|_| ()).ok_or_else(|| ApiError::not_found("album", id))
+}
+
+/// Blocking: `(mime, width, height)` of an accepted upload.
+fn probe(data: &[u8]) -> ApiResult<(&'static str, u32, u32)> {
+    let reader = ImageReader::new(Cursor::new(data)).with_guessed_format()?;
+    let mime = match reader.format() {
+        Some(ImageFormat::Jpeg) => "image/jpeg",
+        Some(ImageFormat::Png)  => "image/png",
+        Some(ImageFormat::WebP) => "image/webp",
+        _ => return Err(ApiError::UnsupportedMediaType("images must be JPEG, PNG or WebP".into())),
+    };
+    let (w, h) = reader
+        .into_dimensions()
+        .map_err(|e| ApiError::Unprocessable(format!("unreadable image: {e}")))?;
+    Ok((mime, w, h))
+}
+
+fn static_mime(mime: &str) -> &'static str {
+    match mime {
+        "image/png"  => "image/png",
+        "image/webp" => "image/webp",
+        _            => "image/jpeg",
+    }
+}
+
+fn ext(mime: &str) -> &'static str {
+    match mime {
+        "image/png"  => "png",
+        "image/webp" => "webp",
+        _            => "jpg",
+    }
+}
+
+async fn write_atomic(dest: &FsPath, data: &[u8]) -> std::io::Result<()> {
+    let (dest, data) = (dest.to_owned(), data.to_owned());
+    tokio::task::spawn_blocking(move || write_atomic_blocking(&dest, &data)).await?
+}
+
+fn write_atomic_blocking(dest: &FsPath, data: &[u8]) -> std::io::Result<()> {
+    let dir = dest.parent().expect("has a parent");
+    std::fs::create_dir_all(dir)?;
+    let tmp = dir.join(format!(".{}.part", Uuid::new_v4()));
+    std::fs::write(&tmp, data)?;
+    std::fs::rename(&tmp, dest).inspect_err(|_| { let _ = std::fs::remove_file(&tmp); })
+}
@@ -0,0 +1,332 @@
+//! `POST /imports/batch` – one album per folder, for migrating an existing
+//! collection in one go.
+//!
+//! Folders come as a list or as every sub-directory of a parent.  Each one
+//! is validated like `POST /albums` (inside the roots, not tombstoned); a
+//! folder that already backs an album is reported, not duplicated.  Import
+//! jobs are queued at [`jobs::LOW_PRIORITY`].  The batch keeps per-folder
+//! results, and `GET /imports/batch/:id` rolls up the albums' progress.
+
+use std::collections::HashSet;
+
+use axum::{extract::State, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use shared::pipeline::Stage;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use tracing::{info, instrument};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    albums::{self, AlbumKind, AlbumMeta, AlbumSource},
+    audit,
+    auth::Principal,
+    error::{ApiError, ApiResult, Json, Path, Problem},
+    jobs, AppState,
+};
+
+/// Cap per request; split bigger migrations.
+const MAX_FOLDERS: usize = 2_000;
+
+/*──────── model ──────────────────────────────────────────────────────────*/
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NewBatch {
+    /// Album folders.  Give either these or `parent`.
+    pub paths:      Option<Vec<String>>,
+    /// Every immediate, non-hidden sub-directory becomes an album.
+    pub parent:     Option<String>,
+    /// Applied to every album created.
+    #[serde(default)]
+    pub album_kind: AlbumKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum Outcome {
+    /// New album, Import queued.
+    Created,
+    /// Folder already backs an album – `album_id` points at it.
+    Exists,
+    /// Not imported; see `detail`.
+    Rejected,
+}
+
+/// Pipeline roll-up for the album behind a folder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum ItemState {
+    /// Import hasn't produced any files yet.
+    Queued,
+    InProgress,
+    Ready,
+    Error,
+    /// Album deleted since.
+    Deleted,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct BatchItem {
+    pub path:     String,
+    pub outcome:  Outcome,
+    pub album_id: Option<Uuid>,
+    pub detail:   Option<String>,
+    pub state:    Option<ItemState>,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct BatchProgress {
+    pub total:       usize,
+    pub created:     usize,
+    pub exists:      usize,
+    pub rejected:    usize,
+    pub queued:      usize,
+    pub in_progress: usize,
+    pub ready:       usize,
+    pub error:       usize,
+    pub deleted:     usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Batch {
+    pub id:         Uuid,
+    pub parent:     Option<String>,
+    pub created_by: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    pub progress:   BatchProgress,
+    pub items:      Vec<BatchItem>,
+}
+
+/*──────── handlers ───────────────────────────────────────────────────────*/
+
+/// `POST /imports/batch`
+#[utoipa::path(
+    post, path = "/imports/batch", tag = "albums", request_body = NewBatch,
+    responses(
+        (status = 201, description = "Batch created; see per-folder outcomes", body = Batch),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all)]
+pub async fn create_batch(
+    State(app): State<AppState>,
+    who: Principal,
+    Json(req): Json<NewBatch>,
+) -> ApiResult<(StatusCode, Json<Batch>)> {
+    let (parent, folders) = match (req.parent, req.paths) {
+        (Some(parent), None) => {
+            let real = albums::folder(&app.roots, &parent)?;
+            let dir = real.clone();
+            (Some(real), tokio::task::spawn_blocking(move || subdirs(&dir)).await??)
+        }
+        (None, Some(paths)) => (None, paths),
+        _ => return Err(ApiError::Unprocessable("give either `paths` or `parent`".into())),
+    };
+    if folders.is_empty() {
+        return Err(ApiError::Unprocessable("no folders to import".into()));
+    }
+    if folders.len() > MAX_FOLDERS {
+        return Err(ApiError::Unprocessable(format!(
+            "{} folders – at most {MAX_FOLDERS} per batch", folders.len()
+        )));
+    }
+
+    let id = Uuid::new_v4();
+    let mut tx = app.db.begin().await?;
+    sqlx::query("INSERT INTO import_batches(id, parent, created_by) VALUES ($1,$2,$3)")
+        .bind(id)
+        .bind(&parent)
+        .bind(&who.username)
+        .execute(&mut *tx)
+        .await?;
+
+    let mut seen = HashSet::new();
+    let mut created = 0;
+    for (pos, given) in folders.into_iter().enumerate() {
+        let (path, outcome, album_id, detail) = match albums::folder(&app.roots, &given) {
+            Err(e) => (given, Outcome::Rejected, None, Some(detail(e))),
+            Ok(real) if !seen.insert(real.clone()) => {
+                (real, Outcome::Rejected, None, Some("listed twice in this batch".to_owned()))
+            }
+            Ok(real) => match existing(&app.db, &real).await? {
+                Some(album) => (real, Outcome::Exists, Some(album), None),
+                None => match albums::refuse_tombstoned(&app.db, &real).await {
+                    Err(e) => (real, Outcome::Rejected, None, Some(detail(e))),
+                    Ok(()) => {
+                        let album = Uuid::new_v4();
+                        let (artist, title, year) = guess(&real);
+                        let source = AlbumSource::LibraryScan { path: real.clone(), batch_id: Some(id) };
+                        let meta = AlbumMeta { title, artist, year, venue: None };
+                        albums::insert_album(&mut *tx, album, meta, req.album_kind, &source).await?;
+                        jobs::enqueue_with_priority(&mut *tx, Stage::Import, Some(album), None, jobs::LOW_PRIORITY)
+                            .await?;
+                        created += 1;
+                        (real, Outcome::Created, Some(album), None)
+                    }
+                },
+            },
+        };
+        sqlx::query(
+            "INSERT INTO import_batch_items(batch_id, position, path, album_id, outcome, detail)
+                  VALUES ($1,$2,$3,$4,$5,$6)",
+        )
+        .bind(id)
+        .bind(pos as i32)
+        .bind(path)
+        .bind(album_id)
+        .bind(outcome)
+        .bind(detail)
+        .execute(&mut *tx)
+        .await?;
+    }
+    audit::record(&mut *tx, &who.username, "import.batch", ("batch", Some(id)),
+                  serde_json::json!({ "parent": parent, "created": created }))
+        .await?;
+    tx.commit().await?;
+    info!(batch_id = %id, created, "import batch queued");
+
+    Ok((StatusCode::CREATED, Json(load(&app.db, id).await?)))
+}
+
+/// `GET /imports/batch/:id`
+#[utoipa::path(
+    get, path = "/imports/batch/{id}", tag = "albums",
+    params(("id" = Uuid, Path, description = "Batch id")),
+    responses((status = 200, body = Batch), (status = 404, response = Problem)),
+)]
+pub async fn get_batch(Path(id): Path<Uuid>, State(app): State<AppState>) -> ApiResult<Json<Batch>> {
+    Ok(Json(load(&app.db, id).await?))
+}
+
+/*──────── helpers ────────────────────────────────────────────────────────*/
+
+async fn load(db: &PgPool, id: Uuid) -> ApiResult<Batch> {
+    let head: Option<(Option<String>, String, OffsetDateTime)> =
+        sqlx::query_as("SELECT parent, created_by, created_at FROM import_batches WHERE id=$1")
+            .bind(id)
+            .fetch_optional(db)
+            .await?;
+    let (parent, created_by, created_at) = head.ok_or_else(|| ApiError::not_found("batch", id))?;
+
+    let items: Vec<BatchItem> = sqlx::query_as(
+        "SELECT i.path, i.outcome, i.album_id, i.detail,
+                CASE WHEN i.album_id IS NULL THEN NULL
+                     WHEN a.id IS NULL       THEN 'deleted'
+                     WHEN st.err             THEN 'error'
+                     WHEN st.n = 0 AND EXISTS (
+                          SELECT 1 FROM jobs j
+                           WHERE j.payload->>'album_id' = a.id::text
+                             AND j.stage = 'import' AND j.status = 'error')
+                                             THEN 'error'
+                     WHEN st.n = 0           THEN 'queued'
+                     WHEN st.ready           THEN 'ready'
+                     ELSE 'in_progress'
+                END AS state
+           FROM import_batch_items i
+           LEFT JOIN albums a ON a.id = i.album_id
+           LEFT JOIN LATERAL (
+                SELECT COUNT(f.id) AS n,
+                       COALESCE(bool_or(f.status = 'ERROR'), FALSE) AS err,
+                       bool_and(f.status = 'READY') AS ready
+                  FROM tracks t JOIN files f ON f.track_id = t.id
+                 WHERE t.album_id = a.id
+           ) st ON true
+          WHERE i.batch_id = $1
+       ORDER BY i.position",
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    let mut p = BatchProgress { total: items.len(), ..Default::default() };
+    for i in &items {
+        match i.outcome {
+            Outcome::Created  => p.created  += 1,
+            Outcome::Exists   => p.exists   += 1,
+            Outcome::Rejected => p.rejected += 1,
+        }
+        match i.state {
+            Some(ItemState::Queued)     => p.queued      += 1,
+            Some(ItemState::InProgress) => p.in_progress += 1,
+            Some(ItemState::Ready)      => p.ready       += 1,
+            Some(ItemState::Error)      => p.error       += 1,
+            Some(ItemState::Deleted)    => p.deleted     += 1,
+            None => {}
+        }
+    }
+    Ok(Batch { id, parent, created_by, created_at, progress: p, items })
+}
+
+async fn existing(db: &PgPool, path: &str) -> sqlx::Result<Option<Uuid>> {
+    // a folder merged into another album still counts as imported
+    let row: Option<(Uuid,)> = sqlx::query_as(
+        "SELECT id FROM albums WHERE source->>'path' = $1
+         UNION ALL SELECT into_id FROM album_merges WHERE path = $1
+         LIMIT 1",
+    )
+    .bind(path)
+    .fetch_optional(db)
+    .await?;
+    Ok(row.map(|(id,)| id))
+}
+
+fn detail(e: ApiError) -> String {
+    match e {
+        ApiError::Unprocessable(m) | ApiError::Conflict(m) => m,
+        other => format!("{other:?}"),
+    }
+}
+
+/// Blocking: sorted, non-hidden sub-directories of `dir`.
+fn subdirs(dir: &str) -> std::io::Result<Vec<String>> {
+    let mut out = Vec::new();
+    for e in std::fs::read_dir(dir)? {
+        let e = e?;
+        if e.file_type()?.is_dir() && !e.file_name().to_string_lossy().starts_with('.') {
+            out.push(e.path().to_string_lossy().into_owned());
+        }
+    }
+    out.sort();
+    Ok(out)
+}
+
+/// Best guess from the folder name until Match knows better:
+/// `Artist - Title (1999)`, `1999 - Title`, or just `Title`.
+fn guess(path: &str) -> (Option<String>, Option<String>, Option<i32>) {
+    let name = std::path::Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().trim().to_owned())
+        .unwrap_or_default();
+    let mut rest = name.as_str();
+    let mut year = None;
+
+    let parse_year = |s: &str| s.parse::<i32>().ok().filter(|y| (1000..=9999).contains(y));
+    if let Some(open) = rest.rfind(['(', '[']) {
+        let inner = rest[open + 1..].trim_end_matches([')', ']']);
+        if rest.ends_with([')', ']']) {
+            if let Some(y) = parse_year(inner) {
+                year = Some(y);
+                rest = rest[..open].trim_end();
+            }
+        }
+    }
+    if year.is_none() {
+        if let Some((head, tail)) = rest.split_once(" - ") {
+            if let Some(y) = parse_year(head.trim()) {
+                year = Some(y);
+                rest = tail.trim();
+            }
+        }
+    }
+    let (artist, title) = match rest.split_once(" - ") {
+        Some((a, t)) => (Some(a.trim()), t.trim()),
+        None => (None, rest),
+    };
+    let clean = |s: &str| Some(s.to_owned()).filter(|s| !s.is_empty() && s.chars().count() <= 512);
+    (artist.and_then(clean), clean(title), year)
+}
@@ -0,0 +1,38 @@
+//! Durable job queue (`jobs` table) – the API only ever appends.
+
+use shared::pipeline::{JobEnvelope, Stage};
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+/// `jobs.priority` for bulk work that should yield to interactive requests.
+pub const LOW_PRIORITY: i16 = -10;
+
+/// Queue `stage` for an album and/or file.
+pub async fn enqueue<'e, E>(db: E, stage: Stage, album_id: Option<Uuid>, file_id: Option<Uuid>)
+    -> sqlx::Result<()>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    enqueue_with_priority(db, stage, album_id, file_id, 0).await
+}
+
+/// As [`enqueue`]; higher `priority` runs first.
+pub async fn enqueue_with_priority<'e, E>(
+    db:       E,
+    stage:    Stage,
+    album_id: Option<Uuid>,
+    file_id:  Option<Uuid>,
+    priority: i16,
+) -> sqlx::Result<()>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let env = JobEnvelope { album_id, track_id: None, file_id, stage };
+    sqlx::query("INSERT INTO jobs(stage, payload, priority) VALUES ($1, $2, $3)")
+        .bind(stage.as_str())
+        .bind(serde_json::to_value(&env).expect("JobEnvelope serialises"))
+        .bind(priority)
+        .execute(db)
+        .await?;
+    Ok(())
+}
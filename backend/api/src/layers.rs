@@ -0,0 +1,193 @@
+//! Cross-cutting HTTP layers: request ids, body-size limits, rate limiting,
+//! timeouts and response compression.
+//!
+//! Every request gets an `x-request-id` (a client-supplied one is kept),
+//! echoed in the response and recorded on its tracing span.  Rate limits are
+//! token buckets per client IP and, when a bearer token or session cookie is
+//! presented, per token as well; a bucket holds a minute's worth of requests.
+//! Subsonic clients, which authenticate in the query string, are limited per
+//! IP only.
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{ConnectInfo, DefaultBodyLimit, Request, State},
+    http::{header, Extensions, HeaderMap, HeaderName, StatusCode, Version},
+    middleware::{self, Next},
+    response::Response,
+    Router,
+};
+use sha2::{Digest, Sha256};
+use tower::ServiceBuilder;
+use tower_http::{
+    compression::{predicate::SizeAbove, CompressionLayer, Predicate},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::{DefaultOnResponse, TraceLayer},
+};
+use tracing::{info_span, Level};
+
+use crate::{
+    auth,
+    error::{ApiError, ApiResult},
+    AppState,
+};
+
+const REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+/// Idle buckets are dropped this often.
+const SWEEP_EVERY: Duration = Duration::from_secs(60);
+
+/*──────── config ─────────────────────────────────────────────────────────*/
+
+#[derive(Debug, Clone)]
+pub struct HttpLimits {
+    /// Request bodies everywhere but uploads (which have their own limits).
+    pub body_bytes:      usize,
+    /// Time to produce the response head; streamed bodies aren't bounded.
+    pub timeout:         Duration,
+    /// Requests per minute; `0` disables the limit.
+    pub per_ip:          u32,
+    pub per_token:       u32,
+    /// Take the client IP from the last `X-Forwarded-For` hop (behind a
+    /// reverse proxy); otherwise the peer address is used.
+    pub trust_forwarded: bool,
+}
+
+impl HttpLimits {
+    pub fn from_env() -> Self {
+        let var = |k: &str, d: u64| std::env::var(k).ok().and_then(|v| v.parse().ok()).unwrap_or(d);
+        Self {
+            body_bytes:      var("HTTP_BODY_LIMIT_BYTES", 1 << 20) as usize,
+            timeout:         Duration::from_secs(var("HTTP_TIMEOUT_SECS", 30).max(1)),
+            per_ip:          var("RATE_LIMIT_PER_IP", 1200) as u32,
+            per_token:       var("RATE_LIMIT_PER_TOKEN", 600) as u32,
+            trust_forwarded: std::env::var("TRUST_FORWARDED_FOR").is_ok_and(|v| v == "1" || v == "true"),
+        }
+    }
+}
+
+/*──────── stack ──────────────────────────────────────────────────────────*/
+
+/// Wrap the whole app: request id → trace span → compression → rate limit →
+/// default body limit.  Outermost first, so even a 429 carries its id.
+pub fn around(router: Router, limits: &HttpLimits) -> Router {
+    let limiter = Arc::new(RateLimiter::new(limits.clone()));
+    router.layer(
+        ServiceBuilder::new()
+            .layer(SetRequestIdLayer::new(REQUEST_ID, MakeRequestUuid))
+            .layer(
+                TraceLayer::new_for_http()
+                    // path only – Subsonic credentials ride in the query string
+                    .make_span_with(|req: &Request| {
+                        let id = req.headers().get(REQUEST_ID).and_then(|v| v.to_str().ok()).unwrap_or("-");
+                        info_span!("request", method = %req.method(), path = %req.uri().path(), request_id = %id)
+                    })
+                    .on_response(DefaultOnResponse::new().level(Level::INFO)),
+            )
+            .layer(PropagateRequestIdLayer::new(REQUEST_ID))
+            .layer(CompressionLayer::new().compress_when(SizeAbove::new(1024).and(compressible)))
+            .layer(middleware::from_fn_with_state(limiter, rate_limit))
+            .layer(DefaultBodyLimit::max(limits.body_bytes)),
+    )
+}
+
+/// JSON and XML (Subsonic, XSPF) – never audio, images or SSE.
+fn compressible(_: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|ct| ct.split(';').next())
+        .is_some_and(|ct| ["/json", "+json", "/xml", "+xml"].iter().any(|s| ct.ends_with(s)))
+}
+
+/// Route layer bounding how long a handler may take to answer.
+pub async fn timeout(State(app): State<AppState>, req: Request, next: Next) -> ApiResult<Response> {
+    let limit = app.http.timeout;
+    tokio::time::timeout(limit, next.run(req))
+        .await
+        .map_err(|_| ApiError::Unavailable(format!("request took longer than {} s", limit.as_secs())))
+}
+
+/*──────── rate limiting ──────────────────────────────────────────────────*/
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Ip(IpAddr),
+    /// SHA-256 of the presented secret – keyed before the (DB) lookup.
+    Token([u8; 32]),
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    at:     Instant,
+}
+
+#[derive(Debug)]
+struct RateLimiter {
+    limits:  HttpLimits,
+    buckets: Mutex<(HashMap<Key, Bucket>, Instant)>,
+}
+
+impl RateLimiter {
+    fn new(limits: HttpLimits) -> Self {
+        Self { limits, buckets: Mutex::new((HashMap::new(), Instant::now())) }
+    }
+
+    /// Take one request from `key`'s bucket, or say how many seconds until
+    /// there is one.
+    fn take(&self, key: Key, per_minute: u32) -> Result<(), u64> {
+        if per_minute == 0 {
+            return Ok(());
+        }
+        let capacity = f64::from(per_minute);
+        let refill = capacity / 60.0;
+        let now = Instant::now();
+        let mut guard = self.buckets.lock().expect("rate limiter poisoned");
+        let (buckets, swept) = &mut *guard;
+        if now.duration_since(*swept) > SWEEP_EVERY {
+            // a bucket idle this long is full again – same as no bucket
+            buckets.retain(|_, b| now.duration_since(b.at) < SWEEP_EVERY);
+            *swept = now;
+        }
+
+        let b = buckets.entry(key).or_insert(Bucket { tokens: capacity, at: now });
+        b.tokens = (b.tokens + now.duration_since(b.at).as_secs_f64() * refill).min(capacity);
+        b.at = now;
+        if b.tokens >= 1.0 {
+            b.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - b.tokens) / refill).ceil() as u64)
+        }
+    }
+
+    fn client_ip(&self, req: &Request) -> Option<IpAddr> {
+        let forwarded = self
+            .limits
+            .trust_forwarded
+            .then(|| req.headers().get("x-forwarded-for")?.to_str().ok()?.rsplit(',').next()?.trim().parse().ok())
+            .flatten();
+        forwarded.or_else(|| req.extensions().get::<ConnectInfo<SocketAddr>>().map(|c| c.0.ip()))
+    }
+}
+
+async fn rate_limit(State(limiter): State<Arc<RateLimiter>>, req: Request, next: Next)
+    -> ApiResult<Response>
+{
+    if req.uri().path() == "/internal/health" {
+        return Ok(next.run(req).await);
+    }
+    if let Some(ip) = limiter.client_ip(&req) {
+        limiter.take(Key::Ip(ip), limiter.limits.per_ip).map_err(ApiError::RateLimited)?;
+    }
+    if let Some(secret) = auth::presented_token(req.headers()) {
+        let key = Key::Token(Sha256::digest(secret.as_bytes()).into());
+        limiter.take(key, limiter.limits.per_token).map_err(ApiError::RateLimited)?;
+    }
+    Ok(next.run(req).await)
+}
@@ -1,19 +1,65 @@
 //! Minimal album-centric façade (v0).
 
+mod albums;
+mod archive;
+mod audit;
+mod auth;
+mod error;
+mod events;
+mod history;
+mod images;
+mod imports;
+mod jobs;
+mod layers;
+mod manifest;
+mod merge;
+mod openapi;
+mod playlists;
+mod removal;
+mod reprocess;
+mod review;
+mod roots;
+mod search;
+mod stats;
+mod stream;
+mod subsonic;
+mod tracks;
+mod transcode;
+mod uploads;
+mod webhooks;
+
+use std::net::SocketAddr;
+
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    routing::{get, post, put},
-    Json, Router,
+    extract::{DefaultBodyLimit, State},
+    middleware,
+    routing::{delete, get, head, patch, post, put},
+    Router,
 };
-use shared::pipeline::{JobEnvelope, Stage};
-use sqlx::{PgPool, migrate::Migrator};
-use uuid::Uuid;
-use tracing::{info, instrument};
+use sqlx::{migrate::Migrator, postgres::PgConnectOptions, PgPool};
 use anyhow::Result;
 
+use auth::{AuthConfig, Scope};
+use events::EventBus;
+use images::ImageStore;
+use layers::HttpLimits;
+use roots::Roots;
+use transcode::Transcoder;
+use uploads::{TusWriters, UploadLimits};
+
 #[derive(Clone)]
-struct AppState { db: PgPool }
+struct AppState {
+    db:     PgPool,
+    roots:  Roots,
+    limits: UploadLimits,
+    tus_writers: TusWriters,
+    events: EventBus,
+    auth:   AuthConfig,
+    http:   HttpLimits,
+    images: ImageStore,
+    bulk:   std::sync::Arc<reprocess::Bulk>,
+    transcoder: std::sync::Arc<Transcoder>,
+}
 
 static MIGRATOR: Migrator = sqlx::migrate!("../migrations");
 
@@ -22,63 +68,177 @@ async fn main() -> Result<()> {
     dotenvy::dotenv().ok();
     tracing_subscriber::fmt::init();
 
-    let db = PgPool::connect(&std::env::var("DATABASE_URL")?).await?;
+    // named, so metadata_history can tell API writes nobody attributed
+    let opts: PgConnectOptions = std::env::var("DATABASE_URL")?.parse()?;
+    let db = PgPool::connect_with(opts.application_name("setlist-api")).await?;
     MIGRATOR.run(&db).await?;
-    let state = AppState { db };
+    auth::bootstrap(&db).await?;
+    stats::spawn_refresher(db.clone());
+    webhooks::spawn_dispatcher(db.clone());
+    let state = AppState {
+        events: events::spawn_listener(db.clone()),
+        db,
+        roots:  Roots::from_env(),
+        limits: UploadLimits::from_env(),
+        tus_writers: TusWriters::default(),
+        auth:   AuthConfig::from_env(),
+        http:   HttpLimits::from_env(),
+        images: ImageStore::from_env(),
+        bulk:   reprocess::Bulk::from_env(),
+        transcoder: Transcoder::from_env(),
+    };
 
     let app = Router::new()
         .route("/internal/health", get(|| async { "ok" }))
-        .route("/albums",               post(create_album))
-        .route("/albums/:id",           get(get_album))
-        .route("/albums/:id/complete",  put(complete_album))
-        .with_state(state);
+        .route("/openapi.json",    get(openapi::spec))
+        .route("/auth/login",      post(auth::login))
+        // Subsonic clients authenticate per request, in the query string
+        .route("/rest/:method",    get(subsonic::rest).post(subsonic::rest))
+        .merge(auth::scoped(account_routes(), &state, None))
+        .merge(auth::scoped(read_routes(),    &state, Some(Scope::Read)))
+        .merge(auth::scoped(stream_routes(),  &state, Some(Scope::Stream)))
+        .merge(auth::scoped(upload_routes(),  &state, Some(Scope::Upload)))
+        .merge(auth::scoped(edit_routes(),    &state, Some(Scope::Edit)))
+        .merge(auth::scoped(admin_routes(),   &state, Some(Scope::Admin)))
+        .route_layer(middleware::from_fn_with_state(state.clone(), layers::timeout))
+        // after the timeout: transfers, checksumming, purges and library dumps take as long as they take
+        .merge(auth::scoped(transfer_routes(),   &state, Some(Scope::Upload)))
+        .merge(auth::scoped(completion_routes(), &state, Some(Scope::Upload)))
+        .merge(auth::scoped(removal_routes(),    &state, Some(Scope::Edit)))
+        .merge(auth::scoped(archive_routes(),    &state, Some(Scope::Admin)))
+        .fallback(error::no_route)
+        .with_state(state.clone());
+    let app = layers::around(app, &state.http);
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await?;
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
     Ok(())
 }
 
-/*──────── handlers ───────────────────────────────────────────────────────*/
-#[instrument(skip_all)]
-async fn create_album(State(app): State<AppState>) -> Result<Json<Uuid>, (StatusCode, String)> {
-    let id = Uuid::new_v4();
-    sqlx::query("INSERT INTO albums(id, source) VALUES ($1, '{\"type\":\"upload\"}')")
-        .bind(id)
-        .execute(&app.db)
-        .await
-        .map_err(internal)?;
-    Ok(Json(id))
+/*──────── route groups, one per scope ───────────────────────────────────*/
+
+/// Any valid token – the caller's own session and tokens.
+fn account_routes() -> Router<AppState> {
+    Router::new()
+        .route("/auth/logout",     post(auth::logout))
+        .route("/auth/me",         get(auth::me))
+        .route("/auth/tokens",     get(auth::list_tokens).post(auth::create_token))
+        .route("/auth/tokens/:id", delete(auth::revoke_token))
 }
 
-async fn get_album(Path(id): Path<Uuid>, State(app): State<AppState>)
-    -> Result<String, (StatusCode, String)>
-{
-    // TODO: materialized view / join with jobs for fancy status
-    let row: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM jobs WHERE payload->>'album_id' = $1")
-        .bind(id.to_string())
-        .fetch_one(&app.db)
-        .await
-        .map_err(internal)?;
-    Ok(format!("{} jobs for album", row.0))
+/// Also the caller's own playlists – personal, not library edits.
+fn read_routes() -> Router<AppState> {
+    Router::new()
+        .route("/events",               get(events::all_events))
+        .route("/albums",               get(albums::list_albums))
+        .route("/albums/:id",           get(albums::get_album))
+        .route("/albums/:id/manifest",  get(manifest::get_manifest))
+        .route("/albums/:id/events",    get(events::album_events))
+        .route("/albums/:id/tracks",    get(tracks::list_album_tracks))
+        .route("/albums/:id/images",    get(images::list_images))
+        .route("/albums/:id/images/:image_id", get(images::get_image))
+        .route("/albums/:id/cover",     get(images::cover))
+        .route("/tracks/:id",           get(tracks::get_track))
+        .route("/albums/:id/history",   get(history::album_history))
+        .route("/tracks/:id/history",   get(history::track_history))
+        .route("/review/files",         get(review::list_files))
+        .route("/review/albums",        get(review::list_albums))
+        .route("/search",               get(search::search))
+        .route("/stats",                get(stats::stats))
+        .route("/playlists",            get(playlists::list_playlists).post(playlists::create_playlist))
+        .route("/playlists/import",
+               post(playlists::import_playlist).layer(DefaultBodyLimit::max(playlists::IMPORT_MAX_BYTES)))
+        .route("/playlists/:id",
+               get(playlists::get_playlist).patch(playlists::patch_playlist).delete(playlists::delete_playlist))
+        .route("/playlists/:id/entries", put(playlists::put_entries).post(playlists::add_entries))
+        .route("/playlists/:id/entries/:index", delete(playlists::remove_entry))
+        .route("/playlists/:id/export", get(playlists::export_playlist))
+        .route("/tombstones",           get(removal::list_tombstones))
+        .route("/imports/batch/:id",    get(imports::get_batch))
 }
 
-#[instrument(skip_all)]
-async fn complete_album(
-    Path(id): Path<Uuid>,
-    State(app): State<AppState>,
-) -> Result<(), (StatusCode, String)> {
-    let env = JobEnvelope { album_id: Some(id), track_id: None, file_id: None, stage: Stage::Import };
-    sqlx::query("INSERT INTO jobs(stage, payload) VALUES ($1, $2)")
-        .bind(Stage::Import.as_str())
-        .bind(serde_json::to_value(&env).unwrap())
-        .execute(&app.db)
-        .await
-        .map_err(internal)?;
-    info!("queued import job");
-    Ok(())
+fn stream_routes() -> Router<AppState> {
+    Router::new()
+        .route("/files/:id/stream",     get(stream::stream_file))
+        .route("/tracks/:id/stream",    get(stream::stream_track))
+}
+
+fn upload_routes() -> Router<AppState> {
+    Router::new()
+        .route("/albums",               post(albums::create_album))
+        .route("/albums/:id/manifest",  put(manifest::put_manifest))
+        .route("/imports/batch",        post(imports::create_batch))
 }
 
-fn internal<E: std::fmt::Display>(e: E) -> (StatusCode, String) {
-    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+/// File bodies, also `upload` scope.  They enforce their own per-file /
+/// per-album (and per-image) limits, so the default body limit is lifted,
+/// and they aren't subject to the request timeout.
+fn transfer_routes() -> Router<AppState> {
+    Router::new()
+        .route("/albums/:id/files",     post(uploads::upload_files))
+        .route("/albums/:id/uploads",   post(uploads::tus_create).options(uploads::tus_options))
+        .route("/albums/:id/uploads/:upload_id",
+               head(uploads::tus_head).patch(uploads::tus_patch).delete(uploads::tus_delete))
+        .route("/albums/:id/images",    post(images::upload_image))
+        .layer(DefaultBodyLimit::disable())
 }
 
+/// Completing an album checksums every manifest entry first – minutes for a
+/// multi-GB box set – so it is untimed too.  Also `upload` scope.
+fn completion_routes() -> Router<AppState> {
+    Router::new()
+        .route("/albums/:id/complete",  put(albums::complete_album))
+}
+
+/// `DELETE /albums/:id?mode=purge` removes the media from disk before it
+/// answers – a large folder takes a while – so it is untimed.  `edit` scope.
+fn removal_routes() -> Router<AppState> {
+    Router::new()
+        .route("/albums/:id",           delete(removal::delete_album))
+}
+
+fn edit_routes() -> Router<AppState> {
+    Router::new()
+        .route("/tombstones/:album_id", delete(removal::lift_tombstone))
+        .route("/albums/:id/reprocess", post(reprocess::reprocess_album))
+        .route("/files/:id/reprocess",  post(reprocess::reprocess_file))
+        .route("/albums/:id/images/:image_id",
+               patch(images::patch_image).delete(images::delete_image))
+        .route("/tracks/:id",           patch(tracks::patch_track))
+        .route("/albums/:id/history/revert", post(history::revert_album))
+        .route("/tracks/:id/history/revert", post(history::revert_track))
+        .route("/albums/:id/discs/:disc/order", put(tracks::reorder_disc))
+        .route("/albums/:id/merge",     post(merge::merge_album))
+        .route("/albums/:id/split",     post(merge::split_album))
+        .route("/files/:id/track",      put(tracks::assign_file))
+        .route("/review/files/:id/choose",  post(review::choose_file))
+        .route("/review/files/:id/manual",  post(review::manual_file))
+        .route("/review/files/:id/reject",  post(review::reject_file))
+        .route("/review/albums/:id/choose", post(review::choose_album))
+        .route("/review/albums/:id/manual", post(review::manual_album))
+        .route("/review/albums/:id/reject", post(review::reject_album))
+}
+
+fn admin_routes() -> Router<AppState> {
+    Router::new()
+        .route("/internal/metrics", get(|State(app): State<AppState>| async move { app.transcoder.render_metrics() }))
+        .route("/users",            get(auth::list_users).post(auth::create_user))
+        .route("/users/:id",        patch(auth::patch_user))
+        .route("/audit",            get(audit::list))
+        .route("/reprocess",
+               get(reprocess::bulk_status).post(reprocess::start_bulk).delete(reprocess::stop_bulk))
+        .route("/webhooks",         get(webhooks::list_webhooks).post(webhooks::create_webhook))
+        .route("/webhooks/:id",
+               get(webhooks::get_webhook).patch(webhooks::patch_webhook).delete(webhooks::delete_webhook))
+        .route("/webhooks/:id/test",       post(webhooks::test_webhook))
+        .route("/webhooks/:id/deliveries", get(webhooks::list_deliveries))
+        .route("/webhooks/:id/deliveries/:delivery_id/retry", post(webhooks::retry_delivery))
+}
+
+/// Whole-library export / restore, `admin` scope.  Untimed like transfers:
+/// both are proportional to the library.
+fn archive_routes() -> Router<AppState> {
+    Router::new()
+        .route("/export",  get(archive::export))
+        .route("/restore", post(archive::restore).layer(DefaultBodyLimit::max(archive::RESTORE_MAX_BYTES)))
+}
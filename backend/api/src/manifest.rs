@@ -0,0 +1,262 @@
+//! Expected-file manifest: clients declare names, sizes and checksums up
+//! front; `PUT /albums/:id/complete` refuses to start the Import until the
+//! folder on disk matches it.  Rows stay in `album_manifest` for audits.
+
+use std::{
+    io::Read,
+    path::{Path as FsPath, PathBuf},
+};
+
+use axum::extract::State;
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+use tracing::{info, instrument};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    error::{ApiError, ApiResult, Json, Path, Problem},
+    uploads::sanitize_rel_path,
+    AppState,
+};
+
+/*──────── model ──────────────────────────────────────────────────────────*/
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct ManifestEntry {
+    pub path:   String,
+    pub size:   i64,
+    pub md5:    Option<String>,
+    pub sha256: Option<String>,
+    #[serde(default, skip_deserializing, with = "time::serde::rfc3339::option")]
+    pub verified_at: Option<OffsetDateTime>,
+}
+
+/// Why an album can't be completed yet.
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ManifestDiff {
+    pub missing:           Vec<String>,
+    pub size_mismatch:     Vec<SizeMismatch>,
+    pub checksum_mismatch: Vec<ChecksumMismatch>,
+    /// On disk but not declared – reported, never blocking.
+    pub unexpected:        Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SizeMismatch { pub path: String, pub expected: i64, pub actual: i64 }
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChecksumMismatch {
+    pub path:      String,
+    pub algorithm: &'static str,
+    pub expected:  String,
+    pub actual:    String,
+}
+
+impl ManifestDiff {
+    pub fn is_blocking(&self) -> bool {
+        !(self.missing.is_empty() && self.size_mismatch.is_empty() && self.checksum_mismatch.is_empty())
+    }
+}
+
+/*──────── handlers ───────────────────────────────────────────────────────*/
+
+/// `PUT /albums/:id/manifest` – replace the declared file list.
+#[utoipa::path(
+    put, path = "/albums/{id}/manifest", tag = "uploads",
+    params(("id" = Uuid, Path, description = "Album id")), request_body = Vec<ManifestEntry>,
+    responses(
+        (status = 200, description = "Stored manifest", body = Vec<ManifestEntry>),
+        (status = 404, response = Problem),
+        (status = 413, response = Problem),
+        (status = 415, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id))]
+pub async fn put_manifest(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    Json(entries): Json<Vec<ManifestEntry>>,
+) -> ApiResult<Json<Vec<ManifestEntry>>> {
+    let mut clean = Vec::with_capacity(entries.len());
+    for e in entries {
+        let path = sanitize_rel_path(&e.path)?.to_string_lossy().into_owned();
+        if e.size < 0 {
+            return Err(ApiError::Unprocessable(format!("{path}: negative size")));
+        }
+        let md5    = e.md5.map(|h| check_hex(&path, "md5", h, 32)).transpose()?;
+        let sha256 = e.sha256.map(|h| check_hex(&path, "sha256", h, 64)).transpose()?;
+        if md5.is_none() && sha256.is_none() {
+            return Err(ApiError::Unprocessable(format!("{path}: md5 or sha256 required")));
+        }
+        clean.push(ManifestEntry { path, size: e.size, md5, sha256, verified_at: None });
+    }
+
+    let mut tx = app.db.begin().await?;
+    let exists: Option<(Uuid,)> = sqlx::query_as("SELECT id FROM albums WHERE id=$1 FOR UPDATE")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    if exists.is_none() {
+        return Err(ApiError::not_found("album", id));
+    }
+    sqlx::query("DELETE FROM album_manifest WHERE album_id=$1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    for e in &clean {
+        sqlx::query(
+            "INSERT INTO album_manifest(album_id, rel_path, size, md5, sha256)
+                  VALUES ($1,$2,$3,$4,$5)",
+        )
+        .bind(id)
+        .bind(&e.path)
+        .bind(e.size)
+        .bind(&e.md5)
+        .bind(&e.sha256)
+        .execute(&mut *tx)
+        .await
+        .map_err(|err| match err.as_database_error().and_then(|d| d.code()) {
+            Some(c) if c == "23505" => ApiError::Unprocessable(format!("{} listed twice", e.path)),
+            _ => err.into(),
+        })?;
+    }
+    tx.commit().await?;
+    info!(files = clean.len(), "manifest stored");
+    Ok(Json(clean))
+}
+
+/// `GET /albums/:id/manifest`
+#[utoipa::path(
+    get, path = "/albums/{id}/manifest", tag = "uploads",
+    params(("id" = Uuid, Path, description = "Album id")),
+    responses(
+        (status = 200, description = "Declared files", body = Vec<ManifestEntry>),
+        (status = 404, response = Problem),
+    ),
+)]
+pub async fn get_manifest(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+) -> ApiResult<Json<Vec<ManifestEntry>>> {
+    let exists: Option<(Uuid,)> = sqlx::query_as("SELECT id FROM albums WHERE id=$1")
+        .bind(id)
+        .fetch_optional(&app.db)
+        .await?;
+    if exists.is_none() {
+        return Err(ApiError::not_found("album", id));
+    }
+    Ok(Json(load(&app.db, id).await?))
+}
+
+/*──────── verification ───────────────────────────────────────────────────*/
+
+pub async fn load(db: &sqlx::PgPool, album_id: Uuid) -> sqlx::Result<Vec<ManifestEntry>> {
+    sqlx::query_as(
+        "SELECT rel_path AS path, size, md5, sha256, verified_at
+           FROM album_manifest WHERE album_id=$1 ORDER BY rel_path",
+    )
+    .bind(album_id)
+    .fetch_all(db)
+    .await
+}
+
+/// Compare the manifest against `root`.  `Ok(None)` when no manifest was
+/// declared; otherwise the diff (possibly empty).  Matching files get their
+/// `verified_at` bumped.
+pub async fn verify(db: &sqlx::PgPool, album_id: Uuid, root: PathBuf)
+    -> anyhow::Result<Option<ManifestDiff>>
+{
+    let entries = load(db, album_id).await?;
+    if entries.is_empty() {
+        return Ok(None);
+    }
+    let (diff, ok) = tokio::task::spawn_blocking(move || diff_dir(&root, &entries)).await??;
+    sqlx::query(
+        "UPDATE album_manifest SET verified_at=now() WHERE album_id=$1 AND rel_path = ANY($2)",
+    )
+    .bind(album_id)
+    .bind(&ok)
+    .execute(db)
+    .await?;
+    Ok(Some(diff))
+}
+
+/// Blocking: stat + hash every declared file.  Returns the diff and the
+/// paths that matched.
+fn diff_dir(root: &FsPath, entries: &[ManifestEntry]) -> anyhow::Result<(ManifestDiff, Vec<String>)> {
+    let mut diff = ManifestDiff::default();
+    let mut ok   = Vec::new();
+
+    for e in entries {
+        let p = root.join(&e.path);
+        let Ok(meta) = std::fs::metadata(&p) else {
+            diff.missing.push(e.path.clone());
+            continue;
+        };
+        if meta.len() as i64 != e.size {
+            diff.size_mismatch.push(SizeMismatch {
+                path: e.path.clone(), expected: e.size, actual: meta.len() as i64,
+            });
+            continue;
+        }
+        let (md5, sha256) = hash_file(&p)?;
+        let mut matched = true;
+        for (algorithm, want, got) in [("md5", &e.md5, md5), ("sha256", &e.sha256, sha256)] {
+            if let Some(want) = want {
+                if *want != got {
+                    matched = false;
+                    diff.checksum_mismatch.push(ChecksumMismatch {
+                        path: e.path.clone(), algorithm, expected: want.clone(), actual: got,
+                    });
+                }
+            }
+        }
+        if matched {
+            ok.push(e.path.clone());
+        }
+    }
+
+    // undeclared files (skip dot-files: tus staging, `.part` temps)
+    let declared: std::collections::HashSet<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+    for entry in walkdir::WalkDir::new(root)
+        .into_iter()
+        .filter_entry(|e| !e.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_file())
+    {
+        if let Ok(rel) = entry.path().strip_prefix(root) {
+            let rel = rel.to_string_lossy();
+            if !declared.contains(rel.as_ref()) {
+                diff.unexpected.push(rel.into_owned());
+            }
+        }
+    }
+    diff.unexpected.sort();
+    Ok((diff, ok))
+}
+
+fn hash_file(p: &FsPath) -> std::io::Result<(String, String)> {
+    let mut f   = std::fs::File::open(p)?;
+    let mut md5 = Md5::new();
+    let mut sha = Sha256::new();
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let n = f.read(&mut buf)?;
+        if n == 0 { break; }
+        md5.update(&buf[..n]);
+        sha.update(&buf[..n]);
+    }
+    Ok((hex::encode(md5.finalize()), hex::encode(sha.finalize())))
+}
+
+fn check_hex(path: &str, algo: &str, h: String, len: usize) -> ApiResult<String> {
+    let h = h.trim().to_ascii_lowercase();
+    if h.len() != len || !h.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(ApiError::Unprocessable(format!("{path}: {algo} must be {len} hex digits")));
+    }
+    Ok(h)
+}
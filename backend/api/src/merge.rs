@@ -0,0 +1,356 @@
+//! Merging and splitting album records.
+//!
+//! Shows often arrive as two folders (sets, or discs rsynced separately) and
+//! some folders hold two shows.  `POST /albums/:id/merge` folds another
+//! album's tracks in and deletes it; `POST /albums/:id/split` moves chosen
+//! tracks into a new album.  Files stay with their tracks.
+//!
+//! Tracks are renumbered so `UNIQUE(album_id, disc, "index")` holds: indices
+//! go negative first, as in `PUT /albums/:id/discs/:disc/order`.  Afterwards
+//! every album involved is reprocessed from MatchAlbum – release candidates
+//! and review flags are dropped and MatchAlbum is queued.  Both are refused
+//! with `409` while an album is still importing or has a job running.
+
+use axum::{extract::State, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use shared::pipeline::Stage;
+use sqlx::{Postgres, Transaction};
+use tracing::{info, instrument, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    albums::{self, clean_text, AlbumKind, AlbumStatus},
+    audit,
+    auth::Principal,
+    error::{ApiError, ApiResult, Json, Path, Problem},
+    history, reprocess, AppState,
+};
+
+/*──────── model ──────────────────────────────────────────────────────────*/
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscPlacement {
+    /// Each disc of the merged album becomes a new disc after the last one
+    /// (Set 1 + Set 2 → discs 1 and 2).
+    #[default]
+    NewDiscs,
+    /// Its tracks continue the last disc.
+    Append,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MergeRequest {
+    /// The album to fold in; it's deleted once its tracks have moved.
+    pub from:  Uuid,
+    #[serde(default)]
+    pub discs: DiscPlacement,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SplitRequest {
+    /// Tracks for the new album – some, not all, of this album's.
+    pub tracks:     Vec<Uuid>,
+    /// Fields left out are copied from this album.
+    pub title:      Option<String>,
+    pub artist:     Option<String>,
+    pub year:       Option<i32>,
+    pub venue:      Option<String>,
+    pub album_kind: Option<AlbumKind>,
+}
+
+/// `(track, disc, index)`
+type Slot = (Uuid, i32, i32);
+/// `(id, title, artist, source path)`
+type Locked = (Uuid, Option<String>, Option<String>, Option<String>);
+
+/*──────── handlers ───────────────────────────────────────────────────────*/
+
+/// `POST /albums/:id/merge` – fold `from` into this album.  Its images move
+/// too (this album's cover wins); its folder keeps counting as imported.
+#[utoipa::path(
+    post, path = "/albums/{id}/merge", tag = "albums", request_body = MergeRequest,
+    params(("id" = Uuid, Path, description = "Album that absorbs `from`")),
+    responses(
+        (status = 200, description = "The merged album, re-matching", body = AlbumStatus),
+        (status = 404, response = Problem),
+        (status = 409, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id, from = %m.from))]
+pub async fn merge_album(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+    Json(m): Json<MergeRequest>,
+) -> ApiResult<Json<AlbumStatus>> {
+    if m.from == id {
+        return Err(ApiError::Unprocessable("an album can't be merged into itself".into()));
+    }
+    let mut tx = app.db.begin().await?;
+    history::attribute(&mut tx, &who.username, "api").await?;
+    // fixed lock order, so two opposite merges can't deadlock
+    let rows: Vec<Locked> = sqlx::query_as(
+        "SELECT id, title, artist, source->>'path' FROM albums WHERE id = ANY($1) ORDER BY id FOR UPDATE",
+    )
+    .bind([id, m.from])
+    .fetch_all(&mut *tx)
+    .await?;
+    for want in [id, m.from] {
+        if !rows.iter().any(|r| r.0 == want) {
+            return Err(ApiError::not_found("album", want));
+        }
+    }
+    let (_, title, artist, path) = rows.into_iter().find(|r| r.0 == m.from).expect("checked above");
+    refuse_busy(&mut tx, &[id, m.from]).await?;
+
+    let last: Option<(i32, Option<i32>)> = sqlx::query_as(
+        r#"SELECT COALESCE(disc, 1), max("index") FROM tracks WHERE album_id=$1
+            GROUP BY 1 ORDER BY 1 DESC LIMIT 1"#,
+    )
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let (last_disc, last_index) = last.map_or((0, 0), |(d, i)| (d, i.unwrap_or(0)));
+    let moving = album_order(&mut tx, m.from).await?;
+    let layout: Vec<Slot> = match m.discs {
+        DiscPlacement::NewDiscs => renumber(&moving, last_disc),
+        DiscPlacement::Append => moving
+            .iter()
+            .zip(1..)
+            .map(|(&(track, _), n)| (track, last_disc.max(1), last_index + n))
+            .collect(),
+    };
+    place(&mut tx, id, &layout).await?;
+
+    sqlx::query(
+        "UPDATE album_images
+            SET album_id = $2,
+                is_primary = is_primary AND NOT EXISTS (SELECT 1 FROM album_images WHERE album_id=$2 AND is_primary)
+          WHERE album_id = $1",
+    )
+    .bind(m.from)
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+    // earlier merges into `from` now point here
+    sqlx::query("UPDATE album_merges SET into_id=$2 WHERE into_id=$1")
+        .bind(m.from)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        "INSERT INTO album_merges(from_id, into_id, path, title, artist, merged_by) VALUES ($1,$2,$3,$4,$5,$6)",
+    )
+    .bind(m.from)
+    .bind(id)
+    .bind(&path)
+    .bind(&title)
+    .bind(&artist)
+    .bind(&who.username)
+    .execute(&mut *tx)
+    .await?;
+
+    // before the row goes, so the job trigger can still attribute events
+    sqlx::query(
+        "UPDATE jobs SET status = 'cancelled', last_error = 'album merged'
+          WHERE status = 'queued' AND payload->>'album_id' = $1 AND payload->>'file_id' IS NULL",
+    )
+    .bind(m.from.to_string())
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM albums WHERE id=$1").bind(m.from).execute(&mut *tx).await?;
+    sqlx::query(
+        "INSERT INTO pipeline_events(album_id, kind, status, detail) VALUES ($1, 'album.merged', 'merged', $2)",
+    )
+    .bind(m.from)
+    .bind(id.to_string())
+    .execute(&mut *tx)
+    .await?;
+
+    let redo = reprocess::album(&mut tx, id, Stage::MatchAlbum, false, None).await?;
+    audit::record(
+        &mut *tx, &who.username, "album.merge", ("album", Some(id)),
+        serde_json::json!({ "from": m.from, "title": title, "artist": artist, "path": path,
+                            "tracks": layout.len(), "discs": m.discs }),
+    )
+    .await?;
+    tx.commit().await?;
+    info!(tracks = layout.len(), jobs = redo.jobs_queued, "album merged");
+
+    let images = app.images.clone();
+    let from = m.from;
+    if let Err(e) = tokio::task::spawn_blocking(move || images.move_album(from, id)).await? {
+        warn!("moving images of merged album {from}: {e}");
+    }
+    Ok(Json(albums::album_status(&app.db, id).await?))
+}
+
+/// `POST /albums/:id/split` – move `tracks` (with their files) into a new
+/// album.  Images stay here.
+#[utoipa::path(
+    post, path = "/albums/{id}/split", tag = "albums", request_body = SplitRequest,
+    params(("id" = Uuid, Path, description = "Album to take the tracks from")),
+    responses(
+        (status = 201, description = "The new album, matching", body = AlbumStatus),
+        (status = 404, response = Problem),
+        (status = 409, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id, tracks = s.tracks.len()))]
+pub async fn split_album(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+    Json(s): Json<SplitRequest>,
+) -> ApiResult<(StatusCode, Json<AlbumStatus>)> {
+    let title = clean_text("title", s.title)?;
+    let artist = clean_text("artist", s.artist)?;
+    let venue = clean_text("venue", s.venue)?;
+    if let Some(y) = s.year {
+        albums::check_year(y)?;
+    }
+
+    let mut tx = app.db.begin().await?;
+    history::attribute(&mut tx, &who.username, "api").await?;
+    let exists: Option<(Uuid,)> = sqlx::query_as("SELECT id FROM albums WHERE id=$1 FOR UPDATE")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    exists.ok_or_else(|| ApiError::not_found("album", id))?;
+    refuse_busy(&mut tx, &[id]).await?;
+
+    let tracks = album_order(&mut tx, id).await?;
+    if let Some(stray) = s.tracks.iter().find(|t| !tracks.iter().any(|(have, _)| have == *t)) {
+        return Err(ApiError::Unprocessable(format!("track {stray} is not on album {id}")));
+    }
+    // album order, whatever order the request listed them in
+    let chosen: Vec<(Uuid, i32)> = tracks.iter().filter(|(t, _)| s.tracks.contains(t)).copied().collect();
+    if chosen.is_empty() || chosen.len() == tracks.len() {
+        return Err(ApiError::Unprocessable("choose some of the album's tracks, not none or all".into()));
+    }
+
+    let new_id = Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO albums(id, title, artist, year, venue, kind, source, imported_at)
+         SELECT $2, COALESCE($3, title), COALESCE($4, artist), COALESCE($5, year), COALESCE($6, venue),
+                COALESCE($7, kind), source, imported_at
+           FROM albums WHERE id=$1",
+    )
+    .bind(id)
+    .bind(new_id)
+    .bind(&title)
+    .bind(&artist)
+    .bind(s.year)
+    .bind(&venue)
+    .bind(s.album_kind)
+    .execute(&mut *tx)
+    .await?;
+    let layout = renumber(&chosen, 0);
+    place(&mut tx, new_id, &layout).await?;
+    sqlx::query(
+        "INSERT INTO pipeline_events(album_id, kind, status, detail) VALUES ($1, 'album.split', 'split', $2)",
+    )
+    .bind(id)
+    .bind(new_id.to_string())
+    .execute(&mut *tx)
+    .await?;
+
+    let mut jobs = 0;
+    for album in [id, new_id] {
+        jobs += reprocess::album(&mut tx, album, Stage::MatchAlbum, false, None).await?.jobs_queued;
+    }
+    audit::record(
+        &mut *tx, &who.username, "album.split", ("album", Some(id)),
+        serde_json::json!({ "into": new_id, "tracks": layout.iter().map(|s| s.0).collect::<Vec<_>>() }),
+    )
+    .await?;
+    tx.commit().await?;
+    info!(%new_id, tracks = layout.len(), jobs, "album split");
+    Ok((StatusCode::CREATED, Json(albums::album_status(&app.db, new_id).await?)))
+}
+
+/*──────── helpers ────────────────────────────────────────────────────────*/
+
+/// `409` while any of `ids` is importing, has an open upload or a job
+/// running – workers hold the ids about to move.
+async fn refuse_busy(tx: &mut Transaction<'_, Postgres>, ids: &[Uuid]) -> ApiResult<()> {
+    let busy: Option<(Uuid,)> = sqlx::query_as(
+        "SELECT a.id FROM unnest($1::uuid[]) AS a(id)
+          WHERE EXISTS (SELECT 1 FROM uploads u WHERE u.album_id = a.id AND u.finished_at IS NULL)
+             OR EXISTS (SELECT 1 FROM jobs j
+                         WHERE (j.status = 'running' OR (j.status = 'queued' AND j.stage = 'import'))
+                           AND (j.payload->>'album_id' = a.id::text
+                                OR j.payload->>'file_id' IN (SELECT f.id::text FROM files f
+                                                               JOIN tracks t ON t.id = f.track_id
+                                                              WHERE t.album_id = a.id)))
+          LIMIT 1",
+    )
+    .bind(ids)
+    .fetch_optional(&mut **tx)
+    .await?;
+    match busy {
+        Some((a,)) => Err(ApiError::Conflict(format!(
+            "album {a} is still importing or being processed – try again once its jobs are done"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// `(track, disc)` in play order.
+async fn album_order(tx: &mut Transaction<'_, Postgres>, album: Uuid) -> sqlx::Result<Vec<(Uuid, i32)>> {
+    sqlx::query_as(
+        r#"SELECT id, COALESCE(disc, 1) FROM tracks WHERE album_id=$1
+            ORDER BY COALESCE(disc, 1), "index" NULLS LAST, id"#,
+    )
+    .bind(album)
+    .fetch_all(&mut **tx)
+    .await
+}
+
+/// Discs numbered on from `after` without gaps, tracks 1‥n on each.
+fn renumber(tracks: &[(Uuid, i32)], after: i32) -> Vec<Slot> {
+    let mut out: Vec<Slot> = Vec::with_capacity(tracks.len());
+    let mut prev: Option<i32> = None;
+    let (mut disc, mut index) = (after, 0);
+    for &(track, old_disc) in tracks {
+        if prev != Some(old_disc) {
+            prev = Some(old_disc);
+            disc += 1;
+            index = 0;
+        }
+        index += 1;
+        out.push((track, disc, index));
+    }
+    out
+}
+
+/// Move the tracks onto `album` at their slots: negative indices first, so
+/// no row ever collides with one that hasn't moved yet.
+async fn place(tx: &mut Transaction<'_, Postgres>, album: Uuid, layout: &[Slot]) -> sqlx::Result<()> {
+    let ids: Vec<Uuid> = layout.iter().map(|s| s.0).collect();
+    let discs: Vec<i32> = layout.iter().map(|s| s.1).collect();
+    let indices: Vec<i32> = layout.iter().map(|s| s.2).collect();
+    sqlx::query(
+        r#"UPDATE tracks t
+              SET album_id = $1, disc = l.disc, "index" = -l.idx
+             FROM unnest($2::uuid[], $3::int[], $4::int[]) AS l(id, disc, idx)
+            WHERE t.id = l.id"#,
+    )
+    .bind(album)
+    .bind(&ids)
+    .bind(&discs)
+    .bind(&indices)
+    .execute(&mut **tx)
+    .await?;
+    sqlx::query(r#"UPDATE tracks SET "index" = -"index" WHERE id = ANY($1)"#)
+        .bind(&ids)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,142 @@
+//! OpenAPI 3 document, generated from the `#[utoipa::path]` annotations on
+//! the handlers and served at `/openapi.json` for client generators.
+//!
+//! A handler that isn't listed under `paths(...)` is missing from the spec –
+//! add it here when adding a route.
+
+use std::sync::OnceLock;
+
+use axum::{
+    http::{header, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use utoipa::{
+    openapi::{
+        security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+        SecurityRequirement,
+    },
+    Modify, OpenApi,
+};
+
+use crate::{
+    albums, archive, audit, auth, error, events, history, images, imports, manifest, merge, playlists, removal,
+    reprocess, review, search, stats, stream, tracks, transcode, uploads, webhooks,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "setlist-os API", description = "Album-centric façade over the ingest pipeline."),
+    modifiers(&Security),
+    paths(
+        auth::login, auth::logout, auth::me,
+        auth::list_tokens, auth::create_token, auth::revoke_token,
+        auth::list_users, auth::create_user, auth::patch_user, audit::list,
+        albums::create_album, albums::list_albums, albums::get_album, albums::complete_album,
+        removal::delete_album, removal::list_tombstones, removal::lift_tombstone,
+        imports::create_batch, imports::get_batch, search::search, stats::stats,
+        images::list_images, images::upload_image, images::get_image, images::patch_image,
+        images::delete_image, images::cover,
+        playlists::list_playlists, playlists::create_playlist, playlists::get_playlist,
+        playlists::patch_playlist, playlists::delete_playlist, playlists::put_entries,
+        playlists::add_entries, playlists::remove_entry, playlists::export_playlist,
+        playlists::import_playlist,
+        reprocess::reprocess_album, reprocess::reprocess_file,
+        reprocess::start_bulk, reprocess::bulk_status, reprocess::stop_bulk,
+        manifest::get_manifest, manifest::put_manifest,
+        events::all_events, events::album_events,
+        tracks::list_album_tracks, tracks::get_track, tracks::patch_track,
+        tracks::reorder_disc, tracks::assign_file,
+        merge::merge_album, merge::split_album,
+        history::album_history, history::track_history, history::revert_album, history::revert_track,
+        stream::stream_file, stream::stream_track,
+        review::list_files, review::list_albums,
+        review::choose_file, review::manual_file, review::reject_file,
+        review::choose_album, review::manual_album, review::reject_album,
+        uploads::upload_files, uploads::tus_options, uploads::tus_create,
+        uploads::tus_head, uploads::tus_patch, uploads::tus_delete,
+        webhooks::list_webhooks, webhooks::create_webhook, webhooks::get_webhook,
+        webhooks::patch_webhook, webhooks::delete_webhook, webhooks::list_deliveries,
+        webhooks::test_webhook, webhooks::retry_delivery,
+        archive::export, archive::restore,
+    ),
+    components(
+        schemas(
+            auth::Scope, auth::Principal, auth::User, auth::Token, auth::IssuedToken,
+            auth::Login, auth::NewToken, auth::NewUser, auth::UserPatch,
+            albums::Album, albums::AlbumKind, albums::AlbumSource, albums::NewAlbum,
+            albums::AlbumStatus, albums::JobCounts, albums::PipelineState,
+            albums::AlbumSummary, albums::AlbumPage, albums::SortKey, albums::SortOrder,
+            albums::FileStatus,
+            removal::DeleteMode, removal::Removal, removal::PurgeReport, removal::Tombstone,
+            imports::NewBatch, imports::Batch, imports::BatchItem, imports::BatchProgress,
+            imports::Outcome, imports::ItemState,
+            search::SearchResults, search::AlbumHit, search::TrackHit, search::ArtistHit,
+            stats::Stats, stats::CodecStorage, stats::KindCount, stats::YearCount, stats::StatusCount,
+            stats::Confidence, stats::Throughput,
+            images::ImageKind, images::AlbumImage, images::ImagePatch,
+            playlists::Playlist, playlists::PlaylistEntry, playlists::PlaylistDetail,
+            playlists::NewPlaylist, playlists::PlaylistPatch, playlists::EntryOrder, playlists::NewEntries,
+            playlists::PlaylistFormat, playlists::EntryLocation, playlists::Unresolved, playlists::ImportReport,
+            reprocess::ReprocessRequest, reprocess::Reprocessed, reprocess::BulkRun,
+            audit::AuditEntry,
+            tracks::Track, tracks::MediaFile, tracks::TrackPatch, tracks::DiscOrder,
+            tracks::FileAssignment,
+            merge::MergeRequest, merge::DiscPlacement, merge::SplitRequest,
+            history::Entity, history::HistoryEntry, history::RevertRequest,
+            manifest::ManifestEntry, manifest::ManifestDiff, manifest::SizeMismatch,
+            manifest::ChecksumMismatch,
+            events::PipelineEvent,
+            review::FileReview, review::TrackCandidate, review::AlbumReview,
+            review::AlbumCandidate, review::Decision, review::Choice,
+            review::ManualTrack, review::ManualAlbum,
+            transcode::Format,
+            uploads::StoredFile,
+            webhooks::WebhookEvent, webhooks::Webhook, webhooks::IssuedWebhook, webhooks::NewWebhook,
+            webhooks::WebhookPatch, webhooks::DeliveryStatus, webhooks::Delivery,
+            archive::ArchiveFormat, archive::ArchiveHeader, archive::Archive, archive::ArchivedAlbum,
+            archive::ArchivedTrack, archive::ArchivedFile, archive::ChosenMatch, archive::ArchivedDecision,
+            archive::ArchivedMerge, archive::ArchivedPlaylist, archive::ArchivedTombstone,
+            archive::RestoreReport, archive::UnmatchedKind, archive::Unmatched,
+            error::Problem,
+        ),
+        responses(error::Problem),
+    ),
+    tags(
+        (name = "auth",    description = "Login, API tokens and users (scopes: read, stream, upload, edit, admin)"),
+        (name = "albums",  description = "Album lifecycle"),
+        (name = "uploads", description = "Multipart + tus uploads and manifests"),
+        (name = "tracks",  description = "Tracks, files and manual corrections"),
+        (name = "stream",  description = "Audio streaming and transcoding"),
+        (name = "playlists", description = "Personal playlists, M3U8 / XSPF import and export"),
+        (name = "review",  description = "Manual match review queue"),
+        (name = "events",  description = "Live pipeline events (SSE)"),
+        (name = "webhooks", description = "Signed outgoing event deliveries, with retries and a delivery log"),
+        (name = "archive", description = "Library metadata export and restore"),
+    ),
+)]
+pub struct ApiDoc;
+
+/// Bearer token or session cookie, required everywhere except operations
+/// that opt out with `security(())`.
+struct Security;
+
+impl Modify for Security {
+    fn modify(&self, doc: &mut utoipa::openapi::OpenApi) {
+        let c = doc.components.get_or_insert_with(Default::default);
+        c.add_security_scheme("bearer", SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()));
+        c.add_security_scheme("session", SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new("setlist_session"))));
+        doc.security = Some(vec![
+            SecurityRequirement::new("bearer", Vec::<String>::new()),
+            SecurityRequirement::new("session", Vec::<String>::new()),
+        ]);
+    }
+}
+
+/// `GET /openapi.json` – rendered once, then served from memory.
+pub async fn spec() -> Response {
+    static JSON: OnceLock<String> = OnceLock::new();
+    let body = JSON.get_or_init(|| ApiDoc::openapi().to_pretty_json().expect("spec serialises"));
+    let mut res = body.as_str().into_response();
+    res.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    res
+}
@@ -0,0 +1,849 @@
+//! Playlists – ordered track lists owned by a user, optionally public.
+//!
+//! The same track may appear more than once.  Entries are addressed by
+//! their index in the current order; positions are rewritten on every edit
+//! so they stay `0..n`.  Only the owner (or an admin) may change a playlist;
+//! anyone may read a public one.  Playlists are personal, so `read` is
+//! enough to manage your own – the same as over `/rest`.
+//!
+//! Export is M3U8 (file paths or stream URLs) or XSPF.  Import takes an
+//! M3U/M3U8 whose entries are files under the media root (absolute,
+//! relative to it, or `file://`) or this server's stream URLs, and reports
+//! the lines it couldn't resolve.
+
+use std::{collections::HashMap, path::{Path as FsPath, PathBuf}};
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use time::OffsetDateTime;
+use tracing::{info, instrument};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    albums::clean_text,
+    auth::{Principal, Scope},
+    error::{ApiError, ApiResult, Json, Path, Problem, Query},
+    roots::Roots,
+    stream, subsonic, AppState,
+};
+
+/// Request body limit for `POST /playlists/import` (others get the default).
+pub const IMPORT_MAX_BYTES: usize = 16 << 20;
+/// Name for an import without `?name=` or `#PLAYLIST:`.
+const IMPORTED_NAME: &str = "Imported playlist";
+/// Unreserved characters plus `/` stay literal in `file://` URIs.
+const URI_PATH: &AsciiSet = &NON_ALPHANUMERIC.remove(b'/').remove(b'-').remove(b'_').remove(b'.').remove(b'~');
+
+/*──────── model ──────────────────────────────────────────────────────────*/
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct Playlist {
+    pub id:           Uuid,
+    /// Owner's username.
+    pub owner:        String,
+    pub name:         String,
+    pub comment:      Option<String>,
+    pub public:       bool,
+    pub entry_count:  i64,
+    pub duration_sec: i64,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at:   OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at:   OffsetDateTime,
+}
+
+const PLAYLIST_SELECT: &str = "
+    SELECT p.id, u.username AS owner, p.name, p.comment, p.public,
+           COUNT(e.track_id) AS entry_count, COALESCE(SUM(t.duration_sec), 0)::bigint AS duration_sec,
+           p.created_at, p.updated_at
+      FROM playlists p
+      JOIN users u ON u.id = p.owner_id
+      LEFT JOIN playlist_entries e ON e.playlist_id = p.id
+      LEFT JOIN tracks t ON t.id = e.track_id";
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct PlaylistEntry {
+    /// 0-based index in the playlist.
+    pub position:     i32,
+    pub track_id:     Uuid,
+    pub album_id:     Option<Uuid>,
+    pub album_title:  Option<String>,
+    pub artist:       Option<String>,
+    pub disc:         Option<i32>,
+    pub index:        Option<i32>,
+    pub title:        Option<String>,
+    pub duration_sec: Option<i32>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub added_at:     OffsetDateTime,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PlaylistDetail {
+    #[serde(flatten)]
+    pub playlist: Playlist,
+    pub entries:  Vec<PlaylistEntry>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NewPlaylist {
+    pub name:    String,
+    pub comment: Option<String>,
+    #[serde(default)]
+    pub public:  bool,
+    /// Initial entries, in order; repeats allowed.
+    #[serde(default)]
+    pub tracks:  Vec<Uuid>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PlaylistPatch {
+    pub name:    Option<String>,
+    /// `""` clears the comment.
+    pub comment: Option<String>,
+    pub public:  Option<bool>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct EntryOrder {
+    /// The whole playlist in the new order.  Entries that keep their track
+    /// keep their `added_at`.
+    pub tracks: Vec<Uuid>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NewEntries {
+    pub tracks: Vec<Uuid>,
+    /// Insert before this index; default appends.
+    pub at:     Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaylistFormat {
+    #[default]
+    M3u8,
+    Xspf,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryLocation {
+    /// Absolute file path (`file://` URI in XSPF) – for players on this box.
+    #[default]
+    Path,
+    /// `/tracks/:id/stream` on this server; the player must authenticate.
+    Url,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(deny_unknown_fields)]
+pub struct ExportQuery {
+    /// `m3u8` (default) or `xspf`.
+    pub format:   Option<PlaylistFormat>,
+    /// `path` (default) or `url`.
+    pub location: Option<EntryLocation>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(deny_unknown_fields)]
+pub struct ImportQuery {
+    /// Defaults to the file's `#PLAYLIST:` line, then "Imported playlist".
+    pub name:   Option<String>,
+    pub public: Option<bool>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Unresolved {
+    /// 1-based line in the uploaded file.
+    pub line:     usize,
+    pub location: String,
+    pub reason:   String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportReport {
+    pub playlist:   PlaylistDetail,
+    pub unresolved: Vec<Unresolved>,
+}
+
+/*──────── queries ────────────────────────────────────────────────────────*/
+
+/// The caller's own playlists and everyone's public ones.
+pub(crate) async fn visible(db: &PgPool, who: &Principal) -> sqlx::Result<Vec<Playlist>> {
+    sqlx::query_as(&format!(
+        "{PLAYLIST_SELECT}
+          WHERE p.owner_id = $1 OR p.public
+       GROUP BY p.id, u.username
+       ORDER BY lower(p.name), p.id"
+    ))
+    .bind(who.user_id)
+    .fetch_all(db)
+    .await
+}
+
+/// `404` unless the caller owns it, it's public, or the caller is admin.
+pub(crate) async fn get(db: &PgPool, id: Uuid, who: &Principal) -> ApiResult<Playlist> {
+    let p: Option<Playlist> = sqlx::query_as(&format!(
+        "{PLAYLIST_SELECT}
+          WHERE p.id = $1 AND (p.owner_id = $2 OR p.public OR $3)
+       GROUP BY p.id, u.username"
+    ))
+    .bind(id)
+    .bind(who.user_id)
+    .bind(who.has(Scope::Admin))
+    .fetch_optional(db)
+    .await?;
+    p.ok_or_else(|| ApiError::not_found("playlist", id))
+}
+
+/// Track ids in playlist order.
+pub(crate) async fn track_ids(db: &PgPool, id: Uuid) -> sqlx::Result<Vec<Uuid>> {
+    let rows: Vec<(Uuid,)> =
+        sqlx::query_as("SELECT track_id FROM playlist_entries WHERE playlist_id=$1 ORDER BY position")
+            .bind(id)
+            .fetch_all(db)
+            .await?;
+    Ok(rows.into_iter().map(|(t,)| t).collect())
+}
+
+async fn detail(db: &PgPool, id: Uuid, who: &Principal) -> ApiResult<PlaylistDetail> {
+    let playlist = get(db, id, who).await?;
+    let entries = sqlx::query_as(
+        r#"SELECT e.position, e.track_id, t.album_id, a.title AS album_title, a.artist,
+                  t.disc, t."index", t.title, t.duration_sec, e.added_at
+             FROM playlist_entries e
+             JOIN tracks t ON t.id = e.track_id
+             LEFT JOIN albums a ON a.id = t.album_id
+            WHERE e.playlist_id = $1
+         ORDER BY e.position"#,
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+    Ok(PlaylistDetail { playlist, entries })
+}
+
+/*──────── changes ────────────────────────────────────────────────────────*/
+
+pub(crate) async fn create(
+    tx:      &mut Transaction<'_, Postgres>,
+    who:     &Principal,
+    name:    &str,
+    comment: Option<&str>,
+    public:  bool,
+) -> ApiResult<Uuid> {
+    let id = Uuid::new_v4();
+    sqlx::query("INSERT INTO playlists(id, owner_id, name, comment, public) VALUES ($1,$2,$3,$4,$5)")
+        .bind(id)
+        .bind(who.user_id)
+        .bind(name)
+        .bind(comment)
+        .bind(public)
+        .execute(&mut **tx)
+        .await?;
+    Ok(id)
+}
+
+/// Lock a playlist the caller may change: `404` if they can't see it,
+/// `403` if they can but don't own it.
+pub(crate) async fn lock_owned(tx: &mut Transaction<'_, Postgres>, id: Uuid, who: &Principal) -> ApiResult<()> {
+    let row: Option<(Uuid, bool)> =
+        sqlx::query_as("SELECT owner_id, public FROM playlists WHERE id=$1 FOR UPDATE")
+            .bind(id)
+            .fetch_optional(&mut **tx)
+            .await?;
+    match row {
+        Some((owner, _)) if owner == who.user_id || who.has(Scope::Admin) => Ok(()),
+        Some((_, true)) => Err(ApiError::Forbidden(format!("playlist {id} belongs to someone else"))),
+        _ => Err(ApiError::not_found("playlist", id)),
+    }
+}
+
+/// One entry; `added_at` is `None` for new ones.
+struct Slot {
+    track_id: Uuid,
+    added_at: Option<OffsetDateTime>,
+}
+
+impl Slot {
+    fn new(track_id: Uuid) -> Self {
+        Slot { track_id, added_at: None }
+    }
+}
+
+async fn load_slots(tx: &mut Transaction<'_, Postgres>, id: Uuid) -> sqlx::Result<Vec<Slot>> {
+    let rows: Vec<(Uuid, OffsetDateTime)> = sqlx::query_as(
+        "SELECT track_id, added_at FROM playlist_entries WHERE playlist_id=$1 ORDER BY position",
+    )
+    .bind(id)
+    .fetch_all(&mut **tx)
+    .await?;
+    Ok(rows.into_iter().map(|(track_id, at)| Slot { track_id, added_at: Some(at) }).collect())
+}
+
+/// Replace every entry with `slots`, positions `0..n`.  Unknown track ids
+/// are `422`.
+async fn write_slots(tx: &mut Transaction<'_, Postgres>, id: Uuid, slots: &[Slot]) -> ApiResult<()> {
+    let mut tracks: Vec<Uuid> = slots.iter().map(|s| s.track_id).collect();
+    let added: Vec<Option<OffsetDateTime>> = slots.iter().map(|s| s.added_at).collect();
+    let (known,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM tracks WHERE id = ANY($1)")
+        .bind(&tracks)
+        .fetch_one(&mut **tx)
+        .await?;
+    let ordered = tracks.clone();
+    tracks.sort();
+    tracks.dedup();
+    if known as usize != tracks.len() {
+        return Err(ApiError::Unprocessable("unknown track id in playlist entries".into()));
+    }
+
+    sqlx::query("DELETE FROM playlist_entries WHERE playlist_id=$1")
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query(
+        "INSERT INTO playlist_entries(playlist_id, position, track_id, added_at)
+         SELECT $1, x.ord - 1, x.track_id, COALESCE(x.added_at, now())
+           FROM unnest($2::uuid[], $3::timestamptz[]) WITH ORDINALITY AS x(track_id, added_at, ord)",
+    )
+    .bind(id)
+    .bind(&ordered)
+    .bind(&added)
+    .execute(&mut **tx)
+    .await?;
+    sqlx::query("UPDATE playlists SET updated_at = now() WHERE id=$1")
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+fn out_of_range(index: usize, len: usize) -> ApiError {
+    ApiError::Unprocessable(format!("entry index {index} out of range – playlist has {len} entries"))
+}
+
+/// Drop the entries at `remove` (indexes into the current order), then
+/// append `add`.
+pub(crate) async fn edit_entries(
+    tx:     &mut Transaction<'_, Postgres>,
+    id:     Uuid,
+    remove: &[usize],
+    add:    &[Uuid],
+) -> ApiResult<()> {
+    let mut slots = load_slots(tx, id).await?;
+    if let Some(bad) = remove.iter().find(|i| **i >= slots.len()) {
+        return Err(out_of_range(*bad, slots.len()));
+    }
+    let mut remove = remove.to_vec();
+    remove.sort_unstable_by(|a, b| b.cmp(a));
+    remove.dedup();
+    for i in remove {
+        slots.remove(i);
+    }
+    slots.extend(add.iter().copied().map(Slot::new));
+    write_slots(tx, id, &slots).await
+}
+
+/// Insert `add` before index `at` (`None` appends).
+async fn insert_entries(
+    tx:  &mut Transaction<'_, Postgres>,
+    id:  Uuid,
+    at:  Option<usize>,
+    add: &[Uuid],
+) -> ApiResult<()> {
+    let mut slots = load_slots(tx, id).await?;
+    let at = at.unwrap_or(slots.len());
+    if at > slots.len() {
+        return Err(out_of_range(at, slots.len()));
+    }
+    slots.splice(at..at, add.iter().copied().map(Slot::new));
+    write_slots(tx, id, &slots).await
+}
+
+/// New order for the whole playlist.  A track that was already in it keeps
+/// the `added_at` of its earliest unused entry.
+pub(crate) async fn set_entries(tx: &mut Transaction<'_, Postgres>, id: Uuid, tracks: &[Uuid]) -> ApiResult<()> {
+    let mut old = load_slots(tx, id).await?;
+    let slots: Vec<Slot> = tracks
+        .iter()
+        .map(|t| match old.iter().position(|s| s.track_id == *t) {
+            Some(i) => old.remove(i),
+            None => Slot::new(*t),
+        })
+        .collect();
+    write_slots(tx, id, &slots).await
+}
+
+/*──────── handlers ───────────────────────────────────────────────────────*/
+
+/// `GET /playlists` – yours and everyone's public ones.
+#[utoipa::path(
+    get, path = "/playlists", tag = "playlists",
+    responses((status = 200, body = Vec<Playlist>), (status = 401, response = Problem)),
+)]
+pub async fn list_playlists(State(app): State<AppState>, who: Principal) -> ApiResult<Json<Vec<Playlist>>> {
+    Ok(Json(visible(&app.db, &who).await?))
+}
+
+/// `POST /playlists`
+#[utoipa::path(
+    post, path = "/playlists", tag = "playlists", request_body = NewPlaylist,
+    responses(
+        (status = 201, description = "Created", body = PlaylistDetail),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(user = %who.username))]
+pub async fn create_playlist(
+    State(app): State<AppState>,
+    who: Principal,
+    Json(p): Json<NewPlaylist>,
+) -> ApiResult<(StatusCode, Json<PlaylistDetail>)> {
+    let name = clean_text("name", Some(p.name))?
+        .ok_or_else(|| ApiError::Unprocessable("name required".into()))?;
+    let comment = clean_text("comment", p.comment)?;
+
+    let mut tx = app.db.begin().await?;
+    let id = create(&mut tx, &who, &name, comment.as_deref(), p.public).await?;
+    set_entries(&mut tx, id, &p.tracks).await?;
+    tx.commit().await?;
+    info!(%id, entries = p.tracks.len(), "playlist created");
+    Ok((StatusCode::CREATED, Json(detail(&app.db, id, &who).await?)))
+}
+
+/// `GET /playlists/:id`
+#[utoipa::path(
+    get, path = "/playlists/{id}", tag = "playlists",
+    params(("id" = Uuid, Path, description = "Playlist id")),
+    responses((status = 200, body = PlaylistDetail), (status = 404, response = Problem)),
+)]
+pub async fn get_playlist(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+) -> ApiResult<Json<PlaylistDetail>> {
+    Ok(Json(detail(&app.db, id, &who).await?))
+}
+
+/// `PATCH /playlists/:id` – name / comment / public.
+#[utoipa::path(
+    patch, path = "/playlists/{id}", tag = "playlists",
+    params(("id" = Uuid, Path, description = "Playlist id")), request_body = PlaylistPatch,
+    responses(
+        (status = 200, body = PlaylistDetail),
+        (status = 403, response = Problem),
+        (status = 404, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id))]
+pub async fn patch_playlist(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+    Json(p): Json<PlaylistPatch>,
+) -> ApiResult<Json<PlaylistDetail>> {
+    let name = match p.name {
+        Some(n) => Some(clean_text("name", Some(n))?.ok_or_else(|| ApiError::Unprocessable("name can't be empty".into()))?),
+        None => None,
+    };
+    let comment = p.comment.map(|c| clean_text("comment", Some(c))).transpose()?;
+
+    let mut tx = app.db.begin().await?;
+    lock_owned(&mut tx, id, &who).await?;
+    sqlx::query(
+        "UPDATE playlists SET name       = COALESCE($2, name),
+                              comment    = CASE WHEN $3 THEN $4 ELSE comment END,
+                              public     = COALESCE($5, public),
+                              updated_at = now()
+          WHERE id=$1",
+    )
+    .bind(id)
+    .bind(name)
+    .bind(comment.is_some())
+    .bind(comment.flatten())
+    .bind(p.public)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(Json(detail(&app.db, id, &who).await?))
+}
+
+/// `DELETE /playlists/:id`
+#[utoipa::path(
+    delete, path = "/playlists/{id}", tag = "playlists",
+    params(("id" = Uuid, Path, description = "Playlist id")),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 403, response = Problem),
+        (status = 404, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id))]
+pub async fn delete_playlist(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+) -> ApiResult<StatusCode> {
+    let mut tx = app.db.begin().await?;
+    lock_owned(&mut tx, id, &who).await?;
+    sqlx::query("DELETE FROM playlists WHERE id=$1").bind(id).execute(&mut *tx).await?;
+    tx.commit().await?;
+    info!("playlist deleted");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// `PUT /playlists/:id/entries` – reorder or replace every entry.
+#[utoipa::path(
+    put, path = "/playlists/{id}/entries", tag = "playlists",
+    params(("id" = Uuid, Path, description = "Playlist id")), request_body = EntryOrder,
+    responses(
+        (status = 200, body = PlaylistDetail),
+        (status = 403, response = Problem),
+        (status = 404, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id))]
+pub async fn put_entries(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+    Json(o): Json<EntryOrder>,
+) -> ApiResult<Json<PlaylistDetail>> {
+    let mut tx = app.db.begin().await?;
+    lock_owned(&mut tx, id, &who).await?;
+    set_entries(&mut tx, id, &o.tracks).await?;
+    tx.commit().await?;
+    Ok(Json(detail(&app.db, id, &who).await?))
+}
+
+/// `POST /playlists/:id/entries` – insert tracks (repeats allowed).
+#[utoipa::path(
+    post, path = "/playlists/{id}/entries", tag = "playlists",
+    params(("id" = Uuid, Path, description = "Playlist id")), request_body = NewEntries,
+    responses(
+        (status = 200, body = PlaylistDetail),
+        (status = 403, response = Problem),
+        (status = 404, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id))]
+pub async fn add_entries(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+    Json(n): Json<NewEntries>,
+) -> ApiResult<Json<PlaylistDetail>> {
+    let mut tx = app.db.begin().await?;
+    lock_owned(&mut tx, id, &who).await?;
+    insert_entries(&mut tx, id, n.at, &n.tracks).await?;
+    tx.commit().await?;
+    Ok(Json(detail(&app.db, id, &who).await?))
+}
+
+/// `DELETE /playlists/:id/entries/:index`
+#[utoipa::path(
+    delete, path = "/playlists/{id}/entries/{index}", tag = "playlists",
+    params(
+        ("id" = Uuid, Path, description = "Playlist id"),
+        ("index" = usize, Path, description = "0-based entry index"),
+    ),
+    responses(
+        (status = 200, body = PlaylistDetail),
+        (status = 403, response = Problem),
+        (status = 404, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id, index))]
+pub async fn remove_entry(
+    Path((id, index)): Path<(Uuid, usize)>,
+    State(app): State<AppState>,
+    who: Principal,
+) -> ApiResult<Json<PlaylistDetail>> {
+    let mut tx = app.db.begin().await?;
+    lock_owned(&mut tx, id, &who).await?;
+    edit_entries(&mut tx, id, &[index], &[]).await?;
+    tx.commit().await?;
+    Ok(Json(detail(&app.db, id, &who).await?))
+}
+
+/*──────── export ─────────────────────────────────────────────────────────*/
+
+#[derive(sqlx::FromRow)]
+struct ExportRow {
+    track_id:     Uuid,
+    title:        Option<String>,
+    artist:       Option<String>,
+    album_title:  Option<String>,
+    index:        Option<i32>,
+    duration_sec: Option<i32>,
+    path:         Option<String>,
+}
+
+/// `GET /playlists/:id/export?format=&location=` – entries without a
+/// playable file are left out.
+#[utoipa::path(
+    get, path = "/playlists/{id}/export", tag = "playlists",
+    params(("id" = Uuid, Path, description = "Playlist id"), ExportQuery),
+    responses(
+        (status = 200, description = "M3U8 or XSPF download",
+         content_type = ["audio/x-mpegurl", "application/xspf+xml"]),
+        (status = 404, response = Problem),
+    ),
+)]
+pub async fn export_playlist(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+    Query(q): Query<ExportQuery>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let list = get(&app.db, id, &who).await?;
+    let rows: Vec<ExportRow> = sqlx::query_as(&format!(
+        r#"SELECT e.track_id, t.title, a.artist, a.title AS album_title, t."index", t.duration_sec, f.path
+             FROM playlist_entries e
+             JOIN tracks t ON t.id = e.track_id
+             LEFT JOIN albums a ON a.id = t.album_id
+             LEFT JOIN LATERAL (
+                  SELECT path FROM files
+                   WHERE track_id = t.id AND status <> 'ERROR'
+                ORDER BY {}
+                   LIMIT 1
+             ) f ON true
+            WHERE e.playlist_id = $1
+         ORDER BY e.position"#,
+        stream::best_file_order(),
+    ))
+    .bind(id)
+    .fetch_all(&app.db)
+    .await?;
+
+    let format = q.format.unwrap_or_default();
+    let base = base_url(&headers);
+    let located = rows.iter().filter_map(|r| {
+        let path = r.path.as_deref()?;
+        let loc = match (q.location.unwrap_or_default(), format) {
+            (EntryLocation::Url, _) => format!("{base}/tracks/{}/stream", r.track_id),
+            (EntryLocation::Path, PlaylistFormat::M3u8) => path.to_owned(),
+            (EntryLocation::Path, PlaylistFormat::Xspf) => format!("file://{}", utf8_percent_encode(path, URI_PATH)),
+        };
+        Some((r, loc))
+    });
+
+    let (body, mime, ext) = match format {
+        PlaylistFormat::M3u8 => {
+            let mut out = format!("#EXTM3U\n#PLAYLIST:{}\n", one_line(&list.name));
+            for (r, loc) in located {
+                let title = one_line(r.title.as_deref().unwrap_or("Untitled"));
+                let label = match &r.artist {
+                    Some(a) => format!("{} - {title}", one_line(a)),
+                    None => title,
+                };
+                out.push_str(&format!("#EXTINF:{},{label}\n{loc}\n", r.duration_sec.unwrap_or(-1)));
+            }
+            (out, "audio/x-mpegurl; charset=utf-8", "m3u8")
+        }
+        PlaylistFormat::Xspf => {
+            let esc = subsonic::escape;
+            let mut out = String::from(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n",
+            );
+            out.push_str(&format!("  <title>{}</title>\n", esc(&list.name)));
+            if let Some(c) = &list.comment {
+                out.push_str(&format!("  <annotation>{}</annotation>\n", esc(c)));
+            }
+            out.push_str("  <trackList>\n");
+            for (r, loc) in located {
+                out.push_str(&format!("    <track>\n      <location>{}</location>\n", esc(&loc)));
+                out.push_str(&format!("      <identifier>urn:uuid:{}</identifier>\n", r.track_id));
+                let fields = [("title", &r.title), ("creator", &r.artist), ("album", &r.album_title)];
+                for (tag, v) in fields {
+                    if let Some(v) = v {
+                        out.push_str(&format!("      <{tag}>{}</{tag}>\n", esc(v)));
+                    }
+                }
+                if let Some(n) = r.index {
+                    out.push_str(&format!("      <trackNum>{n}</trackNum>\n"));
+                }
+                if let Some(d) = r.duration_sec {
+                    out.push_str(&format!("      <duration>{}</duration>\n", i64::from(d) * 1000));
+                }
+                out.push_str("    </track>\n");
+            }
+            out.push_str("  </trackList>\n</playlist>\n");
+            (out, "application/xspf+xml", "xspf")
+        }
+    };
+
+    let mut res = body.into_response();
+    let h = res.headers_mut();
+    h.insert(header::CONTENT_TYPE, HeaderValue::from_static(mime));
+    if let Ok(v) = HeaderValue::from_str(&format!("attachment; filename=\"{}.{ext}\"", file_stem(&list.name))) {
+        h.insert(header::CONTENT_DISPOSITION, v);
+    }
+    Ok(res)
+}
+
+/// `scheme://host` as the client reached us, honouring a reverse proxy's
+/// `X-Forwarded-*`.
+fn base_url(headers: &HeaderMap) -> String {
+    let get = |k: &str| headers.get(k).and_then(|v| v.to_str().ok());
+    let proto = get("x-forwarded-proto").unwrap_or("http");
+    let host = get("x-forwarded-host").or_else(|| get(header::HOST.as_str())).unwrap_or("localhost");
+    format!("{proto}://{host}")
+}
+
+fn one_line(s: &str) -> String {
+    s.replace(['\r', '\n'], " ")
+}
+
+/// ASCII-only download name.
+fn file_stem(name: &str) -> String {
+    let stem: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || " -_.".contains(c) { c } else { '_' })
+        .collect();
+    match stem.trim() {
+        "" => "playlist".to_owned(),
+        s => s.to_owned(),
+    }
+}
+
+/*──────── import ─────────────────────────────────────────────────────────*/
+
+/// `POST /playlists/import?name=&public=` – body is the M3U/M3U8 text.
+#[utoipa::path(
+    post, path = "/playlists/import", tag = "playlists", params(ImportQuery),
+    request_body(content = String, content_type = "audio/x-mpegurl", description = "M3U / M3U8, UTF-8"),
+    responses(
+        (status = 201, description = "Created; lines that matched no track are listed", body = ImportReport),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(user = %who.username))]
+pub async fn import_playlist(
+    State(app): State<AppState>,
+    who: Principal,
+    Query(q): Query<ImportQuery>,
+    body: Bytes,
+) -> ApiResult<(StatusCode, Json<ImportReport>)> {
+    let text = std::str::from_utf8(&body)
+        .map_err(|_| ApiError::Unprocessable("playlist isn't UTF-8 – save it as .m3u8".into()))?;
+    let text = text.trim_start_matches('\u{feff}');
+
+    let mut title = None;
+    let mut lines = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if let Some(t) = line.strip_prefix("#PLAYLIST:") {
+            title = title.or(clean_text("name", Some(t.to_owned()))?);
+        } else if !line.is_empty() && !line.starts_with('#') {
+            lines.push((n + 1, line.to_owned()));
+        }
+    }
+    let name = match q.name {
+        Some(n) => clean_text("name", Some(n))?,
+        None => title,
+    }
+    .unwrap_or_else(|| IMPORTED_NAME.to_owned());
+
+    let roots = app.roots.clone();
+    let targets = {
+        let lines: Vec<String> = lines.iter().map(|(_, l)| l.clone()).collect();
+        tokio::task::spawn_blocking(move || lines.iter().map(|l| locate(&roots, l)).collect::<Vec<_>>()).await?
+    };
+
+    // one lookup each for paths and explicit track ids
+    let paths: Vec<String> = targets.iter().flatten().flat_map(|t| match t {
+        Target::Paths(p) => p.clone(),
+        Target::Track(_) => vec![],
+    }).collect();
+    let by_path: HashMap<String, Uuid> =
+        sqlx::query_as("SELECT path, track_id FROM files WHERE path = ANY($1) AND track_id IS NOT NULL")
+            .bind(&paths)
+            .fetch_all(&app.db)
+            .await?
+            .into_iter()
+            .collect();
+    let ids: Vec<Uuid> = targets.iter().flatten().filter_map(|t| match t {
+        Target::Track(id) => Some(*id),
+        Target::Paths(_) => None,
+    }).collect();
+    let known: Vec<(Uuid,)> = sqlx::query_as("SELECT id FROM tracks WHERE id = ANY($1)")
+        .bind(&ids)
+        .fetch_all(&app.db)
+        .await?;
+
+    let mut tracks = Vec::new();
+    let mut unresolved = Vec::new();
+    for ((line, location), target) in lines.into_iter().zip(targets) {
+        let found = match target {
+            Ok(Target::Track(id)) => known.iter().any(|(k,)| *k == id).then_some(id).ok_or("no such track"),
+            Ok(Target::Paths(p)) => p.iter().find_map(|p| by_path.get(p).copied()).ok_or("no track for this file"),
+            Err(reason) => Err(reason),
+        };
+        match found {
+            Ok(id) => tracks.push(id),
+            Err(reason) => unresolved.push(Unresolved { line, location, reason: reason.to_owned() }),
+        }
+    }
+
+    let mut tx = app.db.begin().await?;
+    let id = create(&mut tx, &who, &name, None, q.public.unwrap_or(false)).await?;
+    set_entries(&mut tx, id, &tracks).await?;
+    tx.commit().await?;
+    info!(%id, resolved = tracks.len(), unresolved = unresolved.len(), "playlist imported");
+    let playlist = detail(&app.db, id, &who).await?;
+    Ok((StatusCode::CREATED, Json(ImportReport { playlist, unresolved })))
+}
+
+enum Target {
+    /// Our own `/tracks/:id/stream` URL.
+    Track(Uuid),
+    /// Candidate `files.path` values – as written and canonicalised.
+    Paths(Vec<String>),
+}
+
+/// Blocking: canonicalises to keep `..` and symlinks inside the media root.
+fn locate(roots: &Roots, raw: &str) -> Result<Target, &'static str> {
+    if raw.starts_with("http://") || raw.starts_with("https://") {
+        let after = raw.split_once("/tracks/").map(|(_, rest)| rest).ok_or("not a stream URL of this server")?;
+        let id = after.split_once("/stream").map(|(id, _)| id).ok_or("not a stream URL of this server")?;
+        return id.parse().map(Target::Track).map_err(|_| "not a stream URL of this server");
+    }
+    let decoded;
+    let raw = match raw.strip_prefix("file://") {
+        Some(rest) => {
+            decoded = percent_decode_str(rest).decode_utf8().map_err(|_| "malformed file URI")?;
+            decoded.strip_prefix("localhost").unwrap_or(&decoded)
+        }
+        None => raw,
+    };
+    let given = FsPath::new(raw);
+    let joined: PathBuf = if given.is_absolute() { given.into() } else { roots.media.join(given) };
+    let real = joined.canonicalize().map_err(|_| "file not found")?;
+    if !real.starts_with(&roots.media) {
+        return Err("outside the media root");
+    }
+    let mut paths = vec![real.to_string_lossy().into_owned()];
+    paths.push(joined.to_string_lossy().into_owned());
+    paths.dedup();
+    Ok(Target::Paths(paths))
+}
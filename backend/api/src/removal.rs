@@ -0,0 +1,300 @@
+//! `DELETE /albums/:id` and the tombstones it can leave behind.
+//!
+//! * `forget`    – drop the DB rows; media stays, a later scan may re-import.
+//! * `tombstone` – as `forget`, but remember the paths so they are never
+//!   imported again (until the tombstone is lifted).
+//! * `purge`     – `tombstone` plus deleting the media from disk.  Admin
+//!   only, and the caller must echo the album id in `confirm`.
+//!
+//! Outstanding jobs are cancelled, the album's artwork and thumbnails are
+//! dropped in every mode, and every deletion lands in `audit_log`.
+
+use std::path::{Path as FsPath, PathBuf};
+
+use axum::{extract::State, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use tracing::{info, instrument, warn};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    audit,
+    auth::{Principal, Scope},
+    error::{ApiError, ApiResult, Json, Path, Problem, Query},
+    roots::Roots,
+    AppState,
+};
+
+/*──────── model ──────────────────────────────────────────────────────────*/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DeleteMode {
+    Forget,
+    #[default]
+    Tombstone,
+    Purge,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(deny_unknown_fields)]
+pub struct DeleteQuery {
+    /// Defaults to `tombstone`.
+    #[serde(default)]
+    pub mode:    DeleteMode,
+    /// `purge` only: must repeat the album id.
+    pub confirm: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Removal {
+    pub album_id:       Uuid,
+    pub mode:           DeleteMode,
+    pub files:          usize,
+    pub jobs_cancelled: u64,
+    /// `purge` only.
+    pub disk:           Option<PurgeReport>,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct PurgeReport {
+    pub files_deleted: u64,
+    pub bytes_freed:   u64,
+    /// Paths that could not be removed (or were outside the roots).
+    pub errors:        Vec<String>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct Tombstone {
+    pub album_id:   Uuid,
+    pub path:       Option<String>,
+    pub file_paths: Vec<String>,
+    pub title:      Option<String>,
+    pub artist:     Option<String>,
+    pub deleted_by: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub deleted_at: OffsetDateTime,
+}
+
+/*──────── handlers ───────────────────────────────────────────────────────*/
+
+/// `DELETE /albums/:id?mode=forget|tombstone|purge[&confirm=<id>]`
+#[utoipa::path(
+    delete, path = "/albums/{id}", tag = "albums",
+    params(("id" = Uuid, Path, description = "Album id"), DeleteQuery),
+    responses(
+        (status = 200, description = "Album deleted", body = Removal),
+        (status = 400, response = Problem),
+        (status = 403, response = Problem),
+        (status = 404, response = Problem),
+        (status = 428, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id, mode = ?q.mode))]
+pub async fn delete_album(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+    Query(q): Query<DeleteQuery>,
+) -> ApiResult<Json<Removal>> {
+    if q.mode == DeleteMode::Purge {
+        if !who.has(Scope::Admin) {
+            return Err(ApiError::Forbidden("purging media from disk needs the admin scope".into()));
+        }
+        if q.confirm != Some(id) {
+            return Err(ApiError::ConfirmationRequired(format!(
+                "purge deletes the media from disk – repeat with confirm={id}"
+            )));
+        }
+    }
+
+    let mut tx = app.db.begin().await?;
+    let row: Option<(Option<String>, Option<String>, Option<String>)> = sqlx::query_as(
+        "SELECT title, artist, source->>'path' FROM albums WHERE id=$1 FOR UPDATE",
+    )
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let (title, artist, path) = row.ok_or_else(|| ApiError::not_found("album", id))?;
+    let files: Vec<(Uuid, String)> = sqlx::query_as(
+        "SELECT f.id, f.path FROM files f JOIN tracks t ON t.id = f.track_id WHERE t.album_id=$1",
+    )
+    .bind(id)
+    .fetch_all(&mut *tx)
+    .await?;
+    let file_paths: Vec<String> = files.iter().map(|(_, p)| p.clone()).collect();
+
+    // before the rows go, so the job trigger can still attribute events
+    let jobs_cancelled = sqlx::query(
+        "UPDATE jobs SET status = 'cancelled', last_error = 'album deleted'
+          WHERE status IN ('queued', 'running')
+            AND (payload->>'album_id' = $1 OR payload->>'file_id' = ANY($2))",
+    )
+    .bind(id.to_string())
+    .bind(files.iter().map(|(f, _)| f.to_string()).collect::<Vec<_>>())
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    if q.mode != DeleteMode::Forget {
+        sqlx::query(
+            "INSERT INTO tombstones(album_id, path, file_paths, title, artist, deleted_by)
+                  VALUES ($1,$2,$3,$4,$5,$6)",
+        )
+        .bind(id)
+        .bind(&path)
+        .bind(&file_paths)
+        .bind(&title)
+        .bind(&artist)
+        .bind(&who.username)
+        .execute(&mut *tx)
+        .await?;
+    }
+    sqlx::query("DELETE FROM albums WHERE id=$1").bind(id).execute(&mut *tx).await?;
+    sqlx::query("INSERT INTO pipeline_events(album_id, kind, status) VALUES ($1, 'album.deleted', $2)")
+        .bind(id)
+        .bind(mode_str(q.mode))
+        .execute(&mut *tx)
+        .await?;
+    let audit_id = audit::record(
+        &mut *tx,
+        &who.username,
+        "album.delete",
+        ("album", Some(id)),
+        serde_json::json!({
+            "mode": q.mode, "title": title, "artist": artist, "path": path,
+            "files": file_paths.len(), "jobs_cancelled": jobs_cancelled,
+        }),
+    )
+    .await?;
+    tx.commit().await?;
+    info!(files = files.len(), jobs_cancelled, "album deleted");
+
+    let file_ids: Vec<Uuid> = files.iter().map(|(f, _)| *f).collect();
+    let transcoder = app.transcoder.clone();
+    let images = app.images.clone();
+    let purge = (q.mode == DeleteMode::Purge).then(|| (app.roots.clone(), path, file_paths));
+    let disk = tokio::task::spawn_blocking(move || {
+        file_ids.into_iter().for_each(|f| transcoder.forget(f));
+        images.remove_album(id);
+        purge.map(|(roots, dir, files)| purge_from_disk(&roots, dir.as_deref(), &files))
+    })
+    .await?;
+
+    if let Some(report) = &disk {
+        sqlx::query("UPDATE audit_log SET detail = detail || jsonb_build_object('disk', $2::jsonb) WHERE id=$1")
+            .bind(audit_id)
+            .bind(serde_json::to_value(report)?)
+            .execute(&app.db)
+            .await?;
+        info!(report.files_deleted, report.bytes_freed, errors = report.errors.len(), "media purged");
+    }
+    Ok(Json(Removal { album_id: id, mode: q.mode, files: files.len(), jobs_cancelled, disk }))
+}
+
+/// `GET /tombstones`
+#[utoipa::path(
+    get, path = "/tombstones", tag = "albums",
+    responses((status = 200, body = Vec<Tombstone>), (status = 403, response = Problem)),
+)]
+pub async fn list_tombstones(State(app): State<AppState>) -> ApiResult<Json<Vec<Tombstone>>> {
+    let rows = sqlx::query_as(
+        "SELECT album_id, path, file_paths, title, artist, deleted_by, deleted_at
+           FROM tombstones ORDER BY deleted_at DESC",
+    )
+    .fetch_all(&app.db)
+    .await?;
+    Ok(Json(rows))
+}
+
+/// `DELETE /tombstones/:album_id` – allow the paths to be imported again.
+#[utoipa::path(
+    delete, path = "/tombstones/{album_id}", tag = "albums",
+    params(("album_id" = Uuid, Path, description = "Id the deleted album had")),
+    responses((status = 204, description = "Tombstone lifted"), (status = 404, response = Problem)),
+)]
+#[instrument(skip_all, fields(%album_id))]
+pub async fn lift_tombstone(
+    Path(album_id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+) -> ApiResult<StatusCode> {
+    let mut tx = app.db.begin().await?;
+    let path: Option<(Option<String>,)> =
+        sqlx::query_as("DELETE FROM tombstones WHERE album_id=$1 RETURNING path")
+            .bind(album_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+    let (path,) = path.ok_or_else(|| ApiError::not_found("tombstone", album_id))?;
+    audit::record(&mut *tx, &who.username, "tombstone.lift", ("tombstone", Some(album_id)),
+                  serde_json::json!({ "path": path }))
+        .await?;
+    tx.commit().await?;
+    info!("tombstone lifted");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/*──────── helpers ────────────────────────────────────────────────────────*/
+
+/// Deleted album whose media lives at `path` (album folder or one file).
+pub async fn tombstoned(db: &PgPool, path: &str) -> sqlx::Result<Option<Uuid>> {
+    let row: Option<(Uuid,)> = sqlx::query_as(
+        "SELECT album_id FROM tombstones WHERE path = $1 OR $1 = ANY(file_paths) LIMIT 1",
+    )
+    .bind(path)
+    .fetch_optional(db)
+    .await?;
+    Ok(row.map(|(id,)| id))
+}
+
+fn mode_str(m: DeleteMode) -> &'static str {
+    match m {
+        DeleteMode::Forget    => "forget",
+        DeleteMode::Tombstone => "tombstone",
+        DeleteMode::Purge     => "purge",
+    }
+}
+
+/// Blocking.  Only paths that resolve inside the media / inbox roots are
+/// touched, and never a root itself.
+fn purge_from_disk(roots: &Roots, dir: Option<&str>, files: &[String]) -> PurgeReport {
+    let mut report = PurgeReport::default();
+    let inside = |p: &str| -> Option<PathBuf> {
+        roots.resolve(FsPath::new(p)).filter(|r| r != &roots.media && r != &roots.inbox)
+    };
+
+    for f in files {
+        match inside(f) {
+            Some(real) => {
+                let len = real.metadata().map(|m| m.len()).unwrap_or(0);
+                match std::fs::remove_file(&real) {
+                    Ok(()) => {
+                        report.files_deleted += 1;
+                        report.bytes_freed += len;
+                    }
+                    Err(e) => report.errors.push(format!("{f}: {e}")),
+                }
+            }
+            None if !FsPath::new(f).exists() => {}   // already gone
+            None => report.errors.push(format!("{f}: outside the media roots – left alone")),
+        }
+    }
+
+    // the folder: cover art, cue sheets, logs …
+    if let Some(real) = dir.and_then(inside).filter(|p| p.is_dir()) {
+        for e in walkdir::WalkDir::new(&real).into_iter().filter_map(Result::ok) {
+            if e.file_type().is_file() {
+                report.files_deleted += 1;
+                report.bytes_freed += e.metadata().map(|m| m.len()).unwrap_or(0);
+            }
+        }
+        if let Err(e) = std::fs::remove_dir_all(&real) {
+            warn!(path = %real.display(), "album folder not removed: {e}");
+            report.errors.push(format!("{}: {e}", real.display()));
+        }
+    }
+    report
+}
@@ -0,0 +1,400 @@
+//! Re-run part of the pipeline without re-importing.
+//!
+//! Each file restarts at the requested stage – or earlier, if it never got
+//! that far (no fingerprint yet → Fingerprint; never tagged → TagTrack).
+//! Whatever the restarted stages produce is reset first: `files.status`,
+//! the timestamps, match candidates and review flags.  Queued jobs for the
+//! same files are cancelled so nothing runs twice.
+//!
+//! `POST /reprocess` walks the whole library in the background, throttled
+//! to `REPROCESS_ALBUMS_PER_MIN` (default 30) so the workers aren't flooded.
+//! One bulk run at a time.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use axum::{extract::State, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use shared::pipeline::Stage;
+use sqlx::{PgPool, Postgres, Transaction};
+use time::OffsetDateTime;
+use tracing::{info, instrument, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    audit,
+    auth::Principal,
+    error::{ApiError, ApiResult, Json, Path, Problem},
+    jobs, AppState,
+};
+
+/*──────── model ──────────────────────────────────────────────────────────*/
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ReprocessRequest {
+    /// Any stage after `import`.
+    #[schema(value_type = String, example = "match_track")]
+    pub from:        Stage,
+    /// Only files whose status is `ERROR`.
+    #[serde(default)]
+    pub only_failed: bool,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct Reprocessed {
+    pub files:          usize,
+    pub jobs_queued:    usize,
+    pub jobs_cancelled: u64,
+}
+
+/// Progress of the library-wide run.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BulkRun {
+    pub id:             Uuid,
+    #[schema(value_type = String)]
+    pub from:           Stage,
+    pub only_failed:    bool,
+    pub started_by:     String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at:     OffsetDateTime,
+    pub albums_total:   usize,
+    pub albums_done:    usize,
+    pub files:          usize,
+    pub jobs_queued:    usize,
+    pub errors:         usize,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub finished_at:    Option<OffsetDateTime>,
+    pub cancelled:      bool,
+}
+
+/// Current / last bulk run; lives in `AppState`.
+pub struct Bulk {
+    per_min: u32,   // REPROCESS_ALBUMS_PER_MIN
+    run:     Mutex<Option<BulkRun>>,
+    stop:    AtomicBool,
+}
+
+impl Bulk {
+    pub fn from_env() -> Arc<Self> {
+        let per_min = std::env::var("REPROCESS_ALBUMS_PER_MIN").ok().and_then(|v| v.parse().ok());
+        Arc::new(Self {
+            per_min: per_min.filter(|n| *n > 0).unwrap_or(30),
+            run:     Mutex::new(None),
+            stop:    AtomicBool::new(false),
+        })
+    }
+
+    fn update(&self, f: impl FnOnce(&mut BulkRun)) {
+        if let Some(r) = self.run.lock().expect("bulk lock").as_mut() {
+            f(r);
+        }
+    }
+}
+
+/*──────── handlers ───────────────────────────────────────────────────────*/
+
+/// `POST /albums/:id/reprocess`
+#[utoipa::path(
+    post, path = "/albums/{id}/reprocess", tag = "albums", request_body = ReprocessRequest,
+    params(("id" = Uuid, Path, description = "Album id")),
+    responses(
+        (status = 200, description = "Files reset and jobs queued", body = Reprocessed),
+        (status = 404, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id, from = %r.from))]
+pub async fn reprocess_album(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+    Json(r): Json<ReprocessRequest>,
+) -> ApiResult<Json<Reprocessed>> {
+    check_stage(r.from)?;
+    let mut tx = app.db.begin().await?;
+    let done = album(&mut tx, id, r.from, r.only_failed, None).await?;
+    audit::record(&mut *tx, &who.username, "album.reprocess", ("album", Some(id)),
+                  serde_json::json!({ "from": r.from, "only_failed": r.only_failed, "files": done.files }))
+        .await?;
+    tx.commit().await?;
+    info!(files = done.files, jobs = done.jobs_queued, "album reprocessing queued");
+    Ok(Json(done))
+}
+
+/// `POST /files/:id/reprocess` – `only_failed` is ignored.
+#[utoipa::path(
+    post, path = "/files/{id}/reprocess", tag = "tracks", request_body = ReprocessRequest,
+    params(("id" = Uuid, Path, description = "File id")),
+    responses(
+        (status = 200, description = "File reset and job queued", body = Reprocessed),
+        (status = 404, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id, from = %r.from))]
+pub async fn reprocess_file(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+    Json(r): Json<ReprocessRequest>,
+) -> ApiResult<Json<Reprocessed>> {
+    check_stage(r.from)?;
+    if r.from == Stage::MatchAlbum {
+        return Err(ApiError::Unprocessable("match_album works on whole albums – reprocess the album".into()));
+    }
+    let row: Option<(Uuid,)> = sqlx::query_as(
+        "SELECT t.album_id FROM files f JOIN tracks t ON t.id = f.track_id WHERE f.id=$1",
+    )
+    .bind(id)
+    .fetch_optional(&app.db)
+    .await?;
+    let (album_id,) = row.ok_or_else(|| ApiError::not_found("file", id))?;
+
+    let mut tx = app.db.begin().await?;
+    let done = album(&mut tx, album_id, r.from, false, Some(id)).await?;
+    audit::record(&mut *tx, &who.username, "file.reprocess", ("file", Some(id)),
+                  serde_json::json!({ "from": r.from }))
+        .await?;
+    tx.commit().await?;
+    info!(jobs = done.jobs_queued, "file reprocessing queued");
+    Ok(Json(done))
+}
+
+/// `POST /reprocess` – every album (with failed files, if `only_failed`).
+#[utoipa::path(
+    post, path = "/reprocess", tag = "albums", request_body = ReprocessRequest,
+    responses(
+        (status = 202, description = "Bulk run started", body = BulkRun),
+        (status = 409, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(from = %r.from))]
+pub async fn start_bulk(
+    State(app): State<AppState>,
+    who: Principal,
+    Json(r): Json<ReprocessRequest>,
+) -> ApiResult<(StatusCode, Json<BulkRun>)> {
+    check_stage(r.from)?;
+    let albums: Vec<(Uuid,)> = sqlx::query_as(
+        "SELECT a.id FROM albums a
+          WHERE NOT $1 OR EXISTS (SELECT 1 FROM tracks t JOIN files f ON f.track_id = t.id
+                                   WHERE t.album_id = a.id AND f.status = 'ERROR')
+       ORDER BY a.imported_at, a.id",
+    )
+    .bind(r.only_failed)
+    .fetch_all(&app.db)
+    .await?;
+
+    let run = BulkRun {
+        id:           Uuid::new_v4(),
+        from:         r.from,
+        only_failed:  r.only_failed,
+        started_by:   who.username.clone(),
+        started_at:   OffsetDateTime::now_utc(),
+        albums_total: albums.len(),
+        albums_done:  0,
+        files:        0,
+        jobs_queued:  0,
+        errors:       0,
+        finished_at:  None,
+        cancelled:    false,
+    };
+    {
+        let mut slot = app.bulk.run.lock().expect("bulk lock");
+        if slot.as_ref().is_some_and(|r| r.finished_at.is_none()) {
+            return Err(ApiError::Conflict("a bulk reprocess is already running".into()));
+        }
+        *slot = Some(run.clone());
+        app.bulk.stop.store(false, Ordering::SeqCst);
+    }
+    audit::record(&app.db, &who.username, "library.reprocess", ("bulk", Some(run.id)),
+                  serde_json::json!({ "from": r.from, "only_failed": r.only_failed, "albums": albums.len() }))
+        .await?;
+    info!(albums = albums.len(), per_min = app.bulk.per_min, "bulk reprocess started");
+
+    tokio::spawn(bulk_loop(app.db.clone(), app.bulk.clone(), albums, r));
+    Ok((StatusCode::ACCEPTED, Json(run)))
+}
+
+/// `GET /reprocess` – the running or most recent bulk run.
+#[utoipa::path(
+    get, path = "/reprocess", tag = "albums",
+    responses((status = 200, body = BulkRun), (status = 404, response = Problem)),
+)]
+pub async fn bulk_status(State(app): State<AppState>) -> ApiResult<Json<BulkRun>> {
+    app.bulk.run.lock().expect("bulk lock").clone().map(Json)
+        .ok_or_else(|| ApiError::NotFound("no bulk reprocess since startup".into()))
+}
+
+/// `DELETE /reprocess` – stop the running bulk run after the current album.
+#[utoipa::path(
+    delete, path = "/reprocess", tag = "albums",
+    responses((status = 202, description = "Stopping"), (status = 404, response = Problem)),
+)]
+pub async fn stop_bulk(State(app): State<AppState>, who: Principal) -> ApiResult<StatusCode> {
+    let id = app.bulk.run.lock().expect("bulk lock").as_ref()
+        .filter(|r| r.finished_at.is_none())
+        .map(|r| r.id)
+        .ok_or_else(|| ApiError::NotFound("no bulk reprocess running".into()))?;
+    app.bulk.stop.store(true, Ordering::SeqCst);
+    audit::record(&app.db, &who.username, "library.reprocess.stop", ("bulk", Some(id)), serde_json::json!({}))
+        .await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+/*──────── core ───────────────────────────────────────────────────────────*/
+
+async fn bulk_loop(db: PgPool, bulk: Arc<Bulk>, albums: Vec<(Uuid,)>, r: ReprocessRequest) {
+    let mut tick = tokio::time::interval(Duration::from_secs(60) / bulk.per_min);
+    for (id,) in albums {
+        tick.tick().await;
+        if bulk.stop.load(Ordering::SeqCst) {
+            bulk.update(|run| run.cancelled = true);
+            break;
+        }
+        let res = async {
+            let mut tx = db.begin().await?;
+            let done = album(&mut tx, id, r.from, r.only_failed, None).await?;
+            tx.commit().await?;
+            ApiResult::Ok(done)
+        }
+        .await;
+        match res {
+            Ok(done) => bulk.update(|run| {
+                run.albums_done += 1;
+                run.files += done.files;
+                run.jobs_queued += done.jobs_queued;
+            }),
+            // deleted since the run started – not worth an error
+            Err(ApiError::NotFound(_)) => bulk.update(|run| run.albums_done += 1),
+            Err(e) => {
+                warn!(album_id = %id, "bulk reprocess: {e:?}");
+                bulk.update(|run| {
+                    run.albums_done += 1;
+                    run.errors += 1;
+                });
+            }
+        }
+    }
+    bulk.update(|run| run.finished_at = Some(OffsetDateTime::now_utc()));
+    info!("bulk reprocess finished");
+}
+
+/// Reset and re-queue the album's files (or just `only`).
+pub(crate) async fn album(
+    tx:          &mut Transaction<'_, Postgres>,
+    id:          Uuid,
+    from:        Stage,
+    only_failed: bool,
+    only:        Option<Uuid>,
+) -> ApiResult<Reprocessed> {
+    let exists: Option<(Uuid,)> = sqlx::query_as("SELECT id FROM albums WHERE id=$1 FOR UPDATE")
+        .bind(id)
+        .fetch_optional(&mut **tx)
+        .await?;
+    exists.ok_or_else(|| ApiError::not_found("album", id))?;
+
+    let files: Vec<(Uuid, bool, bool)> = sqlx::query_as(
+        "SELECT f.id, f.fp_done_at IS NOT NULL, f.tagged_at IS NOT NULL
+           FROM files f JOIN tracks t ON t.id = f.track_id
+          WHERE t.album_id = $1
+            AND (NOT $2 OR f.status = 'ERROR')
+            AND ($3::uuid IS NULL OR f.id = $3)",
+    )
+    .bind(id)
+    .bind(only_failed)
+    .bind(only)
+    .fetch_all(&mut **tx)
+    .await?;
+    let whole_album = only.is_none() && !only_failed;
+
+    let mut out = Reprocessed { files: files.len(), ..Default::default() };
+    out.jobs_cancelled = sqlx::query(
+        "UPDATE jobs SET status = 'cancelled', last_error = 'superseded by reprocess'
+          WHERE status = 'queued'
+            AND (payload->>'file_id' = ANY($1)
+                 OR ($2 AND payload->>'album_id' = $3 AND payload->>'file_id' IS NULL))",
+    )
+    .bind(files.iter().map(|f| f.0.to_string()).collect::<Vec<_>>())
+    .bind(whole_album)
+    .bind(id.to_string())
+    .execute(&mut **tx)
+    .await?
+    .rows_affected();
+
+    // per-file start: never later than what the file has actually been through
+    let mut buckets: Vec<(Stage, Vec<Uuid>)> = Vec::new();
+    for (fid, fingerprinted, tagged) in files {
+        let start = match from {
+            _ if !fingerprinted    => Stage::Fingerprint,
+            Stage::Index if !tagged => Stage::TagTrack,
+            s => s,
+        };
+        match buckets.iter_mut().find(|b| b.0 == start) {
+            Some(b) => b.1.push(fid),
+            None => buckets.push((start, vec![fid])),
+        }
+    }
+
+    for (stage, ids) in &buckets {
+        let reset = match stage {
+            Stage::Fingerprint => "status='NEW', fp_done_at=NULL, tagged_at=NULL, needs_review=FALSE",
+            Stage::MatchTrack  => "status='FP_DONE', tagged_at=NULL, needs_review=FALSE",
+            Stage::MatchAlbum | Stage::TagTrack => "status='FP_DONE', tagged_at=NULL",
+            Stage::Index       => "status='TAG_DONE'",
+            Stage::Import      => unreachable!("rejected by check_stage"),
+        };
+        sqlx::query(&format!("UPDATE files SET {reset} WHERE id = ANY($1)"))
+            .bind(ids)
+            .execute(&mut **tx)
+            .await?;
+        if matches!(stage, Stage::Fingerprint | Stage::MatchTrack) {
+            sqlx::query("DELETE FROM matches_track WHERE file_id = ANY($1)")
+                .bind(ids)
+                .execute(&mut **tx)
+                .await?;
+        }
+        // MatchAlbum is one album-level job; it queues TagTrack itself
+        if *stage != Stage::MatchAlbum {
+            for fid in ids {
+                jobs::enqueue(&mut **tx, *stage, Some(id), Some(*fid)).await?;
+                out.jobs_queued += 1;
+            }
+        }
+    }
+
+    let album_match = from == Stage::MatchAlbum
+        || (whole_album && matches!(from, Stage::Fingerprint | Stage::MatchTrack));
+    if album_match {
+        sqlx::query("DELETE FROM matches_album WHERE album_id=$1")
+            .bind(id)
+            .execute(&mut **tx)
+            .await?;
+        sqlx::query("UPDATE albums SET needs_review = FALSE WHERE id=$1")
+            .bind(id)
+            .execute(&mut **tx)
+            .await?;
+    }
+    if from == Stage::MatchAlbum && out.files > 0 {
+        jobs::enqueue(&mut **tx, Stage::MatchAlbum, Some(id), None).await?;
+        out.jobs_queued += 1;
+    }
+    Ok(out)
+}
+
+fn check_stage(s: Stage) -> ApiResult<()> {
+    if s == Stage::Import {
+        return Err(ApiError::Unprocessable(
+            "reprocessing starts after import – delete the album and import it again instead".into(),
+        ));
+    }
+    Ok(())
+}
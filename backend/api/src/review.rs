@@ -0,0 +1,529 @@
+//! Manual review queue for low-confidence matches.
+//!
+//! The Match workers store every candidate in `matches_track` /
+//! `matches_album` and raise `needs_review` when none is good enough.  Here a
+//! human picks a candidate (flips `chosen`), types the metadata in, or
+//! rejects the lot and re-queues matching.  Every decision lands in
+//! `match_decisions` under the caller's username; resolved items continue to
+//! the Tag stage.
+
+use std::collections::HashMap;
+
+use axum::extract::State;
+use serde::{Deserialize, Serialize};
+use shared::pipeline::Stage;
+use sqlx::{Postgres, Transaction};
+use time::OffsetDateTime;
+use tracing::{info, instrument};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    albums::{check_year, clean_text},
+    auth::Principal,
+    error::{ApiError, ApiResult, Json, Path, Problem, Query},
+    history, jobs, AppState,
+};
+
+/*──────── model ──────────────────────────────────────────────────────────*/
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct FileReview {
+    pub file_id:     Uuid,
+    pub path:        String,
+    pub track_id:    Uuid,
+    pub track_title: Option<String>,
+    pub album_id:    Uuid,
+    pub album_title: Option<String>,
+    pub artist:      Option<String>,
+    #[sqlx(skip)]
+    pub candidates:  Vec<TrackCandidate>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct TrackCandidate {
+    #[serde(skip)]
+    pub file_id:      Uuid,
+    pub mb_recording: Uuid,
+    pub score:        Option<f32>,
+    pub raw_json:     serde_json::Value,
+    pub chosen:       Option<bool>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct AlbumReview {
+    pub album_id:   Uuid,
+    pub title:      Option<String>,
+    pub artist:     Option<String>,
+    pub year:       Option<i32>,
+    #[sqlx(skip)]
+    pub candidates: Vec<AlbumCandidate>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct AlbumCandidate {
+    #[serde(skip)]
+    pub album_id:   Uuid,
+    pub mb_release: Uuid,
+    pub confidence: Option<f32>,
+    pub raw_json:   serde_json::Value,
+    pub chosen:     Option<bool>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct Decision {
+    pub id:         i64,
+    pub file_id:    Option<Uuid>,
+    pub album_id:   Option<Uuid>,
+    pub action:     String,
+    pub mbid:       Option<Uuid>,
+    pub metadata:   Option<serde_json::Value>,
+    pub decided_by: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub decided_at: OffsetDateTime,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ReviewQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Choice {
+    pub mbid: Uuid,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ManualTrack {
+    pub title: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ManualAlbum {
+    pub title:  Option<String>,
+    pub artist: Option<String>,
+    pub year:   Option<i32>,
+    pub venue:  Option<String>,
+}
+
+/*──────── queues ─────────────────────────────────────────────────────────*/
+
+/// `GET /review/files`
+#[utoipa::path(
+    get, path = "/review/files", tag = "review", operation_id = "list_review_files",
+    params(ReviewQuery),
+    responses(
+        (status = 200, description = "Files awaiting review", body = Vec<FileReview>),
+    ),
+)]
+pub async fn list_files(
+    State(app): State<AppState>,
+    Query(q): Query<ReviewQuery>,
+) -> ApiResult<Json<Vec<FileReview>>> {
+    let mut items: Vec<FileReview> = sqlx::query_as(
+        r#"SELECT f.id AS file_id, f.path, t.id AS track_id, t.title AS track_title,
+                  a.id AS album_id, a.title AS album_title, a.artist
+             FROM files f
+             JOIN tracks t ON t.id = f.track_id
+             JOIN albums a ON a.id = t.album_id
+            WHERE f.needs_review
+         ORDER BY a.imported_at, a.id, t.disc, t."index"
+            LIMIT $1"#,
+    )
+    .bind(q.limit.unwrap_or(100).clamp(1, 500))
+    .fetch_all(&app.db)
+    .await?;
+
+    let ids: Vec<Uuid> = items.iter().map(|i| i.file_id).collect();
+    let cands: Vec<TrackCandidate> = sqlx::query_as(
+        "SELECT file_id, mb_recording, score, raw_json, chosen FROM matches_track
+          WHERE file_id = ANY($1) ORDER BY score DESC NULLS LAST",
+    )
+    .bind(&ids)
+    .fetch_all(&app.db)
+    .await?;
+    let mut by_file: HashMap<Uuid, Vec<TrackCandidate>> = HashMap::new();
+    for c in cands {
+        by_file.entry(c.file_id).or_default().push(c);
+    }
+    for i in &mut items {
+        i.candidates = by_file.remove(&i.file_id).unwrap_or_default();
+    }
+    Ok(Json(items))
+}
+
+/// `GET /review/albums`
+#[utoipa::path(
+    get, path = "/review/albums", tag = "review", operation_id = "list_review_albums",
+    params(ReviewQuery),
+    responses(
+        (status = 200, description = "Albums awaiting review", body = Vec<AlbumReview>),
+    ),
+)]
+pub async fn list_albums(
+    State(app): State<AppState>,
+    Query(q): Query<ReviewQuery>,
+) -> ApiResult<Json<Vec<AlbumReview>>> {
+    let mut items: Vec<AlbumReview> = sqlx::query_as(
+        "SELECT id AS album_id, title, artist, year FROM albums
+          WHERE needs_review ORDER BY imported_at, id LIMIT $1",
+    )
+    .bind(q.limit.unwrap_or(100).clamp(1, 500))
+    .fetch_all(&app.db)
+    .await?;
+
+    let ids: Vec<Uuid> = items.iter().map(|i| i.album_id).collect();
+    let cands: Vec<AlbumCandidate> = sqlx::query_as(
+        "SELECT album_id, mb_release, confidence, raw_json, chosen FROM matches_album
+          WHERE album_id = ANY($1) ORDER BY confidence DESC NULLS LAST",
+    )
+    .bind(&ids)
+    .fetch_all(&app.db)
+    .await?;
+    let mut by_album: HashMap<Uuid, Vec<AlbumCandidate>> = HashMap::new();
+    for c in cands {
+        by_album.entry(c.album_id).or_default().push(c);
+    }
+    for i in &mut items {
+        i.candidates = by_album.remove(&i.album_id).unwrap_or_default();
+    }
+    Ok(Json(items))
+}
+
+/*──────── file decisions ─────────────────────────────────────────────────*/
+
+/// `POST /review/files/:id/choose` – pick a recording, continue to Tag.
+#[utoipa::path(
+    post, path = "/review/files/{id}/choose", tag = "review",
+    params(("id" = Uuid, Path, description = "File id")), request_body = Choice,
+    responses(
+        (status = 200, body = Decision),
+        (status = 404, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id))]
+pub async fn choose_file(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+    Json(c): Json<Choice>,
+) -> ApiResult<Json<Decision>> {
+    let mut tx = app.db.begin().await?;
+    lock_file(&mut tx, id).await?;
+    let res = sqlx::query("UPDATE matches_track SET chosen = (mb_recording = $2) WHERE file_id=$1")
+        .bind(id)
+        .bind(c.mbid)
+        .execute(&mut *tx)
+        .await?;
+    let is_candidate: Option<(bool,)> = sqlx::query_as(
+        "SELECT chosen FROM matches_track WHERE file_id=$1 AND mb_recording=$2",
+    )
+    .bind(id)
+    .bind(c.mbid)
+    .fetch_optional(&mut *tx)
+    .await?;
+    if res.rows_affected() == 0 || is_candidate.is_none() {
+        return Err(ApiError::Unprocessable(format!("{} is not a candidate for file {id}", c.mbid)));
+    }
+
+    let d = resolve_file(&mut tx, id, "chosen", Some(c.mbid), None, who.username, Stage::TagTrack).await?;
+    tx.commit().await?;
+    info!(mbid = %c.mbid, "file match chosen");
+    Ok(Json(d))
+}
+
+/// `POST /review/files/:id/manual` – no usable candidate; take the title
+/// as typed.
+#[utoipa::path(
+    post, path = "/review/files/{id}/manual", tag = "review",
+    params(("id" = Uuid, Path, description = "File id")), request_body = ManualTrack,
+    responses(
+        (status = 200, body = Decision),
+        (status = 404, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id))]
+pub async fn manual_file(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+    Json(m): Json<ManualTrack>,
+) -> ApiResult<Json<Decision>> {
+    let title = clean_text("title", Some(m.title))?
+        .ok_or_else(|| ApiError::Unprocessable("title required".into()))?;
+
+    let mut tx = app.db.begin().await?;
+    lock_file(&mut tx, id).await?;
+    history::attribute(&mut tx, &who.username, "review").await?;
+    sqlx::query("UPDATE matches_track SET chosen = FALSE WHERE file_id=$1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("UPDATE tracks SET title=$2 WHERE id = (SELECT track_id FROM files WHERE id=$1)")
+        .bind(id)
+        .bind(&title)
+        .execute(&mut *tx)
+        .await?;
+
+    let meta = serde_json::json!({ "title": title });
+    let d = resolve_file(&mut tx, id, "manual", None, Some(meta), who.username, Stage::TagTrack).await?;
+    tx.commit().await?;
+    info!("file metadata entered by hand");
+    Ok(Json(d))
+}
+
+/// `POST /review/files/:id/reject` – drop all candidates and match again.
+#[utoipa::path(
+    post, path = "/review/files/{id}/reject", tag = "review",
+    params(("id" = Uuid, Path, description = "File id")),
+    responses(
+        (status = 200, body = Decision),
+        (status = 404, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id))]
+pub async fn reject_file(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+) -> ApiResult<Json<Decision>> {
+    let mut tx = app.db.begin().await?;
+    lock_file(&mut tx, id).await?;
+    sqlx::query("DELETE FROM matches_track WHERE file_id=$1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    let d = resolve_file(&mut tx, id, "rejected", None, None, who.username, Stage::MatchTrack).await?;
+    tx.commit().await?;
+    info!("file candidates rejected – re-matching");
+    Ok(Json(d))
+}
+
+async fn lock_file(tx: &mut Transaction<'_, Postgres>, id: Uuid) -> ApiResult<()> {
+    let row: Option<(Uuid,)> = sqlx::query_as("SELECT id FROM files WHERE id=$1 FOR UPDATE")
+        .bind(id)
+        .fetch_optional(&mut **tx)
+        .await?;
+    row.map(|_| ()).ok_or_else(|| ApiError::not_found("file", id))
+}
+
+/// Clear the review flag, record the decision, queue `next`.
+async fn resolve_file(
+    tx:         &mut Transaction<'_, Postgres>,
+    id:         Uuid,
+    action:     &str,
+    mbid:       Option<Uuid>,
+    metadata:   Option<serde_json::Value>,
+    decided_by: String,
+    next:       Stage,
+) -> ApiResult<Decision> {
+    sqlx::query("UPDATE files SET needs_review = FALSE WHERE id=$1")
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
+    let d = record(tx, Some(id), None, action, mbid, metadata, decided_by).await?;
+    let album_id: Option<(Option<Uuid>,)> = sqlx::query_as(
+        "SELECT t.album_id FROM files f JOIN tracks t ON t.id=f.track_id WHERE f.id=$1",
+    )
+    .bind(id)
+    .fetch_optional(&mut **tx)
+    .await?;
+    jobs::enqueue(&mut **tx, next, album_id.and_then(|(a,)| a), Some(id))
+        .await?;
+    Ok(d)
+}
+
+/*──────── album decisions ────────────────────────────────────────────────*/
+
+/// `POST /review/albums/:id/choose` – pick a release, tag every file.
+#[utoipa::path(
+    post, path = "/review/albums/{id}/choose", tag = "review",
+    params(("id" = Uuid, Path, description = "Album id")), request_body = Choice,
+    responses(
+        (status = 200, body = Decision),
+        (status = 404, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id))]
+pub async fn choose_album(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+    Json(c): Json<Choice>,
+) -> ApiResult<Json<Decision>> {
+    let mut tx = app.db.begin().await?;
+    lock_album(&mut tx, id).await?;
+    let is_candidate: Option<(Uuid,)> = sqlx::query_as(
+        "SELECT album_id FROM matches_album WHERE album_id=$1 AND mb_release=$2",
+    )
+    .bind(id)
+    .bind(c.mbid)
+    .fetch_optional(&mut *tx)
+    .await?;
+    if is_candidate.is_none() {
+        return Err(ApiError::Unprocessable(format!("{} is not a candidate for album {id}", c.mbid)));
+    }
+    sqlx::query("UPDATE matches_album SET chosen = (mb_release = $2) WHERE album_id=$1")
+        .bind(id)
+        .bind(c.mbid)
+        .execute(&mut *tx)
+        .await?;
+
+    let d = resolve_album(&mut tx, id, "chosen", Some(c.mbid), None, who.username).await?;
+    tx.commit().await?;
+    info!(mbid = %c.mbid, "album match chosen");
+    Ok(Json(d))
+}
+
+/// `POST /review/albums/:id/manual`
+#[utoipa::path(
+    post, path = "/review/albums/{id}/manual", tag = "review",
+    params(("id" = Uuid, Path, description = "Album id")), request_body = ManualAlbum,
+    responses(
+        (status = 200, body = Decision),
+        (status = 404, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id))]
+pub async fn manual_album(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+    Json(m): Json<ManualAlbum>,
+) -> ApiResult<Json<Decision>> {
+    let title  = clean_text("title", m.title)?;
+    let artist = clean_text("artist", m.artist)?;
+    let venue  = clean_text("venue", m.venue)?;
+    if let Some(y) = m.year {
+        check_year(y)?;
+    }
+
+    let mut tx = app.db.begin().await?;
+    lock_album(&mut tx, id).await?;
+    history::attribute(&mut tx, &who.username, "review").await?;
+    sqlx::query("UPDATE matches_album SET chosen = FALSE WHERE album_id=$1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        "UPDATE albums SET title  = COALESCE($2, title),
+                           artist = COALESCE($3, artist),
+                           year   = COALESCE($4, year),
+                           venue  = COALESCE($5, venue)
+          WHERE id=$1",
+    )
+    .bind(id)
+    .bind(&title)
+    .bind(&artist)
+    .bind(m.year)
+    .bind(&venue)
+    .execute(&mut *tx)
+    .await?;
+
+    let meta = serde_json::json!({ "title": title, "artist": artist, "year": m.year, "venue": venue });
+    let d = resolve_album(&mut tx, id, "manual", None, Some(meta), who.username).await?;
+    tx.commit().await?;
+    info!("album metadata entered by hand");
+    Ok(Json(d))
+}
+
+/// `POST /review/albums/:id/reject` – drop release candidates, re-run
+/// MatchAlbum.
+#[utoipa::path(
+    post, path = "/review/albums/{id}/reject", tag = "review",
+    params(("id" = Uuid, Path, description = "Album id")),
+    responses(
+        (status = 200, body = Decision),
+        (status = 404, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id))]
+pub async fn reject_album(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+) -> ApiResult<Json<Decision>> {
+    let mut tx = app.db.begin().await?;
+    lock_album(&mut tx, id).await?;
+    sqlx::query("DELETE FROM matches_album WHERE album_id=$1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("UPDATE albums SET needs_review = FALSE WHERE id=$1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    let d = record(&mut tx, None, Some(id), "rejected", None, None, who.username).await?;
+    jobs::enqueue(&mut *tx, Stage::MatchAlbum, Some(id), None).await?;
+    tx.commit().await?;
+    info!("album candidates rejected – re-matching");
+    Ok(Json(d))
+}
+
+async fn lock_album(tx: &mut Transaction<'_, Postgres>, id: Uuid) -> ApiResult<()> {
+    let row: Option<(Uuid,)> = sqlx::query_as("SELECT id FROM albums WHERE id=$1 FOR UPDATE")
+        .bind(id)
+        .fetch_optional(&mut **tx)
+        .await?;
+    row.map(|_| ()).ok_or_else(|| ApiError::not_found("album", id))
+}
+
+/// Clear the flag, record the decision, queue TagTrack for every file.
+async fn resolve_album(
+    tx:         &mut Transaction<'_, Postgres>,
+    id:         Uuid,
+    action:     &str,
+    mbid:       Option<Uuid>,
+    metadata:   Option<serde_json::Value>,
+    decided_by: String,
+) -> ApiResult<Decision> {
+    sqlx::query("UPDATE albums SET needs_review = FALSE WHERE id=$1")
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
+    let d = record(tx, None, Some(id), action, mbid, metadata, decided_by).await?;
+    let files: Vec<(Uuid,)> = sqlx::query_as(
+        "SELECT f.id FROM files f JOIN tracks t ON t.id=f.track_id WHERE t.album_id=$1",
+    )
+    .bind(id)
+    .fetch_all(&mut **tx)
+    .await?;
+    for (fid,) in files {
+        jobs::enqueue(&mut **tx, Stage::TagTrack, Some(id), Some(fid)).await?;
+    }
+    Ok(d)
+}
+
+async fn record(
+    tx:         &mut Transaction<'_, Postgres>,
+    file_id:    Option<Uuid>,
+    album_id:   Option<Uuid>,
+    action:     &str,
+    mbid:       Option<Uuid>,
+    metadata:   Option<serde_json::Value>,
+    decided_by: String,
+) -> ApiResult<Decision> {
+    sqlx::query_as(
+        "INSERT INTO match_decisions(file_id, album_id, action, mbid, metadata, decided_by)
+              VALUES ($1,$2,$3,$4,$5,$6)
+           RETURNING id, file_id, album_id, action, mbid, metadata, decided_by, decided_at",
+    )
+    .bind(file_id)
+    .bind(album_id)
+    .bind(action)
+    .bind(mbid)
+    .bind(metadata)
+    .bind(decided_by)
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(Into::into)
+}
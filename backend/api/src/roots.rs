@@ -0,0 +1,42 @@
+//! Filesystem roots the API is allowed to hand to the pipeline.
+//!
+//! Anything that ends up in `albums.source->>'path'` is read by the Import
+//! worker, so client-supplied paths must resolve *inside* one of these.
+
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug)]
+pub struct Roots {
+    pub media: PathBuf,   // long-term library   (MEDIA_ROOT, default /media)
+    pub inbox: PathBuf,   // transient uploads   (INBOX_ROOT, default /inbox)
+}
+
+impl Roots {
+    pub fn from_env() -> Self {
+        let var = |k: &str, d: &str| std::env::var(k).unwrap_or_else(|_| d.into());
+        Self {
+            media: canonical_or_raw(var("MEDIA_ROOT", "/media").into()),
+            inbox: canonical_or_raw(var("INBOX_ROOT", "/inbox").into()),
+        }
+    }
+
+    /// Upload staging directory for one album.
+    pub fn album_inbox(&self, album_id: uuid::Uuid) -> PathBuf {
+        self.inbox.join(album_id.to_string())
+    }
+
+    /// Canonicalise `p` and return it iff it lives under the media or inbox
+    /// root.  Symlinks are resolved first, so `..` tricks don't escape.
+    pub fn resolve(&self, p: &Path) -> Option<PathBuf> {
+        let real = p.canonicalize().ok()?;
+        [&self.media, &self.inbox]
+            .into_iter()
+            .any(|root| real.starts_with(root))
+            .then_some(real)
+    }
+}
+
+// Roots may not exist yet on a fresh dev box – keep the raw path then.
+fn canonical_or_raw(p: PathBuf) -> PathBuf {
+    p.canonicalize().unwrap_or(p)
+}
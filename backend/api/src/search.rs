@@ -0,0 +1,215 @@
+//! `GET /search?q=` – keyword search over albums (title, artist, venue),
+//! tracks and artist names.
+//!
+//! Full-text matches use the generated `search` tsvectors ('simple' config,
+//! web-search syntax: `"quoted phrase"`, `-exclude`, `or`).  Trigram word
+//! similarity on artist and titles catches misspellings the tsquery misses.
+//! Each group is ranked on its own; snippets are HTML-escaped with matches
+//! wrapped in `<mark>`.
+
+use axum::extract::State;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    albums::AlbumKind,
+    error::{ApiError, ApiResult, Json, Problem, Query},
+    AppState,
+};
+
+const DEFAULT_LIMIT: i64 = 10;
+const MAX_LIMIT:     i64 = 50;
+const MAX_QUERY:     usize = 200;
+/// `pg_trgm.word_similarity_threshold` for the `<%` matches – the 0.6
+/// default misses transpositions like "greatful" for "grateful".
+const FUZZY_THRESHOLD: &str = "0.3";
+/// Shorter queries have too few trigrams to say anything – exact words only.
+const FUZZY_MIN_CHARS: usize = 4;
+
+/// `ts_headline` markers; swapped for `<mark>` after escaping.
+const START: char = '\u{2}';
+const STOP:  char = '\u{3}';
+
+/*──────── model ──────────────────────────────────────────────────────────*/
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(deny_unknown_fields)]
+pub struct SearchQuery {
+    /// Words to look for.
+    pub q:     String,
+    /// Per group; default 10, max 50.
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct AlbumHit {
+    pub id:         Uuid,
+    pub title:      Option<String>,
+    pub artist:     Option<String>,
+    pub year:       Option<i32>,
+    pub venue:      Option<String>,
+    #[sqlx(rename = "kind")]
+    pub album_kind: AlbumKind,
+    pub rank:       f32,
+    /// `artist – title – venue` with matches in `<mark>`.
+    pub snippet:    String,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct TrackHit {
+    pub id:          Uuid,
+    pub album_id:    Option<Uuid>,
+    pub album_title: Option<String>,
+    pub artist:      Option<String>,
+    pub disc:        Option<i32>,
+    pub index:       Option<i32>,
+    pub title:       Option<String>,
+    pub rank:        f32,
+    pub snippet:     String,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct ArtistHit {
+    pub name:    String,
+    pub albums:  i64,
+    pub rank:    f32,
+    pub snippet: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchResults {
+    pub query:   String,
+    pub albums:  Vec<AlbumHit>,
+    pub tracks:  Vec<TrackHit>,
+    pub artists: Vec<ArtistHit>,
+}
+
+/*──────── handler ────────────────────────────────────────────────────────*/
+
+/// `GET /search?q=&limit=`
+#[utoipa::path(
+    get, path = "/search", tag = "albums", params(SearchQuery),
+    responses(
+        (status = 200, description = "Ranked hits, grouped", body = SearchResults),
+        (status = 400, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+pub async fn search(State(app): State<AppState>, Query(q): Query<SearchQuery>)
+    -> ApiResult<Json<SearchResults>>
+{
+    let query = q.q.trim().to_owned();
+    if query.is_empty() {
+        return Err(ApiError::Unprocessable("`q` is empty".into()));
+    }
+    if query.chars().count() > MAX_QUERY {
+        return Err(ApiError::Unprocessable(format!("`q` longer than {MAX_QUERY} characters")));
+    }
+    let limit = q.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    // phrases / exclusions / `or` ask for exactly that – no fuzzy widening
+    let operators = query.contains('"')
+        || query.split_whitespace().any(|w| w.starts_with('-') || w.eq_ignore_ascii_case("or"));
+    let fuzzy = !operators && query.chars().count() >= FUZZY_MIN_CHARS;
+    let headline = format!("StartSel={START}, StopSel={STOP}, HighlightAll=true");
+
+    let mut tx = app.db.begin().await?;
+    sqlx::query("SELECT set_config('pg_trgm.word_similarity_threshold', $1, true)")
+        .bind(FUZZY_THRESHOLD)
+        .execute(&mut *tx)
+        .await?;
+
+    // $1 query text, $2 headline options, $3 limit, $4 fuzzy
+    let q_cte = "WITH q AS (SELECT websearch_to_tsquery('simple', $1) AS tsq, lower($1) AS raw, $4 AS fuzzy)";
+
+    let mut albums: Vec<AlbumHit> = sqlx::query_as(&format!(
+        "{q_cte}
+         SELECT a.id, a.title, a.artist, a.year, a.venue, a.kind,
+                (ts_rank_cd(a.search, q.tsq)
+                 + GREATEST(word_similarity(q.raw, lower(a.artist)),
+                            word_similarity(q.raw, lower(a.title))) / 2)::real AS rank,
+                ts_headline('simple', concat_ws(' – ', a.artist, a.title, a.venue), q.tsq, $2) AS snippet
+           FROM albums a, q
+          WHERE a.search @@ q.tsq
+             OR q.fuzzy AND q.raw <% lower(a.artist)
+             OR q.fuzzy AND q.raw <% lower(a.title)
+       ORDER BY rank DESC, a.id
+          LIMIT $3"
+    ))
+    .bind(&query)
+    .bind(&headline)
+    .bind(limit)
+    .bind(fuzzy)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut tracks: Vec<TrackHit> = sqlx::query_as(&format!(
+        "{q_cte}
+         SELECT t.id, t.album_id, a.title AS album_title, a.artist, t.disc, t.\"index\", t.title,
+                (ts_rank_cd(t.search, q.tsq) + word_similarity(q.raw, lower(t.title)) / 2)::real AS rank,
+                ts_headline('simple', COALESCE(t.title, ''), q.tsq, $2) AS snippet
+           FROM tracks t
+           LEFT JOIN albums a ON a.id = t.album_id, q
+          WHERE t.search @@ q.tsq
+             OR q.fuzzy AND q.raw <% lower(t.title)
+       ORDER BY rank DESC, t.id
+          LIMIT $3"
+    ))
+    .bind(&query)
+    .bind(&headline)
+    .bind(limit)
+    .bind(fuzzy)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut artists: Vec<ArtistHit> = sqlx::query_as(&format!(
+        "{q_cte},
+         hits AS (
+           SELECT min(a.artist) AS name, COUNT(*) AS albums,
+                  max(ts_rank_cd(to_tsvector('simple', a.artist), q.tsq)
+                      + word_similarity(q.raw, lower(a.artist)) / 2)::real AS rank
+             FROM albums a, q
+            WHERE a.artist IS NOT NULL
+              AND (to_tsvector('simple', a.artist) @@ q.tsq OR q.fuzzy AND q.raw <% lower(a.artist))
+         GROUP BY lower(a.artist)
+         )
+         SELECT h.name, h.albums, h.rank, ts_headline('simple', h.name, q.tsq, $2) AS snippet
+           FROM hits h, q
+       ORDER BY h.rank DESC, h.albums DESC, h.name
+          LIMIT $3"
+    ))
+    .bind(&query)
+    .bind(&headline)
+    .bind(limit)
+    .bind(fuzzy)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    albums.iter_mut().for_each(|h| h.snippet = mark(&h.snippet));
+    tracks.iter_mut().for_each(|h| h.snippet = mark(&h.snippet));
+    artists.iter_mut().for_each(|h| h.snippet = mark(&h.snippet));
+    Ok(Json(SearchResults { query, albums, tracks, artists }))
+}
+
+/*──────── helpers ────────────────────────────────────────────────────────*/
+
+/// Escape for HTML, then turn the headline markers into `<mark>` tags.
+fn mark(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len() + 16);
+    for c in raw.chars() {
+        match c {
+            START => out.push_str("<mark>"),
+            STOP  => out.push_str("</mark>"),
+            '&'   => out.push_str("&amp;"),
+            '<'   => out.push_str("&lt;"),
+            '>'   => out.push_str("&gt;"),
+            '"'   => out.push_str("&quot;"),
+            '\''  => out.push_str("&#39;"),
+            c     => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,266 @@
+//! `GET /stats` – library totals and distributions, match confidence and
+//! recent pipeline throughput.
+//!
+//! Everything is read from the `stats_*` materialized views (migration 15),
+//! which a background task refreshes every `STATS_REFRESH_SECS` (default
+//! 300), so the request costs a handful of tiny selects however large the
+//! library is.  Figures are as of `refreshed_at`.  The same task stats files
+//! imported before `files.size_bytes` existed, a batch per round.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use axum::extract::State;
+use serde::Serialize;
+use shared::pipeline::Stage;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    albums::{AlbumKind, FileStatus},
+    error::{ApiResult, Json, Problem},
+    AppState,
+};
+
+const VIEWS: &[&str] = &[
+    "stats_totals", "stats_codecs", "stats_album_kinds", "stats_years",
+    "stats_file_status", "stats_confidence", "stats_throughput",
+];
+/// Files without `size_bytes` stat'ed per refresh round.
+const SIZE_BATCH: i64 = 2000;
+/// Confidence buckets: `[0.0, 0.1)`, …, `[0.9, 1.0]`.
+const BUCKETS: usize = 10;
+
+/*──────── model ──────────────────────────────────────────────────────────*/
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Stats {
+    #[serde(with = "time::serde::rfc3339")]
+    pub refreshed_at:            OffsetDateTime,
+    pub albums:                  i64,
+    pub tracks:                  i64,
+    pub files:                   i64,
+    /// Sum of `tracks.duration_sec`.
+    pub hours_of_audio:          f64,
+    /// Not fingerprinted yet – missing from `hours_of_audio`.
+    pub tracks_without_duration: i64,
+    pub total_bytes:             i64,
+    pub storage:                 Vec<CodecStorage>,
+    pub album_kinds:             Vec<KindCount>,
+    /// `year: null` counts albums without one.
+    pub years:                   Vec<YearCount>,
+    pub file_status:             Vec<StatusCount>,
+    pub confidence:              Confidence,
+    /// Keyed by stage, every stage present.
+    #[schema(value_type = BTreeMap<String, Throughput>)]
+    pub throughput:              BTreeMap<&'static str, Throughput>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct CodecStorage {
+    pub codec:        String,
+    pub files:        i64,
+    pub bytes:        i64,
+    /// Files whose size isn't known yet (not in `bytes`).
+    pub unknown_size: i64,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct KindCount {
+    #[sqlx(rename = "kind")]
+    pub album_kind: AlbumKind,
+    pub albums:     i64,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct YearCount {
+    pub year:   Option<i32>,
+    pub albums: i64,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct StatusCount {
+    #[sqlx(rename = "status")]
+    pub file_status: FileStatus,
+    pub files:       i64,
+}
+
+/// Best candidate per album / per file, in tenths: `album[3]` is how many
+/// albums have a best confidence in `[0.3, 0.4)`.
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct Confidence {
+    pub album: [i64; BUCKETS],
+    pub track: [i64; BUCKETS],
+}
+
+#[derive(Debug, Default, Serialize, sqlx::FromRow, ToSchema)]
+pub struct Throughput {
+    /// Jobs finished in the last hour / 24 hours.
+    pub done_1h:    i64,
+    pub done_24h:   i64,
+    pub failed_24h: i64,
+    /// Backlog at refresh time.
+    pub queued:     i64,
+    pub running:    i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct Totals {
+    albums:                  i64,
+    tracks:                  i64,
+    files:                   i64,
+    duration_sec:            i64,
+    tracks_without_duration: i64,
+    refreshed_at:            OffsetDateTime,
+}
+
+/*──────── handler ────────────────────────────────────────────────────────*/
+
+/// `GET /stats`
+#[utoipa::path(
+    get, path = "/stats", tag = "albums",
+    responses((status = 200, description = "As of the last refresh", body = Stats), (status = 401, response = Problem)),
+)]
+pub async fn stats(State(app): State<AppState>) -> ApiResult<Json<Stats>> {
+    // one snapshot, one refresh round
+    let mut tx = app.db.begin().await?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY").execute(&mut *tx).await?;
+
+    let t: Totals = sqlx::query_as(
+        "SELECT albums, tracks, files, duration_sec, tracks_without_duration, refreshed_at FROM stats_totals",
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    let storage: Vec<CodecStorage> =
+        sqlx::query_as("SELECT codec, files, bytes, unknown_size FROM stats_codecs ORDER BY bytes DESC, codec")
+            .fetch_all(&mut *tx)
+            .await?;
+    let album_kinds = sqlx::query_as("SELECT kind, albums FROM stats_album_kinds ORDER BY kind")
+        .fetch_all(&mut *tx)
+        .await?;
+    let years = sqlx::query_as("SELECT year, albums FROM stats_years ORDER BY year NULLS LAST")
+        .fetch_all(&mut *tx)
+        .await?;
+    let file_status = sqlx::query_as("SELECT status, files FROM stats_file_status ORDER BY status")
+        .fetch_all(&mut *tx)
+        .await?;
+
+    let buckets: Vec<(String, i32, i64)> = sqlx::query_as("SELECT level, bucket, n FROM stats_confidence")
+        .fetch_all(&mut *tx)
+        .await?;
+    let mut confidence = Confidence::default();
+    for (level, bucket, n) in buckets {
+        let row = if level == "album" { &mut confidence.album } else { &mut confidence.track };
+        if let Some(slot) = usize::try_from(bucket).ok().and_then(|b| row.get_mut(b)) {
+            *slot = n;
+        }
+    }
+
+    #[derive(sqlx::FromRow)]
+    struct StageRow {
+        stage: String,
+        #[sqlx(flatten)]
+        t:     Throughput,
+    }
+    let rows: Vec<StageRow> =
+        sqlx::query_as("SELECT stage, done_1h, done_24h, failed_24h, queued, running FROM stats_throughput")
+            .fetch_all(&mut *tx)
+            .await?;
+    tx.commit().await?;
+    let mut throughput: BTreeMap<&'static str, Throughput> =
+        Stage::ALL.iter().map(|s| (s.as_str(), Throughput::default())).collect();
+    for r in rows {
+        if let Some(slot) = throughput.get_mut(r.stage.as_str()) {
+            *slot = r.t;
+        }
+    }
+
+    Ok(Json(Stats {
+        refreshed_at: t.refreshed_at,
+        albums: t.albums,
+        tracks: t.tracks,
+        files: t.files,
+        hours_of_audio: (t.duration_sec as f64 / 3600.0 * 10.0).round() / 10.0,
+        tracks_without_duration: t.tracks_without_duration,
+        total_bytes: storage.iter().map(|c| c.bytes).sum(),
+        storage,
+        album_kinds,
+        years,
+        file_status,
+        confidence,
+        throughput,
+    }))
+}
+
+/*──────── refresher ──────────────────────────────────────────────────────*/
+
+/// Start the refresh task; the first round runs right away.
+pub fn spawn_refresher(db: PgPool) {
+    let every = std::env::var("STATS_REFRESH_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|s: &u64| *s > 0)
+        .unwrap_or(300);
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(Duration::from_secs(every));
+        let mut cursor = Uuid::nil();
+        loop {
+            tick.tick().await;
+            match fill_sizes(&db, cursor).await {
+                Ok(next) => cursor = next,
+                Err(e) => warn!("stats: sizing files failed: {e:#}"),
+            }
+            if let Err(e) = refresh(&db).await {
+                warn!("stats: refresh failed: {e:#}");
+            }
+        }
+    });
+}
+
+async fn refresh(db: &PgPool) -> sqlx::Result<()> {
+    let t0 = std::time::Instant::now();
+    // one transaction, so readers see every view from the same round
+    let mut tx = db.begin().await?;
+    for view in VIEWS {
+        sqlx::query(&format!("REFRESH MATERIALIZED VIEW CONCURRENTLY {view}")).execute(&mut *tx).await?;
+    }
+    tx.commit().await?;
+    info!(took = ?t0.elapsed(), "stats refreshed");
+    Ok(())
+}
+
+/// Stat the next batch of unsized files after `cursor` (by id, wrapping),
+/// so files that stay missing on disk don't starve the rest.
+async fn fill_sizes(db: &PgPool, cursor: Uuid) -> anyhow::Result<Uuid> {
+    let batch: Vec<(Uuid, String)> = sqlx::query_as(
+        "SELECT id, path FROM files WHERE size_bytes IS NULL AND id > $1 ORDER BY id LIMIT $2",
+    )
+    .bind(cursor)
+    .bind(SIZE_BATCH)
+    .fetch_all(db)
+    .await?;
+    let Some(&(last, _)) = batch.last() else {
+        return Ok(Uuid::nil());
+    };
+
+    let sized: Vec<(Uuid, i64)> = tokio::task::spawn_blocking(move || {
+        batch
+            .into_iter()
+            .filter_map(|(id, path)| Some((id, i64::try_from(std::fs::metadata(path).ok()?.len()).ok()?)))
+            .collect()
+    })
+    .await?;
+    let (ids, sizes): (Vec<Uuid>, Vec<i64>) = sized.into_iter().unzip();
+    sqlx::query(
+        "UPDATE files f SET size_bytes = x.size
+           FROM unnest($1::uuid[], $2::bigint[]) AS x(id, size)
+          WHERE f.id = x.id",
+    )
+    .bind(&ids)
+    .bind(&sizes)
+    .execute(db)
+    .await?;
+    Ok(last)
+}
@@ -0,0 +1,270 @@
+//! Audio streaming: `GET /files/:id/stream` and `GET /tracks/:id/stream`.
+//!
+//! Single-range `Range` requests get `206` so browsers and mobile players can
+//! seek; `ETag` / `If-None-Match` / `If-Range` keep re-fetches cheap.  Paths
+//! are re-checked against the library roots on every request – the DB is not
+//! trusted to point only at media.  `?format=` hands off to [`crate::transcode`].
+
+use std::{path::PathBuf, time::UNIX_EPOCH};
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    error::{ApiError, ApiResult, Path, Problem, Query},
+    transcode::StreamQuery,
+    AppState,
+};
+
+/// Preference when a track has several files, best first.
+pub(crate) const CODEC_RANK: &[&str] = &["flac", "wav", "m4a", "opus", "ogg", "mp3"];
+
+/// [`best_file`]'s preference as an `ORDER BY` over `files` columns, for
+/// queries that pick the file in SQL.
+pub(crate) fn best_file_order() -> String {
+    let rank = CODEC_RANK.iter().map(|c| format!("'{c}'")).collect::<Vec<_>>().join(",");
+    format!("status = 'READY' DESC, array_position(ARRAY[{rank}], codec) NULLS LAST")
+}
+
+/// `GET /files/:id/stream`
+#[utoipa::path(
+    get, path = "/files/{id}/stream", tag = "stream",
+    params(("id" = Uuid, Path, description = "File id"), StreamQuery),
+    responses(
+        (
+            status = 200,
+            description = "Audio (original or transcoded)",
+            content_type = "audio/*",
+            body = Vec<u8>,
+        ),
+        (status = 206, description = "Requested byte range", content_type = "audio/*", body = Vec<u8>),
+        (status = 304, description = "Not modified"),
+        (status = 416, description = "Range not satisfiable"),
+        (status = 400, response = Problem),
+        (status = 403, response = Problem),
+        (status = 404, response = Problem),
+        (status = 503, response = Problem),
+    ),
+)]
+pub async fn stream_file(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    Query(q): Query<StreamQuery>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let row: Option<(String, String)> = sqlx::query_as("SELECT path, codec FROM files WHERE id=$1")
+        .bind(id)
+        .fetch_optional(&app.db)
+        .await?;
+    let (path, codec) = row.ok_or_else(|| ApiError::not_found("file", id))?;
+    dispatch(&app, id, &path, &codec, &q, &headers).await
+}
+
+/// `GET /tracks/:id/stream` – best file for the track.
+#[utoipa::path(
+    get, path = "/tracks/{id}/stream", tag = "stream",
+    params(("id" = Uuid, Path, description = "Track id"), StreamQuery),
+    responses(
+        (
+            status = 200,
+            description = "Audio (original or transcoded)",
+            content_type = "audio/*",
+            body = Vec<u8>,
+        ),
+        (status = 206, description = "Requested byte range", content_type = "audio/*", body = Vec<u8>),
+        (status = 304, description = "Not modified"),
+        (status = 416, description = "Range not satisfiable"),
+        (status = 400, response = Problem),
+        (status = 403, response = Problem),
+        (status = 404, response = Problem),
+        (status = 503, response = Problem),
+    ),
+)]
+pub async fn stream_track(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    Query(q): Query<StreamQuery>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let (file_id, path, codec) = best_file(&app.db, id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("track {id} has no playable file")))?;
+    dispatch(&app, file_id, &path, &codec, &q, &headers).await
+}
+
+/// Original bytes, a cached transcode, or a fresh ffmpeg run.
+pub(crate) async fn dispatch(
+    app: &AppState,
+    file_id: Uuid,
+    path: &str,
+    codec: &str,
+    q: &StreamQuery,
+    headers: &HeaderMap,
+) -> ApiResult<Response> {
+    let target = q.target()?;
+    let real = resolve(app, path)?;
+    let Some(target) = target else {
+        return serve(&real, content_type(codec), headers).await;
+    };
+    let tc = app.transcoder.clone();
+    let (src, t) = (real.clone(), target.clone());
+    let cached = tokio::task::spawn_blocking(move || tc.lookup(file_id, &src, &t))
+        .await?;
+    match cached {
+        Some(hit) => serve(&hit, target.content_type(), headers).await,
+        None => app.transcoder.transcode(file_id, real, target).await,
+    }
+}
+
+/// Best file for a track: READY before anything else, then by codec rank.
+pub async fn best_file(db: &sqlx::PgPool, track_id: Uuid)
+    -> sqlx::Result<Option<(Uuid, String, String)>>
+{
+    let mut files: Vec<(Uuid, String, String, bool)> = sqlx::query_as(
+        "SELECT id, path, codec, status = 'READY' FROM files WHERE track_id=$1 AND status <> 'ERROR'",
+    )
+    .bind(track_id)
+    .fetch_all(db)
+    .await?;
+    files.sort_by_key(|(_, _, codec, ready)| {
+        (!ready, CODEC_RANK.iter().position(|c| c == codec).unwrap_or(CODEC_RANK.len()))
+    });
+    Ok(files.into_iter().next().map(|(id, p, c, _)| (id, p, c)))
+}
+
+pub fn content_type(codec: &str) -> &'static str {
+    match codec {
+        "flac" => "audio/flac",
+        "mp3"  => "audio/mpeg",
+        "ogg"  => "audio/ogg",
+        "opus" => "audio/ogg; codecs=opus",
+        "m4a" | "aac" => "audio/mp4",
+        "wav"  => "audio/wav",
+        _      => "application/octet-stream",
+    }
+}
+
+/// Serve an already-resolved `real` path honouring conditional and range headers.
+pub async fn serve(real: &std::path::Path, mime: &'static str, headers: &HeaderMap)
+    -> ApiResult<Response>
+{
+    let mut file = tokio::fs::File::open(&real).await.map_err(|e| {
+        warn!(path = %real.display(), "stream open failed: {e}");
+        ApiError::NotFound("media file missing on disk".to_owned())
+    })?;
+    let meta = file.metadata().await?;
+    let len  = meta.len();
+    let mtime = meta.modified().ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let etag = format!("\"{len:x}-{mtime:x}\"");
+
+    if headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|t| t.trim() == etag || t.trim() == "*"))
+    {
+        return Ok(with_common((StatusCode::NOT_MODIFIED, ()).into_response(), &etag, mime));
+    }
+
+    // If-Range with a stale validator → full body
+    let range_ok = headers
+        .get(header::IF_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .is_none_or(|v| v == etag);
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .filter(|_| range_ok)
+        .map(|r| parse_range(r, len));
+
+    let (status, start, end) = match range {
+        None | Some(RangeSpec::Ignore) => (StatusCode::OK, 0, len.saturating_sub(1)),
+        Some(RangeSpec::Unsatisfiable) => {
+            let mut res = StatusCode::RANGE_NOT_SATISFIABLE.into_response();
+            res.headers_mut().insert(
+                header::CONTENT_RANGE,
+                HeaderValue::from_str(&format!("bytes */{len}")).expect("ascii"),
+            );
+            return Ok(with_common(res, &etag, mime));
+        }
+        Some(RangeSpec::Bytes(s, e)) => (StatusCode::PARTIAL_CONTENT, s, e),
+    };
+    let body_len = if len == 0 { 0 } else { end - start + 1 };
+
+    file.seek(std::io::SeekFrom::Start(start)).await?;
+    let body = Body::from_stream(ReaderStream::new(file.take(body_len)));
+    let mut res = (status, body).into_response();
+    let h = res.headers_mut();
+    h.insert(header::CONTENT_LENGTH, HeaderValue::from(body_len));
+    if status == StatusCode::PARTIAL_CONTENT {
+        h.insert(
+            header::CONTENT_RANGE,
+            HeaderValue::from_str(&format!("bytes {start}-{end}/{len}")).expect("ascii"),
+        );
+    }
+    Ok(with_common(res, &etag, mime))
+}
+
+fn with_common(mut res: Response, etag: &str, mime: &'static str) -> Response {
+    let h = res.headers_mut();
+    h.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    h.insert(header::CONTENT_TYPE, HeaderValue::from_static(mime));
+    if let Ok(v) = HeaderValue::from_str(etag) {
+        h.insert(header::ETAG, v);
+    }
+    res
+}
+
+/// Library paths only – anything resolving outside the roots is a 403.
+fn resolve(app: &AppState, path: &str) -> ApiResult<PathBuf> {
+    let p = std::path::Path::new(path);
+    if !p.exists() {
+        return Err(ApiError::NotFound("media file missing on disk".into()));
+    }
+    app.roots.resolve(p).ok_or_else(|| {
+        warn!(%path, "refusing to stream outside the library roots");
+        ApiError::Forbidden("file is outside the library".into())
+    })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum RangeSpec {
+    /// Multi-range or malformed – serve the whole file.
+    Ignore,
+    Unsatisfiable,
+    /// Inclusive byte range.
+    Bytes(u64, u64),
+}
+
+/// `bytes=a-b`, `bytes=a-`, `bytes=-n`; one range only.
+fn parse_range(raw: &str, len: u64) -> RangeSpec {
+    let Some(spec) = raw.trim().strip_prefix("bytes=") else { return RangeSpec::Ignore };
+    if spec.contains(',') {
+        return RangeSpec::Ignore;
+    }
+    let Some((a, b)) = spec.split_once('-') else { return RangeSpec::Ignore };
+    let (a, b) = (a.trim(), b.trim());
+    let (start, end) = match (a.parse::<u64>().ok(), b.parse::<u64>().ok()) {
+        (Some(s), Some(e)) if s <= e => (s, e.min(len.saturating_sub(1))),
+        (Some(s), None) if b.is_empty() => (s, len.saturating_sub(1)),
+        (None, Some(n)) if a.is_empty() => {
+            if n == 0 { return RangeSpec::Unsatisfiable; }
+            (len.saturating_sub(n), len.saturating_sub(1))
+        }
+        _ => return RangeSpec::Ignore,
+    };
+    if len == 0 || start >= len {
+        return RangeSpec::Unsatisfiable;
+    }
+    RangeSpec::Bytes(start, end)
+}
@@ -0,0 +1,943 @@
+//! `/rest/*` – a Subsonic / OpenSubsonic (API 1.16.1) surface, so phone
+//! players (DSub, Symfonium, play:Sub …) can browse and play the library.
+//!
+//! One handler takes every `/rest/<method>[.view]`, as GET or form POST.
+//! Replies are XML unless `f=json`; failures are `status="failed"` with a
+//! Subsonic error code and HTTP 200, which is what clients expect.  Callers
+//! authenticate with `u` plus `t`+`s` or `p` against a Subsonic app password
+//! (see [`crate::auth`]), or with OpenSubsonic `apiKey` = a regular API
+//! token.  Everything needs `read`; `stream` / `download` need `stream`.
+//!
+//! Mapping: artists are the distinct `albums.artist` names (id `ar-<md5>`),
+//! albums and songs are album and track ids, and a song plays its best file.
+//! Only albums with a playable track are listed.  There is one music folder;
+//! stars, ratings and genres aren't modelled, so those lists are empty.
+//!
+//! Not in the OpenAPI spec – the contract is Subsonic's.
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use axum::{
+    body::Bytes,
+    extract::{Path, RawQuery, State},
+    http::{header, HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use serde_json::{json, Map, Value};
+use sqlx::{FromRow, Postgres, QueryBuilder};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::{
+    auth::{self, Principal, Scope, SubsonicCredential},
+    error::ApiError,
+    images, playlists,
+    stream,
+    transcode::{Format, StreamQuery},
+    AppState,
+};
+
+const API_VERSION: &str = "1.16.1";
+const UNKNOWN_ARTIST: &str = "Unknown Artist";
+/// Leading words skipped when filing artists under a letter.
+const IGNORED_ARTICLES: &str = "The El La Los Las Le Les";
+const MAX_LIST: i64 = 500;
+/// Lossy codecs streamed as-is when `maxBitRate` ≥ 320.
+const LOSSY: &[&str] = &["mp3", "ogg", "opus", "m4a", "aac"];
+
+/*──────── entry point ────────────────────────────────────────────────────*/
+
+/// `GET|POST /rest/:method`
+pub async fn rest(
+    State(app): State<AppState>,
+    Path(method): Path<String>,
+    RawQuery(query): RawQuery,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let mut params = Params::parse(query.as_deref().unwrap_or_default().as_bytes());
+    let form = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/x-www-form-urlencoded"));
+    if form {
+        params.0.extend(Params::parse(&body).0);   // OpenSubsonic `formPost`
+    }
+    let json = params.get("f") == Some("json");
+    let method = method.strip_suffix(".view").unwrap_or(&method);
+
+    match handle(&app, method, &params, &headers).await {
+        Ok(Reply::Data(payload)) => envelope(json, Ok(payload)),
+        Ok(Reply::Raw(res)) => res,
+        Err(fault) => envelope(json, Err(fault)),
+    }
+}
+
+enum Reply {
+    /// Merged into `subsonic-response`.
+    Data(Value),
+    /// Audio or image bytes.
+    Raw(Response),
+}
+
+type Outcome = Result<Reply, Fault>;
+
+async fn handle(app: &AppState, method: &str, p: &Params, headers: &HeaderMap) -> Outcome {
+    if method == "getOpenSubsonicExtensions" {
+        return data(json!({ "openSubsonicExtensions": [
+            { "name": "formPost",             "versions": [1] },
+            { "name": "apiKeyAuthentication", "versions": [1] },
+        ]}));
+    }
+    let who = authenticate(app, p).await?;
+    if !who.has(Scope::Read) {
+        return Err(Fault::new(50, "token lacks the read scope"));
+    }
+
+    match method {
+        "ping"             => data(json!({})),
+        "getLicense"       => data(json!({ "license": { "valid": true } })),
+        "getMusicFolders"  => data(json!({ "musicFolders": { "musicFolder": [{ "id": 1, "name": "Library" }] } })),
+        "getUser"          => get_user(&who, p),
+        "getScanStatus"    => scan_status(app).await,
+        "getGenres"        => data(json!({ "genres": {} })),
+        "getStarred"       => data(json!({ "starred": {} })),
+        "getStarred2"      => data(json!({ "starred2": {} })),
+        "getIndexes"       => artist_index(app, "indexes").await,
+        "getArtists"       => artist_index(app, "artists").await,
+        "getMusicDirectory" => music_directory(app, p).await,
+        "getArtist"        => get_artist(app, p).await,
+        "getAlbum"         => get_album(app, p).await,
+        "getSong"          => get_song(app, p).await,
+        "getAlbumList"     => album_list(app, &who, p, "albumList").await,
+        "getAlbumList2"    => album_list(app, &who, p, "albumList2").await,
+        "getRandomSongs"   => random_songs(app, p).await,
+        "search2"          => search(app, p, "searchResult2").await,
+        "search3"          => search(app, p, "searchResult3").await,
+        "stream"           => play(app, &who, p, headers, false).await,
+        "download"         => play(app, &who, p, headers, true).await,
+        "getCoverArt"      => cover_art(app, p, headers).await,
+        "getPlaylists"     => get_playlists(app, &who).await,
+        "getPlaylist"      => get_playlist(app, &who, p).await,
+        "createPlaylist"   => create_playlist(app, &who, p).await,
+        "updatePlaylist"   => update_playlist(app, &who, p).await,
+        "deletePlaylist"   => delete_playlist(app, &who, p).await,
+        "scrobble"         => scrobble(app, &who, p).await,
+        other => Err(Fault::new(0, format!("{other} is not implemented"))),
+    }
+}
+
+fn data(v: Value) -> Outcome {
+    Ok(Reply::Data(v))
+}
+
+/*──────── auth ───────────────────────────────────────────────────────────*/
+
+async fn authenticate(app: &AppState, p: &Params) -> Result<Principal, Fault> {
+    if let Some(key) = p.get("apiKey") {
+        if p.get("u").is_some() {
+            return Err(Fault::new(43, "use either apiKey or u, not both"));
+        }
+        return auth::resolve(&app.db, key).await?.ok_or_else(|| Fault::new(44, "invalid API key"));
+    }
+
+    let user = p.req("u")?;
+    let pass;
+    let cred = match (p.get("t"), p.get("s"), p.get("p")) {
+        (Some(token), Some(salt), _) => SubsonicCredential::Token { token, salt },
+        (_, _, Some(raw)) => {
+            pass = match raw.strip_prefix("enc:") {
+                Some(h) => hex::decode(h).ok().and_then(|b| String::from_utf8(b).ok()).unwrap_or_default(),
+                None => raw.to_owned(),
+            };
+            SubsonicCredential::Password(&pass)
+        }
+        _ => return Err(Fault::missing("t and s, or p")),
+    };
+    let who = auth::subsonic_principal(&app.db, user, cred).await.map_err(ApiError::from)?;
+    who.ok_or_else(|| {
+        warn!(user, "failed Subsonic login");
+        Fault::new(40, "wrong username or password")
+    })
+}
+
+fn get_user(who: &Principal, p: &Params) -> Outcome {
+    if p.req("username")? != who.username && !who.has(Scope::Admin) {
+        return Err(Fault::new(50, "only your own user"));
+    }
+    let stream = who.has(Scope::Stream);
+    data(json!({ "user": {
+        "username":          who.username,
+        "scrobblingEnabled": true,
+        "adminRole":         who.has(Scope::Admin),
+        "settingsRole":      false,
+        "downloadRole":      stream,
+        "uploadRole":        who.has(Scope::Upload),
+        "playlistRole":      true,
+        "coverArtRole":      false,
+        "commentRole":       false,
+        "podcastRole":       false,
+        "streamRole":        stream,
+        "jukeboxRole":       false,
+        "shareRole":         false,
+        "folder":            [1],
+    }}))
+}
+
+/*──────── browsing ───────────────────────────────────────────────────────*/
+
+#[derive(FromRow)]
+struct ArtistRow {
+    id:     String,
+    name:   String,
+    albums: i64,
+}
+
+#[derive(FromRow)]
+struct AlbumRow {
+    id:          Uuid,
+    title:       Option<String>,
+    artist:      String,
+    artist_id:   String,
+    year:        Option<i32>,
+    imported_at: Option<OffsetDateTime>,
+    songs:       i64,
+    duration:    i64,
+}
+
+#[derive(FromRow)]
+struct SongRow {
+    id:          Uuid,
+    album_id:    Uuid,
+    title:       Option<String>,
+    album:       Option<String>,
+    artist:      String,
+    artist_id:   String,
+    year:        Option<i32>,
+    disc:        Option<i32>,
+    index:       Option<i32>,
+    duration:    Option<i32>,
+    codec:       String,
+    imported_at: Option<OffsetDateTime>,
+}
+
+fn artist_expr(col: &str) -> String {
+    format!("COALESCE({col}, '{UNKNOWN_ARTIST}')")
+}
+
+fn artist_id_expr(col: &str) -> String {
+    format!("'ar-' || md5(lower({}))", artist_expr(col))
+}
+
+/// Playable = has a file that isn't in ERROR.
+const PLAYABLE: &str = "EXISTS (SELECT 1 FROM files f WHERE f.track_id = t.id AND f.status <> 'ERROR')";
+
+fn artist_select() -> String {
+    format!(
+        "SELECT {id} AS id, min({name}) AS name, COUNT(*) AS albums
+           FROM albums a
+          WHERE EXISTS (SELECT 1 FROM tracks t WHERE t.album_id = a.id AND {PLAYABLE})",
+        id = artist_id_expr("a.artist"), name = artist_expr("a.artist"),
+    )
+}
+
+fn album_select() -> String {
+    format!(
+        "SELECT a.id, a.title, {name} AS artist, {id} AS artist_id, a.year, a.imported_at,
+                s.songs, s.duration
+           FROM albums a
+           JOIN LATERAL (
+                SELECT COUNT(*) AS songs, COALESCE(SUM(t.duration_sec), 0)::bigint AS duration
+                  FROM tracks t WHERE t.album_id = a.id AND {PLAYABLE}
+           ) s ON s.songs > 0",
+        name = artist_expr("a.artist"), id = artist_id_expr("a.artist"),
+    )
+}
+
+fn song_select() -> String {
+    format!(
+        "SELECT t.id, t.album_id, t.title, a.title AS album, {name} AS artist, {id} AS artist_id,
+                a.year, t.disc, t.\"index\", t.duration_sec AS duration, f.codec, a.imported_at
+           FROM tracks t
+           JOIN albums a ON a.id = t.album_id
+           JOIN LATERAL (
+                SELECT codec FROM files
+                 WHERE track_id = t.id AND status <> 'ERROR'
+              ORDER BY {order}
+                 LIMIT 1
+           ) f ON true",
+        name = artist_expr("a.artist"), id = artist_id_expr("a.artist"), order = stream::best_file_order(),
+    )
+}
+
+const SONG_ORDER: &str = "t.disc, t.\"index\", t.id";
+
+async fn scan_status(app: &AppState) -> Outcome {
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM tracks").fetch_one(&app.db).await?;
+    data(json!({ "scanStatus": { "scanning": false, "count": count } }))
+}
+
+/// `getIndexes` / `getArtists` – artists filed under A–Z / `#`.
+async fn artist_index(app: &AppState, key: &str) -> Outcome {
+    let rows: Vec<ArtistRow> = sqlx::query_as(&format!("{} GROUP BY 1", artist_select()))
+        .fetch_all(&app.db)
+        .await?;
+    let mut rows: Vec<(String, ArtistRow)> = rows.into_iter().map(|r| (sort_name(&r.name), r)).collect();
+    rows.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut index: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+    for (sort, r) in rows {
+        let letter = sort.chars().next().filter(|c| c.is_alphabetic()).map_or("#".into(), |c| c.to_string());
+        index.entry(letter).or_default().push(artist_json(&r));
+    }
+    let index: Vec<Value> = index.into_iter().map(|(name, artist)| json!({ "name": name, "artist": artist })).collect();
+
+    let mut body = json!({ "ignoredArticles": IGNORED_ARTICLES, "index": index });
+    if key == "indexes" {
+        body["lastModified"] = json!(OffsetDateTime::now_utc().unix_timestamp() * 1000);
+    }
+    data(json!({ key: body }))
+}
+
+/// `getMusicDirectory` – artist (`ar-…`) → albums, album → songs.
+async fn music_directory(app: &AppState, p: &Params) -> Outcome {
+    let id = p.req("id")?;
+    if id.starts_with("ar-") {
+        let (artist, albums) = artist_albums(app, id).await?;
+        let children: Vec<Value> = albums.iter().map(album_json).collect();
+        return data(json!({ "directory": { "id": artist.id, "name": artist.name, "child": children } }));
+    }
+    let album = one_album(app, parse_id(id)?).await?;
+    let songs = album_songs(app, album.id).await?;
+    data(json!({ "directory": {
+        "id":     album.id,
+        "parent": album.artist_id,
+        "name":   album_name(&album.title),
+        "child":  songs.iter().map(song_json).collect::<Vec<_>>(),
+    }}))
+}
+
+async fn get_artist(app: &AppState, p: &Params) -> Outcome {
+    let (artist, albums) = artist_albums(app, p.req("id")?).await?;
+    let mut body = artist_json(&artist);
+    body["album"] = albums.iter().map(album_json).collect();
+    data(json!({ "artist": body }))
+}
+
+async fn get_album(app: &AppState, p: &Params) -> Outcome {
+    let album = one_album(app, p.uuid("id")?).await?;
+    let songs = album_songs(app, album.id).await?;
+    let mut body = album_json(&album);
+    body["song"] = songs.iter().map(song_json).collect();
+    data(json!({ "album": body }))
+}
+
+async fn get_song(app: &AppState, p: &Params) -> Outcome {
+    let id = p.uuid("id")?;
+    let song: Option<SongRow> = sqlx::query_as(&format!("{} WHERE t.id = $1", song_select()))
+        .bind(id)
+        .fetch_optional(&app.db)
+        .await?;
+    let song = song.ok_or_else(|| Fault::not_found("song", id))?;
+    data(json!({ "song": song_json(&song) }))
+}
+
+/// `getAlbumList` / `getAlbumList2`
+async fn album_list(app: &AppState, who: &Principal, p: &Params, key: &str) -> Outcome {
+    let size = p.num("size", 10i64)?.clamp(1, MAX_LIST);
+    let offset = p.num("offset", 0i64)?.max(0);
+    let kind = p.req("type")?;
+
+    let mut qb = QueryBuilder::<Postgres>::new(album_select());
+    match kind {
+        "frequent" | "recent" => {
+            qb.push(
+                " JOIN LATERAL (
+                    SELECT COUNT(*) AS n, max(pl.played_at) AS last
+                      FROM plays pl JOIN tracks t ON t.id = pl.track_id
+                     WHERE t.album_id = a.id AND pl.user_id = ",
+            )
+            .push_bind(who.user_id)
+            .push(") pl ON pl.n > 0");
+            qb.push(if kind == "frequent" { " ORDER BY pl.n DESC, a.id" } else { " ORDER BY pl.last DESC, a.id" });
+        }
+        "random"               => { qb.push(" ORDER BY random()"); }
+        "newest"               => { qb.push(" ORDER BY a.imported_at DESC NULLS LAST, a.id"); }
+        "alphabeticalByName"   => { qb.push(" ORDER BY lower(a.title), a.id"); }
+        "alphabeticalByArtist" => {
+            qb.push(format!(" ORDER BY lower({}), a.year, lower(a.title), a.id", artist_expr("a.artist")));
+        }
+        "byYear" => {
+            let (from, to) = (p.num("fromYear", 0i32)?, p.num("toYear", 9999i32)?);
+            qb.push(" WHERE a.year BETWEEN ").push_bind(from.min(to)).push(" AND ").push_bind(from.max(to));
+            qb.push(if from <= to { " ORDER BY a.year, a.id" } else { " ORDER BY a.year DESC, a.id" });
+        }
+        "starred" | "highest" | "byGenre" => return data(json!({ key: {} })),
+        other => return Err(Fault::new(0, format!("unknown list type {other:?}"))),
+    }
+    qb.push(" LIMIT ").push_bind(size).push(" OFFSET ").push_bind(offset);
+
+    let albums: Vec<AlbumRow> = qb.build_query_as().fetch_all(&app.db).await?;
+    data(json!({ key: { "album": albums.iter().map(album_json).collect::<Vec<_>>() } }))
+}
+
+async fn random_songs(app: &AppState, p: &Params) -> Outcome {
+    let size = p.num("size", 10i64)?.clamp(1, MAX_LIST);
+    let mut qb = QueryBuilder::<Postgres>::new(song_select());
+    qb.push(" WHERE true");
+    if let Some(y) = p.opt::<i32>("fromYear")? { qb.push(" AND a.year >= ").push_bind(y); }
+    if let Some(y) = p.opt::<i32>("toYear")?   { qb.push(" AND a.year <= ").push_bind(y); }
+    qb.push(" ORDER BY random() LIMIT ").push_bind(size);
+    let songs: Vec<SongRow> = qb.build_query_as().fetch_all(&app.db).await?;
+    data(json!({ "randomSongs": { "song": songs.iter().map(song_json).collect::<Vec<_>>() } }))
+}
+
+/// `search2` / `search3` – every word as a prefix; an empty query lists
+/// everything (clients sync the library that way).
+async fn search(app: &AppState, p: &Params, key: &str) -> Outcome {
+    let raw = p.get("query").unwrap_or_default().trim().trim_matches('"');
+    let words: Vec<String> = raw
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| format!("{}:*", w.to_lowercase()))
+        .collect();
+    let tsq = (!words.is_empty()).then(|| words.join(" & "));
+    let fuzzy = (raw.chars().count() >= 4).then(|| raw.to_lowercase());
+    let page = |count: &str, offset: &str| -> Result<(i64, i64), Fault> {
+        Ok((p.num(count, 20i64)?.clamp(0, MAX_LIST), p.num(offset, 0i64)?.max(0)))
+    };
+
+    // tsvector and trigram columns per group
+    let filter = |qb: &mut QueryBuilder<'_, Postgres>, vector: &str, trgm: &[&str]| {
+        qb.push(" AND (false");
+        if let Some(q) = &tsq {
+            qb.push(format!(" OR {vector} @@ to_tsquery('simple', ")).push_bind(q.clone()).push(")");
+        }
+        for col in trgm.iter().filter(|_| fuzzy.is_some()) {
+            qb.push(" OR ").push_bind(fuzzy.clone()).push(format!(" <% lower({col})"));
+        }
+        if tsq.is_none() && fuzzy.is_none() {
+            qb.push(" OR true");
+        }
+        qb.push(")");
+    };
+
+    let (n, skip) = page("artistCount", "artistOffset")?;
+    let mut qb = QueryBuilder::<Postgres>::new(artist_select());
+    filter(&mut qb, "to_tsvector('simple', COALESCE(a.artist, ''))", &["a.artist"]);
+    qb.push(" GROUP BY 1 ORDER BY lower(min(a.artist)) LIMIT ").push_bind(n).push(" OFFSET ").push_bind(skip);
+    let artists: Vec<ArtistRow> = qb.build_query_as().fetch_all(&app.db).await?;
+
+    let (n, skip) = page("albumCount", "albumOffset")?;
+    let mut qb = QueryBuilder::<Postgres>::new(album_select());
+    qb.push(" WHERE true");
+    filter(&mut qb, "a.search", &["a.artist", "a.title"]);
+    qb.push(" ORDER BY lower(a.title), a.id LIMIT ").push_bind(n).push(" OFFSET ").push_bind(skip);
+    let albums: Vec<AlbumRow> = qb.build_query_as().fetch_all(&app.db).await?;
+
+    let (n, skip) = page("songCount", "songOffset")?;
+    let mut qb = QueryBuilder::<Postgres>::new(song_select());
+    qb.push(" WHERE true");
+    filter(&mut qb, "t.search", &["t.title"]);
+    qb.push(" ORDER BY a.id, t.disc, t.\"index\", t.id LIMIT ").push_bind(n).push(" OFFSET ").push_bind(skip);
+    let songs: Vec<SongRow> = qb.build_query_as().fetch_all(&app.db).await?;
+
+    data(json!({ key: {
+        "artist": artists.iter().map(artist_json).collect::<Vec<_>>(),
+        "album":  albums.iter().map(album_json).collect::<Vec<_>>(),
+        "song":   songs.iter().map(song_json).collect::<Vec<_>>(),
+    }}))
+}
+
+async fn artist_albums(app: &AppState, id: &str) -> Result<(ArtistRow, Vec<AlbumRow>), Fault> {
+    let artist: Option<ArtistRow> =
+        sqlx::query_as(&format!("{} AND {} = $1 GROUP BY 1", artist_select(), artist_id_expr("a.artist")))
+            .bind(id)
+            .fetch_optional(&app.db)
+            .await?;
+    let artist = artist.ok_or_else(|| Fault::not_found("artist", id))?;
+    let albums = sqlx::query_as(&format!(
+        "{} WHERE {} = $1 ORDER BY a.year, lower(a.title), a.id",
+        album_select(), artist_id_expr("a.artist"),
+    ))
+    .bind(id)
+    .fetch_all(&app.db)
+    .await?;
+    Ok((artist, albums))
+}
+
+async fn one_album(app: &AppState, id: Uuid) -> Result<AlbumRow, Fault> {
+    let album: Option<AlbumRow> = sqlx::query_as(&format!("{} WHERE a.id = $1", album_select()))
+        .bind(id)
+        .fetch_optional(&app.db)
+        .await?;
+    album.ok_or_else(|| Fault::not_found("album", id))
+}
+
+async fn album_songs(app: &AppState, album: Uuid) -> Result<Vec<SongRow>, Fault> {
+    Ok(sqlx::query_as(&format!("{} WHERE t.album_id = $1 ORDER BY {SONG_ORDER}", song_select()))
+        .bind(album)
+        .fetch_all(&app.db)
+        .await?)
+}
+
+/*──────── media ──────────────────────────────────────────────────────────*/
+
+/// `stream` / `download` – `format` (`raw`, `mp3`, `opus`, `aac`) and
+/// `maxBitRate` (kbit/s, 0 = unlimited) map onto the transcoder.
+async fn play(app: &AppState, who: &Principal, p: &Params, headers: &HeaderMap, download: bool) -> Outcome {
+    if !who.has(Scope::Stream) {
+        return Err(Fault::new(50, "token lacks the stream scope"));
+    }
+    let id = p.uuid("id")?;
+    let (file_id, path, codec) = stream::best_file(&app.db, id)
+        .await?
+        .ok_or_else(|| Fault::not_found("song", id))?;
+
+    let format = match p.get("format").filter(|_| !download) {
+        None                   => None,
+        Some("raw")            => Some(Format::Original),
+        Some("mp3")            => Some(Format::Mp3),
+        Some("opus" | "ogg")   => Some(Format::Opus),
+        Some("aac" | "m4a")    => Some(Format::Aac),
+        Some(other) => return Err(Fault::new(0, format!("unsupported format {other:?}"))),
+    };
+    let max = if download { 0 } else { p.num("maxBitRate", 0u32)? };
+    let kbps = |m: u32| Some(format!("{}k", m.clamp(32, 320)));
+    let q = match (format, max) {
+        (None | Some(Format::Original), 0) | (Some(Format::Original), _) => StreamQuery::default(),
+        (None, m) if m >= 320 && LOSSY.contains(&codec.as_str()) => StreamQuery::default(),
+        (None, m)    => StreamQuery { format: Some(Format::Mp3), bitrate: kbps(m) },
+        (Some(f), 0) => StreamQuery { format: Some(f), bitrate: None },
+        (Some(f), m) => StreamQuery { format: Some(f), bitrate: kbps(m) },
+    };
+    Ok(Reply::Raw(stream::dispatch(app, file_id, &path, &codec, &q, headers).await?))
+}
+
+/// `getCoverArt` – `id` is an album (or song) id.
+async fn cover_art(app: &AppState, p: &Params, headers: &HeaderMap) -> Outcome {
+    let id = p.uuid("id")?;
+    let album: Option<(Uuid,)> = sqlx::query_as(
+        "SELECT id FROM albums WHERE id=$1 UNION ALL SELECT album_id FROM tracks WHERE id=$1 LIMIT 1",
+    )
+    .bind(id)
+    .fetch_optional(&app.db)
+    .await?;
+    let (album,) = album.ok_or_else(|| Fault::not_found("cover art", id))?;
+    let size = p.opt::<u32>("size")?;
+    Ok(Reply::Raw(images::album_cover(app, album, size, headers).await?))
+}
+
+/*──────── playlists & scrobbles ──────────────────────────────────────────*/
+
+async fn get_playlists(app: &AppState, who: &Principal) -> Outcome {
+    let lists = playlists::visible(&app.db, who).await?;
+    data(json!({ "playlists": { "playlist": lists.iter().map(playlist_json).collect::<Vec<_>>() } }))
+}
+
+async fn get_playlist(app: &AppState, who: &Principal, p: &Params) -> Outcome {
+    let list = playlists::get(&app.db, p.uuid("id")?, who).await?;
+    let ids = playlists::track_ids(&app.db, list.id).await?;
+    let songs: Vec<SongRow> = sqlx::query_as(&format!("{} WHERE t.id = ANY($1)", song_select()))
+        .bind(&ids)
+        .fetch_all(&app.db)
+        .await?;
+    // duplicates allowed: map back onto the playlist order
+    let by_id: BTreeMap<Uuid, Value> = songs.iter().map(|s| (s.id, song_json(s))).collect();
+    let mut body = playlist_json(&list);
+    body["entry"] = ids.iter().filter_map(|id| by_id.get(id).cloned()).collect();
+    data(json!({ "playlist": body }))
+}
+
+/// `createPlaylist` – new (`name`) or replace the songs of `playlistId`.
+async fn create_playlist(app: &AppState, who: &Principal, p: &Params) -> Outcome {
+    let songs = p.uuids("songId")?;
+    let mut tx = app.db.begin().await.map_err(ApiError::from)?;
+    let id = match p.get("playlistId") {
+        Some(raw) => {
+            let id = parse_id(raw)?;
+            playlists::lock_owned(&mut tx, id, who).await?;
+            playlists::set_entries(&mut tx, id, &songs).await?;
+            id
+        }
+        None => {
+            let name = clean(p.req("name")?)?;
+            let id = playlists::create(&mut tx, who, &name, None, false).await?;
+            playlists::set_entries(&mut tx, id, &songs).await?;
+            id
+        }
+    };
+    tx.commit().await.map_err(ApiError::from)?;
+    let mut q = Params::default();
+    q.0.push(("id".into(), id.to_string()));
+    get_playlist(app, who, &q).await
+}
+
+async fn update_playlist(app: &AppState, who: &Principal, p: &Params) -> Outcome {
+    let id = p.uuid("playlistId")?;
+    let add = p.uuids("songIdToAdd")?;
+    let remove = p
+        .all("songIndexToRemove")
+        .map(|v| v.parse::<usize>().map_err(|_| Fault::new(0, format!("invalid songIndexToRemove {v:?}"))))
+        .collect::<Result<Vec<_>, _>>()?;
+    let name = p.get("name").map(clean).transpose()?;
+    let public = p.opt::<bool>("public")?;
+
+    let mut tx = app.db.begin().await.map_err(ApiError::from)?;
+    playlists::lock_owned(&mut tx, id, who).await?;
+    sqlx::query(
+        "UPDATE playlists SET name    = COALESCE($2, name),
+                              comment = CASE WHEN $3 THEN NULLIF(trim($4), '') ELSE comment END,
+                              public  = COALESCE($5, public)
+          WHERE id=$1",
+    )
+    .bind(id)
+    .bind(name)
+    .bind(p.get("comment").is_some())
+    .bind(p.get("comment"))
+    .bind(public)
+    .execute(&mut *tx)
+    .await
+    .map_err(ApiError::from)?;
+    playlists::edit_entries(&mut tx, id, &remove, &add).await?;
+    tx.commit().await.map_err(ApiError::from)?;
+    data(json!({}))
+}
+
+async fn delete_playlist(app: &AppState, who: &Principal, p: &Params) -> Outcome {
+    let id = p.uuid("id")?;
+    let mut tx = app.db.begin().await.map_err(ApiError::from)?;
+    playlists::lock_owned(&mut tx, id, who).await?;
+    sqlx::query("DELETE FROM playlists WHERE id=$1").bind(id).execute(&mut *tx).await.map_err(ApiError::from)?;
+    tx.commit().await.map_err(ApiError::from)?;
+    data(json!({}))
+}
+
+/// `scrobble` – `submission=false` is "now playing" and isn't kept.
+async fn scrobble(app: &AppState, who: &Principal, p: &Params) -> Outcome {
+    if p.opt::<bool>("submission")? == Some(false) {
+        return data(json!({}));
+    }
+    let ids = p.uuids("id")?;
+    if ids.is_empty() {
+        return Err(Fault::missing("id"));
+    }
+    let times: Vec<OffsetDateTime> = p
+        .all("time")
+        .map(|t| {
+            t.parse::<i128>()
+                .ok()
+                .and_then(|ms| OffsetDateTime::from_unix_timestamp_nanos(ms * 1_000_000).ok())
+                .ok_or_else(|| Fault::new(0, format!("invalid time {t:?}")))
+        })
+        .collect::<Result<_, _>>()?;
+    let now = OffsetDateTime::now_utc();
+    let times: Vec<OffsetDateTime> = (0..ids.len()).map(|i| times.get(i).copied().unwrap_or(now)).collect();
+
+    let res = sqlx::query(
+        "INSERT INTO plays(user_id, track_id, played_at, client)
+         SELECT $1, x.track_id, x.played_at, $4
+           FROM unnest($2::uuid[], $3::timestamptz[]) AS x(track_id, played_at)
+           JOIN tracks t ON t.id = x.track_id",
+    )
+    .bind(who.user_id)
+    .bind(&ids)
+    .bind(&times)
+    .bind(p.get("c"))
+    .execute(&app.db)
+    .await?;
+    if res.rows_affected() < ids.len() as u64 {
+        return Err(Fault::not_found("song", "in scrobble"));
+    }
+    data(json!({}))
+}
+
+/*──────── JSON shapes ────────────────────────────────────────────────────*/
+
+fn artist_json(a: &ArtistRow) -> Value {
+    json!({ "id": a.id, "name": a.name, "albumCount": a.albums, "coverArt": Value::Null })
+}
+
+fn album_json(a: &AlbumRow) -> Value {
+    let name = album_name(&a.title);
+    json!({
+        "id":        a.id,
+        "parent":    a.artist_id,
+        "isDir":     true,
+        "name":      name,
+        "title":     name,
+        "album":     name,
+        "artist":    a.artist,
+        "artistId":  a.artist_id,
+        "year":      a.year,
+        "coverArt":  a.id,
+        "songCount": a.songs,
+        "duration":  a.duration,
+        "created":   rfc3339(a.imported_at),
+    })
+}
+
+fn song_json(s: &SongRow) -> Value {
+    json!({
+        "id":          s.id,
+        "parent":      s.album_id,
+        "isDir":       false,
+        "title":       s.title.as_deref().unwrap_or("Untitled"),
+        "album":       album_name(&s.album),
+        "artist":      s.artist,
+        "track":       s.index,
+        "discNumber":  s.disc,
+        "year":        s.year,
+        "coverArt":    s.album_id,
+        "duration":    s.duration,
+        "suffix":      s.codec,
+        "contentType": stream::content_type(&s.codec),
+        "albumId":     s.album_id,
+        "artistId":    s.artist_id,
+        "type":        "music",
+        "isVideo":     false,
+        "created":     rfc3339(s.imported_at),
+    })
+}
+
+fn playlist_json(l: &playlists::Playlist) -> Value {
+    json!({
+        "id":        l.id,
+        "name":      l.name,
+        "comment":   l.comment,
+        "owner":     l.owner,
+        "public":    l.public,
+        "songCount": l.entry_count,
+        "duration":  l.duration_sec,
+        "created":   rfc3339(Some(l.created_at)),
+        "changed":   rfc3339(Some(l.updated_at)),
+    })
+}
+
+fn album_name(title: &Option<String>) -> &str {
+    title.as_deref().unwrap_or("Untitled")
+}
+
+fn rfc3339(t: Option<OffsetDateTime>) -> Option<String> {
+    t.and_then(|t| t.format(&Rfc3339).ok())
+}
+
+/// Case-folded, leading article dropped: "The Band" files under B.
+fn sort_name(name: &str) -> String {
+    let lower = name.to_lowercase();
+    IGNORED_ARTICLES
+        .split(' ')
+        .find_map(|a| lower.strip_prefix(&format!("{} ", a.to_lowercase())))
+        .unwrap_or(&lower)
+        .trim()
+        .to_uppercase()
+}
+
+/*──────── params ─────────────────────────────────────────────────────────*/
+
+/// Query (and form) pairs in order – `id`, `songId` … may repeat.
+#[derive(Default)]
+struct Params(Vec<(String, String)>);
+
+impl Params {
+    fn parse(raw: &[u8]) -> Self {
+        Params(form_urlencoded::parse(raw).into_owned().collect())
+    }
+
+    fn get(&self, k: &str) -> Option<&str> {
+        self.0.iter().find(|(n, _)| n == k).map(|(_, v)| v.as_str())
+    }
+
+    fn all<'a>(&'a self, k: &'a str) -> impl Iterator<Item = &'a str> {
+        self.0.iter().filter(move |(n, _)| n == k).map(|(_, v)| v.as_str())
+    }
+
+    fn req(&self, k: &str) -> Result<&str, Fault> {
+        self.get(k).ok_or_else(|| Fault::missing(k))
+    }
+
+    fn opt<T: FromStr>(&self, k: &str) -> Result<Option<T>, Fault> {
+        self.get(k)
+            .map(|v| v.parse().map_err(|_| Fault::new(0, format!("invalid {k} {v:?}"))))
+            .transpose()
+    }
+
+    fn num<T: FromStr>(&self, k: &str, default: T) -> Result<T, Fault> {
+        Ok(self.opt(k)?.unwrap_or(default))
+    }
+
+    fn uuid(&self, k: &str) -> Result<Uuid, Fault> {
+        parse_id(self.req(k)?)
+    }
+
+    fn uuids(&self, k: &str) -> Result<Vec<Uuid>, Fault> {
+        self.all(k).map(parse_id).collect()
+    }
+}
+
+/// Malformed ids can't exist – report them as not found, like Subsonic.
+fn parse_id(raw: &str) -> Result<Uuid, Fault> {
+    raw.parse().map_err(|_| Fault::not_found("item", raw))
+}
+
+fn clean(v: &str) -> Result<String, Fault> {
+    crate::albums::clean_text("name", Some(v.to_owned()))?.ok_or_else(|| Fault::missing("name"))
+}
+
+/*──────── errors ─────────────────────────────────────────────────────────*/
+
+/// Subsonic error: 0 generic, 10 missing parameter, 40 bad credentials,
+/// 43/44 apiKey problems, 50 not allowed, 70 not found.
+#[derive(Debug)]
+struct Fault {
+    code:    u16,
+    message: String,
+}
+
+impl Fault {
+    fn new(code: u16, message: impl Into<String>) -> Self {
+        Fault { code, message: message.into() }
+    }
+
+    fn missing(what: &str) -> Self {
+        Fault::new(10, format!("required parameter missing: {what}"))
+    }
+
+    fn not_found(what: &str, id: impl std::fmt::Display) -> Self {
+        Fault::new(70, format!("{what} {id} not found"))
+    }
+}
+
+impl From<ApiError> for Fault {
+    fn from(e: ApiError) -> Self {
+        let code = match &e {
+            ApiError::NotFound(_)     => 70,
+            ApiError::Unauthorized(_) => 40,
+            ApiError::Forbidden(_)    => 50,
+            _                         => 0,
+        };
+        let message = match e {
+            ApiError::Internal(e) => {
+                error!("internal error: {e:#}");
+                "internal error".to_owned()
+            }
+            ApiError::BadRequest(m)
+            | ApiError::Unauthorized(m)
+            | ApiError::Forbidden(m)
+            | ApiError::NotFound(m)
+            | ApiError::Conflict(m)
+            | ApiError::PreconditionFailed(m)
+            | ApiError::ConfirmationRequired(m)
+            | ApiError::PayloadTooLarge(m)
+            | ApiError::UnsupportedMediaType(m)
+            | ApiError::Unprocessable(m)
+            | ApiError::Locked(m)
+            | ApiError::Unavailable(m) => m,
+            ApiError::ManifestMismatch(_) => "files on disk don't match the manifest".to_owned(),
+            ApiError::RateLimited(secs) => format!("too many requests – retry in {secs} s"),
+        };
+        Fault { code, message }
+    }
+}
+
+impl From<sqlx::Error> for Fault {
+    fn from(e: sqlx::Error) -> Self {
+        ApiError::from(e).into()
+    }
+}
+
+/*──────── rendering ──────────────────────────────────────────────────────*/
+
+fn envelope(json: bool, outcome: Result<Value, Fault>) -> Response {
+    let mut body = Map::new();
+    body.insert("status".into(), json!(if outcome.is_ok() { "ok" } else { "failed" }));
+    body.insert("version".into(), json!(API_VERSION));
+    body.insert("type".into(), json!("setlist"));
+    body.insert("serverVersion".into(), json!(env!("CARGO_PKG_VERSION")));
+    body.insert("openSubsonic".into(), json!(true));
+    match outcome {
+        Ok(Value::Object(payload)) => body.extend(payload),
+        Ok(_) => {}
+        Err(f) => {
+            body.insert("error".into(), json!({ "code": f.code, "message": f.message }));
+        }
+    }
+    let body = strip_nulls(Value::Object(body));
+
+    if json {
+        return axum::Json(json!({ "subsonic-response": body })).into_response();
+    }
+    let mut out = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let mut root = body;
+    root["xmlns"] = json!("http://subsonic.org/restapi");
+    write_xml(&mut out, "subsonic-response", &root);
+    let mut res = out.into_response();
+    res.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("text/xml; charset=utf-8"));
+    res
+}
+
+fn strip_nulls(v: Value) -> Value {
+    match v {
+        Value::Object(m) => Value::Object(
+            m.into_iter().filter(|(_, v)| !v.is_null()).map(|(k, v)| (k, strip_nulls(v))).collect(),
+        ),
+        Value::Array(a) => Value::Array(a.into_iter().map(strip_nulls).collect()),
+        v => v,
+    }
+}
+
+/// Subsonic's JSON ↔ XML convention: scalars are attributes, objects are
+/// child elements, arrays repeat the element.
+fn write_xml(out: &mut String, name: &str, v: &Value) {
+    match v {
+        Value::Object(m) => {
+            out.push('<');
+            out.push_str(name);
+            for (k, v) in m {
+                let text = match v {
+                    Value::String(s) => s.clone(),
+                    Value::Number(n) => n.to_string(),
+                    Value::Bool(b) => b.to_string(),
+                    _ => continue,
+                };
+                out.push_str(&format!(" {k}=\"{}\"", escape(&text)));
+            }
+            let children: Vec<_> = m.iter().filter(|(_, v)| v.is_object() || v.is_array()).collect();
+            if children.is_empty() {
+                out.push_str("/>");
+                return;
+            }
+            out.push('>');
+            for (k, v) in children {
+                write_xml(out, k, v);
+            }
+            out.push_str(&format!("</{name}>"));
+        }
+        Value::Array(items) => items.iter().for_each(|i| write_xml(out, name, i)),
+        Value::Null => {}
+        scalar => {
+            let text = match scalar {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            out.push_str(&format!("<{name}>{}</{name}>", escape(&text)));
+        }
+    }
+}
+
+/// XML text / attribute escaping (the XSPF export uses it too).
+pub(crate) fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&'  => out.push_str("&amp;"),
+            '<'  => out.push_str("&lt;"),
+            '>'  => out.push_str("&gt;"),
+            '"'  => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c    => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,365 @@
+//! Tracks & files – read endpoints plus manual correction of what the Import
+//! heuristics guessed (disc detection, index-0 fallbacks, empty titles).
+//!
+//! Every edit queues TagTrack for the already-tagged files it touches, so
+//! the Tag stage re-writes them.
+
+use axum::extract::State;
+use serde::{Deserialize, Serialize};
+use shared::pipeline::Stage;
+use sqlx::{PgPool, Postgres, Transaction};
+use time::OffsetDateTime;
+use tracing::{info, instrument};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    albums::{clean_text, FileStatus},
+    audit,
+    auth::Principal,
+    error::{ApiError, ApiResult, Json, Path, Problem},
+    history, jobs, AppState,
+};
+
+/*──────── model ──────────────────────────────────────────────────────────*/
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct Track {
+    pub id:           Uuid,
+    pub album_id:     Option<Uuid>,
+    pub disc:         Option<i32>,
+    pub index:        Option<i32>,
+    pub title:        Option<String>,
+    pub duration_sec: Option<i32>,
+    #[sqlx(skip)]
+    pub files:        Vec<MediaFile>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct MediaFile {
+    pub id:          Uuid,
+    pub track_id:    Option<Uuid>,
+    pub path:        String,
+    pub codec:       String,
+    #[serde(rename = "file_status")]
+    pub status:      FileStatus,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub fp_done_at:  Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub tagged_at:   Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub inserted_at: Option<OffsetDateTime>,
+}
+
+const TRACK_COLUMNS: &str = r#"id, album_id, disc, "index", title, duration_sec"#;
+const FILE_COLUMNS:  &str = "f.id, f.track_id, f.path, f.codec, f.status, f.fp_done_at, f.tagged_at, f.inserted_at";
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct TrackPatch {
+    /// `""` clears the title.
+    pub title: Option<String>,
+    pub disc:  Option<i32>,
+    pub index: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DiscOrder {
+    /// Every track currently on the disc, in the desired order.
+    pub tracks: Vec<Uuid>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct FileAssignment {
+    pub track_id: Uuid,
+}
+
+/*──────── loaders ────────────────────────────────────────────────────────*/
+
+/// All tracks of an album ordered by disc/index, files attached.
+pub async fn load_album_tracks(db: &PgPool, album_id: Uuid) -> sqlx::Result<Vec<Track>> {
+    let mut tracks: Vec<Track> = sqlx::query_as(&format!(
+        r#"SELECT {TRACK_COLUMNS} FROM tracks WHERE album_id=$1 ORDER BY disc, "index""#
+    ))
+    .bind(album_id)
+    .fetch_all(db)
+    .await?;
+
+    let files: Vec<MediaFile> = sqlx::query_as(&format!(
+        "SELECT {FILE_COLUMNS}
+           FROM files f JOIN tracks t ON t.id = f.track_id
+          WHERE t.album_id=$1
+       ORDER BY f.path"
+    ))
+    .bind(album_id)
+    .fetch_all(db)
+    .await?;
+    attach(&mut tracks, files);
+    Ok(tracks)
+}
+
+async fn load_track(db: &PgPool, id: Uuid) -> ApiResult<Track> {
+    let mut track: Track = sqlx::query_as(&format!("SELECT {TRACK_COLUMNS} FROM tracks WHERE id=$1"))
+        .bind(id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| ApiError::not_found("track", id))?;
+    track.files = sqlx::query_as(&format!(
+        "SELECT {FILE_COLUMNS} FROM files f WHERE f.track_id=$1 ORDER BY f.path"
+    ))
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+    Ok(track)
+}
+
+fn attach(tracks: &mut [Track], files: Vec<MediaFile>) {
+    for f in files {
+        if let Some(t) = tracks.iter_mut().find(|t| Some(t.id) == f.track_id) {
+            t.files.push(f);
+        }
+    }
+}
+
+/*──────── read handlers ──────────────────────────────────────────────────*/
+
+/// `GET /albums/:id/tracks`
+#[utoipa::path(
+    get, path = "/albums/{id}/tracks", tag = "tracks",
+    params(("id" = Uuid, Path, description = "Album id")),
+    responses(
+        (status = 200, description = "Tracks with files", body = Vec<Track>),
+        (status = 404, response = Problem),
+    ),
+)]
+pub async fn list_album_tracks(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+) -> ApiResult<Json<Vec<Track>>> {
+    let exists: Option<(Uuid,)> = sqlx::query_as("SELECT id FROM albums WHERE id=$1")
+        .bind(id)
+        .fetch_optional(&app.db)
+        .await?;
+    if exists.is_none() {
+        return Err(ApiError::not_found("album", id));
+    }
+    Ok(Json(load_album_tracks(&app.db, id).await?))
+}
+
+/// `GET /tracks/:id`
+#[utoipa::path(
+    get, path = "/tracks/{id}", tag = "tracks",
+    params(("id" = Uuid, Path, description = "Track id")),
+    responses(
+        (status = 200, description = "Track with files", body = Track),
+        (status = 404, response = Problem),
+    ),
+)]
+pub async fn get_track(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+) -> ApiResult<Json<Track>> {
+    Ok(Json(load_track(&app.db, id).await?))
+}
+
+/*──────── corrections ────────────────────────────────────────────────────*/
+
+/// `PATCH /tracks/:id` – title / disc / index.  Moving onto an occupied
+/// (disc, index) slot is a `409`; reorder the disc instead.
+#[utoipa::path(
+    patch, path = "/tracks/{id}", tag = "tracks",
+    params(("id" = Uuid, Path, description = "Track id")), request_body = TrackPatch,
+    responses(
+        (status = 200, description = "Updated track", body = Track),
+        (status = 404, response = Problem),
+        (status = 409, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id))]
+pub async fn patch_track(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+    Json(p): Json<TrackPatch>,
+) -> ApiResult<Json<Track>> {
+    let title = p.title.map(|t| clean_text("title", Some(t))).transpose()?;
+    for (field, v) in [("disc", p.disc), ("index", p.index)] {
+        if v.is_some_and(|v| v < 1) {
+            return Err(ApiError::Unprocessable(format!("{field} must be ≥ 1")));
+        }
+    }
+
+    let mut tx = app.db.begin().await?;
+    history::attribute(&mut tx, &who.username, "api").await?;
+    let row: Option<(Option<Uuid>,)> = sqlx::query_as(
+        r#"UPDATE tracks
+              SET title   = CASE WHEN $2 THEN $3 ELSE title END,
+                  disc    = COALESCE($4, disc),
+                  "index" = COALESCE($5, "index")
+            WHERE id=$1
+        RETURNING album_id"#,
+    )
+    .bind(id)
+    .bind(title.is_some())
+    .bind(title.flatten())
+    .bind(p.disc)
+    .bind(p.index)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| match e.as_database_error().and_then(|d| d.code()) {
+        Some(c) if c == "23505" => ApiError::Conflict("disc/index already taken on this album".into()),
+        _ => e.into(),
+    })?;
+    let (album_id,) = row.ok_or_else(|| ApiError::not_found("track", id))?;
+    retag(&mut tx, album_id, Some(&[id])).await?;
+    tx.commit().await?;
+    info!("track edited");
+
+    Ok(Json(load_track(&app.db, id).await?))
+}
+
+/// `PUT /albums/:id/discs/:disc/order` – renumber a whole disc 1‥n in one
+/// transaction.  Indices go negative first so `UNIQUE(album_id, disc,
+/// "index")` never sees a transient duplicate.
+#[utoipa::path(
+    put, path = "/albums/{id}/discs/{disc}/order", tag = "tracks",
+    params(
+        ("id" = Uuid, Path, description = "Album id"),
+        ("disc" = i32, Path, description = "Disc number"),
+    ), request_body = DiscOrder,
+    responses(
+        (status = 200, description = "The disc in its new order", body = Vec<Track>),
+        (status = 404, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id, disc))]
+pub async fn reorder_disc(
+    Path((id, disc)): Path<(Uuid, i32)>,
+    State(app): State<AppState>,
+    who: Principal,
+    Json(order): Json<DiscOrder>,
+) -> ApiResult<Json<Vec<Track>>> {
+    let mut tx = app.db.begin().await?;
+    history::attribute(&mut tx, &who.username, "api").await?;
+    let current: Vec<(Uuid,)> = sqlx::query_as(
+        "SELECT id FROM tracks WHERE album_id=$1 AND disc=$2 FOR UPDATE",
+    )
+    .bind(id)
+    .bind(disc)
+    .fetch_all(&mut *tx)
+    .await?;
+    if current.is_empty() {
+        return Err(ApiError::NotFound(format!("album {id} has no disc {disc}")));
+    }
+
+    let mut want = order.tracks.clone();
+    let mut have: Vec<Uuid> = current.into_iter().map(|(t,)| t).collect();
+    want.sort();
+    have.sort();
+    if want != have {
+        return Err(ApiError::Unprocessable(format!(
+            "order must list each of the {} tracks on disc {disc} exactly once", have.len(),
+        )));
+    }
+
+    for sign in [-1, 1] {
+        sqlx::query(
+            r#"UPDATE tracks t
+                  SET "index" = $2 * o.pos
+                 FROM unnest($1::uuid[]) WITH ORDINALITY AS o(id, pos)
+                WHERE t.id = o.id"#,
+        )
+        .bind(&order.tracks)
+        .bind(sign)
+        .execute(&mut *tx)
+        .await?;
+    }
+    retag(&mut tx, Some(id), Some(&order.tracks)).await?;
+    audit::record(&mut *tx, &who.username, "track.reorder", ("album", Some(id)),
+                  serde_json::json!({ "disc": disc, "tracks": order.tracks }))
+        .await?;
+    tx.commit().await?;
+    info!(tracks = order.tracks.len(), "disc reordered");
+
+    let tracks = load_album_tracks(&app.db, id).await?;
+    Ok(Json(tracks.into_iter().filter(|t| t.disc == Some(disc)).collect()))
+}
+
+/// `PUT /files/:id/track` – move a file onto another track of the same
+/// album (e.g. two takes that Import split into separate tracks).
+#[utoipa::path(
+    put, path = "/files/{id}/track", tag = "tracks",
+    params(("id" = Uuid, Path, description = "File id")), request_body = FileAssignment,
+    responses(
+        (status = 200, description = "Target track with files", body = Track),
+        (status = 404, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id))]
+pub async fn assign_file(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+    Json(a): Json<FileAssignment>,
+) -> ApiResult<Json<Track>> {
+    let mut tx = app.db.begin().await?;
+    let row: Option<(Option<Uuid>, Option<Uuid>)> = sqlx::query_as(
+        "SELECT (SELECT t.album_id FROM files f JOIN tracks t ON t.id=f.track_id WHERE f.id=$1),
+                (SELECT album_id FROM tracks WHERE id=$2)",
+    )
+    .bind(id)
+    .bind(a.track_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let (from, to) = row.unwrap_or((None, None));
+    let from = from.ok_or_else(|| ApiError::not_found("file", id))?;
+    let to   = to.ok_or_else(|| ApiError::not_found("track", a.track_id))?;
+    if from != to {
+        return Err(ApiError::Unprocessable("file and track belong to different albums".into()));
+    }
+
+    let (previous,): (Option<Uuid>,) = sqlx::query_as(
+        "UPDATE files f SET track_id=$2 FROM files old WHERE f.id=$1 AND old.id=f.id RETURNING old.track_id",
+    )
+    .bind(id)
+    .bind(a.track_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    retag(&mut tx, Some(to), Some(&[a.track_id])).await?;
+    audit::record(&mut *tx, &who.username, "file.assign", ("file", Some(id)),
+                  serde_json::json!({ "from": previous, "to": a.track_id }))
+        .await?;
+    tx.commit().await?;
+    info!(track_id = %a.track_id, "file reassigned");
+
+    Ok(Json(load_track(&app.db, a.track_id).await?))
+}
+
+/// Queue TagTrack for the files of `tracks`, or of every track on the album
+/// if `None`.  Only files tagged before: the rest are still on their way to
+/// the Tag stage and pick the edit up there.  One already queued will do.
+pub(crate) async fn retag(tx: &mut Transaction<'_, Postgres>, album_id: Option<Uuid>, tracks: Option<&[Uuid]>)
+    -> ApiResult<()>
+{
+    let files: Vec<(Uuid, Option<Uuid>)> = sqlx::query_as(
+        "SELECT f.id, t.album_id FROM files f JOIN tracks t ON t.id = f.track_id
+          WHERE CASE WHEN $2::uuid[] IS NULL THEN t.album_id = $1 ELSE t.id = ANY($2) END
+            AND f.tagged_at IS NOT NULL
+            AND NOT EXISTS (SELECT 1 FROM jobs j
+                             WHERE j.stage = 'tag_track' AND j.status = 'queued'
+                               AND j.payload->>'file_id' = f.id::text)",
+    )
+    .bind(album_id)
+    .bind(tracks)
+    .fetch_all(&mut **tx)
+    .await?;
+    for (file_id, album_id) in files {
+        jobs::enqueue(&mut **tx, Stage::TagTrack, album_id, Some(file_id)).await?;
+    }
+    Ok(())
+}
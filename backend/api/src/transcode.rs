@@ -0,0 +1,312 @@
+//! On-the-fly transcoding for the stream endpoints (`?format=opus&bitrate=128k`).
+//!
+//! ffmpeg runs as a supervised child (concurrency-capped, time-boxed, killed
+//! on drop).  Its stdout is sent to the client *and* teed into a temp file
+//! that is renamed into the on-disk cache once ffmpeg exits cleanly.  The
+//! cache is an LRU by mtime, bounded in bytes; a hit is served from disk
+//! with full `Range` support.
+
+use std::{
+    path::{Path as FsPath, PathBuf},
+    process::Stdio,
+    sync::{atomic::{AtomicU64, Ordering}, Arc},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    body::{Body, Bytes},
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    process::Command,
+    sync::{mpsc, Semaphore},
+};
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{info, warn};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::error::{ApiError, ApiResult};
+
+/// Hard ceiling for one encode (a 4 h show at ~20× realtime fits easily).
+const MAX_ENCODE: Duration = Duration::from_secs(30 * 60);
+
+/*──────── request ────────────────────────────────────────────────────────*/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Original,
+    Opus,
+    Mp3,
+    Aac,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StreamQuery {
+    pub format:  Option<Format>,
+    /// `128k`‥`320k`; mp3 also takes `v0`‥`v9` (VBR quality).
+    pub bitrate: Option<String>,
+}
+
+/// A validated encode target.
+#[derive(Debug, Clone)]
+pub struct Target {
+    format:  Format,
+    bitrate: String,
+}
+
+impl StreamQuery {
+    /// `None` → serve the original file.
+    pub fn target(&self) -> ApiResult<Option<Target>> {
+        let format = match self.format {
+            None | Some(Format::Original) => return Ok(None),
+            Some(f) => f,
+        };
+        let bitrate = self.bitrate.clone().unwrap_or_else(|| match format {
+            Format::Mp3 => "v0".into(),
+            Format::Aac => "256k".into(),
+            _           => "128k".into(),
+        }).to_ascii_lowercase();
+
+        let vbr = bitrate.strip_prefix('v').and_then(|q| q.parse::<u8>().ok()).filter(|q| *q <= 9);
+        let cbr = bitrate.strip_suffix('k').and_then(|k| k.parse::<u32>().ok()).filter(|k| (32..=320).contains(k));
+        match (format, vbr, cbr) {
+            (Format::Mp3, Some(_), _) | (_, _, Some(_)) => Ok(Some(Target { format, bitrate })),
+            _ => Err(ApiError::BadRequest(format!("unsupported bitrate {bitrate:?} for {format:?}"))),
+        }
+    }
+}
+
+impl Target {
+    fn ext(&self) -> &'static str {
+        match self.format {
+            Format::Opus => "opus",
+            Format::Mp3  => "mp3",
+            Format::Aac  => "aac",
+            Format::Original => unreachable!("original is never transcoded"),
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self.format {
+            Format::Opus => "audio/ogg; codecs=opus",
+            Format::Mp3  => "audio/mpeg",
+            Format::Aac  => "audio/aac",
+            Format::Original => unreachable!("original is never transcoded"),
+        }
+    }
+
+    fn ffmpeg_args(&self) -> Vec<String> {
+        let mut a: Vec<String> = match self.format {
+            Format::Opus => vec!["-c:a".into(), "libopus".into(), "-f".into(), "ogg".into()],
+            Format::Mp3  => vec!["-c:a".into(), "libmp3lame".into(), "-f".into(), "mp3".into()],
+            Format::Aac  => vec!["-c:a".into(), "aac".into(), "-f".into(), "adts".into()],
+            Format::Original => unreachable!("original is never transcoded"),
+        };
+        match self.bitrate.strip_prefix('v') {
+            Some(q) => a.extend(["-q:a".into(), q.to_owned()]),
+            None    => a.extend(["-b:a".into(), self.bitrate.clone()]),
+        }
+        a
+    }
+}
+
+/*──────── cache + supervisor ─────────────────────────────────────────────*/
+
+pub struct Transcoder {
+    dir:       PathBuf,   // TRANSCODE_CACHE_DIR       (default /var/lib/setlist/transcode)
+    max_bytes: u64,       // TRANSCODE_CACHE_MAX_BYTES (default 10 GiB)
+    slots:     Arc<Semaphore>,   // TRANSCODE_MAX_JOBS (default 2)
+    hits:      AtomicU64,
+    misses:    AtomicU64,
+    evictions: AtomicU64,
+    bytes:     AtomicU64,
+}
+
+impl Transcoder {
+    pub fn from_env() -> Arc<Self> {
+        let var = |k: &str| std::env::var(k).ok();
+        let t = Arc::new(Self {
+            dir: var("TRANSCODE_CACHE_DIR").unwrap_or_else(|| "/var/lib/setlist/transcode".into()).into(),
+            max_bytes: var("TRANSCODE_CACHE_MAX_BYTES").and_then(|v| v.parse().ok()).unwrap_or(10 << 30),
+            slots: Arc::new(Semaphore::new(
+                var("TRANSCODE_MAX_JOBS").and_then(|v| v.parse().ok()).unwrap_or(2),
+            )),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+        });
+        let bg = t.clone();
+        tokio::task::spawn_blocking(move || bg.evict());   // prime the size gauge
+        t
+    }
+
+    /// `<dir>/<file_id>/<format>-<bitrate>-<source mtime>.<ext>` – a re-tagged
+    /// source gets a new key, the stale entry ages out.
+    fn key(&self, file_id: Uuid, src: &FsPath, t: &Target) -> PathBuf {
+        let version = std::fs::metadata(src)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.dir
+            .join(file_id.to_string())
+            .join(format!("{:?}-{}-{version:x}.{}", t.format, t.bitrate, t.ext()).to_ascii_lowercase())
+    }
+
+    /// Cached copy, if any (bumps its LRU position).
+    pub fn lookup(&self, file_id: Uuid, src: &FsPath, t: &Target) -> Option<PathBuf> {
+        let p = self.key(file_id, src, t);
+        match std::fs::File::options().write(true).open(&p) {
+            Ok(f) => {
+                let _ = f.set_modified(SystemTime::now());
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(p)
+            }
+            Err(_) => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Start ffmpeg and stream its output; the cache entry appears once the
+    /// encode finishes, even if the client has gone away by then.
+    pub async fn transcode(self: &Arc<Self>, file_id: Uuid, src: PathBuf, t: Target)
+        -> ApiResult<Response>
+    {
+        let permit = self.slots.clone().try_acquire_owned().map_err(|_| {
+            ApiError::Unavailable("all transcoder slots busy – retry shortly".to_owned())
+        })?;
+        let dest = self.key(file_id, &src, &t);
+        let parent = dest.parent().expect("key has a parent").to_owned();
+        tokio::fs::create_dir_all(&parent).await?;
+        let tmp = parent.join(format!(".{}.part", Uuid::new_v4()));
+
+        let mut child = Command::new("ffmpeg")
+            .args(["-nostdin", "-v", "error", "-i"])
+            .arg(&src)
+            .args(["-map", "0:a:0", "-vn"])
+            .args(t.ffmpeg_args())
+            .arg("pipe:1")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| ApiError::Internal(anyhow::anyhow!("spawn ffmpeg: {e}")))?;
+        let mut stdout = child.stdout.take().expect("piped");
+        let mut stderr = child.stderr.take().expect("piped");
+        let mut out = tokio::fs::File::create(&tmp).await?;
+        info!(%file_id, ?t, "transcode started");
+
+        let (tx, rx) = mpsc::channel::<std::io::Result<Bytes>>(16);
+        let me = self.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            let errlog = tokio::spawn(async move {
+                let mut s = String::new();
+                let _ = (&mut stderr).take(8 * 1024).read_to_string(&mut s).await;
+                s
+            });
+            let pump = async {
+                let mut client = Some(tx);
+                let mut buf = vec![0u8; 64 * 1024];
+                loop {
+                    let n = stdout.read(&mut buf).await?;
+                    if n == 0 { break; }
+                    out.write_all(&buf[..n]).await?;
+                    if let Some(c) = &client {
+                        if c.send(Ok(Bytes::copy_from_slice(&buf[..n]))).await.is_err() {
+                            client = None;   // listener left; finish for the cache
+                        }
+                    }
+                }
+                out.sync_all().await?;
+                child.wait().await
+            };
+            match tokio::time::timeout(MAX_ENCODE, pump).await {
+                Ok(Ok(status)) if status.success() => {
+                    if let Err(e) = tokio::fs::rename(&tmp, &dest).await {
+                        warn!("cache insert failed: {e}");
+                    }
+                    info!(%file_id, "transcode cached");
+                    let _ = tokio::task::spawn_blocking(move || me.evict()).await;
+                    return;
+                }
+                Ok(Ok(status)) => {
+                    let stderr = errlog.await.unwrap_or_default();
+                    warn!(%status, %stderr, "ffmpeg failed");
+                }
+                Ok(Err(e))     => warn!("transcode I/O error: {e}"),
+                Err(_)         => warn!("transcode exceeded {MAX_ENCODE:?} – killed"),
+            }
+            let _ = tokio::fs::remove_file(&tmp).await;
+        });
+
+        let mut res = (StatusCode::OK, Body::from_stream(ReceiverStream::new(rx))).into_response();
+        let h = res.headers_mut();
+        h.insert(header::CONTENT_TYPE, HeaderValue::from_static(t.content_type()));
+        // length unknown until the encode ends; seeking works once cached
+        h.insert(header::ACCEPT_RANGES, HeaderValue::from_static("none"));
+        h.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        Ok(res)
+    }
+
+    /// Blocking: drop every cached rendition of a file that no longer exists.
+    pub fn forget(&self, file_id: Uuid) {
+        let dir = self.dir.join(file_id.to_string());
+        if std::fs::remove_dir_all(&dir).is_ok() {
+            self.evict();   // refresh the size gauge
+        }
+    }
+
+    /// Blocking: drop least-recently-used entries until under `max_bytes`.
+    fn evict(&self) {
+        let mut entries: Vec<(SystemTime, u64, PathBuf)> = walkdir::WalkDir::new(&self.dir)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_file() && !e.file_name().to_string_lossy().starts_with('.'))
+            .filter_map(|e| {
+                let m = e.metadata().ok()?;
+                Some((m.modified().ok()?, m.len(), e.into_path()))
+            })
+            .collect();
+        let mut total: u64 = entries.iter().map(|e| e.1).sum();
+        entries.sort_by_key(|e| e.0);
+        for (_, len, path) in entries {
+            if total <= self.max_bytes { break; }
+            if std::fs::remove_file(&path).is_ok() {
+                total -= len;
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.bytes.store(total, Ordering::Relaxed);
+    }
+
+    /// Prometheus text exposition.
+    pub fn render_metrics(&self) -> String {
+        let g = |a: &AtomicU64| a.load(Ordering::Relaxed);
+        format!(
+            "# TYPE setlist_transcode_cache_hits_total counter\n\
+             setlist_transcode_cache_hits_total {}\n\
+             # TYPE setlist_transcode_cache_misses_total counter\n\
+             setlist_transcode_cache_misses_total {}\n\
+             # TYPE setlist_transcode_cache_evictions_total counter\n\
+             setlist_transcode_cache_evictions_total {}\n\
+             # TYPE setlist_transcode_cache_bytes gauge\n\
+             setlist_transcode_cache_bytes {}\n\
+             # TYPE setlist_transcode_jobs_available gauge\n\
+             setlist_transcode_jobs_available {}\n",
+            g(&self.hits), g(&self.misses), g(&self.evictions), g(&self.bytes),
+            self.slots.available_permits(),
+        )
+    }
+}
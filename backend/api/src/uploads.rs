@@ -0,0 +1,586 @@
+//! Getting media onto the box.
+//!
+//! * `POST /albums/:id/files` – one-shot `multipart/form-data`, any number of
+//!   file fields; the field's filename may carry a disc folder (`CD1/01.flac`).
+//! * `/albums/:id/uploads[/:upload_id]` – tus 1.0 (core + creation +
+//!   termination) for multi-GB box sets over flaky links.
+//!
+//! Both stream straight to `<inbox>/<album_id>/…` through a `.part` temp file
+//! that is renamed into place only once complete, so the Import worker never
+//! sees a half-written file.
+
+use std::{
+    collections::HashSet,
+    path::{Component, Path as FsPath, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    body::Body,
+    extract::{Multipart, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use base64::Engine;
+use futures_util::StreamExt;
+use serde::Serialize;
+use tokio::{fs, io::{AsyncSeekExt, AsyncWriteExt}};
+use tracing::{info, instrument, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    error::{ApiError, ApiResult, Json, Path, Problem},
+    AppState,
+};
+
+const TUS_VERSION: &str = "1.0.0";
+
+/// Audio the Import worker understands plus the usual show-folder sidecars.
+const ALLOWED_EXT: &[&str] = &[
+    "flac", "mp3", "ogg", "opus", "m4a",
+    "cue", "log", "txt", "md5", "ffp", "st5",
+    "jpg", "jpeg", "png",
+];
+
+/*──────── limits ─────────────────────────────────────────────────────────*/
+
+#[derive(Clone, Copy, Debug)]
+pub struct UploadLimits {
+    pub max_file:  u64,   // UPLOAD_MAX_FILE_BYTES   (default 4 GiB)
+    pub max_album: u64,   // UPLOAD_MAX_ALBUM_BYTES  (default 32 GiB)
+}
+
+impl UploadLimits {
+    pub fn from_env() -> Self {
+        let var = |k: &str, d: u64| std::env::var(k).ok().and_then(|v| v.parse().ok()).unwrap_or(d);
+        Self {
+            max_file:  var("UPLOAD_MAX_FILE_BYTES", 4 << 30),
+            max_album: var("UPLOAD_MAX_ALBUM_BYTES", 32 << 30),
+        }
+    }
+}
+
+/*──────── tus writers ───────────────────────────────────────────────────*/
+
+/// Uploads with a PATCH in flight – one writer per upload.  In-process rather
+/// than a row lock, so a multi-GB PATCH doesn't hold a pool connection open
+/// in a transaction for the whole transfer.
+#[derive(Clone, Default)]
+pub struct TusWriters(Arc<Mutex<HashSet<Uuid>>>);
+
+/// Releases the upload when the PATCH ends, however it ends.
+struct TusWriter {
+    writers:   TusWriters,
+    upload_id: Uuid,
+}
+
+impl TusWriters {
+    fn claim(&self, upload_id: Uuid) -> Option<TusWriter> {
+        let mut busy = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        busy.insert(upload_id).then(|| TusWriter { writers: self.clone(), upload_id })
+    }
+}
+
+impl Drop for TusWriter {
+    fn drop(&mut self) {
+        self.writers.0.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.upload_id);
+    }
+}
+
+/*──────── multipart ──────────────────────────────────────────────────────*/
+
+type Created<T> = (StatusCode, Json<T>);
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StoredFile {
+    pub path:  String,   // relative to the album inbox
+    pub bytes: u64,
+}
+
+#[utoipa::path(
+    post, path = "/albums/{id}/files", tag = "uploads",
+    params(("id" = Uuid, Path, description = "Album id")),
+    request_body(
+        content = Vec<u8>,
+        content_type = "multipart/form-data",
+        description = "One part per file; the filename may include a disc folder",
+    ),
+    responses(
+        (status = 201, description = "Files stored", body = Vec<StoredFile>),
+        (status = 400, response = Problem),
+        (status = 404, response = Problem),
+        (status = 409, response = Problem),
+        (status = 413, response = Problem),
+        (status = 415, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id))]
+pub async fn upload_files(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    mut mp: Multipart,
+) -> ApiResult<Created<Vec<StoredFile>>> {
+    let dir = album_upload_dir(&app, id).await?;
+    let mut used = dir_usage(&dir).await;
+    let mut stored = Vec::new();
+
+    while let Some(mut field) = mp.next_field().await? {
+        let Some(name) = field.file_name().map(str::to_owned) else {
+            continue;                       // plain form field → ignore
+        };
+        let rel  = sanitize_rel_path(&name)?;
+        let dest = dir.join(&rel);
+        // overwriting a file frees its old bytes
+        used -= fs::metadata(&dest).await.map(|m| m.len()).unwrap_or(0).min(used);
+
+        let budget = app.limits.max_file.min(app.limits.max_album.saturating_sub(used));
+        let mut part = PartFile::create(&dest).await?;
+        while let Some(chunk) = field.chunk().await? {
+            part.write(&chunk, budget).await?;
+        }
+        let bytes = part.commit(&dest).await?;
+        used += bytes;
+        info!(path = %rel.display(), bytes, "file stored");
+        stored.push(StoredFile { path: rel.to_string_lossy().into_owned(), bytes });
+    }
+    Ok((StatusCode::CREATED, Json(stored)))
+}
+
+/*──────── tus 1.0 ────────────────────────────────────────────────────────*/
+
+#[derive(sqlx::FromRow)]
+struct TusUpload {
+    rel_path:    String,
+    length:      i64,
+    received:    i64,
+}
+
+/// `OPTIONS /albums/:id/uploads` – capability discovery.
+#[utoipa::path(
+    options, path = "/albums/{id}/uploads", tag = "uploads",
+    params(("id" = Uuid, Path, description = "Album id")),
+    responses(
+        (status = 204, description = "tus capabilities in `Tus-*` headers"),
+    ),
+)]
+pub async fn tus_options(State(app): State<AppState>) -> Response {
+    tus_response(StatusCode::NO_CONTENT, [
+        ("Tus-Version",   TUS_VERSION.to_owned()),
+        ("Tus-Extension", "creation,termination".to_owned()),
+        ("Tus-Max-Size",  app.limits.max_file.to_string()),
+    ])
+}
+
+/// `POST /albums/:id/uploads` – creation extension.
+#[utoipa::path(
+    post, path = "/albums/{id}/uploads", tag = "uploads",
+    params(
+        ("id" = Uuid, Path, description = "Album id"),
+        ("Tus-Resumable" = String, Header, description = "Must be `1.0.0`"),
+        ("Upload-Length" = u64, Header, description = "Total size in bytes"),
+        ("Upload-Metadata" = String, Header, description = "tus metadata; must carry `filename`"),
+    ),
+    responses(
+        (status = 201, description = "Upload created; see `Location`"),
+        (status = 400, response = Problem),
+        (status = 404, response = Problem),
+        (status = 409, response = Problem),
+        (status = 412, response = Problem),
+        (status = 413, response = Problem),
+        (status = 415, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id))]
+pub async fn tus_create(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    check_tus_version(&headers)?;
+    let length: u64 = header_str(&headers, "Upload-Length")
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| bad_request("Upload-Length header required"))?;
+    let name = header_str(&headers, "Upload-Metadata")
+        .and_then(|m| tus_metadata(m, "filename"))
+        .ok_or_else(|| bad_request("Upload-Metadata must carry a filename"))?;
+    let rel = sanitize_rel_path(&name)?;
+
+    let dir = album_upload_dir(&app, id).await?;
+    if length > app.limits.max_file {
+        return Err(too_large(app.limits.max_file));
+    }
+    let (pending,): (i64,) = sqlx::query_as(
+        "SELECT COALESCE(SUM(length - received), 0)::BIGINT
+           FROM uploads WHERE album_id=$1 AND finished_at IS NULL",
+    )
+    .bind(id)
+    .fetch_one(&app.db)
+    .await?;
+    if dir_usage(&dir).await + pending as u64 + length > app.limits.max_album {
+        return Err(too_large(app.limits.max_album));
+    }
+
+    let upload_id = Uuid::new_v4();
+    sqlx::query("INSERT INTO uploads(id, album_id, rel_path, length) VALUES ($1,$2,$3,$4)")
+        .bind(upload_id)
+        .bind(id)
+        .bind(rel.to_string_lossy().as_ref())
+        .bind(length as i64)
+        .execute(&app.db)
+        .await
+        .map_err(|e| match e.as_database_error().and_then(|d| d.code()) {
+            Some(c) if c == "23505" => ApiError::Conflict(format!("{} is already being uploaded", rel.display())),
+            _ => e.into(),
+        })?;
+    fs::create_dir_all(tus_dir(&dir)).await?;
+    fs::File::create(tus_part(&dir, upload_id)).await?;
+    info!(%upload_id, length, "tus upload created");
+
+    Ok(tus_response(StatusCode::CREATED, [
+        ("Location",      format!("/albums/{id}/uploads/{upload_id}")),
+        ("Upload-Offset", "0".to_owned()),
+    ]))
+}
+
+/// `HEAD /albums/:id/uploads/:upload_id` – where to resume from.
+#[utoipa::path(
+    head, path = "/albums/{id}/uploads/{upload_id}", tag = "uploads",
+    params(
+        ("id" = Uuid, Path, description = "Album id"),
+        ("upload_id" = Uuid, Path, description = "tus upload id"),
+        ("Tus-Resumable" = String, Header, description = "Must be `1.0.0`"),
+    ),
+    responses(
+        (status = 200, description = "`Upload-Offset` / `Upload-Length` headers"),
+        (status = 404, response = Problem),
+        (status = 412, response = Problem),
+    ),
+)]
+pub async fn tus_head(
+    Path((id, upload_id)): Path<(Uuid, Uuid)>,
+    State(app): State<AppState>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    check_tus_version(&headers)?;
+    let up = fetch_upload(&app.db, id, upload_id).await?;
+    Ok(tus_response(StatusCode::OK, [
+        ("Upload-Offset", up.received.to_string()),
+        ("Upload-Length", up.length.to_string()),
+        ("Cache-Control", "no-store".to_owned()),
+    ]))
+}
+
+/// `PATCH /albums/:id/uploads/:upload_id` – append bytes at `Upload-Offset`.
+///
+/// Whatever arrives before the connection drops is kept, so the client can
+/// `HEAD` and carry on from there.
+#[utoipa::path(
+    patch, path = "/albums/{id}/uploads/{upload_id}", tag = "uploads",
+    params(
+        ("id" = Uuid, Path, description = "Album id"),
+        ("upload_id" = Uuid, Path, description = "tus upload id"),
+        ("Tus-Resumable" = String, Header, description = "Must be `1.0.0`"),
+        ("Upload-Offset" = u64, Header, description = "Must equal the current offset"),
+    ),
+    request_body(content = Vec<u8>, content_type = "application/offset+octet-stream"),
+    responses(
+        (status = 204, description = "Bytes appended; new `Upload-Offset`"),
+        (status = 400, response = Problem),
+        (status = 404, response = Problem),
+        (status = 409, response = Problem),
+        (status = 412, response = Problem),
+        (status = 413, response = Problem),
+        (status = 415, response = Problem),
+        (status = 423, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id, %upload_id))]
+pub async fn tus_patch(
+    Path((id, upload_id)): Path<(Uuid, Uuid)>,
+    State(app): State<AppState>,
+    headers: HeaderMap,
+    body: Body,
+) -> ApiResult<Response> {
+    check_tus_version(&headers)?;
+    if header_str(&headers, header::CONTENT_TYPE.as_str()) != Some("application/offset+octet-stream") {
+        return Err(ApiError::UnsupportedMediaType("expected application/offset+octet-stream".into()));
+    }
+    let offset: i64 = header_str(&headers, "Upload-Offset")
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| bad_request("Upload-Offset header required"))?;
+
+    // one writer per upload; a second PATCH gets 423
+    let _writer = app.tus_writers.claim(upload_id).ok_or_else(|| ApiError::Locked("upload is busy".into()))?;
+    let up: TusUpload = sqlx::query_as(
+        "SELECT rel_path, length, received FROM uploads
+          WHERE id=$1 AND album_id=$2 AND finished_at IS NULL",
+    )
+    .bind(upload_id)
+    .bind(id)
+    .fetch_optional(&app.db)
+    .await?
+    .ok_or_else(|| ApiError::not_found("upload", upload_id))?;
+    if offset != up.received {
+        return Err(ApiError::Conflict(format!("Upload-Offset {offset} ≠ {}", up.received)));
+    }
+
+    let dir  = album_upload_dir(&app, id).await?;
+    let part = tus_part(&dir, upload_id);
+    let mut file = fs::OpenOptions::new().write(true).open(&part).await?;
+    // drop anything past the last acknowledged offset (crash mid-write)
+    file.set_len(up.received as u64).await?;
+    file.seek(std::io::SeekFrom::End(0)).await?;
+
+    let remaining = (up.length - up.received) as u64;
+    let mut written = 0u64;
+    let mut stream = body.into_data_stream();
+    let mut outcome = Ok(());
+    while let Some(chunk) = stream.next().await {
+        let chunk = match chunk {
+            Ok(c) => c,
+            Err(e) => { warn!("client went away: {e}"); break; }
+        };
+        if written + chunk.len() as u64 > remaining {
+            outcome = Err(too_large(up.length as u64));
+            break;
+        }
+        if let Err(e) = file.write_all(&chunk).await {
+            outcome = Err(e.into());
+            break;
+        }
+        written += chunk.len() as u64;
+    }
+    file.sync_data().await?;
+
+    let received = up.received + written as i64;
+    let done = received == up.length;
+    // the row may have gone meanwhile (album removed): then so has the upload
+    let mut tx = app.db.begin().await?;
+    let res = sqlx::query(
+        "UPDATE uploads SET received=$2, finished_at = CASE WHEN $3 THEN now() END
+          WHERE id=$1 AND finished_at IS NULL",
+    )
+    .bind(upload_id)
+    .bind(received)
+    .bind(done)
+    .execute(&mut *tx)
+    .await?;
+    if res.rows_affected() == 0 {
+        let _ = fs::remove_file(&part).await;
+        return Err(ApiError::not_found("upload", upload_id));
+    }
+    if done {
+        let dest = dir.join(&up.rel_path);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::rename(&part, &dest).await?;
+    }
+    tx.commit().await?;
+    outcome?;
+    if done {
+        info!(path = %up.rel_path, bytes = up.length, "tus upload finished");
+    }
+
+    Ok(tus_response(StatusCode::NO_CONTENT, [("Upload-Offset", received.to_string())]))
+}
+
+/// `DELETE /albums/:id/uploads/:upload_id` – termination extension.
+#[utoipa::path(
+    delete, path = "/albums/{id}/uploads/{upload_id}", tag = "uploads",
+    params(
+        ("id" = Uuid, Path, description = "Album id"),
+        ("upload_id" = Uuid, Path, description = "tus upload id"),
+        ("Tus-Resumable" = String, Header, description = "Must be `1.0.0`"),
+    ),
+    responses(
+        (status = 204, description = "Upload terminated"),
+        (status = 404, response = Problem),
+        (status = 412, response = Problem),
+        (status = 423, response = Problem),
+    ),
+)]
+pub async fn tus_delete(
+    Path((id, upload_id)): Path<(Uuid, Uuid)>,
+    State(app): State<AppState>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    check_tus_version(&headers)?;
+    // not while a PATCH is still writing the part file
+    let _writer = app.tus_writers.claim(upload_id).ok_or_else(|| ApiError::Locked("upload is busy".into()))?;
+    let res = sqlx::query("DELETE FROM uploads WHERE id=$1 AND album_id=$2 AND finished_at IS NULL")
+        .bind(upload_id)
+        .bind(id)
+        .execute(&app.db)
+        .await?;
+    if res.rows_affected() == 0 {
+        return Err(ApiError::not_found("upload", upload_id));
+    }
+    let dir = album_upload_dir(&app, id).await?;
+    let _ = fs::remove_file(tus_part(&dir, upload_id)).await;
+    Ok(tus_response(StatusCode::NO_CONTENT, []))
+}
+
+/*──────── helpers ────────────────────────────────────────────────────────*/
+
+/// Inbox folder of an *upload* album (created on first use).
+async fn album_upload_dir(app: &AppState, id: Uuid) -> ApiResult<PathBuf> {
+    let row: Option<(String, Option<String>)> = sqlx::query_as(
+        "SELECT source->>'type', source->>'path' FROM albums WHERE id=$1",
+    )
+    .bind(id)
+    .fetch_optional(&app.db)
+    .await?;
+    let (kind, path) = row.ok_or_else(|| ApiError::not_found("album", id))?;
+    if kind != "upload" {
+        return Err(ApiError::Conflict(format!("album {id} has a {kind} source; uploads not accepted")));
+    }
+    let dir = path.map(PathBuf::from).unwrap_or_else(|| app.roots.album_inbox(id));
+    fs::create_dir_all(&dir).await?;
+    Ok(dir)
+}
+
+/// Client filename → safe relative path: at most `disc/file.ext`, no dot
+/// segments, extension on the allow-list.
+pub(crate) fn sanitize_rel_path(name: &str) -> ApiResult<PathBuf> {
+    let name = name.replace('\\', "/");
+    let rel  = PathBuf::from(name.trim_matches('/'));
+    let ok_parts = rel.components().all(|c| matches!(
+        c, Component::Normal(s) if !s.to_string_lossy().starts_with('.')
+    ));
+    if !ok_parts || rel.components().count() == 0 || rel.components().count() > 2 {
+        return Err(bad_request(format!("invalid file name {name:?}")));
+    }
+    let ext = rel.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+    if !ALLOWED_EXT.contains(&ext.as_str()) {
+        return Err(ApiError::UnsupportedMediaType(format!("extension {ext:?} not allowed")));
+    }
+    Ok(rel)
+}
+
+/// Bytes already committed under `dir` (temp files excluded).
+async fn dir_usage(dir: &FsPath) -> u64 {
+    let dir = dir.to_owned();
+    tokio::task::spawn_blocking(move || {
+        walkdir::WalkDir::new(dir)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_file())
+            .filter(|e| !e.file_name().to_string_lossy().starts_with('.'))
+            .filter_map(|e| e.metadata().ok())
+            .map(|m| m.len())
+            .sum()
+    })
+    .await
+    .unwrap_or(0)
+}
+
+fn tus_dir(album_dir: &FsPath) -> PathBuf {
+    album_dir.join(".uploads")
+}
+
+fn tus_part(album_dir: &FsPath, upload_id: Uuid) -> PathBuf {
+    tus_dir(album_dir).join(format!("{upload_id}.part"))
+}
+
+async fn fetch_upload(db: &sqlx::PgPool, id: Uuid, upload_id: Uuid) -> ApiResult<TusUpload> {
+    sqlx::query_as(
+        "SELECT rel_path, length, received FROM uploads
+          WHERE id=$1 AND album_id=$2 AND finished_at IS NULL",
+    )
+    .bind(upload_id)
+    .bind(id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| ApiError::not_found("upload", upload_id))
+}
+
+fn check_tus_version(headers: &HeaderMap) -> ApiResult<()> {
+    match header_str(headers, "Tus-Resumable") {
+        Some(TUS_VERSION) => Ok(()),
+        _ => Err(ApiError::PreconditionFailed(format!("Tus-Resumable: {TUS_VERSION} required"))),
+    }
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+/// `Upload-Metadata: filename ZGlzYzEvMDEuZmxhYw==,foo YmFy` → value for `key`.
+fn tus_metadata(raw: &str, key: &str) -> Option<String> {
+    raw.split(',').find_map(|pair| {
+        let mut it = pair.trim().splitn(2, ' ');
+        (it.next()? == key).then_some(())?;
+        let bytes = base64::engine::general_purpose::STANDARD.decode(it.next()?).ok()?;
+        String::from_utf8(bytes).ok()
+    })
+}
+
+fn tus_response<const N: usize>(status: StatusCode, extra: [(&'static str, String); N]) -> Response {
+    let mut res = status.into_response();
+    let h = res.headers_mut();
+    h.insert("Tus-Resumable", HeaderValue::from_static(TUS_VERSION));
+    for (k, v) in extra {
+        if let Ok(v) = HeaderValue::from_str(&v) {
+            h.insert(k, v);
+        }
+    }
+    res
+}
+
+fn bad_request(msg: impl Into<String>) -> ApiError {
+    ApiError::BadRequest(msg.into())
+}
+
+fn too_large(limit: u64) -> ApiError {
+    ApiError::PayloadTooLarge(format!("upload exceeds limit of {limit} bytes"))
+}
+
+/*──────── temp file → atomic rename ──────────────────────────────────────*/
+
+/// `.<name>.<uuid>.part` next to the destination; removed on drop unless
+/// committed.
+struct PartFile {
+    tmp:     PathBuf,
+    file:    Option<fs::File>,
+    written: u64,
+}
+
+impl PartFile {
+    async fn create(dest: &FsPath) -> ApiResult<Self> {
+        let parent = dest.parent().expect("dest is inside album dir");
+        fs::create_dir_all(parent).await?;
+        let name = dest.file_name().unwrap_or_default().to_string_lossy();
+        let tmp  = parent.join(format!(".{name}.{}.part", Uuid::new_v4()));
+        let file = fs::File::create(&tmp).await?;
+        Ok(Self { tmp, file: Some(file), written: 0 })
+    }
+
+    async fn write(&mut self, chunk: &[u8], budget: u64) -> ApiResult<()> {
+        if self.written + chunk.len() as u64 > budget {
+            return Err(too_large(budget));
+        }
+        let file = self.file.as_mut().expect("not committed");
+        file.write_all(chunk).await?;
+        self.written += chunk.len() as u64;
+        Ok(())
+    }
+
+    async fn commit(mut self, dest: &FsPath) -> ApiResult<u64> {
+        let file = self.file.take().expect("not committed");
+        file.sync_all().await?;
+        drop(file);
+        fs::rename(&self.tmp, dest).await?;
+        self.tmp = PathBuf::new();
+        Ok(self.written)
+    }
+}
+
+impl Drop for PartFile {
+    fn drop(&mut self) {
+        if !self.tmp.as_os_str().is_empty() {
+            let _ = std::fs::remove_file(&self.tmp);
+        }
+    }
+}
@@ -0,0 +1,599 @@
+//! Outgoing webhooks – admins register a URL, a secret and the events it
+//! wants; matching pipeline events are POSTed to it as signed JSON.
+//!
+//! Deliveries are queued by a trigger on `pipeline_events` (migration 16) in
+//! the same transaction as the event, then sent by [`spawn_dispatcher`].  A
+//! non-2xx answer or a transport error is retried after 30 s, 2 min, 10 min,
+//! 1 h and 6 h before the delivery is marked `failed`.  Each attempt is
+//! recorded in the delivery log.
+//!
+//! Receivers verify `X-Setlist-Signature: sha256=<hex>`, the HMAC-SHA256 of
+//! `"{X-Setlist-Timestamp}.{body}"` under the webhook's secret.
+
+use std::time::Duration;
+
+use axum::{extract::State, http::StatusCode};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use time::OffsetDateTime;
+use tracing::{info, instrument, warn};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    albums::clean_text,
+    audit,
+    auth::Principal,
+    error::{ApiError, ApiResult, Json, Path, Problem, Query},
+    AppState,
+};
+
+/// Seconds to wait before attempt 2, 3, …; then the delivery fails.
+const RETRY_DELAYS: [i64; 5] = [30, 120, 600, 3600, 21_600];
+/// Deliveries claimed per poll.
+const BATCH: i64 = 20;
+const POLL: Duration = Duration::from_secs(2);
+/// Per-request timeout; the claim lease must outlast it.
+const TIMEOUT: Duration = Duration::from_secs(10);
+const LEASE: &str = "1 minute";
+/// Finished deliveries older than this are pruned by the dispatcher.
+const RETENTION: &str = "30 days";
+/// Only sent by `POST /webhooks/:id/test`, whatever the webhook subscribes to.
+const TEST_EVENT: &str = "webhook.test";
+
+/*──────── model ──────────────────────────────────────────────────────────*/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "text")]
+pub enum WebhookEvent {
+    /// The Import job of an album finished.
+    #[serde(rename = "album.imported")]
+    #[sqlx(rename = "album.imported")]
+    AlbumImported,
+    /// Every file of an album is `READY`.
+    #[serde(rename = "album.ready")]
+    #[sqlx(rename = "album.ready")]
+    AlbumReady,
+    /// A file went to `ERROR`.
+    #[serde(rename = "file.error")]
+    #[sqlx(rename = "file.error")]
+    FileError,
+    /// A file or album was put in the manual review queue.
+    #[serde(rename = "match.needs_review")]
+    #[sqlx(rename = "match.needs_review")]
+    MatchNeedsReview,
+}
+
+impl sqlx::postgres::PgHasArrayType for WebhookEvent {
+    fn array_type_info() -> sqlx::postgres::PgTypeInfo {
+        <String as sqlx::postgres::PgHasArrayType>::array_type_info()
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct Webhook {
+    pub id:          Uuid,
+    pub url:         String,
+    /// Empty = every event.
+    pub events:      Vec<WebhookEvent>,
+    pub description: Option<String>,
+    pub active:      bool,
+    pub created_by:  String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at:  OffsetDateTime,
+}
+
+const WEBHOOK_COLUMNS: &str = "id, url, events, description, active, created_by, created_at";
+
+/// A newly registered webhook – the only time the secret is shown.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IssuedWebhook {
+    pub secret:  String,
+    #[serde(flatten)]
+    pub webhook: Webhook,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NewWebhook {
+    /// `http://` or `https://`; redirects aren't followed.
+    pub url:         String,
+    /// At least 16 characters; omit to have one generated.
+    pub secret:      Option<String>,
+    /// Omit or `[]` for every event.
+    #[serde(default)]
+    pub events:      Vec<WebhookEvent>,
+    pub description: Option<String>,
+    /// Default `true`.
+    pub active:      Option<bool>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookPatch {
+    pub url:         Option<String>,
+    /// Rotates the signing secret.
+    pub secret:      Option<String>,
+    pub events:      Option<Vec<WebhookEvent>>,
+    /// `""` clears the description.
+    pub description: Option<String>,
+    /// Paused webhooks get no new deliveries; pending ones wait.
+    pub active:      Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// Not sent yet, or waiting for a retry.
+    Pending,
+    Delivered,
+    /// Every retry used up.
+    Failed,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct Delivery {
+    pub id:              i64,
+    pub webhook_id:      Uuid,
+    /// A [`WebhookEvent`], or `webhook.test`.
+    pub event:           String,
+    /// The body sent, without `delivery_id`.
+    pub payload:         serde_json::Value,
+    pub status:          DeliveryStatus,
+    pub attempts:        i32,
+    /// When a pending delivery is (re)tried.
+    #[serde(with = "time::serde::rfc3339")]
+    pub next_attempt:    OffsetDateTime,
+    /// HTTP status of the last attempt, if it got one.
+    pub response_status: Option<i32>,
+    pub last_error:      Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at:      OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub delivered_at:    Option<OffsetDateTime>,
+}
+
+const DELIVERY_COLUMNS: &str = "id, webhook_id, event, payload, status, attempts, next_attempt, \
+                                response_status, last_error, created_at, delivered_at";
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(deny_unknown_fields)]
+pub struct DeliveryQuery {
+    pub status: Option<DeliveryStatus>,
+    /// Only deliveries with a smaller id (next page).
+    pub before: Option<i64>,
+    pub limit:  Option<i64>,
+}
+
+/*──────── handlers ───────────────────────────────────────────────────────*/
+
+/// `GET /webhooks`
+#[utoipa::path(
+    get, path = "/webhooks", tag = "webhooks",
+    responses((status = 200, body = Vec<Webhook>), (status = 401, response = Problem), (status = 403, response = Problem)),
+)]
+pub async fn list_webhooks(State(app): State<AppState>) -> ApiResult<Json<Vec<Webhook>>> {
+    let hooks = sqlx::query_as(&format!("SELECT {WEBHOOK_COLUMNS} FROM webhooks ORDER BY created_at"))
+        .fetch_all(&app.db)
+        .await?;
+    Ok(Json(hooks))
+}
+
+/// `POST /webhooks`
+#[utoipa::path(
+    post, path = "/webhooks", tag = "webhooks", request_body = NewWebhook,
+    responses(
+        (status = 201, description = "Secret is shown only in this response", body = IssuedWebhook),
+        (status = 401, response = Problem),
+        (status = 403, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(user = %who.username))]
+pub async fn create_webhook(
+    State(app): State<AppState>,
+    who: Principal,
+    Json(w): Json<NewWebhook>,
+) -> ApiResult<(StatusCode, Json<IssuedWebhook>)> {
+    let url = check_url(&w.url)?;
+    let secret = match w.secret {
+        Some(s) => check_secret(s)?,
+        None => generate_secret(),
+    };
+    let description = clean_text("description", w.description)?;
+
+    let mut tx = app.db.begin().await?;
+    let webhook: Webhook = sqlx::query_as(&format!(
+        "INSERT INTO webhooks(id, url, secret, events, description, active, created_by)
+              VALUES ($1,$2,$3,$4,$5,$6,$7)
+           RETURNING {WEBHOOK_COLUMNS}"
+    ))
+    .bind(Uuid::new_v4())
+    .bind(url)
+    .bind(&secret)
+    .bind(dedup(w.events))
+    .bind(description)
+    .bind(w.active.unwrap_or(true))
+    .bind(&who.username)
+    .fetch_one(&mut *tx)
+    .await?;
+    audit::record(
+        &mut *tx, &who.username, "webhook.create", ("webhook", Some(webhook.id)),
+        serde_json::json!({ "url": webhook.url, "events": webhook.events }),
+    )
+    .await?;
+    tx.commit().await?;
+    info!(webhook_id = %webhook.id, "webhook registered");
+    Ok((StatusCode::CREATED, Json(IssuedWebhook { secret, webhook })))
+}
+
+/// `GET /webhooks/:id`
+#[utoipa::path(
+    get, path = "/webhooks/{id}", tag = "webhooks",
+    params(("id" = Uuid, Path, description = "Webhook id")),
+    responses((status = 200, body = Webhook), (status = 403, response = Problem), (status = 404, response = Problem)),
+)]
+pub async fn get_webhook(Path(id): Path<Uuid>, State(app): State<AppState>) -> ApiResult<Json<Webhook>> {
+    Ok(Json(fetch(&app.db, id).await?))
+}
+
+/// `PATCH /webhooks/:id` – URL, secret, events, description, pause/resume.
+#[utoipa::path(
+    patch, path = "/webhooks/{id}", tag = "webhooks", request_body = WebhookPatch,
+    params(("id" = Uuid, Path, description = "Webhook id")),
+    responses(
+        (status = 200, body = Webhook),
+        (status = 403, response = Problem),
+        (status = 404, response = Problem),
+        (status = 422, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id))]
+pub async fn patch_webhook(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+    Json(p): Json<WebhookPatch>,
+) -> ApiResult<Json<Webhook>> {
+    let url = p.url.as_deref().map(check_url).transpose()?;
+    let secret = p.secret.map(check_secret).transpose()?;
+    let description = p.description.map(|d| clean_text("description", Some(d))).transpose()?;
+    let events = p.events.map(dedup);
+
+    let mut tx = app.db.begin().await?;
+    let webhook: Webhook = sqlx::query_as(&format!(
+        "UPDATE webhooks SET url         = COALESCE($2, url),
+                             secret      = COALESCE($3, secret),
+                             events      = COALESCE($4, events),
+                             description = CASE WHEN $5 THEN $6 ELSE description END,
+                             active      = COALESCE($7, active)
+          WHERE id=$1
+      RETURNING {WEBHOOK_COLUMNS}"
+    ))
+    .bind(id)
+    .bind(url)
+    .bind(&secret)
+    .bind(&events)
+    .bind(description.is_some())
+    .bind(description.flatten())
+    .bind(p.active)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::not_found("webhook", id))?;
+    audit::record(
+        &mut *tx, &who.username, "webhook.update", ("webhook", Some(id)),
+        serde_json::json!({ "url": webhook.url, "events": webhook.events, "active": webhook.active,
+                            "secret_rotated": secret.is_some() }),
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(Json(webhook))
+}
+
+/// `DELETE /webhooks/:id` – its delivery log goes with it.
+#[utoipa::path(
+    delete, path = "/webhooks/{id}", tag = "webhooks",
+    params(("id" = Uuid, Path, description = "Webhook id")),
+    responses((status = 204, description = "Deleted"), (status = 403, response = Problem), (status = 404, response = Problem)),
+)]
+#[instrument(skip_all, fields(%id))]
+pub async fn delete_webhook(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+) -> ApiResult<StatusCode> {
+    let mut tx = app.db.begin().await?;
+    let (url,): (String,) = sqlx::query_as("DELETE FROM webhooks WHERE id=$1 RETURNING url")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| ApiError::not_found("webhook", id))?;
+    audit::record(&mut *tx, &who.username, "webhook.delete", ("webhook", Some(id)), serde_json::json!({ "url": url }))
+        .await?;
+    tx.commit().await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// `GET /webhooks/:id/deliveries` – the delivery log, newest first.
+#[utoipa::path(
+    get, path = "/webhooks/{id}/deliveries", tag = "webhooks",
+    params(("id" = Uuid, Path, description = "Webhook id"), DeliveryQuery),
+    responses(
+        (status = 200, body = Vec<Delivery>),
+        (status = 400, response = Problem),
+        (status = 403, response = Problem),
+        (status = 404, response = Problem),
+    ),
+)]
+pub async fn list_deliveries(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    Query(q): Query<DeliveryQuery>,
+) -> ApiResult<Json<Vec<Delivery>>> {
+    fetch(&app.db, id).await?;
+    let mut qb = QueryBuilder::<Postgres>::new(format!(
+        "SELECT {DELIVERY_COLUMNS} FROM webhook_deliveries WHERE webhook_id = "
+    ));
+    qb.push_bind(id);
+    if let Some(s) = q.status { qb.push(" AND status = ").push_bind(s); }
+    if let Some(b) = q.before { qb.push(" AND id < ").push_bind(b); }
+    qb.push(" ORDER BY id DESC LIMIT ").push_bind(q.limit.unwrap_or(100).clamp(1, 500));
+    Ok(Json(qb.build_query_as().fetch_all(&app.db).await?))
+}
+
+/// `POST /webhooks/:id/test` – queue a `webhook.test` delivery, sent within
+/// seconds even while the webhook is paused.
+#[utoipa::path(
+    post, path = "/webhooks/{id}/test", tag = "webhooks",
+    params(("id" = Uuid, Path, description = "Webhook id")),
+    responses(
+        (status = 202, description = "Queued; poll the delivery log for the outcome", body = Delivery),
+        (status = 403, response = Problem),
+        (status = 404, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id))]
+pub async fn test_webhook(
+    Path(id): Path<Uuid>,
+    State(app): State<AppState>,
+    who: Principal,
+) -> ApiResult<(StatusCode, Json<Delivery>)> {
+    let delivery = sqlx::query_as(&format!(
+        "INSERT INTO webhook_deliveries(webhook_id, event, payload)
+              SELECT id, $2, jsonb_build_object('event', $2::text, 'occurred_at', now(),
+                                                'webhook_id', id, 'sent_by', $3::text)
+                FROM webhooks WHERE id=$1
+           RETURNING {DELIVERY_COLUMNS}"
+    ))
+    .bind(id)
+    .bind(TEST_EVENT)
+    .bind(&who.username)
+    .fetch_optional(&app.db)
+    .await?
+    .ok_or_else(|| ApiError::not_found("webhook", id))?;
+    Ok((StatusCode::ACCEPTED, Json(delivery)))
+}
+
+/// `POST /webhooks/:id/deliveries/:delivery_id/retry` – send again now,
+/// with a fresh retry schedule.  Works on delivered ones too (a replay).
+#[utoipa::path(
+    post, path = "/webhooks/{id}/deliveries/{delivery_id}/retry", tag = "webhooks",
+    params(("id" = Uuid, Path, description = "Webhook id"), ("delivery_id" = i64, Path, description = "Delivery id")),
+    responses(
+        (status = 202, description = "Queued", body = Delivery),
+        (status = 403, response = Problem),
+        (status = 404, response = Problem),
+    ),
+)]
+#[instrument(skip_all, fields(%id, delivery_id))]
+pub async fn retry_delivery(
+    Path((id, delivery_id)): Path<(Uuid, i64)>,
+    State(app): State<AppState>,
+) -> ApiResult<(StatusCode, Json<Delivery>)> {
+    let delivery = sqlx::query_as(&format!(
+        "UPDATE webhook_deliveries
+            SET status = 'pending', attempts = 0, next_attempt = now(), delivered_at = NULL
+          WHERE id=$1 AND webhook_id=$2
+      RETURNING {DELIVERY_COLUMNS}"
+    ))
+    .bind(delivery_id)
+    .bind(id)
+    .fetch_optional(&app.db)
+    .await?
+    .ok_or_else(|| ApiError::not_found("delivery", delivery_id))?;
+    Ok((StatusCode::ACCEPTED, Json(delivery)))
+}
+
+/*──────── dispatcher ─────────────────────────────────────────────────────*/
+
+#[derive(sqlx::FromRow)]
+struct Due {
+    id:       i64,
+    event:    String,
+    payload:  serde_json::Value,
+    attempts: i32,
+    url:      String,
+    secret:   String,
+}
+
+/// Start the delivery task: polls for due deliveries, sends them
+/// concurrently and reschedules failures.
+pub fn spawn_dispatcher(db: PgPool) {
+    let http = reqwest::Client::builder()
+        .timeout(TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .user_agent(concat!("setlist-os-webhooks/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .expect("reqwest client");
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(POLL);
+        let mut prune = tokio::time::interval(Duration::from_secs(3600));
+        loop {
+            tokio::select! {
+                _ = tick.tick() => {
+                    if let Err(e) = dispatch(&db, &http).await {
+                        warn!("webhooks: dispatch failed: {e:#}");
+                    }
+                }
+                _ = prune.tick() => {
+                    let res = sqlx::query(&format!(
+                        "DELETE FROM webhook_deliveries
+                          WHERE status <> 'pending' AND created_at < now() - interval '{RETENTION}'"
+                    ))
+                    .execute(&db)
+                    .await;
+                    if let Err(e) = res {
+                        warn!("webhooks: pruning deliveries failed: {e:#}");
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Claim up to [`BATCH`] due deliveries (leased, so a slow receiver isn't
+/// sent the same one twice) and send them.  Returns once all are recorded.
+async fn dispatch(db: &PgPool, http: &reqwest::Client) -> sqlx::Result<()> {
+    let due: Vec<Due> = sqlx::query_as(&format!(
+        "UPDATE webhook_deliveries d
+            SET attempts = d.attempts + 1, next_attempt = now() + interval '{LEASE}'
+           FROM webhooks w
+          WHERE w.id = d.webhook_id
+            AND d.id IN (SELECT d2.id FROM webhook_deliveries d2 JOIN webhooks w2 ON w2.id = d2.webhook_id
+                          WHERE d2.status = 'pending' AND d2.next_attempt <= now()
+                            AND (w2.active OR d2.event = $1)
+                       ORDER BY d2.next_attempt
+                          LIMIT $2
+                            FOR UPDATE OF d2 SKIP LOCKED)
+      RETURNING d.id, d.event, d.payload, d.attempts, w.url, w.secret"
+    ))
+    .bind(TEST_EVENT)
+    .bind(BATCH)
+    .fetch_all(db)
+    .await?;
+
+    let outcomes = futures_util::future::join_all(due.iter().map(|d| send(http, d))).await;
+    for (d, (code, error)) in due.iter().zip(outcomes) {
+        let delay = if error.is_none() {
+            info!(delivery_id = d.id, event = %d.event, "webhook delivered");
+            None
+        } else {
+            let delay = usize::try_from(d.attempts - 1).ok().and_then(|i| RETRY_DELAYS.get(i)).copied();
+            warn!(delivery_id = d.id, attempts = d.attempts, retry_in = ?delay, "webhook delivery failed: {}",
+                  error.as_deref().unwrap_or_default());
+            delay
+        };
+        sqlx::query(
+            "UPDATE webhook_deliveries
+                SET status          = CASE WHEN $2 IS NULL THEN 'delivered'
+                                           WHEN $4::bigint IS NULL THEN 'failed' ELSE 'pending' END,
+                    delivered_at    = CASE WHEN $2 IS NULL THEN now() END,
+                    next_attempt    = now() + make_interval(secs => COALESCE($4::bigint, 0)),
+                    response_status = $3,
+                    last_error      = $2
+              WHERE id = $1",
+        )
+        .bind(d.id)
+        .bind(error)
+        .bind(code)
+        .bind(delay)
+        .execute(db)
+        .await?;
+    }
+    Ok(())
+}
+
+/// One attempt: `(HTTP status, error)`; no error means a 2xx.
+async fn send(http: &reqwest::Client, d: &Due) -> (Option<i32>, Option<String>) {
+    let mut body = d.payload.clone();
+    if let Some(o) = body.as_object_mut() {
+        o.insert("delivery_id".into(), d.id.into());
+    }
+    let body = body.to_string();
+    let ts = OffsetDateTime::now_utc().unix_timestamp().to_string();
+
+    let res = http
+        .post(&d.url)
+        .header("content-type", "application/json")
+        .header("x-setlist-event", &d.event)
+        .header("x-setlist-delivery", d.id.to_string())
+        .header("x-setlist-timestamp", &ts)
+        .header("x-setlist-signature", format!("sha256={}", sign(&d.secret, &ts, &body)))
+        .body(body)
+        .send()
+        .await;
+    match res {
+        Ok(r) if r.status().is_success() => (Some(r.status().as_u16().into()), None),
+        Ok(r) => {
+            let code = r.status();
+            let text = r.text().await.unwrap_or_default();
+            let snippet: String = text.chars().take(200).collect();
+            (Some(code.as_u16().into()), Some(format!("HTTP {code}: {}", snippet.trim())))
+        }
+        Err(e) => {
+            let mut msg = e.to_string();
+            let mut src = std::error::Error::source(&e);
+            while let Some(s) = src {
+                msg = format!("{msg}: {s}");
+                src = s.source();
+            }
+            (None, Some(msg))
+        }
+    }
+}
+
+/// Hex HMAC-SHA256 of `"{ts}.{body}"`.
+fn sign(secret: &str, ts: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes any key length");
+    mac.update(ts.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/*──────── helpers ────────────────────────────────────────────────────────*/
+
+async fn fetch(db: &PgPool, id: Uuid) -> ApiResult<Webhook> {
+    sqlx::query_as(&format!("SELECT {WEBHOOK_COLUMNS} FROM webhooks WHERE id=$1"))
+        .bind(id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| ApiError::not_found("webhook", id))
+}
+
+fn check_url(raw: &str) -> ApiResult<String> {
+    let url = reqwest::Url::parse(raw.trim()).map_err(|e| ApiError::Unprocessable(format!("url: {e}")))?;
+    if !matches!(url.scheme(), "http" | "https") || url.host().is_none() {
+        return Err(ApiError::Unprocessable("url must be http:// or https:// with a host".into()));
+    }
+    Ok(url.into())
+}
+
+fn check_secret(s: String) -> ApiResult<String> {
+    if !(16..=256).contains(&s.len()) {
+        return Err(ApiError::Unprocessable("secret must be 16–256 bytes".into()));
+    }
+    Ok(s)
+}
+
+fn generate_secret() -> String {
+    use argon2::password_hash::rand_core::{OsRng, RngCore};
+    let mut raw = [0u8; 32];
+    OsRng.fill_bytes(&mut raw);
+    format!("whsec_{}", URL_SAFE_NO_PAD.encode(raw))
+}
+
+fn dedup(events: Vec<WebhookEvent>) -> Vec<WebhookEvent> {
+    let mut out = Vec::with_capacity(events.len());
+    for e in events {
+        if !out.contains(&e) {
+            out.push(e);
+        }
+    }
+    out
+}
@@ -0,0 +1,27 @@
+//! Log in as the bootstrap admin the API creates on first start.
+//!
+//! Pass `("SETLIST_ADMIN_PASSWORD", ADMIN_PASSWORD)` to the API process, then
+//! use the client from `admin_client` for every request.
+
+use anyhow::{Context, Result};
+use reqwest::{header, Client};
+
+pub const ADMIN_PASSWORD: &str = "e2e-admin-password";
+
+/// A client that sends the admin's session token on every request.
+pub async fn admin_client(base: &str) -> Result<Client> {
+    let session: serde_json::Value = Client::new()
+        .post(format!("{base}/auth/login"))
+        .json(&serde_json::json!({ "username": "admin", "password": ADMIN_PASSWORD }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let token = session["token"].as_str().context("login response has no token")?;
+
+    let mut auth = header::HeaderValue::from_str(&format!("Bearer {token}"))?;
+    auth.set_sensitive(true);
+    let headers = header::HeaderMap::from_iter([(header::AUTHORIZATION, auth)]);
+    Ok(Client::builder().default_headers(headers).build()?)
+}
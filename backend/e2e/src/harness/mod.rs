@@ -1,13 +1,17 @@
+pub mod auth;
 pub mod docker;
 pub mod process;
+pub mod receiver;
 pub mod wait;
 
 pub mod prelude {
     pub use anyhow::{Context, Result};
     pub use uuid::Uuid;
 
+    pub use super::auth::*;
     pub use super::docker::*;
     pub use super::process::*;
+    pub use super::receiver::*;
     pub use super::wait::*;
 }
 
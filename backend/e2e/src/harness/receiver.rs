@@ -0,0 +1,61 @@
+//! One-shot local HTTP receiver for webhook deliveries: accepts a single
+//! request, answers `204` and hands back its headers and body.
+
+use anyhow::{Context, Result};
+use std::time::Duration;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpListener,
+    task::JoinHandle,
+};
+
+#[derive(Debug)]
+pub struct Received {
+    pub path:    String,
+    /// Lower-cased names.
+    pub headers: Vec<(String, String)>,
+    pub body:    String,
+}
+
+impl Received {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+}
+
+/// Bind `127.0.0.1:<random>`; returns the URL to register and a handle that
+/// resolves to the first request (or an error after `timeout`).
+pub async fn receive_one(timeout: Duration) -> Result<(String, JoinHandle<Result<Received>>)> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}/hook", listener.local_addr()?);
+    let handle = tokio::spawn(async move {
+        tokio::time::timeout(timeout, async {
+            let (sock, _) = listener.accept().await?;
+            let mut rd = BufReader::new(sock);
+
+            let mut line = String::new();
+            rd.read_line(&mut line).await?;
+            let path = line.split_whitespace().nth(1).context("request line")?.to_owned();
+            let mut headers = Vec::new();
+            loop {
+                line.clear();
+                rd.read_line(&mut line).await?;
+                let Some((k, v)) = line.trim_end().split_once(':') else { break };
+                headers.push((k.to_ascii_lowercase(), v.trim().to_owned()));
+            }
+            let len: usize = headers
+                .iter()
+                .find(|(k, _)| k == "content-length")
+                .map_or(Ok(0), |(_, v)| v.parse())?;
+            let mut body = vec![0; len];
+            rd.read_exact(&mut body).await?;
+            rd.get_mut()
+                .write_all(b"HTTP/1.1 204 No Content\r\nconnection: close\r\n\r\n")
+                .await?;
+            Ok(Received { path, headers, body: String::from_utf8(body)? })
+        })
+        .await
+        .context("timed-out waiting for a webhook delivery")?
+    });
+    Ok((url, handle))
+}
@@ -2,7 +2,9 @@
 //! Smoke-test: create an album through the API → make sure an *import* job is queued.
 
 use e2e::harness::prelude::*;
+use hmac::{Hmac, Mac};
 use reqwest::Client;
+use sha2::Sha256;
 use std::time::{Duration, Instant};
 
 #[tokio::test]
@@ -18,7 +20,11 @@ async fn album_upload_flow() -> Result<()> {
     let (_api, _api_log) = spawn_with_logs(
         "API",
         &api_bin,
-        &[("DATABASE_URL", &infra.db_url), ("AMQP_URL", &infra.amqp_url)],
+        &[
+            ("DATABASE_URL",           &infra.db_url),
+            ("AMQP_URL",               &infra.amqp_url),
+            ("SETLIST_ADMIN_PASSWORD", ADMIN_PASSWORD),
+        ],
         34,
     )?;
 
@@ -27,14 +33,29 @@ async fn album_upload_flow() -> Result<()> {
     println!("API up in {:.1?}", t0.elapsed());
 
     /*──  1️⃣  create album  ─────────────────────────────────────────────*/
-    let client = Client::new();
-    let album_id: Uuid = client
+    let client = admin_client("http://127.0.0.1:8080").await?;
+    let album: serde_json::Value = client
         .post("http://127.0.0.1:8080/albums")
+        .json(&serde_json::json!({
+            "title":      "Live at the Fillmore",
+            "artist":     "Test Band",
+            "year":       1970,
+            "venue":      "Fillmore East",
+            "album_kind": "concert",
+            "source":     { "type": "upload" },
+        }))
         .send()
         .await?
         .error_for_status()?
         .json()
         .await?;
+    let album_id: Uuid = album["id"].as_str().context("album.id")?.parse()?;
+    assert_eq!(album["album_kind"], "concert");
+    assert_eq!(album["source"]["type"], "upload");
+    assert!(
+        album["source"]["path"].as_str().is_some_and(|p| p.ends_with(&album_id.to_string())),
+        "upload albums are staged under <inbox>/<album_id>"
+    );
     println!("created album {album_id}");
 
     /*──  2️⃣  mark complete (queues Import)  ────────────────────────────*/
@@ -57,6 +78,335 @@ async fn album_upload_flow() -> Result<()> {
 
     assert_eq!(cnt, 1, "exactly one import job queued");
 
+    /*──  4️⃣  status resource reflects it; unknown ids are 404  ──────────*/
+    let status: serde_json::Value = client
+        .get(format!("http://127.0.0.1:8080/albums/{album_id}"))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(status["jobs"]["import"]["queued"], 1);
+    assert_eq!(status["tracks"], serde_json::json!([]));
+
+    let page: serde_json::Value = client
+        .get("http://127.0.0.1:8080/albums?album_kind=concert&state=in_progress")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(page["items"][0]["id"], album_id.to_string());
+    assert!(page["next_cursor"].is_null());
+
+    let missing = client
+        .get(format!("http://127.0.0.1:8080/albums/{}", Uuid::new_v4()))
+        .send()
+        .await?;
+    assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+    assert_eq!(missing.headers()["content-type"], "application/problem+json");
+    let problem: serde_json::Value = missing.json().await?;
+    assert_eq!(problem["code"], "not_found");
+
+    let spec: serde_json::Value = client
+        .get("http://127.0.0.1:8080/openapi.json")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert!(spec["paths"]["/albums/{id}"]["get"].is_object());
+    let traced = client
+        .get("http://127.0.0.1:8080/openapi.json")
+        .header("x-request-id", "e2e-trace-1")
+        .header("accept-encoding", "gzip")
+        .send()
+        .await?
+        .error_for_status()?;
+    assert_eq!(traced.headers()["x-request-id"], "e2e-trace-1", "client ids are echoed");
+    assert_eq!(traced.headers()["content-encoding"], "gzip");
+
+    let images: serde_json::Value = client
+        .get(format!("http://127.0.0.1:8080/albums/{album_id}/images"))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(images, serde_json::json!([]));
+
+    let cover = client
+        .get(format!("http://127.0.0.1:8080/albums/{album_id}/cover?size=256"))
+        .send()
+        .await?;
+    assert_eq!(cover.status(), reqwest::StatusCode::NOT_FOUND, "no artwork anywhere yet");
+
+    let found: serde_json::Value = client
+        .get("http://127.0.0.1:8080/search?q=tset%20band")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(found["albums"][0]["id"], album_id.to_string(), "fuzzy artist match");
+    assert_eq!(found["albums"][0]["venue"], "Fillmore East");
+
+    let imported: serde_json::Value = client
+        .post("http://127.0.0.1:8080/playlists/import?name=e2e")
+        .body("#EXTM3U\n#EXTINF:-1,Nowhere\n/not/in/the/library.flac\n")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(imported["playlist"]["entries"], serde_json::json!([]));
+    assert_eq!(imported["unresolved"][0]["line"], 3);
+    let playlist_id = imported["playlist"]["id"].as_str().context("playlist.id")?;
+    let m3u = client
+        .get(format!("http://127.0.0.1:8080/playlists/{playlist_id}/export?format=m3u8"))
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    assert!(m3u.starts_with("#EXTM3U\n#PLAYLIST:e2e"));
+
+    let stats: serde_json::Value = client
+        .get("http://127.0.0.1:8080/stats")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert!(stats["albums"].is_i64(), "materialized at startup, refreshed on a timer");
+    assert!(stats["throughput"]["import"]["queued"].is_i64());
+
+    let (hook_url, delivery) = receive_one(Duration::from_secs(15)).await?;
+    let hook: serde_json::Value = client
+        .post("http://127.0.0.1:8080/webhooks")
+        .json(&serde_json::json!({ "url": hook_url, "events": ["album.ready"] }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let hook_id = hook["id"].as_str().context("webhook.id")?;
+    let hook_secret = hook["secret"].as_str().context("generated secret")?;
+    client
+        .post(format!("http://127.0.0.1:8080/webhooks/{hook_id}/test"))
+        .send()
+        .await?
+        .error_for_status()?;
+    let req = delivery.await??;
+    assert_eq!(req.header("x-setlist-event"), Some("webhook.test"), "sent despite the event filter");
+    let ts = req.header("x-setlist-timestamp").context("timestamp header")?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(hook_secret.as_bytes())?;
+    mac.update(format!("{ts}.{}", req.body).as_bytes());
+    let expected = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+    assert_eq!(req.header("x-setlist-signature"), Some(expected.as_str()));
+    let sent: serde_json::Value = serde_json::from_str(&req.body)?;
+    assert_eq!(sent["event"], "webhook.test");
+    assert_eq!(sent["webhook_id"], hook_id);
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let log: serde_json::Value = client
+            .get(format!("http://127.0.0.1:8080/webhooks/{hook_id}/deliveries"))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if log[0]["status"] == "delivered" {
+            assert_eq!(log[0]["id"], sent["delivery_id"]);
+            assert_eq!(log[0]["response_status"], 204);
+            break;
+        }
+        anyhow::ensure!(Instant::now() < deadline, "delivery never logged as delivered: {log}");
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+
+    let into_itself = client
+        .post(format!("http://127.0.0.1:8080/albums/{album_id}/merge"))
+        .json(&serde_json::json!({ "from": album_id }))
+        .send()
+        .await?;
+    assert_eq!(into_itself.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let split = client
+        .post(format!("http://127.0.0.1:8080/albums/{album_id}/split"))
+        .json(&serde_json::json!({ "tracks": [] }))
+        .send()
+        .await?;
+    assert_eq!(split.status(), reqwest::StatusCode::CONFLICT, "Import is still queued");
+
+    sqlx::query("UPDATE albums SET venue = 'Fillmore West' WHERE id=$1")
+        .bind(album_id)
+        .execute(&sqlx::PgPool::connect(&infra.db_url).await?)
+        .await?;
+    let history: serde_json::Value = client
+        .get(format!("http://127.0.0.1:8080/albums/{album_id}/history"))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(history[0]["field"], "venue");
+    assert_eq!(history[0]["old_value"], "Fillmore East");
+    assert_eq!(history[0]["via"], "sql", "recorded by trigger, not just API edits");
+    let reverted: serde_json::Value = client
+        .post(format!("http://127.0.0.1:8080/albums/{album_id}/history/revert"))
+        .json(&serde_json::json!({ "to": history[0]["id"] }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(reverted[0]["new_value"], "Fillmore East");
+    assert_eq!(reverted[0]["via"], "revert");
+
+    let export = client.get("http://127.0.0.1:8080/export?format=ndjson").send().await?.error_for_status()?;
+    let export = export.text().await?;
+    assert!(export.starts_with(r#"{"type":"header","format":"setlist-export""#));
+    let archived = export
+        .lines()
+        .map(serde_json::from_str::<serde_json::Value>)
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .find(|r| r["type"] == "album" && r["id"] == album_id.to_string())
+        .context("album in export")?;
+    assert!(archived["history"].as_array().map(Vec::len) >= Some(2), "edit and revert");
+    let restored: serde_json::Value = client
+        .post("http://127.0.0.1:8080/restore?dry_run=true")
+        .body(export.clone())
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert!(restored["albums_updated"].as_u64() >= Some(1), "matched by id: {restored}");
+    assert_eq!(restored["albums_created"], 0);
+
+    // a fresh database: history lost, metadata drifted – restore puts both back
+    let pool = sqlx::PgPool::connect(&infra.db_url).await?;
+    sqlx::query("DELETE FROM metadata_history WHERE entity_id=$1").bind(album_id).execute(&pool).await?;
+    sqlx::query("UPDATE albums SET venue = 'Winterland' WHERE id=$1").bind(album_id).execute(&pool).await?;
+    sqlx::query("DELETE FROM metadata_history WHERE entity_id=$1").bind(album_id).execute(&pool).await?;
+    client
+        .post("http://127.0.0.1:8080/restore")
+        .body(export)
+        .send()
+        .await?
+        .error_for_status()?;
+    let history: Vec<serde_json::Value> = client
+        .get(format!("http://127.0.0.1:8080/albums/{album_id}/history?field=venue"))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let vias: Vec<&str> = history.iter().filter_map(|h| h["via"].as_str()).collect();
+    assert_eq!(vias, ["restore", "revert", "sql"], "archived history stays older than the restore");
+    assert_eq!(history[0]["old_value"], "Winterland");
+    assert_eq!(history[0]["new_value"], "Fillmore East");
+
+    // again, from an archive holding that restore: its rows aren't the new
+    // restore's to extend, and a second run adds nothing
+    let export = client.get("http://127.0.0.1:8080/export").send().await?.error_for_status()?.text().await?;
+    sqlx::query("DELETE FROM metadata_history WHERE entity_id=$1").bind(album_id).execute(&pool).await?;
+    sqlx::query("UPDATE albums SET venue = 'Winterland' WHERE id=$1").bind(album_id).execute(&pool).await?;
+    sqlx::query("DELETE FROM metadata_history WHERE entity_id=$1").bind(album_id).execute(&pool).await?;
+    for _ in 0..2 {
+        client
+            .post("http://127.0.0.1:8080/restore")
+            .body(export.clone())
+            .send()
+            .await?
+            .error_for_status()?;
+        let history: Vec<serde_json::Value> = client
+            .get(format!("http://127.0.0.1:8080/albums/{album_id}/history?field=venue"))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let vias: Vec<&str> = history.iter().filter_map(|h| h["via"].as_str()).collect();
+        assert_eq!(vias, ["restore", "restore", "revert", "sql"]);
+    }
+
+    /*──  5️⃣  auth: anonymous is turned away, scopes are enforced  ─────*/
+    let anon = Client::new().get("http://127.0.0.1:8080/albums").send().await?;
+    assert_eq!(anon.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let issued: serde_json::Value = client
+        .post("http://127.0.0.1:8080/auth/tokens")
+        .json(&serde_json::json!({ "name": "e2e read-only", "scopes": ["read"] }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let token = issued["token"].as_str().context("token secret")?;
+    let listed = Client::new()
+        .get("http://127.0.0.1:8080/albums")
+        .bearer_auth(token)
+        .send()
+        .await?;
+    assert_eq!(listed.status(), reqwest::StatusCode::OK);
+    let denied = Client::new()
+        .put(format!("http://127.0.0.1:8080/albums/{album_id}/complete"))
+        .bearer_auth(token)
+        .send()
+        .await?;
+    assert_eq!(denied.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let app_password: serde_json::Value = client
+        .post("http://127.0.0.1:8080/auth/tokens")
+        .json(&serde_json::json!({ "name": "e2e phone", "scopes": ["read", "stream"], "subsonic": true }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let secret = app_password["token"].as_str().context("subsonic secret")?;
+    let ping: serde_json::Value = Client::new()
+        .get(format!("http://127.0.0.1:8080/rest/ping.view?u=admin&p={secret}&v=1.16.1&c=e2e&f=json"))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(ping["subsonic-response"]["status"], "ok");
+    let as_bearer = Client::new().get("http://127.0.0.1:8080/albums").bearer_auth(secret).send().await?;
+    assert_eq!(as_bearer.status(), reqwest::StatusCode::UNAUTHORIZED, "app passwords are Subsonic-only");
+
+    /*──  6️⃣  delete: queued Import is cancelled, action is audited  ─────*/
+    let purge = client
+        .delete(format!("http://127.0.0.1:8080/albums/{album_id}?mode=purge"))
+        .send()
+        .await?;
+    assert_eq!(purge.status().as_u16(), 428, "purge needs confirm=<id>");
+
+    let removal: serde_json::Value = client
+        .delete(format!("http://127.0.0.1:8080/albums/{album_id}?mode=tombstone"))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(removal["jobs_cancelled"], 1);
+    let gone = client.get(format!("http://127.0.0.1:8080/albums/{album_id}")).send().await?;
+    assert_eq!(gone.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let audit: serde_json::Value = client
+        .get(format!("http://127.0.0.1:8080/audit?target_id={album_id}"))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(audit[0]["action"], "album.delete");
+    assert_eq!(audit[0]["detail"]["mode"], "tombstone");
+
     println!("✔ album flow OK in {:.1?}", t0.elapsed());
     Ok(())
 }
@@ -0,0 +1,89 @@
+// tests/events_flow.rs
+//! SSE: queueing the Import stage shows up on `/albums/:id/events`, and a
+//! reconnect with `Last-Event-ID` replays only what came after it.
+
+use e2e::harness::prelude::*;
+use futures::StreamExt;
+use std::time::{Duration, Instant};
+
+#[tokio::test]
+async fn album_events_stream_and_resume() -> Result<()> {
+    let t0 = Instant::now();
+    let infra = Infra::spin_up()?;
+
+    let api_bin = std::env::var("API_BIN").context("API_BIN not set")?;
+    let (_api, _api_log) = spawn_with_logs(
+        "API",
+        &api_bin,
+        &[
+            ("DATABASE_URL",           &infra.db_url),
+            ("AMQP_URL",               &infra.amqp_url),
+            ("SETLIST_ADMIN_PASSWORD", ADMIN_PASSWORD),
+        ],
+        34,
+    )?;
+    wait_for_http_ok("http://127.0.0.1:8080/internal/health", Duration::from_secs(10)).await?;
+
+    let client = admin_client("http://127.0.0.1:8080").await?;
+    let album: serde_json::Value = client
+        .post("http://127.0.0.1:8080/albums")
+        .json(&serde_json::json!({ "source": { "type": "upload" } }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let album_id = album["id"].as_str().context("album.id")?.to_owned();
+    let events_url = format!("http://127.0.0.1:8080/albums/{album_id}/events");
+
+    /*──  1️⃣  live: subscribe, then queue Import  ───────────────────────*/
+    let mut live = client.get(&events_url).send().await?.error_for_status()?.bytes_stream();
+    client
+        .put(format!("http://127.0.0.1:8080/albums/{album_id}/complete"))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let mut seen = String::new();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while let Some(chunk) = live.next().await {
+            seen.push_str(&String::from_utf8_lossy(&chunk?));
+            if seen.contains("\n\n") { break; }
+        }
+        anyhow::Ok(())
+    })
+    .await
+    .context("no SSE event within 5s")??;
+    assert!(seen.contains("event: job.status"), "got: {seen}");
+    assert!(seen.contains(r#""stage":"import""#), "got: {seen}");
+    let first_id = seen
+        .lines()
+        .find_map(|l| l.strip_prefix("id: "))
+        .context("event id")?
+        .to_owned();
+
+    /*──  2️⃣  resume after the first event → nothing older is replayed  ─*/
+    let pool = sqlx::PgPool::connect(&infra.db_url).await?;
+    sqlx::query("UPDATE jobs SET status='done' WHERE payload->>'album_id' = $1")
+        .bind(&album_id)
+        .execute(&pool)
+        .await?;
+
+    let mut resumed = client
+        .get(&events_url)
+        .header("Last-Event-ID", &first_id)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes_stream();
+    let chunk = tokio::time::timeout(Duration::from_secs(5), resumed.next())
+        .await
+        .context("no replay within 5s")?
+        .context("stream closed")??;
+    let text = String::from_utf8_lossy(&chunk);
+    assert!(text.contains(r#""status":"done""#), "got: {text}");
+    assert!(!text.contains(&format!("id: {first_id}\n")), "replayed the acknowledged event");
+
+    println!("✔ events flow OK in {:.1?}", t0.elapsed());
+    Ok(())
+}
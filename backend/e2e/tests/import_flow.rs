@@ -2,11 +2,9 @@
 //! Full round-trip: album folder → Import worker → tracks/files rows & FP jobs.
 
 use e2e::harness::prelude::*;
-use reqwest::Client;
 use std::{
     env,
     fs,
-    path::PathBuf,
     time::{Duration, Instant},
 };
 use tokio::process::Command;
@@ -16,15 +14,54 @@ async fn import_creates_tracks_and_fp_jobs() -> Result<()> {
     let t0 = Instant::now();
     let infra = Infra::spin_up()?;
 
+    /*──  prepare a temp album dir with two tiny FLACs  ──────────────────*/
+    let tmp_root   = tempfile::tempdir()?;
+    let album_dir  = tmp_root.path().join(Uuid::new_v4().to_string());
+    fs::create_dir(&album_dir)?;
+    let (art, back) = (tmp_root.path().join("art.png"), tmp_root.path().join("back.png"));
+    for (png, colour) in [(&art, "color=c=red:s=300x200"), (&back, "color=c=blue:s=200x200")] {
+        Command::new("ffmpeg")
+            .args(["-f","lavfi","-i",colour, "-frames:v","1", png.to_str().unwrap(), "-y","-loglevel","error"])
+            .status().await?;
+    }
+    for n in 1..=2 {
+        let file = album_dir.join(format!("{n:02}.flac"));
+        // the first one carries front art
+        let picture: &[&str] = match n {
+            1 => &["-i", art.to_str().unwrap(), "-map","0:a","-map","1:v",
+                   "-c:v","copy","-disposition:v","attached_pic"],
+            _ => &[],
+        };
+        Command::new("ffmpeg")
+            .args(["-f","lavfi","-i","anullsrc=r=44100:cl=stereo"])
+            .args(picture)
+            .args([
+                "-t","1","-c:a","flac",
+                file.to_str().unwrap(),
+                "-y","-loglevel","error",
+            ])
+            .status().await?;
+    }
+
     /*──  launch API + Import worker  ────────────────────────────────────*/
     let api_bin    = env::var("API_BIN").context("API_BIN not set")?;
     let import_bin = env::var("IMPORT_BIN")
         .unwrap_or_else(|_| "../target/debug/worker-import".into());
 
+    let transcodes = tempfile::tempdir()?;
+    let images     = tempfile::tempdir()?;
     let (_api,   _api_log)   = spawn_with_logs(
         "API",
         &api_bin,
-        &[("DATABASE_URL", &infra.db_url), ("AMQP_URL", &infra.amqp_url)],
+        &[
+            ("DATABASE_URL",             &infra.db_url),
+            ("AMQP_URL",                 &infra.amqp_url),
+            ("MEDIA_ROOT",               tmp_root.path().to_str().unwrap()),
+            ("TRANSCODE_CACHE_DIR",      transcodes.path().to_str().unwrap()),
+            ("IMAGE_ROOT",               images.path().to_str().unwrap()),
+            ("SETLIST_ADMIN_PASSWORD",   ADMIN_PASSWORD),
+            ("REPROCESS_ALBUMS_PER_MIN", "60"),
+        ],
         34,
     )?;
     let (_imp,   _imp_log)   = spawn_with_logs(
@@ -36,41 +73,20 @@ async fn import_creates_tracks_and_fp_jobs() -> Result<()> {
 
     wait_for_http_ok("http://127.0.0.1:8080/internal/health", Duration::from_secs(10)).await?;
 
-    /*──  prepare a temp album dir with two tiny FLACs  ──────────────────*/
-    let tmp_root   = tempfile::tempdir()?;
-    let album_dir  = tmp_root.path().join(Uuid::new_v4().to_string());
-    fs::create_dir(&album_dir)?;
-    for n in 1..=2 {
-        let file = album_dir.join(format!("{n:02}.flac"));
-        Command::new("ffmpeg")
-            .args([
-                "-f","lavfi","-i","anullsrc=r=44100:cl=stereo",
-                "-t","1","-c:a","flac",
-                file.to_str().unwrap(),
-                "-y","-loglevel","error",
-            ])
-            .status().await?;
-    }
-
-    /*──  create album in DB & update source.path  ───────────────────────*/
-    let client   = Client::new();
-    let album_id: Uuid = client
+    /*──  create album pointing at the folder  ───────────────────────────*/
+    let client   = admin_client("http://127.0.0.1:8080").await?;
+    let album: serde_json::Value = client
         .post("http://127.0.0.1:8080/albums")
+        .json(&serde_json::json!({
+            "title":  "Import Flow",
+            "source": { "type": "library_scan", "path": album_dir },
+        }))
         .send()
         .await?
         .error_for_status()?
         .json()
         .await?;
-
-    sqlx::query(
-        "UPDATE albums
-            SET source = jsonb_build_object('type','upload','path',$1)
-          WHERE id = $2",
-    )
-    .bind(album_dir.to_str().unwrap())
-    .bind(album_id)
-    .execute(&sqlx::PgPool::connect(&infra.db_url).await?)
-    .await?;
+    let album_id: Uuid = album["id"].as_str().context("album.id")?.parse()?;
 
     /*──  kick the Import stage  ─────────────────────────────────────────*/
     client
@@ -112,7 +128,529 @@ async fn import_creates_tracks_and_fp_jobs() -> Result<()> {
     .await?;
     assert_eq!(fp_jobs, 2, "2 fingerprint jobs queued");
 
+    // unattributed metadata_history rows take the writer's application_name
+    let (named,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM pg_stat_activity
+          WHERE datname = current_database() AND application_name = 'setlist-import'",
+    )
+    .fetch_one(&pool)
+    .await?;
+    assert!(named > 0, "the Import worker's connections carry its stage name");
+
+    /*──  manual correction: swap the two tracks, collide on disc/index  ─*/
+    let tracks: serde_json::Value = client
+        .get(format!("http://127.0.0.1:8080/albums/{album_id}/tracks"))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let (first, second) = (tracks[0]["id"].clone(), tracks[1]["id"].clone());
+    assert_eq!((tracks[0]["disc"].clone(), tracks[0]["index"].clone()), (1.into(), 1.into()));
+    let swapped: serde_json::Value = client
+        .put(format!("http://127.0.0.1:8080/albums/{album_id}/discs/1/order"))
+        .json(&serde_json::json!({ "tracks": [second, first] }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(swapped[0]["id"], second);
+    assert_eq!(swapped[0]["index"], 1);
+    assert_eq!(swapped[1]["id"], first);
+    assert_eq!(swapped[1]["index"], 2);
+    let taken = client
+        .patch(format!("http://127.0.0.1:8080/tracks/{}", first.as_str().context("track id")?))
+        .json(&serde_json::json!({ "index": 1 }))
+        .send()
+        .await?;
+    assert_eq!(taken.status(), reqwest::StatusCode::CONFLICT, "index 1 belongs to the other track");
+    let audit: serde_json::Value = client
+        .get(format!("http://127.0.0.1:8080/audit?target_id={album_id}"))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(audit[0]["action"], "track.reorder");
+    assert_eq!(audit[0]["actor"], "admin");
+    let moves: Vec<serde_json::Value> = client
+        .get(format!("http://127.0.0.1:8080/albums/{album_id}/history?field=index"))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(moves.len(), 2, "one row per moved track, not one per renumbering pass");
+    assert!(moves.iter().all(|m| m["changed_by"] == "admin" && m["via"] == "api"));
+    let restored: Vec<serde_json::Value> = client
+        .post(format!("http://127.0.0.1:8080/albums/{album_id}/history/revert"))
+        .json(&serde_json::json!({ "to": moves[1]["id"] }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(restored.len(), 2);
+    let track: serde_json::Value = client
+        .get(format!("http://127.0.0.1:8080/tracks/{}", first.as_str().context("track id")?))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(track["index"], 1, "revert puts the swap back");
+
+    // a restore puts the archived order back the same way
+    let export = client.get("http://127.0.0.1:8080/export").send().await?.error_for_status()?.text().await?;
+    client
+        .put(format!("http://127.0.0.1:8080/albums/{album_id}/discs/1/order"))
+        .json(&serde_json::json!({ "tracks": [second, first] }))
+        .send()
+        .await?
+        .error_for_status()?;
+    let report: serde_json::Value = client
+        .post("http://127.0.0.1:8080/restore")
+        .body(export)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(report["unmatched"], serde_json::json!([]), "{report}");
+    let order: serde_json::Value = client
+        .get(format!("http://127.0.0.1:8080/albums/{album_id}/tracks"))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!((order[0]["id"].clone(), order[0]["index"].clone()), (first.clone(), 1.into()));
+    assert_eq!((order[1]["id"].clone(), order[1]["index"].clone()), (second.clone(), 2.into()));
+
+    /*──  review queue: choose / manual / reject, per file and album  ────*/
+    let file_a: Uuid = tracks[0]["files"][0]["id"].as_str().context("file id")?.parse()?;
+    let file_b: Uuid = tracks[1]["files"][0]["id"].as_str().context("file id")?.parse()?;
+    let (good, bad, release) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    sqlx::query("UPDATE files SET needs_review = TRUE WHERE id = ANY($1)")
+        .bind([file_a, file_b])
+        .execute(&pool)
+        .await?;
+    sqlx::query(
+        "INSERT INTO matches_track(file_id, mb_recording, score, raw_json)
+         VALUES ($1, $2, 0.6, '{}'), ($1, $3, 0.5, '{}')",
+    )
+    .bind(file_a)
+    .bind(good)
+    .bind(bad)
+    .execute(&pool)
+    .await?;
+    sqlx::query("UPDATE albums SET needs_review = TRUE WHERE id=$1").bind(album_id).execute(&pool).await?;
+    sqlx::query(
+        "INSERT INTO matches_album(album_id, mb_release, confidence, raw_json) VALUES ($1, $2, 0.4, '{}')",
+    )
+    .bind(album_id)
+    .bind(release)
+    .execute(&pool)
+    .await?;
+
+    let queue: serde_json::Value = client
+        .get("http://127.0.0.1:8080/review/files")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(queue.as_array().map(Vec::len), Some(2));
+    let listed = queue.as_array().context("queue")?.iter().find(|f| f["file_id"] == file_a.to_string());
+    assert_eq!(listed.context("file a queued")?["candidates"][0]["mb_recording"], good.to_string());
+
+    let review = |what: &str, id: Uuid, action: &str, body: serde_json::Value| {
+        client.post(format!("http://127.0.0.1:8080/review/{what}/{id}/{action}")).json(&body).send()
+    };
+    let mbid = |id: Uuid| serde_json::json!({ "mbid": id });
+    let not_candidate = review("files", file_a, "choose", mbid(release)).await?;
+    assert_eq!(not_candidate.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let chosen: serde_json::Value = review("files", file_a, "choose", mbid(good)).await?.json().await?;
+    assert_eq!(chosen["action"], "chosen");
+    assert_eq!(chosen["mbid"], good.to_string());
+    let manual: serde_json::Value =
+        review("files", file_b, "manual", serde_json::json!({ "title": "Encore" })).await?.json().await?;
+    assert_eq!(manual["action"], "manual");
+    let (title,): (Option<String>,) =
+        sqlx::query_as("SELECT t.title FROM tracks t JOIN files f ON f.track_id = t.id WHERE f.id=$1")
+            .bind(file_b)
+            .fetch_one(&pool)
+            .await?;
+    assert_eq!(title.as_deref(), Some("Encore"));
+    let rejected: serde_json::Value =
+        review("files", file_a, "reject", serde_json::json!(null)).await?.json().await?;
+    assert_eq!(rejected["action"], "rejected");
+    let (candidates, requeued): (i64, i64) = sqlx::query_as(
+        "SELECT (SELECT COUNT(*) FROM matches_track WHERE file_id=$1),
+                (SELECT COUNT(*) FROM jobs WHERE stage='match_track' AND payload->>'file_id' = $1::text)",
+    )
+    .bind(file_a)
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!((candidates, requeued), (0, 1), "rejecting drops candidates and matches again");
+    let queue: serde_json::Value =
+        client.get("http://127.0.0.1:8080/review/files").send().await?.error_for_status()?.json().await?;
+    assert_eq!(queue, serde_json::json!([]));
+
+    let albums: serde_json::Value =
+        client.get("http://127.0.0.1:8080/review/albums").send().await?.error_for_status()?.json().await?;
+    assert_eq!(albums[0]["candidates"][0]["mb_release"], release.to_string());
+    let bad_year = review("albums", album_id, "manual", serde_json::json!({ "year": 99 })).await?;
+    assert_eq!(bad_year.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY, "same range as POST /albums");
+    let manual: serde_json::Value =
+        review("albums", album_id, "manual", serde_json::json!({ "artist": "Test Band", "year": 1971 }))
+            .await?
+            .json()
+            .await?;
+    assert_eq!(manual["metadata"]["year"], 1971);
+    let chosen: serde_json::Value = review("albums", album_id, "choose", mbid(release)).await?.json().await?;
+    assert_eq!(chosen["action"], "chosen");
+    let rejected: serde_json::Value =
+        review("albums", album_id, "reject", serde_json::json!(null)).await?.json().await?;
+    assert_eq!(rejected["action"], "rejected");
+    let (rematch,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM jobs WHERE stage='match_album' AND payload->>'album_id' = $1::text",
+    )
+    .bind(album_id)
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!(rematch, 1);
+
+    /*──  streaming: ranges, validators, library roots only  ────────────*/
+    let flac = fs::read(tracks[0]["files"][0]["path"].as_str().context("file path")?)?;
+    let stream = format!("http://127.0.0.1:8080/files/{file_a}/stream");
+    let full = client.get(&stream).send().await?.error_for_status()?;
+    assert_eq!(full.headers()["accept-ranges"], "bytes");
+    assert_eq!(full.headers()["content-type"], "audio/flac");
+    let etag = full.headers()["etag"].to_str()?.to_owned();
+    assert_eq!(full.bytes().await?, flac);
+
+    let head = client.get(&stream).header("range", "bytes=0-99").send().await?;
+    assert_eq!(head.status(), reqwest::StatusCode::PARTIAL_CONTENT);
+    assert_eq!(head.headers()["content-range"], format!("bytes 0-99/{}", flac.len()));
+    assert_eq!(head.bytes().await?, flac[..100]);
+    let tail = client.get(&stream).header("range", "bytes=-10").send().await?;
+    assert_eq!(tail.status(), reqwest::StatusCode::PARTIAL_CONTENT);
+    assert_eq!(tail.bytes().await?, flac[flac.len() - 10..], "suffix range");
+    let beyond = client.get(&stream).header("range", format!("bytes={}-", flac.len())).send().await?;
+    assert_eq!(beyond.status(), reqwest::StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(beyond.headers()["content-range"], format!("bytes */{}", flac.len()));
+    let stale = client.get(&stream).header("range", "bytes=0-99").header("if-range", "\"stale\"");
+    let stale = stale.send().await?;
+    assert_eq!(stale.status(), reqwest::StatusCode::OK, "stale If-Range gets the whole file");
+    let cached = client.get(&stream).header("if-none-match", &etag).send().await?;
+    assert_eq!(cached.status(), reqwest::StatusCode::NOT_MODIFIED);
+
+    let outside = tempfile::tempdir()?;
+    let secret = outside.path().join("secret.flac");
+    fs::write(&secret, b"not library media")?;
+    let (stray,): (Uuid,) = sqlx::query_as(
+        "INSERT INTO files(id, track_id, path, codec) SELECT gen_random_uuid(), track_id, $2, 'flac'
+           FROM files WHERE id=$1 RETURNING id",
+    )
+    .bind(file_a)
+    .bind(secret.to_str().context("utf-8 path")?)
+    .fetch_one(&pool)
+    .await?;
+    let refused = client.get(format!("http://127.0.0.1:8080/files/{stray}/stream")).send().await?;
+    assert_eq!(refused.status(), reqwest::StatusCode::FORBIDDEN, "outside the media root");
+    sqlx::query("DELETE FROM files WHERE id=$1").bind(stray).execute(&pool).await?;
+
+    /*──  transcoding: encode once, then served from the cache  ─────────*/
+    let mp3 = format!("http://127.0.0.1:8080/files/{file_a}/stream?format=mp3&bitrate=128k");
+    let fresh = client.get(&mp3).send().await?.error_for_status()?;
+    assert_eq!(fresh.headers()["content-type"], "audio/mpeg");
+    assert_eq!(fresh.headers()["accept-ranges"], "none", "length unknown while encoding");
+    let encoded = fresh.bytes().await?;
+    assert!(!encoded.is_empty());
+    let cache_entry = transcodes.path().join(file_a.to_string());
+    for _ in 0..50 {
+        let done = fs::read_dir(&cache_entry)
+            .map(|d| d.filter_map(Result::ok).any(|e| !e.file_name().to_string_lossy().starts_with('.')))
+            .unwrap_or(false);
+        if done { break; }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let hit = client.get(&mp3).send().await?.error_for_status()?;
+    assert_eq!(hit.headers()["accept-ranges"], "bytes", "cached copies are seekable");
+    assert_eq!(hit.bytes().await?, encoded);
+    let metrics = client
+        .get("http://127.0.0.1:8080/internal/metrics")
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    assert!(metrics.contains("setlist_transcode_cache_misses_total 1\n"), "{metrics}");
+    assert!(metrics.contains("setlist_transcode_cache_hits_total 1\n"), "{metrics}");
+
+    /*──  reprocess: files go back to the chosen stage and are re-queued  ─*/
+    sqlx::query(
+        "UPDATE files SET status='READY', fp_done_at=now(), tagged_at=now()
+          WHERE track_id IN (SELECT id FROM tracks WHERE album_id=$1)",
+    )
+    .bind(album_id)
+    .execute(&pool)
+    .await?;
+    let reprocess = |from: &str| {
+        client
+            .post(format!("http://127.0.0.1:8080/albums/{album_id}/reprocess"))
+            .json(&serde_json::json!({ "from": from }))
+            .send()
+    };
+    let from_import = reprocess("import").await?;
+    assert_eq!(from_import.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let done: serde_json::Value = reprocess("match_track").await?.error_for_status()?.json().await?;
+    assert_eq!(done["files"], 2);
+    assert_eq!(done["jobs_queued"], 2);
+    assert!(done["jobs_cancelled"].as_u64() >= Some(2), "the still-queued fingerprint jobs: {done}");
+    let statuses: Vec<(String, bool, i64)> = sqlx::query_as(
+        "SELECT f.status::text, f.tagged_at IS NULL,
+                (SELECT COUNT(*) FROM jobs j
+                  WHERE j.stage = 'match_track' AND j.status = 'queued'
+                    AND j.payload->>'file_id' = f.id::text)
+           FROM files f JOIN tracks t ON t.id = f.track_id
+          WHERE t.album_id = $1",
+    )
+    .bind(album_id)
+    .fetch_all(&pool)
+    .await?;
+    assert_eq!(statuses, vec![("FP_DONE".to_owned(), true, 1); 2]);
+
+    /*──  bulk reprocess: one run at a time, throttled per album  ───────*/
+    client
+        .post("http://127.0.0.1:8080/albums")
+        .json(&serde_json::json!({ "source": { "type": "upload" } }))
+        .send()
+        .await?
+        .error_for_status()?;
+    let bulk = |from: &str| {
+        client.post("http://127.0.0.1:8080/reprocess").json(&serde_json::json!({ "from": from })).send()
+    };
+    let started = Instant::now();
+    let run: serde_json::Value = bulk("match_track").await?.error_for_status()?.json().await?;
+    assert_eq!(run["albums_total"], 2);
+    assert_eq!(bulk("match_track").await?.status(), reqwest::StatusCode::CONFLICT, "already running");
+    let status = || async {
+        client.get("http://127.0.0.1:8080/reprocess").send().await?.error_for_status()?.json().await
+    };
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let early: serde_json::Value = status().await?;
+    assert!(early["albums_done"].as_u64() < Some(2), "60/min: the second album waits a second: {early}");
+    let run = loop {
+        let run: serde_json::Value = status().await?;
+        if !run["finished_at"].is_null() {
+            break run;
+        }
+        anyhow::ensure!(started.elapsed() < Duration::from_secs(10), "bulk run never finished: {run}");
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    };
+    assert!(started.elapsed() >= Duration::from_secs(1), "throttled");
+    assert_eq!((run["albums_done"].clone(), run["errors"].clone()), (2.into(), 0.into()));
+    assert_eq!(run["files"], 2, "the upload album has none yet");
+    let stop = client.delete("http://127.0.0.1:8080/reprocess").send().await?;
+    assert_eq!(stop.status(), reqwest::StatusCode::NOT_FOUND, "nothing left to stop");
+
+    /*──  batch import: existing folder is reported, new one is created  ─*/
+    fs::create_dir(tmp_root.path().join("Test Band - Second Album (2001)"))?;
+    let batch: serde_json::Value = client
+        .post("http://127.0.0.1:8080/imports/batch")
+        .json(&serde_json::json!({ "parent": tmp_root.path() }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(batch["progress"]["total"], 2);
+    assert_eq!(batch["progress"]["created"], 1);
+    assert_eq!(batch["progress"]["exists"], 1);
+    let created = batch["items"]
+        .as_array()
+        .context("items")?
+        .iter()
+        .find(|i| i["outcome"] == "created")
+        .context("created item")?;
+    let (artist, year, priority): (Option<String>, Option<i32>, i16) = sqlx::query_as(
+        "SELECT a.artist, a.year, j.priority FROM albums a
+           JOIN jobs j ON j.payload->>'album_id' = a.id::text AND j.stage = 'import'
+          WHERE a.id = $1",
+    )
+    .bind(created["album_id"].as_str().context("album_id")?.parse::<Uuid>()?)
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!(artist.as_deref(), Some("Test Band"));
+    assert_eq!(year, Some(2001));
+    assert!(priority < 0, "batch imports queue at low priority");
+
+    /*──  artwork: embedded fallback, uploads, the cover, thumbnails  ────*/
+    let cover_url = format!("http://127.0.0.1:8080/albums/{album_id}/cover");
+    let image_url = |image: &serde_json::Value| {
+        format!("http://127.0.0.1:8080/albums/{album_id}/images/{}", image["id"].as_str().unwrap_or_default())
+    };
+    let embedded = client.get(&cover_url).send().await?.error_for_status()?;
+    assert_eq!(embedded.headers()["content-type"], "image/png");
+    assert_eq!(embedded.bytes().await?.as_ref(), fs::read(&art)?, "01.flac's front art, no image uploaded");
+
+    let upload = |kind: &str, png: &std::path::Path| -> Result<_> {
+        let form = reqwest::multipart::Form::new()
+            .text("kind", kind.to_owned())
+            .part("file", reqwest::multipart::Part::bytes(fs::read(png)?).file_name("art.png"));
+        Ok(client.post(format!("http://127.0.0.1:8080/albums/{album_id}/images")).multipart(form).send())
+    };
+    let sleeve = upload("sleeve", &art)?.await?;
+    assert_eq!(sleeve.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY, "no such kind");
+    let rear: serde_json::Value = upload("back", &back)?.await?.error_for_status()?.json().await?;
+    assert_eq!(rear["is_primary"], false, "only a front becomes the cover by itself");
+    let front: serde_json::Value = upload("front", &art)?.await?.error_for_status()?.json().await?;
+    assert_eq!(front["is_primary"], true, "the first front becomes the cover");
+    assert_eq!((front["width"].clone(), front["height"].clone()), (300.into(), 200.into()));
+    let other: serde_json::Value = upload("front", &back)?.await?.error_for_status()?.json().await?;
+    assert_eq!(other["is_primary"], false, "the album has a cover already");
+
+    let refused = client.patch(image_url(&front)).json(&serde_json::json!({ "primary": false })).send().await?;
+    assert_eq!(refused.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let picked: serde_json::Value = client
+        .patch(image_url(&other))
+        .json(&serde_json::json!({ "primary": true }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(picked["is_primary"], true);
+    let listed: Vec<serde_json::Value> = client
+        .get(format!("http://127.0.0.1:8080/albums/{album_id}/images"))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(listed.len(), 3);
+    assert_eq!(listed[0]["id"], other["id"], "the cover lists first");
+    assert_eq!(listed.iter().filter(|i| i["is_primary"] == true).count(), 1);
+    let cover = client.get(&cover_url).send().await?.error_for_status()?.bytes().await?;
+    assert_eq!(cover.as_ref(), fs::read(&back)?);
+
+    // ?size= snaps up to 128 and is rendered once, then served from the cache
+    let thumbs = images.path().join("thumbs").join(album_id.to_string());
+    let small = client.get(format!("{}?size=100", image_url(&front))).send().await?.error_for_status()?;
+    assert_eq!(small.headers()["content-type"], "image/jpeg");
+    let etag = small.headers()["etag"].clone();
+    let (w, h) = jpeg_size(&small.bytes().await?).context("thumbnail is a JPEG")?;
+    assert_eq!((w.max(h), w > h), (128, true), "{w}×{h}");
+    let cached = fs::read_dir(&thumbs)?
+        .filter_map(Result::ok)
+        .filter(|e| e.file_name().to_string_lossy().ends_with("-128.jpg"))
+        .count();
+    assert_eq!(cached, 1);
+    let again = client
+        .get(format!("{}?size=120", image_url(&front)))
+        .header("If-None-Match", etag)
+        .send()
+        .await?;
+    assert_eq!(again.status(), reqwest::StatusCode::NOT_MODIFIED, "same cached file");
+
+    client.delete(image_url(&other)).send().await?.error_for_status()?;
+    let gone = client.get(image_url(&other)).send().await?;
+    assert_eq!(gone.status(), reqwest::StatusCode::NOT_FOUND);
+    assert!(!thumbs.exists(), "changing the images drops the album's thumbnails");
+    let cover = client.get(&cover_url).send().await?.error_for_status()?.bytes().await?;
+    assert_eq!(cover.as_ref(), fs::read(&art)?, "without a primary, the newest front");
+
+    /*──  subsonic: token + salt auth, browsing, stream, cover art  ──────*/
+    let app_password: serde_json::Value = client
+        .post("http://127.0.0.1:8080/auth/tokens")
+        .json(&serde_json::json!({ "name": "e2e player", "scopes": ["read", "stream"], "subsonic": true }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let secret = app_password["token"].as_str().context("subsonic secret")?.to_owned();
+    let rest = |method: &str, query: &str| {
+        use md5::{Digest, Md5};
+        let salt = Uuid::new_v4().simple().to_string();
+        let token = hex::encode(Md5::digest(format!("{secret}{salt}")));
+        reqwest::Client::new()
+            .get(format!(
+                "http://127.0.0.1:8080/rest/{method}.view?u=admin&t={token}&s={salt}&v=1.16.1&c=e2e&f=json{query}"
+            ))
+            .send()
+    };
+    let subsonic = |method: &'static str, query: String| {
+        let req = rest(method, &query);
+        async move {
+            let body: serde_json::Value = req.await?.error_for_status()?.json().await?;
+            anyhow::Ok(body["subsonic-response"].clone())
+        }
+    };
+
+    let ping = subsonic("ping", String::new()).await?;
+    assert_eq!(ping["status"], "ok", "{ping}");
+    let forged: serde_json::Value = reqwest::Client::new()
+        .get("http://127.0.0.1:8080/rest/ping.view?u=admin&t=00000000000000000000000000000000&s=abc&v=1.16.1&c=e2e&f=json")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(forged["subsonic-response"]["error"]["code"], 40, "wrong token");
+
+    let artists = subsonic("getArtists", String::new()).await?;
+    let index = &artists["artists"]["index"];
+    assert_eq!(index.as_array().map(Vec::len), Some(1), "albums without tracks aren't listed: {artists}");
+    assert_eq!(index[0]["name"], "U");
+    assert_eq!(index[0]["artist"][0]["name"], "Unknown Artist");
+    assert_eq!(index[0]["artist"][0]["albumCount"], 1);
+
+    let got = subsonic("getAlbum", format!("&id={album_id}")).await?;
+    let songs = got["album"]["song"].as_array().context("songs")?;
+    assert_eq!((got["album"]["name"].clone(), got["album"]["songCount"].clone()), ("Import Flow".into(), 2.into()));
+    assert_eq!(songs.iter().map(|s| s["id"].clone()).collect::<Vec<_>>(), [first.clone(), second.clone()]);
+    assert_eq!((songs[0]["track"].clone(), songs[0]["discNumber"].clone()), (1.into(), 1.into()));
+    assert_eq!((songs[0]["suffix"].clone(), songs[0]["contentType"].clone()), ("flac".into(), "audio/flac".into()));
+
+    let found = subsonic("search3", "&query=import".into()).await?;
+    assert_eq!(found["searchResult3"]["album"][0]["id"], album_id.to_string(), "{found}");
+    let missing = subsonic("getAlbum", format!("&id={}", Uuid::new_v4())).await?;
+    assert_eq!(missing["error"]["code"], 70);
+
+    let song = first.as_str().context("track id")?;
+    let raw = rest("stream", &format!("&id={song}&format=raw")).await?.error_for_status()?;
+    assert_eq!(raw.headers()["content-type"], "audio/flac");
+    assert_eq!(raw.bytes().await?, flac, "format=raw is the file itself");
+    let lossy = rest("stream", &format!("&id={song}&maxBitRate=128")).await?.error_for_status()?;
+    assert_eq!(lossy.headers()["content-type"], "audio/mpeg", "lossless over the cap becomes mp3");
+    assert_eq!(lossy.bytes().await?, encoded, "the 128k encode from the cache");
+
+    let art_of = |id: String| rest("getCoverArt", &format!("&id={id}"));
+    let by_album = art_of(album_id.to_string()).await?.error_for_status()?;
+    assert_eq!(by_album.headers()["content-type"], "image/png");
+    assert_eq!(by_album.bytes().await?.as_ref(), fs::read(&art)?);
+    let by_song = art_of(song.to_owned()).await?.error_for_status()?.bytes().await?;
+    assert_eq!(by_song.as_ref(), fs::read(&art)?, "a song's art is its album's");
+
     println!("✔ import flow OK in {:.1?}", t0.elapsed());
     Ok(())
 }
 
+/// `(width, height)` from a JPEG's start-of-frame segment.
+fn jpeg_size(jpeg: &[u8]) -> Option<(u16, u16)> {
+    let mut at = 2;
+    while at + 9 < jpeg.len() && jpeg[at] == 0xFF {
+        let marker = jpeg[at + 1];
+        if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+            let h = u16::from_be_bytes([jpeg[at + 5], jpeg[at + 6]]);
+            let w = u16::from_be_bytes([jpeg[at + 7], jpeg[at + 8]]);
+            return Some((w, h));
+        }
+        at += 2 + u16::from_be_bytes([jpeg[at + 2], jpeg[at + 3]]) as usize;
+    }
+    None
+}
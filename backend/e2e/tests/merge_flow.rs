@@ -0,0 +1,245 @@
+// tests/merge_flow.rs
+//! Merge and split on imported albums: the resulting disc layout, the
+//! folded-in album's images and folder, and MatchAlbum queued again for
+//! every album involved.
+
+use e2e::harness::prelude::*;
+use std::{
+    env,
+    fs,
+    time::{Duration, Instant},
+};
+use tokio::process::Command;
+
+#[tokio::test]
+async fn merge_and_split_imported_albums() -> Result<()> {
+    let t0 = Instant::now();
+    let infra = Infra::spin_up()?;
+
+    /*──  three folders of tiny FLACs: two sets and an encore  ───────────*/
+    let media = tempfile::tempdir()?;
+    for (folder, tracks) in [("Set 1", 2), ("Set 2", 2), ("Encore", 1)] {
+        let dir = media.path().join(folder);
+        fs::create_dir(&dir)?;
+        for n in 1..=tracks {
+            Command::new("ffmpeg")
+                .args([
+                    "-f","lavfi","-i","anullsrc=r=44100:cl=stereo",
+                    "-t","1","-c:a","flac",
+                    dir.join(format!("{n:02}.flac")).to_str().unwrap(),
+                    "-y","-loglevel","error",
+                ])
+                .status().await?;
+        }
+    }
+    let cover = media.path().join("cover.png");
+    Command::new("ffmpeg")
+        .args([
+            "-f","lavfi","-i","color=c=red:s=300x200",
+            "-frames:v","1", cover.to_str().unwrap(),
+            "-y","-loglevel","error",
+        ])
+        .status().await?;
+
+    /*──  launch API + Import worker  ────────────────────────────────────*/
+    let api_bin    = env::var("API_BIN").context("API_BIN not set")?;
+    let import_bin = env::var("IMPORT_BIN")
+        .unwrap_or_else(|_| "../target/debug/worker-import".into());
+
+    let images = tempfile::tempdir()?;
+    let (_api, _api_log) = spawn_with_logs(
+        "API",
+        &api_bin,
+        &[
+            ("DATABASE_URL",           &infra.db_url),
+            ("AMQP_URL",               &infra.amqp_url),
+            ("MEDIA_ROOT",             media.path().to_str().unwrap()),
+            ("IMAGE_ROOT",             images.path().to_str().unwrap()),
+            ("SETLIST_ADMIN_PASSWORD", ADMIN_PASSWORD),
+        ],
+        34,
+    )?;
+    let (_imp, _imp_log) = spawn_with_logs(
+        "IMPORT",
+        &import_bin,
+        &[("DATABASE_URL", &infra.db_url), ("AMQP_URL", &infra.amqp_url)],
+        35,
+    )?;
+    wait_for_http_ok("http://127.0.0.1:8080/internal/health", Duration::from_secs(10)).await?;
+
+    /*──  import all three as one batch  ─────────────────────────────────*/
+    let client = admin_client("http://127.0.0.1:8080").await?;
+    let batch: serde_json::Value = client
+        .post("http://127.0.0.1:8080/imports/batch")
+        .json(&serde_json::json!({ "parent": media.path() }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let album = |folder: &str| -> Result<String> {
+        let item = batch["items"]
+            .as_array()
+            .and_then(|items| items.iter().find(|i| i["path"].as_str().is_some_and(|p| p.ends_with(folder))))
+            .with_context(|| format!("{folder} in batch: {batch}"))?;
+        Ok(item["album_id"].as_str().context("album_id")?.to_owned())
+    };
+    let (set1, set2, encore) = (album("Set 1")?, album("Set 2")?, album("Encore")?);
+
+    let pool = sqlx::PgPool::connect(&infra.db_url).await?;
+    let deadline = Instant::now() + Duration::from_secs(20);
+    loop {
+        let (tracks, importing): (i64, i64) = sqlx::query_as(
+            "SELECT (SELECT COUNT(*) FROM tracks),
+                    (SELECT COUNT(*) FROM jobs WHERE stage = 'import' AND status IN ('queued', 'running'))",
+        )
+        .fetch_one(&pool)
+        .await?;
+        if tracks == 5 && importing == 0 {
+            break;
+        }
+        anyhow::ensure!(Instant::now() < deadline, "import not done: {tracks} tracks, {importing} jobs");
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+
+    let tracks_of = |album: &str| {
+        let req = client.get(format!("http://127.0.0.1:8080/albums/{album}/tracks"));
+        async move {
+            let tracks: Vec<serde_json::Value> = req.send().await?.error_for_status()?.json().await?;
+            anyhow::Ok(tracks)
+        }
+    };
+    let layout = |tracks: &[serde_json::Value]| -> Vec<(String, i64, i64)> {
+        tracks
+            .iter()
+            .map(|t| (
+                t["id"].as_str().unwrap_or_default().to_owned(),
+                t["disc"].as_i64().unwrap_or_default(),
+                t["index"].as_i64().unwrap_or_default(),
+            ))
+            .collect()
+    };
+    let ids = |tracks: &[serde_json::Value]| -> Vec<String> {
+        tracks.iter().filter_map(|t| t["id"].as_str().map(str::to_owned)).collect()
+    };
+    let match_jobs = |album: String| {
+        let pool = pool.clone();
+        async move {
+            let (n,): (i64,) = sqlx::query_as(
+                "SELECT COUNT(*) FROM jobs WHERE stage = 'match_album' AND status = 'queued'
+                                             AND payload->>'album_id' = $1",
+            )
+            .bind(album)
+            .fetch_one(&pool)
+            .await?;
+            anyhow::Ok(n)
+        }
+    };
+    let ones = ids(&tracks_of(&set1).await?);
+    let twos = ids(&tracks_of(&set2).await?);
+    let last = ids(&tracks_of(&encore).await?);
+
+    // Set 2 brings a cover along
+    let form = reqwest::multipart::Form::new()
+        .text("kind", "front")
+        .part("file", reqwest::multipart::Part::bytes(fs::read(&cover)?).file_name("cover.png"));
+    let image: serde_json::Value = client
+        .post(format!("http://127.0.0.1:8080/albums/{set2}/images"))
+        .multipart(form)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let image_id = image["id"].as_str().context("image id")?;
+
+    /*──  merge Set 2 in as new discs  ───────────────────────────────────*/
+    client
+        .post(format!("http://127.0.0.1:8080/albums/{set1}/merge"))
+        .json(&serde_json::json!({ "from": set2, "discs": "new_discs" }))
+        .send()
+        .await?
+        .error_for_status()?;
+    let merged = tracks_of(&set1).await?;
+    assert_eq!(layout(&merged), [
+        (ones[0].clone(), 1, 1), (ones[1].clone(), 1, 2),
+        (twos[0].clone(), 2, 1), (twos[1].clone(), 2, 2),
+    ]);
+    let gone = client.get(format!("http://127.0.0.1:8080/albums/{set2}")).send().await?;
+    assert_eq!(gone.status(), reqwest::StatusCode::NOT_FOUND, "the merged album is deleted");
+    assert!(match_jobs(set1.clone()).await? >= 1, "Set 1 is matched again");
+
+    let moved: serde_json::Value = client
+        .get(format!("http://127.0.0.1:8080/albums/{set1}/images"))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(moved[0]["id"], image_id, "images follow their tracks");
+    assert_eq!(moved[0]["is_primary"], true, "Set 1 had no cover of its own");
+    let bytes = client
+        .get(format!("http://127.0.0.1:8080/albums/{set1}/images/{image_id}"))
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    assert_eq!(bytes.as_ref(), fs::read(&cover)?, "the file moved under the surviving album");
+
+    /*──  append the encore to the last disc  ────────────────────────────*/
+    client
+        .post(format!("http://127.0.0.1:8080/albums/{set1}/merge"))
+        .json(&serde_json::json!({ "from": encore, "discs": "append" }))
+        .send()
+        .await?
+        .error_for_status()?;
+    let merged = tracks_of(&set1).await?;
+    assert_eq!(layout(&merged)[4], (last[0].clone(), 2, 3));
+    let gone = client.get(format!("http://127.0.0.1:8080/albums/{encore}")).send().await?;
+    assert_eq!(gone.status(), reqwest::StatusCode::NOT_FOUND);
+
+    // the merged folders still count as imported, into the album that absorbed them
+    let again: serde_json::Value = client
+        .post("http://127.0.0.1:8080/imports/batch")
+        .json(&serde_json::json!({ "parent": media.path() }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(again["progress"]["created"], 0);
+    for item in again["items"].as_array().context("items")? {
+        assert_eq!(item["outcome"], "exists", "{item}");
+        assert_eq!(item["album_id"], set1.as_str(), "{item}");
+    }
+
+    /*──  split Set 2 back out  ──────────────────────────────────────────*/
+    let split: serde_json::Value = client
+        .post(format!("http://127.0.0.1:8080/albums/{set1}/split"))
+        .json(&serde_json::json!({ "tracks": twos, "title": "Set 2" }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let set2 = split["id"].as_str().context("new album id")?.to_owned();
+    assert_eq!(split["title"], "Set 2");
+    assert_eq!(layout(&tracks_of(&set2).await?), [(twos[0].clone(), 1, 1), (twos[1].clone(), 1, 2)]);
+    assert_eq!(layout(&tracks_of(&set1).await?), [
+        (ones[0].clone(), 1, 1), (ones[1].clone(), 1, 2), (last[0].clone(), 2, 3),
+    ]);
+    assert!(match_jobs(set2.clone()).await? >= 1, "the new album is matched");
+    assert!(match_jobs(set1.clone()).await? >= 1);
+    let kept: serde_json::Value = client
+        .get(format!("http://127.0.0.1:8080/albums/{set2}/images"))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(kept, serde_json::json!([]), "images stay with the original album");
+
+    println!("✔ merge flow OK in {:.1?}", t0.elapsed());
+    Ok(())
+}
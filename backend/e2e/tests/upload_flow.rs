@@ -0,0 +1,171 @@
+// tests/upload_flow.rs
+//! Upload album → files land in `<inbox>/<album_id>/` via multipart and tus.
+
+use e2e::harness::prelude::*;
+use reqwest::{multipart, StatusCode};
+use std::{fs, time::{Duration, Instant}};
+
+#[tokio::test]
+async fn multipart_and_tus_uploads_land_in_inbox() -> Result<()> {
+    let t0 = Instant::now();
+    let infra = Infra::spin_up()?;
+    let inbox = tempfile::tempdir()?;
+
+    /*──  launch API  ────────────────────────────────────────────────────*/
+    let api_bin = std::env::var("API_BIN").context("API_BIN not set")?;
+    let (_api, _api_log) = spawn_with_logs(
+        "API",
+        &api_bin,
+        &[
+            ("DATABASE_URL",           &infra.db_url),
+            ("AMQP_URL",               &infra.amqp_url),
+            ("INBOX_ROOT",             inbox.path().to_str().unwrap()),
+            ("SETLIST_ADMIN_PASSWORD", ADMIN_PASSWORD),
+        ],
+        34,
+    )?;
+    wait_for_http_ok("http://127.0.0.1:8080/internal/health", Duration::from_secs(10)).await?;
+
+    let client = admin_client("http://127.0.0.1:8080").await?;
+    let album: serde_json::Value = client
+        .post("http://127.0.0.1:8080/albums")
+        .json(&serde_json::json!({ "source": { "type": "upload" } }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let album_id = album["id"].as_str().context("album.id")?.to_owned();
+    let album_dir = inbox.path().join(&album_id);
+
+    /*──  1️⃣  multipart  ────────────────────────────────────────────────*/
+    let payload = vec![7u8; 4096];
+    let form = multipart::Form::new()
+        .part("file", multipart::Part::bytes(payload.clone()).file_name("CD1/01 Intro.flac"));
+    client
+        .post(format!("http://127.0.0.1:8080/albums/{album_id}/files"))
+        .multipart(form)
+        .send()
+        .await?
+        .error_for_status()?;
+    assert_eq!(fs::read(album_dir.join("CD1/01 Intro.flac"))?, payload);
+
+    let form = multipart::Form::new()
+        .part("file", multipart::Part::bytes(vec![0u8; 8]).file_name("setup.exe"));
+    let res = client
+        .post(format!("http://127.0.0.1:8080/albums/{album_id}/files"))
+        .multipart(form)
+        .send()
+        .await?;
+    assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    /*──  2️⃣  tus: create → partial PATCH → HEAD → finish  ──────────────*/
+    let location = client
+        .post(format!("http://127.0.0.1:8080/albums/{album_id}/uploads"))
+        .header("Tus-Resumable", "1.0.0")
+        .header("Upload-Length", payload.len().to_string())
+        .header("Upload-Metadata", "filename MDIuZmxhYw==")   // "02.flac"
+        .send()
+        .await?
+        .error_for_status()?
+        .headers()["location"]
+        .to_str()?
+        .to_owned();
+    let url = format!("http://127.0.0.1:8080{location}");
+
+    for (offset, chunk) in [(0, &payload[..1000]), (1000, &payload[1000..])] {
+        if offset > 0 {
+            let head = client.head(&url).header("Tus-Resumable", "1.0.0").send().await?;
+            assert_eq!(head.headers()["upload-offset"], offset.to_string().as_str());
+        }
+        client
+            .patch(&url)
+            .header("Tus-Resumable", "1.0.0")
+            .header("Upload-Offset", offset.to_string())
+            .header("Content-Type", "application/offset+octet-stream")
+            .body(chunk.to_vec())
+            .send()
+            .await?
+            .error_for_status()?;
+    }
+    assert_eq!(fs::read(album_dir.join("02.flac"))?, payload);
+
+    // terminating an upload waits for the PATCH still writing to it
+    let location = client
+        .post(format!("http://127.0.0.1:8080/albums/{album_id}/uploads"))
+        .header("Tus-Resumable", "1.0.0")
+        .header("Upload-Length", payload.len().to_string())
+        .header("Upload-Metadata", "filename MDQuZmxhYw==")   // "04.flac"
+        .send()
+        .await?
+        .error_for_status()?
+        .headers()["location"]
+        .to_str()?
+        .to_owned();
+    let url = format!("http://127.0.0.1:8080{location}");
+    let (mut feed, body) = futures::channel::mpsc::channel::<std::io::Result<Vec<u8>>>(1);
+    feed.try_send(Ok(payload[..1000].to_vec()))?;
+    let patch = tokio::spawn(
+        client
+            .patch(&url)
+            .header("Tus-Resumable", "1.0.0")
+            .header("Upload-Offset", "0")
+            .header("Content-Type", "application/offset+octet-stream")
+            .body(reqwest::Body::wrap_stream(body))
+            .send(),
+    );
+    let upload_id = location.rsplit('/').next().context("upload id")?;
+    let part = album_dir.join(".uploads").join(format!("{upload_id}.part"));
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while fs::metadata(&part).map_or(0, |m| m.len()) < 1000 {
+        anyhow::ensure!(Instant::now() < deadline, "first chunk never reached {}", part.display());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    let busy = client.delete(&url).header("Tus-Resumable", "1.0.0").send().await?;
+    assert_eq!(busy.status(), StatusCode::LOCKED);
+    drop(feed);
+    patch.await??.error_for_status()?;
+    client
+        .delete(&url)
+        .header("Tus-Resumable", "1.0.0")
+        .send()
+        .await?
+        .error_for_status()?;
+    let head = client.head(&url).header("Tus-Resumable", "1.0.0").send().await?;
+    assert_eq!(head.status(), StatusCode::NOT_FOUND);
+    assert!(!album_dir.join("04.flac").exists());
+
+    /*──  3️⃣  manifest gates completion  ────────────────────────────────*/
+    let manifest = serde_json::json!([
+        { "path": "CD1/01 Intro.flac", "size": payload.len(), "sha256": sha256_hex(&payload) },
+        { "path": "03.flac",           "size": 1, "md5": "00000000000000000000000000000000" },
+    ]);
+    client
+        .put(format!("http://127.0.0.1:8080/albums/{album_id}/manifest"))
+        .json(&manifest)
+        .send()
+        .await?
+        .error_for_status()?;
+    let unknown = client
+        .get(format!("http://127.0.0.1:8080/albums/{}/manifest", Uuid::new_v4()))
+        .send()
+        .await?;
+    assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+    let res = client
+        .put(format!("http://127.0.0.1:8080/albums/{album_id}/complete"))
+        .send()
+        .await?;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+    let problem: serde_json::Value = res.json().await?;
+    assert_eq!(problem["code"], "manifest_mismatch");
+    assert_eq!(problem["diff"]["missing"], serde_json::json!(["03.flac"]));
+    assert_eq!(problem["diff"]["unexpected"], serde_json::json!(["02.flac"]));
+
+    println!("✔ upload flow OK in {:.1?}", t0.elapsed());
+    Ok(())
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    use sha2::Digest;
+    hex::encode(sha2::Sha256::digest(bytes))
+}
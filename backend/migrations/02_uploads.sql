@@ -0,0 +1,17 @@
+-- 02_uploads.sql  ── resumable (tus) upload sessions
+
+-------------------------------------------------------------------------------
+-- UPLOADS (one row per in-flight tus upload) ─────────────────────────────────
+-------------------------------------------------------------------------------
+CREATE TABLE uploads (
+    id          UUID        PRIMARY KEY,
+    album_id    UUID        NOT NULL REFERENCES albums(id) ON DELETE CASCADE,
+    rel_path    TEXT        NOT NULL,             -- target under <inbox>/<album_id>/
+    length      BIGINT      NOT NULL,             -- Upload-Length (bytes)
+    received    BIGINT      NOT NULL DEFAULT 0,   -- Upload-Offset (bytes on disk)
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    finished_at TIMESTAMPTZ
+);
+-- only one *open* upload per destination file
+CREATE UNIQUE INDEX uploads_open_path ON uploads(album_id, rel_path)
+  WHERE finished_at IS NULL;
@@ -0,0 +1,15 @@
+-- 03_manifests.sql  ── expected-file manifest declared by upload clients
+
+-------------------------------------------------------------------------------
+-- ALBUM_MANIFEST (what *should* be on disk) ──────────────────────────────────
+-------------------------------------------------------------------------------
+CREATE TABLE album_manifest (
+    album_id    UUID        NOT NULL REFERENCES albums(id) ON DELETE CASCADE,
+    rel_path    TEXT        NOT NULL,   -- relative to albums.source->>'path'
+    size        BIGINT      NOT NULL,
+    md5         TEXT,                   -- lowercase hex
+    sha256      TEXT,                   -- lowercase hex
+    verified_at TIMESTAMPTZ,            -- last time the file on disk matched
+    PRIMARY KEY (album_id, rel_path),
+    CHECK (md5 IS NOT NULL OR sha256 IS NOT NULL)
+);
@@ -0,0 +1,5 @@
+-- 04_job_payload_indexes.sql  ── per-album / per-file job lookups
+
+-- GET /albums/:id aggregates jobs by the ids inside the JobEnvelope
+CREATE INDEX jobs_payload_album ON jobs ((payload->>'album_id'));
+CREATE INDEX jobs_payload_file  ON jobs ((payload->>'file_id'));
@@ -0,0 +1,15 @@
+-- 05_album_listing_indexes.sql  ── GET /albums filters, sort keys & state
+
+-- filters
+CREATE INDEX albums_artist_lower ON albums (lower(artist));
+CREATE INDEX albums_kind         ON albums (kind);
+CREATE INDEX albums_source_type  ON albums ((source->>'type'));
+
+-- keyset pagination: (sort expression, id) – must match api/src/albums.rs
+CREATE INDEX albums_sort_imported ON albums ((COALESCE(imported_at, 'epoch'::timestamptz)), id);
+CREATE INDEX albums_sort_title    ON albums ((COALESCE(lower(title),  '')), id);
+CREATE INDEX albums_sort_artist   ON albums ((COALESCE(lower(artist), '')), id);
+CREATE INDEX albums_sort_year     ON albums ((COALESCE(year, 0)), id);
+
+-- pipeline state roll-up walks tracks → files
+CREATE INDEX files_track_id ON files (track_id);
@@ -0,0 +1,82 @@
+-- 07_pipeline_events.sql  ── persisted stage transitions + LISTEN/NOTIFY feed
+
+-------------------------------------------------------------------------------
+-- PIPELINE_EVENTS (append-only; id doubles as SSE Last-Event-ID) ─────────────
+-------------------------------------------------------------------------------
+CREATE TABLE pipeline_events (
+    id          BIGSERIAL   PRIMARY KEY,
+    album_id    UUID,
+    file_id     UUID,
+    kind        TEXT        NOT NULL,   -- file.status | job.status | album.ready
+    stage       TEXT,                   -- shared::Stage (job.status only)
+    status      TEXT        NOT NULL,   -- file_status / jobs.status value
+    detail      TEXT,                   -- e.g. jobs.last_error
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX pipeline_events_album   ON pipeline_events(album_id, id);
+CREATE INDEX pipeline_events_created ON pipeline_events(created_at);
+
+-- every new event is pushed to listeners as JSON
+CREATE FUNCTION pipeline_events_notify() RETURNS trigger LANGUAGE plpgsql AS $$
+BEGIN
+    PERFORM pg_notify('pipeline_events', row_to_json(NEW)::text);
+    RETURN NULL;
+END $$;
+CREATE TRIGGER pipeline_events_notify AFTER INSERT ON pipeline_events
+    FOR EACH ROW EXECUTE FUNCTION pipeline_events_notify();
+
+-------------------------------------------------------------------------------
+-- files.status transitions (+ album.ready once every file is READY) ──────────
+-------------------------------------------------------------------------------
+CREATE FUNCTION files_status_event() RETURNS trigger LANGUAGE plpgsql AS $$
+DECLARE
+    aid UUID;
+BEGIN
+    SELECT album_id INTO aid FROM tracks WHERE id = NEW.track_id;
+    INSERT INTO pipeline_events(album_id, file_id, kind, status)
+         VALUES (aid, NEW.id, 'file.status', NEW.status::text);
+
+    IF NEW.status = 'READY' AND aid IS NOT NULL THEN
+        -- one file at a time per album, so the last two to finish in
+        -- concurrent transactions can't both miss the other
+        PERFORM 1 FROM albums WHERE id = aid FOR UPDATE;
+        IF NOT EXISTS (SELECT 1 FROM files f JOIN tracks t ON t.id = f.track_id
+                            WHERE t.album_id = aid AND f.status <> 'READY')
+           -- several files flipping in one statement → announce once per
+           -- time the album becomes ready
+           AND NOT EXISTS (SELECT 1 FROM pipeline_events
+                            WHERE album_id = aid AND kind = 'album.ready'
+                              AND id > COALESCE((SELECT max(id) FROM pipeline_events
+                                                  WHERE album_id = aid AND kind = 'file.status'
+                                                    AND status <> 'READY'), 0))
+        THEN
+            INSERT INTO pipeline_events(album_id, kind, status) VALUES (aid, 'album.ready', 'READY');
+        END IF;
+    END IF;
+    RETURN NULL;
+END $$;
+CREATE TRIGGER files_status_event AFTER UPDATE OF status ON files
+    FOR EACH ROW WHEN (OLD.status IS DISTINCT FROM NEW.status)
+    EXECUTE FUNCTION files_status_event();
+
+-------------------------------------------------------------------------------
+-- jobs.status transitions ────────────────────────────────────────────────────
+-------------------------------------------------------------------------------
+CREATE FUNCTION jobs_status_event() RETURNS trigger LANGUAGE plpgsql AS $$
+DECLARE
+    fid UUID := (NEW.payload->>'file_id')::uuid;
+    aid UUID := (NEW.payload->>'album_id')::uuid;
+BEGIN
+    IF aid IS NULL AND fid IS NOT NULL THEN
+        SELECT t.album_id INTO aid FROM files f JOIN tracks t ON t.id = f.track_id WHERE f.id = fid;
+    END IF;
+    INSERT INTO pipeline_events(album_id, file_id, kind, stage, status, detail)
+         VALUES (aid, fid, 'job.status', NEW.stage, NEW.status,
+                 CASE WHEN NEW.status = 'error' THEN left(NEW.last_error, 1000) END);
+    RETURN NULL;
+END $$;
+CREATE TRIGGER jobs_insert_event AFTER INSERT ON jobs
+    FOR EACH ROW EXECUTE FUNCTION jobs_status_event();
+CREATE TRIGGER jobs_status_event AFTER UPDATE OF status ON jobs
+    FOR EACH ROW WHEN (OLD.status IS DISTINCT FROM NEW.status)
+    EXECUTE FUNCTION jobs_status_event();
@@ -0,0 +1,26 @@
+-- 08_match_review.sql  ── manual review of low-confidence matches
+
+-- set by the Match workers when no candidate is confident enough
+ALTER TABLE files  ADD COLUMN needs_review BOOL NOT NULL DEFAULT FALSE;
+ALTER TABLE albums ADD COLUMN needs_review BOOL NOT NULL DEFAULT FALSE;
+CREATE INDEX files_needs_review  ON files  (id) WHERE needs_review;
+CREATE INDEX albums_needs_review ON albums (id) WHERE needs_review;
+
+ALTER TABLE matches_album ADD COLUMN chosen BOOL DEFAULT FALSE;
+
+-------------------------------------------------------------------------------
+-- MATCH_DECISIONS (who resolved what, and how) ───────────────────────────────
+-------------------------------------------------------------------------------
+CREATE TABLE match_decisions (
+    id          BIGSERIAL   PRIMARY KEY,
+    file_id     UUID        REFERENCES files(id)  ON DELETE CASCADE,
+    album_id    UUID        REFERENCES albums(id) ON DELETE CASCADE,
+    action      TEXT        NOT NULL,   -- chosen | manual | rejected
+    mbid        UUID,                   -- recording / release for 'chosen'
+    metadata    JSONB,                  -- hand-entered fields for 'manual'
+    decided_by  TEXT        NOT NULL,
+    decided_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CHECK ((file_id IS NULL) <> (album_id IS NULL))
+);
+CREATE INDEX match_decisions_file  ON match_decisions(file_id)  WHERE file_id  IS NOT NULL;
+CREATE INDEX match_decisions_album ON match_decisions(album_id) WHERE album_id IS NOT NULL;
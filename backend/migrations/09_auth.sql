@@ -0,0 +1,33 @@
+-- 09_auth.sql  ── users, login sessions and API tokens
+
+-------------------------------------------------------------------------------
+-- USERS ──────────────────────────────────────────────────────────────────────
+-------------------------------------------------------------------------------
+CREATE TABLE users (
+    id            UUID        PRIMARY KEY,
+    username      TEXT        NOT NULL,
+    password_hash TEXT        NOT NULL,              -- argon2id, PHC string
+    scopes        TEXT[]      NOT NULL DEFAULT '{read,stream}',
+    created_at    TIMESTAMPTZ NOT NULL DEFAULT now(),
+    disabled_at   TIMESTAMPTZ,
+    CHECK (scopes <@ ARRAY['read','stream','upload','edit','admin'])
+);
+CREATE UNIQUE INDEX users_username ON users (lower(username));
+
+-------------------------------------------------------------------------------
+-- AUTH_TOKENS (sessions from /auth/login + long-lived API tokens) ────────────
+-------------------------------------------------------------------------------
+CREATE TABLE auth_tokens (
+    id           UUID        PRIMARY KEY,
+    user_id      UUID        NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind         TEXT        NOT NULL CHECK (kind IN ('session', 'api')),
+    name         TEXT,                               -- label, API tokens only
+    token_hash   BYTEA       NOT NULL UNIQUE,        -- sha256(secret); secret never stored
+    scopes       TEXT[]      NOT NULL,               -- narrowed further by users.scopes
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at   TIMESTAMPTZ,                        -- NULL = until revoked
+    last_used_at TIMESTAMPTZ,
+    revoked_at   TIMESTAMPTZ,
+    CHECK (scopes <@ ARRAY['read','stream','upload','edit','admin'])
+);
+CREATE INDEX auth_tokens_user ON auth_tokens (user_id) WHERE revoked_at IS NULL;
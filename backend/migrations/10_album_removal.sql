@@ -0,0 +1,33 @@
+-- 10_album_removal.sql  ── DELETE /albums/:id: tombstones + audit trail
+--
+-- jobs.status gains 'cancelled' (outstanding jobs of a deleted album).
+
+-------------------------------------------------------------------------------
+-- TOMBSTONES (deleted albums whose media must not be imported again) ────────
+-------------------------------------------------------------------------------
+CREATE TABLE tombstones (
+    album_id    UUID        PRIMARY KEY,          -- no FK: the album row is gone
+    path        TEXT,                             -- albums.source->>'path'
+    file_paths  TEXT[]      NOT NULL DEFAULT '{}',
+    title       TEXT,
+    artist      TEXT,
+    deleted_by  TEXT        NOT NULL,
+    deleted_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX tombstones_path       ON tombstones (path);
+CREATE INDEX tombstones_file_paths ON tombstones USING GIN (file_paths);
+
+-------------------------------------------------------------------------------
+-- AUDIT_LOG (append-only record of destructive / administrative actions) ────
+-------------------------------------------------------------------------------
+CREATE TABLE audit_log (
+    id          BIGSERIAL   PRIMARY KEY,
+    actor       TEXT        NOT NULL,             -- users.username
+    action      TEXT        NOT NULL,             -- e.g. album.delete
+    target_kind TEXT        NOT NULL,             -- album | tombstone | …
+    target_id   UUID,
+    detail      JSONB       NOT NULL DEFAULT '{}',
+    at          TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX audit_log_target ON audit_log (target_id, id);
+CREATE INDEX audit_log_action ON audit_log (action, id);
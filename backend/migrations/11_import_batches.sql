@@ -0,0 +1,30 @@
+-- 11_import_batches.sql  ── POST /imports/batch + job priorities
+
+-- higher runs first; bulk imports queue below interactive work
+ALTER TABLE jobs ADD COLUMN priority SMALLINT NOT NULL DEFAULT 0;
+DROP INDEX jobs_next_attempt;
+CREATE INDEX jobs_next_attempt ON jobs (priority DESC, next_attempt)
+  WHERE status = 'queued';
+
+-- "is this folder already an album?"
+CREATE INDEX albums_source_path ON albums ((source->>'path'));
+
+-------------------------------------------------------------------------------
+-- IMPORT_BATCHES (one request, many folders) ─────────────────────────────────
+-------------------------------------------------------------------------------
+CREATE TABLE import_batches (
+    id          UUID        PRIMARY KEY,
+    parent      TEXT,                             -- set when a parent dir was given
+    created_by  TEXT        NOT NULL,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE import_batch_items (
+    batch_id    UUID        NOT NULL REFERENCES import_batches(id) ON DELETE CASCADE,
+    position    INT         NOT NULL,             -- order of the request
+    path        TEXT        NOT NULL,             -- as given / as found
+    album_id    UUID,                             -- no FK: albums may be deleted later
+    outcome     TEXT        NOT NULL CHECK (outcome IN ('created', 'exists', 'rejected')),
+    detail      TEXT,
+    PRIMARY KEY (batch_id, position)
+);
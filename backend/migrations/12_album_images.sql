@@ -0,0 +1,20 @@
+-- 12_album_images.sql  ── typed album artwork (covers, tickets, posters …)
+
+-------------------------------------------------------------------------------
+-- ALBUM_IMAGES (files live under IMAGE_ROOT/<album_id>/<id>.<ext>) ──────────
+-------------------------------------------------------------------------------
+CREATE TABLE album_images (
+    id          UUID        PRIMARY KEY,
+    album_id    UUID        NOT NULL REFERENCES albums(id) ON DELETE CASCADE,
+    kind        TEXT        NOT NULL CHECK (kind IN ('front', 'back', 'ticket', 'poster', 'other')),
+    mime        TEXT        NOT NULL,             -- image/jpeg | image/png | image/webp
+    width       INT         NOT NULL,
+    height      INT         NOT NULL,
+    bytes       BIGINT      NOT NULL,
+    caption     TEXT,
+    is_primary  BOOL        NOT NULL DEFAULT FALSE,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX album_images_album ON album_images (album_id, created_at);
+-- at most one cover per album
+CREATE UNIQUE INDEX album_images_primary ON album_images (album_id) WHERE is_primary;
@@ -0,0 +1,28 @@
+-- 13_search.sql  ── keyword search (GET /search): tsvector + trigram fuzzy match
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- concerts: where it was recorded
+ALTER TABLE albums ADD COLUMN venue TEXT;
+
+-------------------------------------------------------------------------------
+-- FULL TEXT ('simple' config – names and titles, any language, no stemming) ─
+-------------------------------------------------------------------------------
+ALTER TABLE albums ADD COLUMN search tsvector GENERATED ALWAYS AS (
+       setweight(to_tsvector('simple', COALESCE(title,  '')), 'A')
+    || setweight(to_tsvector('simple', COALESCE(artist, '')), 'A')
+    || setweight(to_tsvector('simple', COALESCE(venue,  '')), 'B')
+) STORED;
+ALTER TABLE tracks ADD COLUMN search tsvector GENERATED ALWAYS AS (
+    to_tsvector('simple', COALESCE(title, ''))
+) STORED;
+
+CREATE INDEX albums_search ON albums USING GIN (search);
+CREATE INDEX tracks_search ON tracks USING GIN (search);
+
+-------------------------------------------------------------------------------
+-- FUZZY (misspelled names) – must match the expressions in api/src/search.rs ─
+-------------------------------------------------------------------------------
+CREATE INDEX albums_artist_trgm ON albums USING GIN (lower(artist) gin_trgm_ops);
+CREATE INDEX albums_title_trgm  ON albums USING GIN (lower(title)  gin_trgm_ops);
+CREATE INDEX tracks_title_trgm  ON tracks USING GIN (lower(title)  gin_trgm_ops);
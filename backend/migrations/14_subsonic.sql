@@ -0,0 +1,51 @@
+-- 14_subsonic.sql  ── /rest/* (Subsonic / OpenSubsonic): app passwords,
+--                     playlists and scrobbles
+
+-------------------------------------------------------------------------------
+-- SUBSONIC APP PASSWORDS ─────────────────────────────────────────────────────
+-- Subsonic token auth sends md5(password + salt), so the server needs the
+-- clear secret.  These are random, per-device and revocable – never the
+-- login password – and are not accepted as Bearer tokens.
+-------------------------------------------------------------------------------
+ALTER TABLE auth_tokens DROP CONSTRAINT auth_tokens_kind_check;
+ALTER TABLE auth_tokens ADD CONSTRAINT auth_tokens_kind_check
+    CHECK (kind IN ('session', 'api', 'subsonic'));
+ALTER TABLE auth_tokens ADD COLUMN subsonic_secret TEXT;
+ALTER TABLE auth_tokens ADD CONSTRAINT auth_tokens_subsonic_secret
+    CHECK ((kind = 'subsonic') = (subsonic_secret IS NOT NULL));
+
+-------------------------------------------------------------------------------
+-- PLAYLISTS (ordered; the same track may appear more than once) ──────────────
+-------------------------------------------------------------------------------
+CREATE TABLE playlists (
+    id          UUID        PRIMARY KEY,
+    owner_id    UUID        NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name        TEXT        NOT NULL,
+    comment     TEXT,
+    public      BOOL        NOT NULL DEFAULT FALSE,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX playlists_owner ON playlists (owner_id);
+
+CREATE TABLE playlist_entries (
+    playlist_id UUID        NOT NULL REFERENCES playlists(id) ON DELETE CASCADE,
+    position    INT         NOT NULL,               -- 0-based, gaps allowed
+    track_id    UUID        NOT NULL REFERENCES tracks(id) ON DELETE CASCADE,
+    added_at    TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (playlist_id, position)
+);
+CREATE INDEX playlist_entries_track ON playlist_entries (track_id);
+
+-------------------------------------------------------------------------------
+-- PLAYS (scrobbles) ──────────────────────────────────────────────────────────
+-------------------------------------------------------------------------------
+CREATE TABLE plays (
+    id          BIGSERIAL   PRIMARY KEY,
+    user_id     UUID        NOT NULL REFERENCES users(id)  ON DELETE CASCADE,
+    track_id    UUID        NOT NULL REFERENCES tracks(id) ON DELETE CASCADE,
+    played_at   TIMESTAMPTZ NOT NULL DEFAULT now(),
+    client      TEXT                                -- Subsonic `c` parameter
+);
+CREATE INDEX plays_user_time ON plays (user_id, played_at DESC);
+CREATE INDEX plays_track     ON plays (track_id);
@@ -0,0 +1,86 @@
+-- 15_stats.sql  ── GET /stats: library and pipeline aggregates, precomputed
+--
+-- Materialized views refreshed CONCURRENTLY by the API on a timer (each
+-- needs a unique index for that), so /stats never scans the library.
+
+-- on-disk size, set by Import; older rows are filled in by the refresher
+ALTER TABLE files ADD COLUMN size_bytes BIGINT;
+CREATE INDEX files_unsized ON files (id) WHERE size_bytes IS NULL;
+
+-------------------------------------------------------------------------------
+-- LIBRARY ────────────────────────────────────────────────────────────────────
+-------------------------------------------------------------------------------
+CREATE MATERIALIZED VIEW stats_totals AS
+SELECT 1                                                   AS id,
+       (SELECT COUNT(*) FROM albums)                       AS albums,
+       (SELECT COUNT(*) FROM tracks)                       AS tracks,
+       (SELECT COUNT(*) FROM files)                        AS files,
+       (SELECT COALESCE(SUM(duration_sec), 0) FROM tracks)::bigint AS duration_sec,
+       (SELECT COUNT(*) FROM tracks WHERE duration_sec IS NULL)    AS tracks_without_duration,
+       now()                                               AS refreshed_at;
+CREATE UNIQUE INDEX stats_totals_id ON stats_totals (id);
+
+CREATE MATERIALIZED VIEW stats_codecs AS
+SELECT codec,
+       COUNT(*)                                   AS files,
+       COALESCE(SUM(size_bytes), 0)::bigint       AS bytes,
+       COUNT(*) FILTER (WHERE size_bytes IS NULL) AS unknown_size
+  FROM files
+ GROUP BY codec;
+CREATE UNIQUE INDEX stats_codecs_codec ON stats_codecs (codec);
+
+CREATE MATERIALIZED VIEW stats_album_kinds AS
+SELECT kind, COUNT(*) AS albums FROM albums GROUP BY kind;
+CREATE UNIQUE INDEX stats_album_kinds_kind ON stats_album_kinds (kind);
+
+CREATE MATERIALIZED VIEW stats_years AS
+SELECT year, COUNT(*) AS albums FROM albums GROUP BY year;
+CREATE UNIQUE INDEX stats_years_year ON stats_years (year);
+
+CREATE MATERIALIZED VIEW stats_file_status AS
+SELECT status, COUNT(*) AS files FROM files GROUP BY status;
+CREATE UNIQUE INDEX stats_file_status_status ON stats_file_status (status);
+
+-------------------------------------------------------------------------------
+-- MATCH CONFIDENCE (best candidate per album / per file, tenths 0‥9) ─────────
+-------------------------------------------------------------------------------
+CREATE MATERIALIZED VIEW stats_confidence AS
+SELECT 'album'::text AS level, LEAST(floor(best * 10), 9)::int AS bucket, COUNT(*) AS n
+  FROM (SELECT max(confidence) AS best FROM matches_album
+         WHERE confidence IS NOT NULL GROUP BY album_id) a
+ GROUP BY 2
+UNION ALL
+SELECT 'track', LEAST(floor(best * 10), 9)::int, COUNT(*)
+  FROM (SELECT max(score) AS best FROM matches_track
+         WHERE score IS NOT NULL GROUP BY file_id) t
+ GROUP BY 2;
+CREATE UNIQUE INDEX stats_confidence_bucket ON stats_confidence (level, bucket);
+
+-------------------------------------------------------------------------------
+-- PIPELINE THROUGHPUT (job.status events, last 24 h) + current backlog ───────
+-------------------------------------------------------------------------------
+CREATE MATERIALIZED VIEW stats_throughput AS
+WITH done AS (
+    SELECT stage,
+           COUNT(*) FILTER (WHERE status = 'done' AND created_at > now() - interval '1 hour') AS done_1h,
+           COUNT(*) FILTER (WHERE status = 'done')  AS done_24h,
+           COUNT(*) FILTER (WHERE status = 'error') AS failed_24h
+      FROM pipeline_events
+     WHERE kind = 'job.status' AND created_at > now() - interval '24 hours'
+  GROUP BY stage
+), backlog AS (
+    SELECT stage,
+           COUNT(*) FILTER (WHERE status = 'queued')  AS queued,
+           COUNT(*) FILTER (WHERE status = 'running') AS running
+      FROM jobs
+     WHERE status IN ('queued', 'running')
+  GROUP BY stage
+)
+SELECT stage,
+       COALESCE(d.done_1h, 0)    AS done_1h,
+       COALESCE(d.done_24h, 0)   AS done_24h,
+       COALESCE(d.failed_24h, 0) AS failed_24h,
+       COALESCE(b.queued, 0)     AS queued,
+       COALESCE(b.running, 0)    AS running
+  FROM done d FULL JOIN backlog b USING (stage);
+CREATE UNIQUE INDEX stats_throughput_stage ON stats_throughput (stage);
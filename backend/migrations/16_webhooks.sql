@@ -0,0 +1,96 @@
+-- 16_webhooks.sql  ── outgoing webhooks: registrations, a delivery outbox
+--                     filled by trigger, and a needs_review event
+
+-------------------------------------------------------------------------------
+-- WEBHOOKS ───────────────────────────────────────────────────────────────────
+-------------------------------------------------------------------------------
+CREATE TABLE webhooks (
+    id          UUID        PRIMARY KEY,
+    url         TEXT        NOT NULL,
+    secret      TEXT        NOT NULL,               -- HMAC key, needed in clear
+    events      TEXT[]      NOT NULL DEFAULT '{}',  -- empty = every event
+    description TEXT,
+    active      BOOL        NOT NULL DEFAULT TRUE,
+    created_by  TEXT        NOT NULL,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-------------------------------------------------------------------------------
+-- WEBHOOK_DELIVERIES (outbox + delivery log) ─────────────────────────────────
+-------------------------------------------------------------------------------
+CREATE TABLE webhook_deliveries (
+    id              BIGSERIAL   PRIMARY KEY,
+    webhook_id      UUID        NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+    event           TEXT        NOT NULL,
+    payload         JSONB       NOT NULL,
+    status          TEXT        NOT NULL DEFAULT 'pending',  -- pending|delivered|failed
+    attempts        INT         NOT NULL DEFAULT 0,
+    next_attempt    TIMESTAMPTZ NOT NULL DEFAULT now(),
+    response_status INT,                                     -- last HTTP status
+    last_error      TEXT,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT now(),
+    delivered_at    TIMESTAMPTZ
+);
+CREATE INDEX webhook_deliveries_due  ON webhook_deliveries (next_attempt) WHERE status = 'pending';
+CREATE INDEX webhook_deliveries_hook ON webhook_deliveries (webhook_id, id DESC);
+
+-------------------------------------------------------------------------------
+-- needs_review flips on → pipeline event (SSE gets it too) ───────────────────
+-------------------------------------------------------------------------------
+CREATE FUNCTION needs_review_event() RETURNS trigger LANGUAGE plpgsql AS $$
+BEGIN
+    IF TG_TABLE_NAME = 'files' THEN
+        INSERT INTO pipeline_events(album_id, file_id, kind, status)
+             SELECT t.album_id, NEW.id, 'match.needs_review', 'needs_review'
+               FROM tracks t WHERE t.id = NEW.track_id;
+    ELSE
+        INSERT INTO pipeline_events(album_id, kind, status)
+             VALUES (NEW.id, 'match.needs_review', 'needs_review');
+    END IF;
+    RETURN NULL;
+END $$;
+CREATE TRIGGER files_needs_review_event AFTER UPDATE OF needs_review ON files
+    FOR EACH ROW WHEN (NEW.needs_review AND NOT OLD.needs_review)
+    EXECUTE FUNCTION needs_review_event();
+CREATE TRIGGER albums_needs_review_event AFTER UPDATE OF needs_review ON albums
+    FOR EACH ROW WHEN (NEW.needs_review AND NOT OLD.needs_review)
+    EXECUTE FUNCTION needs_review_event();
+
+-------------------------------------------------------------------------------
+-- pipeline event → one delivery per subscribed webhook ───────────────────────
+-- Same transaction as the event, so nothing is lost while the API is down.
+-------------------------------------------------------------------------------
+CREATE FUNCTION webhook_fanout() RETURNS trigger LANGUAGE plpgsql AS $$
+DECLARE
+    ev   TEXT;
+    body JSONB;
+BEGIN
+    ev := CASE
+        WHEN NEW.kind = 'album.ready'                          THEN 'album.ready'
+        WHEN NEW.kind = 'file.status' AND NEW.status = 'ERROR' THEN 'file.error'
+        WHEN NEW.kind = 'job.status'  AND NEW.stage = 'import'
+                                      AND NEW.status = 'done'  THEN 'album.imported'
+        WHEN NEW.kind = 'match.needs_review'                   THEN 'match.needs_review'
+    END;
+    IF ev IS NULL OR NOT EXISTS (SELECT 1 FROM webhooks WHERE active) THEN
+        RETURN NULL;
+    END IF;
+
+    body := jsonb_build_object(
+        'event',       ev,
+        'event_id',    NEW.id,
+        'occurred_at', NEW.created_at,
+        'album_id',    NEW.album_id,
+        'file_id',     NEW.file_id,
+        'detail',      NEW.detail,
+        'album',       (SELECT jsonb_build_object('title', title, 'artist', artist, 'year', year)
+                          FROM albums WHERE id = NEW.album_id),
+        'path',        (SELECT path FROM files WHERE id = NEW.file_id)
+    );
+    INSERT INTO webhook_deliveries(webhook_id, event, payload)
+         SELECT id, ev, body FROM webhooks
+          WHERE active AND (events = '{}' OR ev = ANY(events));
+    RETURN NULL;
+END $$;
+CREATE TRIGGER webhook_fanout AFTER INSERT ON pipeline_events
+    FOR EACH ROW EXECUTE FUNCTION webhook_fanout();
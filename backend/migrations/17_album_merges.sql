@@ -0,0 +1,16 @@
+-- 17_album_merges.sql  ── POST /albums/:id/merge folds one album into another
+
+-------------------------------------------------------------------------------
+-- ALBUM_MERGES (albums folded into another; their folder still counts) ──────
+-------------------------------------------------------------------------------
+CREATE TABLE album_merges (
+    from_id     UUID        PRIMARY KEY,          -- no FK: the album row is gone
+    into_id     UUID        NOT NULL REFERENCES albums(id) ON DELETE CASCADE,
+    path        TEXT,                             -- albums.source->>'path' of from_id
+    title       TEXT,
+    artist      TEXT,
+    merged_by   TEXT        NOT NULL,
+    merged_at   TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX album_merges_into ON album_merges (into_id);
+CREATE INDEX album_merges_path ON album_merges (path);
@@ -0,0 +1,67 @@
+-- 18_metadata_history.sql  ── every change to album / track metadata, filled
+--                             by trigger whoever makes it
+
+-------------------------------------------------------------------------------
+-- METADATA_HISTORY (one row per changed field) ───────────────────────────────
+-------------------------------------------------------------------------------
+CREATE TABLE metadata_history (
+    id          BIGSERIAL   PRIMARY KEY,
+    entity      TEXT        NOT NULL,                 -- album | track
+    entity_id   UUID        NOT NULL,                 -- no FK: outlives the row
+    field       TEXT        NOT NULL,                 -- column name
+    old_value   JSONB,
+    new_value   JSONB,
+    changed_by  TEXT        NOT NULL,                 -- user name, or the writer's application_name
+    via         TEXT        NOT NULL,                 -- api | review | revert | sql
+    tx_id       BIGINT      NOT NULL DEFAULT txid_current(),
+    changed_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX metadata_history_entity ON metadata_history (entity, entity_id, id);
+
+-------------------------------------------------------------------------------
+-- UPDATE → history rows ──────────────────────────────────────────────────────
+-- TG_ARGV: entity, then the columns to watch.  The API names the user with
+--   SELECT set_config('setlist.actor', <user>, true), set_config('setlist.via', <via>, true)
+-- inside its transaction; anything else is recorded under its application_name.
+-- Renumbering moves indices through negative values first (UNIQUE(album_id,
+-- disc, "index")), so a field changed again by the same writer in the same
+-- transaction extends that row instead of adding one per pass; a round trip
+-- drops it.
+-------------------------------------------------------------------------------
+CREATE FUNCTION record_metadata_change() RETURNS trigger LANGUAGE plpgsql AS $$
+DECLARE
+    o      JSONB := to_jsonb(OLD);
+    n      JSONB := to_jsonb(NEW);
+    f      TEXT;
+    h      BIGINT;
+    actor  TEXT  := COALESCE(NULLIF(current_setting('setlist.actor', true), ''),
+                             NULLIF(current_setting('application_name'), ''),
+                             session_user);
+    route  TEXT  := COALESCE(NULLIF(current_setting('setlist.via', true), ''), 'sql');
+BEGIN
+    FOR i IN 1 .. TG_NARGS - 1 LOOP
+        f := TG_ARGV[i];
+        IF o->f IS DISTINCT FROM n->f THEN
+            SELECT id INTO h
+              FROM metadata_history
+             WHERE entity = TG_ARGV[0] AND entity_id = NEW.id AND field = f
+               AND tx_id = txid_current() AND changed_by = actor AND via = route
+          ORDER BY id DESC LIMIT 1;
+            IF h IS NOT NULL
+               AND (SELECT new_value FROM metadata_history WHERE id = h) IS NOT DISTINCT FROM o->f THEN
+                UPDATE metadata_history SET new_value = n->f WHERE id = h;
+                DELETE FROM metadata_history WHERE id = h AND old_value IS NOT DISTINCT FROM new_value;
+            ELSE
+                INSERT INTO metadata_history(entity, entity_id, field, old_value, new_value, changed_by, via)
+                VALUES (TG_ARGV[0], NEW.id, f, o->f, n->f, actor, route);
+            END IF;
+        END IF;
+    END LOOP;
+    RETURN NULL;
+END $$;
+
+-- album_id is structural: merge / split are audited instead
+CREATE TRIGGER albums_metadata_history AFTER UPDATE OF title, artist, year, venue, kind ON albums
+    FOR EACH ROW EXECUTE FUNCTION record_metadata_change('album', 'title', 'artist', 'year', 'venue', 'kind');
+CREATE TRIGGER tracks_metadata_history AFTER UPDATE OF title, disc, "index" ON tracks
+    FOR EACH ROW EXECUTE FUNCTION record_metadata_change('track', 'title', 'disc', 'index');
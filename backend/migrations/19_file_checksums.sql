@@ -0,0 +1,17 @@
+-- 19_file_checksums.sql  ── per-file SHA-256, so an export can re-link
+--                            records to media that moved
+
+-- set by the Import worker; NULL for files imported before this migration
+ALTER TABLE files ADD COLUMN sha256 TEXT;
+CREATE INDEX files_sha256 ON files (sha256) WHERE sha256 IS NOT NULL;
+
+-- verified manifest entries already know theirs
+UPDATE files f
+   SET sha256 = m.sha256
+  FROM tracks t
+  JOIN albums a         ON a.id = t.album_id
+  JOIN album_manifest m ON m.album_id = a.id
+ WHERE t.id = f.track_id
+   AND f.path = (a.source->>'path') || '/' || m.rel_path
+   AND m.sha256 IS NOT NULL
+   AND m.verified_at IS NOT NULL;
@@ -23,6 +23,16 @@ impl fmt::Display for Stage {
 }
 
 impl Stage {
+    /// Every stage, in pipeline order.
+    pub const ALL: [Stage; 6] = [
+        Stage::Import,
+        Stage::Fingerprint,
+        Stage::MatchTrack,
+        Stage::MatchAlbum,
+        Stage::TagTrack,
+        Stage::Index,
+    ];
+
     pub const fn as_str(self) -> &'static str {
         match self {
             Stage::Import      => "import",
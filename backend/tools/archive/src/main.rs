@@ -0,0 +1,146 @@
+//! ────────────────────────────────────────────────────────────────────────────
+//!  TOOL:  ARCHIVE  (manual / cron)
+//! ────────────────────────────────────────────────────────────────────────────
+//! Responsibility
+//! --------------
+//! • `export` – save the library's metadata (`GET /export`) to a file, for
+//!   backups or moving to a new database.
+//! • `restore` – send an export back (`POST /restore`) and print the report:
+//!   what was re-linked, recreated, and what couldn't be matched.
+//!
+//! Usage
+//! -----
+//!     setlist-archive export  [--ndjson] [--out FILE]     (default: stdout)
+//!     setlist-archive restore FILE [--dry-run]
+//!
+//! Talks to the API at `SETLIST_URL` (default http://127.0.0.1:8080) with an
+//! `admin`-scoped token in `SETLIST_TOKEN`.  Exits 2 when a restore left
+//! anything unmatched.
+//!
+
+use std::{env, path::PathBuf, process::ExitCode};
+
+use anyhow::{bail, Context, Result};
+use reqwest::{Client, RequestBuilder, Response};
+use tokio::io::AsyncWriteExt;
+
+const USAGE: &str = "usage: setlist-archive export [--ndjson] [--out FILE]\n       \
+                     setlist-archive restore FILE [--dry-run]";
+
+enum Command {
+    Export { ndjson: bool, out: Option<PathBuf> },
+    Restore { file: PathBuf, dry_run: bool },
+}
+
+fn parse(mut args: impl Iterator<Item = String>) -> Result<Command> {
+    match args.next().as_deref() {
+        Some("export") => {
+            let (mut ndjson, mut out) = (false, None);
+            while let Some(a) = args.next() {
+                match a.as_str() {
+                    "--ndjson" => ndjson = true,
+                    "--out" | "-o" => out = Some(args.next().context("--out needs a file")?.into()),
+                    _ => bail!("unexpected argument {a:?}\n{USAGE}"),
+                }
+            }
+            Ok(Command::Export { ndjson, out })
+        }
+        Some("restore") => {
+            let (mut file, mut dry_run) = (None, false);
+            for a in args {
+                match a.as_str() {
+                    "--dry-run" => dry_run = true,
+                    _ if file.is_none() && !a.starts_with('-') => file = Some(a.into()),
+                    _ => bail!("unexpected argument {a:?}\n{USAGE}"),
+                }
+            }
+            Ok(Command::Restore { file: file.context(USAGE)?, dry_run })
+        }
+        _ => bail!(USAGE),
+    }
+}
+
+struct Api {
+    http:  Client,
+    base:  String,
+    token: String,
+}
+
+impl Api {
+    fn from_env() -> Result<Self> {
+        let base = env::var("SETLIST_URL").unwrap_or_else(|_| "http://127.0.0.1:8080".into());
+        let token = env::var("SETLIST_TOKEN").context("SETLIST_TOKEN is not set (an admin-scoped token)")?;
+        Ok(Self { http: Client::new(), base: base.trim_end_matches('/').into(), token })
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> RequestBuilder {
+        self.http.request(method, format!("{}{path}", self.base)).bearer_auth(&self.token)
+    }
+}
+
+/// The response, or the problem document's detail as the error.
+async fn checked(res: Response) -> Result<Response> {
+    let status = res.status();
+    if status.is_success() {
+        return Ok(res);
+    }
+    let body: serde_json::Value = res.json().await.unwrap_or_default();
+    let detail = body["detail"].as_str().or(body["title"].as_str()).unwrap_or("no detail");
+    bail!("{status}: {detail}")
+}
+
+async fn export(api: &Api, ndjson: bool, out: Option<PathBuf>) -> Result<()> {
+    let format = if ndjson { "ndjson" } else { "json" };
+    let req = api.request(reqwest::Method::GET, &format!("/export?format={format}"));
+    let mut res = checked(req.send().await?).await?;
+
+    let mut sink: Box<dyn tokio::io::AsyncWrite + Unpin> = match &out {
+        Some(p) => Box::new(tokio::fs::File::create(p).await.with_context(|| format!("{}", p.display()))?),
+        None => Box::new(tokio::io::stdout()),
+    };
+    let mut bytes = 0;
+    while let Some(chunk) = res.chunk().await? {
+        bytes += chunk.len();
+        sink.write_all(&chunk).await?;
+    }
+    sink.flush().await?;
+    if let Some(p) = out {
+        eprintln!("exported {bytes} bytes to {}", p.display());
+    }
+    Ok(())
+}
+
+/// `true` when everything was placed.
+async fn restore(api: &Api, file: PathBuf, dry_run: bool) -> Result<bool> {
+    let body = tokio::fs::read(&file).await.with_context(|| format!("{}", file.display()))?;
+    let req = api.request(reqwest::Method::POST, &format!("/restore?dry_run={dry_run}")).body(body);
+    let report: serde_json::Value = checked(req.send().await?).await?.json().await?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+
+    let unmatched = report["unmatched"].as_array().map_or(0, Vec::len);
+    if unmatched > 0 {
+        eprintln!("{unmatched} record(s) could not be matched – see \"unmatched\"");
+    }
+    Ok(unmatched == 0)
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenvy::dotenv().ok();
+    let run = async {
+        let cmd = parse(env::args().skip(1))?;
+        let api = Api::from_env()?;
+        match cmd {
+            Command::Export { ndjson, out } => export(&api, ndjson, out).await.map(|()| true),
+            Command::Restore { file, dry_run } => restore(&api, file, dry_run).await,
+        }
+    };
+    match run.await {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(2),
+        Err(e) => {
+            eprintln!("setlist-archive: {e:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -5,7 +5,8 @@
 //! --------------
 //! • Walk the long-term media directory (`/media`) to detect new albums that
 //!   were added *outside* the pipeline (e.g. rsync, torrent).
-//! • For each new folder:
+//! • For each new folder (skipping any listed in `tombstones` – albums
+//!   deleted with mode=tombstone|purge must not come back):
 //!       INSERT albums(... source={"type":"library_scan"} )
 //!       queue Import job
 //! • For removed folders: optionally tombstone albums / files.
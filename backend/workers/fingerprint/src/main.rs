@@ -40,7 +40,7 @@ use lapin::{
     BasicProperties, Channel, Connection, ConnectionProperties,
 };
 use shared::pipeline::{JobEnvelope, Stage};
-use sqlx::PgPool;
+use sqlx::{postgres::PgConnectOptions, PgPool};
 use std::process::Command;
 use tracing::{debug, error, info, instrument, span, Level, Span, Instrument};
 use std::default::Default;
@@ -56,7 +56,9 @@ async fn main() -> Result<()> {
     /*── DB pool ───────────────────────────────────────────────────────────*/
     let db_url = std::env::var("DATABASE_URL")?;
     debug!(%db_url, "connecting to Postgres");
-    let db = PgPool::connect(&db_url).await?;
+    // named, so metadata_history attributes this worker's writes to it
+    let opts: PgConnectOptions = db_url.parse()?;
+    let db = PgPool::connect_with(opts.application_name("setlist-fingerprint")).await?;
     info!("Postgres connection ready");
 
     /*── AMQP setup ────────────────────────────────────────────────────────*/
@@ -32,12 +32,13 @@ use shared::{
     pipeline::{JobEnvelope, Stage},
     amqp,
 };
-use sqlx::PgPool;
+use sqlx::{postgres::PgConnectOptions, PgPool};
 use tokio::task;
 use tracing::{debug, error, info, instrument};
 use uuid::Uuid;
 use walkdir::WalkDir;
 use lofty::{TaggedFileExt, Accessor};
+use sha2::{Digest, Sha256};
 
 /*──────────────────────────────────────────────────────────────────────────*/
 
@@ -47,7 +48,9 @@ async fn main() -> Result<()> {
     shared::tracing_init::init("worker-import");
 
     /*── postgres ─────────────────────────────────────────────────────────*/
-    let db = PgPool::connect(&std::env::var("DATABASE_URL")?).await?;
+    // named, so metadata_history attributes this worker's writes to it
+    let opts: PgConnectOptions = std::env::var("DATABASE_URL")?.parse()?;
+    let db = PgPool::connect_with(opts.application_name("setlist-import")).await?;
 
     /*── amqp ─────────────────────────────────────────────────────────────*/
     let conn = Connection::connect(
@@ -126,13 +129,15 @@ async fn handle_job(ch: &Channel, db: &PgPool, payload: &[u8]) -> Result<()> {
         // 2) file row
         let file_id = Uuid::new_v4();
         sqlx::query(
-            "INSERT INTO files(id, track_id, path, codec)
-                   VALUES ($1,$2,$3,$4)"
+            "INSERT INTO files(id, track_id, path, codec, size_bytes, sha256)
+                   VALUES ($1,$2,$3,$4,$5,$6)"
         )
         .bind(file_id)
         .bind(track_id)
         .bind(&info.path)
         .bind(&info.codec)
+        .bind(info.size)
+        .bind(&info.sha256)
         .execute(&mut *tx)
         .await?;
 
@@ -169,6 +174,8 @@ struct FileInfo {
     path:   String,   // absolute path
     title:  String,   // best-guess title (may be empty)
     codec:  String,   // "flac" | "mp3" | …
+    size:   Option<i64>,
+    sha256: String,   // hex – lets an export re-link the file if it moves
 }
 
 /// Walk `root`, returning (disc,index) → FileInfo
@@ -176,6 +183,7 @@ fn scan_album(root: &Path) -> Result<BTreeMap<(i32,i32), FileInfo>> {
     let mut out = BTreeMap::<(i32,i32), FileInfo>::new();
 
     for entry in WalkDir::new(root).into_iter().filter_map(Result::ok).filter(|e| e.file_type().is_file()) {
+        let size = entry.metadata().ok().and_then(|m| i64::try_from(m.len()).ok());
         let path = entry.into_path();
         let ext  = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
         if !matches!(ext.as_str(), "flac" | "mp3" | "ogg" | "opus" | "m4a") {
@@ -191,6 +199,7 @@ fn scan_album(root: &Path) -> Result<BTreeMap<(i32,i32), FileInfo>> {
 
         // index from tag OR filename prefix
         let (index, title_guess) = parse_track_index(&path)?;
+        let sha256 = sha256_file(&path).with_context(|| format!("hashing {}", path.display()))?;
 
         out.insert(
             (disc, index),
@@ -198,6 +207,8 @@ fn scan_album(root: &Path) -> Result<BTreeMap<(i32,i32), FileInfo>> {
                 path:  path.to_string_lossy().into_owned(),
                 title: title_guess,
                 codec: ext,
+                size,
+                sha256,
             }
         );
     }
@@ -207,6 +218,12 @@ fn scan_album(root: &Path) -> Result<BTreeMap<(i32,i32), FileInfo>> {
     Ok(out)
 }
 
+fn sha256_file(p: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut std::fs::File::open(p)?, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
 /*── heuristics helpers (pure) ────────────────────────────────────────────*/
 
 fn parse_disc(s: &str) -> Option<i32> {
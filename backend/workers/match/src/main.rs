@@ -18,7 +18,9 @@
 //! 3. INSERT INTO matches(file_id, mbid, score, raw_json)
 //! 4. Decide:
 //!      – High-confidence → emit Tag job.
-//!      – Low confidence  → mark for manual review (future).
+//!      – Low confidence  → SET files.needs_review = TRUE (albums.needs_review
+//!        for MatchAlbum); a human resolves it via the API's `/review/*`,
+//!        which flips `chosen` and queues Tag (or re-queues Match).
 //!
 //! DB additions (future)
 //! --------------------
@@ -19,6 +19,11 @@
 //!        routing_key="index"
 //!        JobEnvelope { file_id, stage=Index }
 //!
+//! Manual edits
+//! ------------
+//! • The API queues a Tag job for every already-tagged file a track / file
+//!   correction touches; re-tagging is the same job as the first pass.
+//!
 //! Considerations
 //! --------------
 //! • Artwork download/caching directory.